@@ -11,7 +11,11 @@ class ReteEditor {
         this.outputBuffers = new Map(); // Buffer for node outputs
         this.taskRoutes = new Map(); // Route configuration for tasks
         this.bufferLogs = new Map(); // Store buffer logs for each node
+        this.nodeImages = new Map(); // Images attached to LLM nodes
         this.modals = new Map(); // Store all modal instances
+        this.pendingConnection = null; // Output point a connection is being dragged from
+        this.lastRunId = null; // Latest backend run, the checkpoint for resuming
+        this.approvalPoll = null; // Interval checking for tool actions awaiting approval
         
         this.initializeEditor();
         this.initializeModals();
@@ -43,6 +47,41 @@ class ReteEditor {
             icon: this.getModalIcon('logs'),
             extraButtons: this.getModalExtraButtons('logs')
         });
+
+        this.createModal('Approvals', 'approvals', {
+            icon: this.getModalIcon('approvals'),
+            extraButtons: this.getModalExtraButtons('approvals')
+        });
+
+        this.createModal('Workflows', 'workflows', {
+            icon: this.getModalIcon('workflows'),
+            extraButtons: this.getModalExtraButtons('workflows')
+        });
+
+        this.createModal('Knowledge Bases', 'knowledge', {
+            icon: this.getModalIcon('knowledge'),
+            extraButtons: this.getModalExtraButtons('knowledge')
+        });
+
+        this.createModal('Memory', 'memory', {
+            icon: this.getModalIcon('memory'),
+            extraButtons: this.getModalExtraButtons('memory')
+        });
+
+        this.createModal('Prompts', 'prompts', {
+            icon: this.getModalIcon('prompts'),
+            extraButtons: this.getModalExtraButtons('prompts')
+        });
+
+        this.createModal('Evals', 'evals', {
+            icon: this.getModalIcon('evals'),
+            extraButtons: this.getModalExtraButtons('evals')
+        });
+
+        this.createModal('Regression', 'regression', {
+            icon: this.getModalIcon('regression'),
+            extraButtons: this.getModalExtraButtons('regression')
+        });
     }
 
     getModalIcon(type) {
@@ -71,6 +110,54 @@ class ReteEditor {
                 <svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2" stroke-linecap="round" stroke-linejoin="round">
                     <path d="M21 15a2 2 0 0 1-2 2H7l-4 4V5a2 2 0 0 1 2-2h14a2 2 0 0 1 2 2z"/>
                 </svg>
+            `,
+            approvals: `
+                <svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2" stroke-linecap="round" stroke-linejoin="round">
+                    <path d="M12 22s8-4 8-10V5l-8-3-8 3v7c0 6 8 10 8 10z"/>
+                    <polyline points="9 12 11 14 15 10"/>
+                </svg>
+            `,
+            workflows: `
+                <svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2" stroke-linecap="round" stroke-linejoin="round">
+                    <polygon points="12 2 2 7 12 12 22 7 12 2"/>
+                    <polyline points="2 17 12 22 22 17"/>
+                    <polyline points="2 12 12 17 22 12"/>
+                </svg>
+            `,
+            knowledge: `
+                <svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2" stroke-linecap="round" stroke-linejoin="round">
+                    <path d="M4 19.5A2.5 2.5 0 0 1 6.5 17H20"/>
+                    <path d="M6.5 2H20v20H6.5A2.5 2.5 0 0 1 4 19.5v-15A2.5 2.5 0 0 1 6.5 2z"/>
+                </svg>
+            `,
+            memory: `
+                <svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2" stroke-linecap="round" stroke-linejoin="round">
+                    <rect x="4" y="4" width="16" height="16" rx="2"/>
+                    <rect x="9" y="9" width="6" height="6"/>
+                    <line x1="9" y1="1" x2="9" y2="4"/>
+                    <line x1="15" y1="1" x2="15" y2="4"/>
+                    <line x1="9" y1="20" x2="9" y2="23"/>
+                    <line x1="15" y1="20" x2="15" y2="23"/>
+                </svg>
+            `,
+            prompts: `
+                <svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2" stroke-linecap="round" stroke-linejoin="round">
+                    <polyline points="4 17 10 11 4 5"/>
+                    <line x1="12" y1="19" x2="20" y2="19"/>
+                </svg>
+            `,
+            evals: `
+                <svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2" stroke-linecap="round" stroke-linejoin="round">
+                    <line x1="18" y1="20" x2="18" y2="10"/>
+                    <line x1="12" y1="20" x2="12" y2="4"/>
+                    <line x1="6" y1="20" x2="6" y2="14"/>
+                </svg>
+            `,
+            regression: `
+                <svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2" stroke-linecap="round" stroke-linejoin="round">
+                    <path d="M9 11l3 3L22 4"/>
+                    <path d="M21 12v7a2 2 0 0 1-2 2H5a2 2 0 0 1-2-2V5a2 2 0 0 1 2-2h11"/>
+                </svg>
             `
         };
         return icons[type] || '';
@@ -143,6 +230,27 @@ class ReteEditor {
                 case 'logs':
                     this.setupLogsModal(modal.modal);
                     break;
+                case 'approvals':
+                    this.refreshApprovals();
+                    break;
+                case 'workflows':
+                    this.setupWorkflowsModal(modal.modal);
+                    break;
+                case 'knowledge':
+                    this.setupKnowledgeModal(modal.modal);
+                    break;
+                case 'memory':
+                    this.setupMemoryModal(modal.modal);
+                    break;
+                case 'prompts':
+                    this.setupPromptsModal(modal.modal);
+                    break;
+                case 'evals':
+                    this.setupEvalsModal(modal.modal);
+                    break;
+                case 'regression':
+                    this.setupRegressionModal(modal.modal);
+                    break;
             }
         }
         return modal;
@@ -153,6 +261,29 @@ class ReteEditor {
         if (clearBtn) {
             clearBtn.addEventListener('click', () => this.clearBufferLogs());
         }
+
+        modal.addEventListener('click', (e) => {
+            const button = e.target.closest('.artifact-btn');
+            if (button) {
+                this.downloadArtifact(button.dataset.artifactId);
+            }
+        });
+    }
+
+    // Fetch a saved node artifact from the run history and hand it to the browser
+    async downloadArtifact(id) {
+        try {
+            const { invoke } = window.__TAURI__.core;
+            const artifact = await invoke('get_artifact', { id });
+            const blob = new Blob([artifact.content], { type: artifact.media_type });
+            const link = document.createElement('a');
+            link.href = URL.createObjectURL(blob);
+            link.download = artifact.name;
+            link.click();
+            URL.revokeObjectURL(link.href);
+        } catch (error) {
+            console.error('Failed to download artifact:', error);
+        }
     }
 
     setupLLMServersModal(modal) {
@@ -207,6 +338,26 @@ class ReteEditor {
                         <h3>Output Node</h3>
                         <p>Create a new output node</p>
                     </div>
+                    <div class="node-type" data-type="router">
+                        <h3>Router Node</h3>
+                        <p>Send input down the branch whose condition matches</p>
+                    </div>
+                    <div class="node-type" data-type="map">
+                        <h3>Map Node</h3>
+                        <p>Run the task over each item of the upstream list</p>
+                    </div>
+                    <div class="node-type" data-type="while">
+                        <h3>While Node</h3>
+                        <p>Refine the output until a condition holds</p>
+                    </div>
+                    <div class="node-type" data-type="retriever">
+                        <h3>Retriever Node</h3>
+                        <p>Find the knowledge base passages closest to the input</p>
+                    </div>
+                    <div class="node-type" data-type="memory">
+                        <h3>Memory Node</h3>
+                        <p>Remember the input across runs, or recall past memories</p>
+                    </div>
                 </div>
             `;
 
@@ -311,6 +462,16 @@ class ReteEditor {
     }
 
     createNodeContent(type = 'llm') {
+        if (type === 'router') {
+            return this.createRouterContent();
+        }
+        if (type === 'retriever') {
+            return this.createRetrieverContent();
+        }
+        if (type === 'memory') {
+            return this.createMemoryContent();
+        }
+
         const content = document.createElement('div');
         content.className = 'node-content';
 
@@ -325,6 +486,7 @@ class ReteEditor {
             <option value="">Select Server</option>
             <option value="http://localhost:1234/v1">LM Studio</option>
             <option value="http://localhost:11434">Ollama</option>
+            <option value="mock://">Mock</option>
         `;
 
         // Model selection
@@ -349,6 +511,11 @@ class ReteEditor {
         taskInput.className = 'node-input';
         taskInput.placeholder = 'Enter task description...';
 
+        // Optional system prompt, sent before the task
+        const systemInput = document.createElement('textarea');
+        systemInput.className = 'node-system';
+        systemInput.placeholder = 'System prompt (optional)...';
+
         // Temperature control
         const tempContainer = document.createElement('div');
         tempContainer.className = 'param-row';
@@ -370,8 +537,21 @@ class ReteEditor {
         // Add elements to container
         container.appendChild(serverSelect);
         container.appendChild(modelSelectContainer);
+        if (['llm', 'map', 'while'].includes(type)) {
+            container.appendChild(systemInput);
+        }
         container.appendChild(taskInput);
         container.appendChild(tempContainer);
+        if (type === 'map' || type === 'while') {
+            container.appendChild(this.createIterationSection(type));
+        }
+        if (['llm', 'map', 'while'].includes(type)) {
+            container.appendChild(this.createPromptSection());
+            container.appendChild(this.createImageSection());
+            container.appendChild(this.createToolsSection());
+            container.appendChild(this.createSchemaSection());
+            container.appendChild(this.createContextSection());
+        }
 
         // Add container to content
         content.appendChild(container);
@@ -515,6 +695,347 @@ class ReteEditor {
         }
     }
 
+    createRouterContent() {
+        const content = document.createElement('div');
+        content.className = 'node-content';
+        content.innerHTML = `
+            <div class="node-inputs-container">
+                <select class="node-select router-mode">
+                    <option value="rules">Rules (regex / JSON field)</option>
+                    <option value="classifier">LLM classifier</option>
+                </select>
+                <div class="classifier-settings" style="display: none">
+                    <select class="node-select classifier-server">
+                        <option value="">Select Server</option>
+                        <option value="http://localhost:1234/v1">LM Studio</option>
+                        <option value="http://localhost:11434">Ollama</option>
+                        <option value="mock://">Mock</option>
+                    </select>
+                    <div class="select-wrapper">
+                        <select class="node-select classifier-model" disabled>
+                            <option value="">Select Model</option>
+                        </select>
+                        <div class="loading-indicator" style="display: none">Loading models...</div>
+                    </div>
+                    <input type="text" class="param-input classifier-labels" placeholder="Labels, comma separated">
+                </div>
+                <div class="branches-header">Branches</div>
+                <div class="router-branches">
+                    <div class="router-branch-empty">Connect this node to add branches</div>
+                </div>
+            </div>
+        `;
+
+        const modeSelect = content.querySelector('.router-mode');
+        const classifierSettings = content.querySelector('.classifier-settings');
+        modeSelect.addEventListener('change', () => {
+            classifierSettings.style.display = modeSelect.value === 'classifier' ? 'block' : 'none';
+        });
+
+        const serverSelect = content.querySelector('.classifier-server');
+        const modelSelect = content.querySelector('.classifier-model');
+        const loadingIndicator = content.querySelector('.loading-indicator');
+        serverSelect.addEventListener('change', async (e) => {
+            const serverUrl = e.target.value;
+            if (!serverUrl) {
+                modelSelect.innerHTML = '<option value="">Select Model</option>';
+                modelSelect.disabled = true;
+                return;
+            }
+            if (this.modelCache.has(serverUrl)) {
+                this.updateModelSelect(modelSelect, this.modelCache.get(serverUrl));
+                return;
+            }
+            await this.fetchModels(serverUrl, modelSelect, loadingIndicator);
+        });
+
+        return content;
+    }
+
+    createRetrieverContent() {
+        const content = document.createElement('div');
+        content.className = 'node-content';
+        content.innerHTML = `
+            <div class="node-inputs-container">
+                <select class="node-select retriever-kb">
+                    <option value="">Select Knowledge Base</option>
+                </select>
+                <textarea class="node-input retriever-query" placeholder="Query (defaults to the input; {{input}} inserts it)"></textarea>
+                <div class="param-item">
+                    <label>Top K:</label>
+                    <input type="number" value="5" min="1" max="50" class="param-input retriever-top-k">
+                </div>
+                <select class="node-select retriever-mode">
+                    <option value="hybrid">Hybrid (keywords + vectors)</option>
+                    <option value="vector">Vectors only</option>
+                    <option value="keyword">Keywords only (BM25)</option>
+                </select>
+                <select class="node-select rerank-method">
+                    <option value="">No reranking</option>
+                    <option value="llm">Rerank with LLM</option>
+                    <option value="cross_encoder">Rerank with cross-encoder</option>
+                </select>
+                <div class="rerank-settings" style="display: none">
+                    <select class="node-select rerank-server">
+                        <option value="http://localhost:1234/v1">LM Studio</option>
+                        <option value="http://localhost:11434">Ollama</option>
+                    </select>
+                    <input type="text" class="param-input rerank-model" placeholder="Rerank model">
+                    <div class="param-item">
+                        <label>Candidates:</label>
+                        <input type="number" value="20" min="1" max="100" class="param-input rerank-candidates">
+                    </div>
+                </div>
+            </div>
+        `;
+        this.fillKnowledgeBaseSelect(content.querySelector('.retriever-kb'));
+
+        const methodSelect = content.querySelector('.rerank-method');
+        methodSelect.addEventListener('change', () => {
+            content.querySelector('.rerank-settings').style.display = methodSelect.value ? 'block' : 'none';
+        });
+        return content;
+    }
+
+    createMemoryContent() {
+        const content = document.createElement('div');
+        content.className = 'node-content';
+        content.innerHTML = `
+            <div class="node-inputs-container">
+                <select class="node-select memory-operation">
+                    <option value="write">Write input to memory</option>
+                    <option value="recall">Recall memories</option>
+                </select>
+                <select class="node-select memory-scope">
+                    <option value="workflow">This workflow</option>
+                    <option value="global">Global</option>
+                </select>
+                <div class="memory-write-settings">
+                    <select class="node-select memory-kind">
+                        <option value="fact">Fact</option>
+                        <option value="episode">Episode summary</option>
+                        <option value="note">Key/value note</option>
+                    </select>
+                    <input type="text" class="param-input memory-key" placeholder="Note key, e.g. owner-{{input.team}}" style="display: none">
+                </div>
+                <div class="memory-recall-settings" style="display: none">
+                    <textarea class="node-input memory-query" placeholder="Query (defaults to the input; {{input}} inserts it)"></textarea>
+                    <div class="memory-kinds">
+                        <label><input type="checkbox" value="fact" checked> Facts</label>
+                        <label><input type="checkbox" value="episode" checked> Episodes</label>
+                        <label><input type="checkbox" value="note" checked> Notes</label>
+                    </div>
+                    <div class="param-item">
+                        <label>Top K:</label>
+                        <input type="number" value="5" min="1" max="50" class="param-input memory-top-k">
+                    </div>
+                    <select class="node-select memory-embedding-server">
+                        <option value="">Keyword matching</option>
+                        <option value="http://localhost:11434">Ollama embeddings</option>
+                        <option value="http://localhost:1234/v1">LM Studio embeddings</option>
+                    </select>
+                    <input type="text" class="param-input memory-embedding-model" placeholder="Embedding model" style="display: none">
+                </div>
+            </div>
+        `;
+
+        const operation = content.querySelector('.memory-operation');
+        operation.addEventListener('change', () => {
+            const recall = operation.value === 'recall';
+            content.querySelector('.memory-write-settings').style.display = recall ? 'none' : 'block';
+            content.querySelector('.memory-recall-settings').style.display = recall ? 'block' : 'none';
+        });
+        const kind = content.querySelector('.memory-kind');
+        kind.addEventListener('change', () => {
+            content.querySelector('.memory-key').style.display = kind.value === 'note' ? 'block' : 'none';
+        });
+        const server = content.querySelector('.memory-embedding-server');
+        server.addEventListener('change', () => {
+            content.querySelector('.memory-embedding-model').style.display = server.value ? 'block' : 'none';
+        });
+        return content;
+    }
+
+    readRerankConfig(node) {
+        const method = node.querySelector('.rerank-method').value;
+        if (!method) return null;
+        return {
+            method,
+            server_url: node.querySelector('.rerank-server').value,
+            model: node.querySelector('.rerank-model').value.trim(),
+            candidates: parseInt(node.querySelector('.rerank-candidates').value, 10) || 20
+        };
+    }
+
+    async fillKnowledgeBaseSelect(select) {
+        try {
+            const { invoke } = window.__TAURI__.core;
+            const bases = await invoke('list_knowledge_bases');
+            const current = select.value;
+            select.innerHTML = '<option value="">Select Knowledge Base</option>';
+            for (const base of bases) {
+                const option = document.createElement('option');
+                option.value = base.id;
+                option.textContent = `${base.name} (${base.chunks} chunks)`;
+                select.appendChild(option);
+            }
+            select.value = current;
+        } catch (error) {
+            console.error('Failed to list knowledge bases:', error);
+        }
+    }
+
+    isRouterNode(node) {
+        return node?.dataset.type === 'router';
+    }
+
+    // Rebuild the per-branch condition editors of a router node
+    refreshRouterBranches(nodeId) {
+        const node = this.nodes.find(n => n.id === nodeId);
+        if (!this.isRouterNode(node)) return;
+
+        const branches = node.querySelector('.router-branches');
+        const outgoing = this.connections.filter(conn => conn.from === nodeId);
+        branches.innerHTML = '';
+
+        if (outgoing.length === 0) {
+            branches.innerHTML = '<div class="router-branch-empty">Connect this node to add branches</div>';
+            return;
+        }
+
+        outgoing.forEach(conn => {
+            const routeKey = `${conn.from}-${conn.to}`;
+            const condition = this.taskRoutes.get(routeKey)?.condition || { kind: 'default' };
+            const targetName = this.nodes.find(n => n.id === conn.to)?.querySelector('.node-header span')?.textContent || conn.to;
+
+            const row = document.createElement('div');
+            row.className = 'router-branch';
+            row.innerHTML = `
+                <div class="router-branch-target">→ ${targetName}</div>
+                <select class="node-select branch-kind">
+                    <option value="default">Default</option>
+                    <option value="regex">Regex match</option>
+                    <option value="json_field">JSON field</option>
+                    <option value="label">Classifier label</option>
+                </select>
+                <input type="text" class="param-input branch-pattern" placeholder="Pattern / field path / label">
+                <select class="node-select branch-op">
+                    <option value="eq">=</option>
+                    <option value="ne">≠</option>
+                    <option value="gt">&gt;</option>
+                    <option value="gte">≥</option>
+                    <option value="lt">&lt;</option>
+                    <option value="lte">≤</option>
+                    <option value="contains">contains</option>
+                    <option value="exists">exists</option>
+                </select>
+                <input type="text" class="param-input branch-value" placeholder="Value (JSON)">
+            `;
+
+            const kindSelect = row.querySelector('.branch-kind');
+            const patternInput = row.querySelector('.branch-pattern');
+            const opSelect = row.querySelector('.branch-op');
+            const valueInput = row.querySelector('.branch-value');
+
+            kindSelect.value = condition.kind;
+            patternInput.value = condition.pattern ?? condition.path ?? condition.label ?? '';
+            opSelect.value = condition.op ?? 'eq';
+            valueInput.value = condition.value === undefined ? '' : JSON.stringify(condition.value);
+
+            const syncVisibility = () => {
+                patternInput.style.display = kindSelect.value === 'default' ? 'none' : '';
+                opSelect.style.display = kindSelect.value === 'json_field' ? '' : 'none';
+                valueInput.style.display = kindSelect.value === 'json_field' ? '' : 'none';
+            };
+
+            const save = () => {
+                syncVisibility();
+                this.setTaskRoute(conn.from, conn.to, {
+                    ...this.taskRoutes.get(routeKey),
+                    condition: this.readBranchCondition(kindSelect.value, patternInput.value, opSelect.value, valueInput.value)
+                });
+            };
+
+            [kindSelect, opSelect].forEach(el => el.addEventListener('change', save));
+            [patternInput, valueInput].forEach(el => el.addEventListener('input', save));
+            syncVisibility();
+
+            branches.appendChild(row);
+        });
+    }
+
+    readBranchCondition(kind, pattern, op, rawValue) {
+        switch (kind) {
+            case 'regex':
+                return { kind, pattern };
+            case 'label':
+                return { kind, label: pattern.trim() };
+            case 'json_field': {
+                let value = rawValue;
+                try {
+                    value = JSON.parse(rawValue);
+                } catch {
+                    // Plain text is compared as a string
+                }
+                return { kind, path: pattern.trim(), op, value };
+            }
+            default:
+                return { kind: 'default' };
+        }
+    }
+
+    createIterationSection(type) {
+        const section = document.createElement('div');
+        section.className = 'params-container iteration-settings';
+
+        if (type === 'map') {
+            section.innerHTML = `
+                <div class="params-header">Map</div>
+                <div class="param-item">
+                    <label>Concurrency:</label>
+                    <input type="number" value="4" min="1" max="32" class="param-input iter-concurrency">
+                </div>
+                <div class="param-item">
+                    <label>Max Items:</label>
+                    <input type="number" value="10" min="1" class="param-input iter-limit">
+                </div>
+            `;
+        } else {
+            section.innerHTML = `
+                <div class="params-header">Loop Until</div>
+                <select class="node-select until-kind">
+                    <option value="regex">Regex match</option>
+                    <option value="json_field">JSON field</option>
+                </select>
+                <input type="text" class="param-input until-pattern" placeholder="Pattern / field path">
+                <select class="node-select until-op" style="display: none">
+                    <option value="eq">=</option>
+                    <option value="ne">≠</option>
+                    <option value="gt">&gt;</option>
+                    <option value="gte">≥</option>
+                    <option value="lt">&lt;</option>
+                    <option value="lte">≤</option>
+                    <option value="contains">contains</option>
+                    <option value="exists">exists</option>
+                </select>
+                <input type="text" class="param-input until-value" placeholder="Value (JSON)" style="display: none">
+                <div class="param-item">
+                    <label>Max Iterations:</label>
+                    <input type="number" value="10" min="1" class="param-input iter-limit">
+                </div>
+            `;
+
+            const kindSelect = section.querySelector('.until-kind');
+            kindSelect.addEventListener('change', () => {
+                const isJson = kindSelect.value === 'json_field';
+                section.querySelector('.until-op').style.display = isJson ? '' : 'none';
+                section.querySelector('.until-value').style.display = isJson ? '' : 'none';
+            });
+        }
+
+        return section;
+    }
+
     createToolsSection() {
         const toolsContainer = document.createElement('div');
         toolsContainer.className = 'tools-container';
@@ -525,11 +1046,323 @@ class ReteEditor {
                 <label><input type="checkbox" value="code_analysis"> Code Analysis</label>
                 <label><input type="checkbox" value="file_operations"> File Operations</label>
                 <label><input type="checkbox" value="data_processing"> Data Processing</label>
+                <label><input type="checkbox" value="shell"> Shell Commands</label>
+                <label>Max steps: <input type="number" class="tool-steps" value="8" min="1" max="50"></label>
             </div>
         `;
+        this.addMcpToolGroups(toolsContainer.querySelector('.tools-list'));
         return toolsContainer;
     }
 
+    // Optional JSON Schema the node's reply must match; validated output flows on as JSON
+    createSchemaSection() {
+        const section = document.createElement('div');
+        section.className = 'params-container schema-settings';
+        section.innerHTML = `
+            <div class="params-header">Structured Output</div>
+            <textarea class="node-input output-schema" placeholder='JSON Schema, e.g. {"type": "object", "properties": {...}}'></textarea>
+            <div class="param-item">
+                <label>Repair Attempts:</label>
+                <input type="number" value="2" min="0" max="10" class="param-input schema-repairs">
+            </div>
+            <div class="schema-error"></div>
+        `;
+
+        const schemaInput = section.querySelector('.output-schema');
+        const error = section.querySelector('.schema-error');
+        schemaInput.addEventListener('input', () => {
+            try {
+                if (schemaInput.value.trim()) JSON.parse(schemaInput.value);
+                error.textContent = '';
+            } catch (e) {
+                error.textContent = `Invalid JSON: ${e.message}`;
+            }
+        });
+        return section;
+    }
+
+    // Images sent with the task: picked, pasted or dropped onto the node
+    createImageSection() {
+        const section = document.createElement('div');
+        section.className = 'params-container image-settings';
+        section.innerHTML = `
+            <div class="params-header">Images</div>
+            <div class="image-list"></div>
+            <button class="image-add-btn">Add Image</button>
+            <span class="image-hint">or paste / drop onto the node</span>
+            <input type="file" class="image-file" accept="image/png,image/jpeg,image/gif,image/webp" multiple hidden>
+            <div class="image-error"></div>
+        `;
+
+        const fileInput = section.querySelector('.image-file');
+        section.querySelector('.image-add-btn').addEventListener('click', () => fileInput.click());
+        fileInput.addEventListener('change', async () => {
+            await this.attachImages(section.closest('.rete-node'), [...fileInput.files]);
+            fileInput.value = '';
+        });
+        return section;
+    }
+
+    setupImageInput(node) {
+        this.nodeImages.set(node.id, []);
+        node.addEventListener('dragover', (e) => {
+            if (e.dataTransfer?.types.includes('Files')) {
+                e.preventDefault();
+                node.classList.add('image-drop');
+            }
+        });
+        node.addEventListener('dragleave', () => node.classList.remove('image-drop'));
+        node.addEventListener('drop', (e) => {
+            node.classList.remove('image-drop');
+            const files = [...(e.dataTransfer?.files || [])];
+            if (files.length) {
+                e.preventDefault();
+                this.attachImages(node, files);
+            }
+        });
+        node.addEventListener('paste', (e) => {
+            const files = [...(e.clipboardData?.items || [])]
+                .filter(item => item.kind === 'file' && item.type.startsWith('image/'))
+                .map(item => item.getAsFile());
+            if (files.length) {
+                e.preventDefault();
+                this.attachImages(node, files);
+            }
+        });
+        const modelSelect = node.querySelectorAll('.node-select')[1];
+        modelSelect?.addEventListener('change', () => this.checkVisionSupport(node));
+    }
+
+    readFileAsDataUrl(file) {
+        return new Promise((resolve, reject) => {
+            const reader = new FileReader();
+            reader.onload = () => resolve(reader.result);
+            reader.onerror = () => reject(reader.error);
+            reader.readAsDataURL(file);
+        });
+    }
+
+    // The backend checks format and size before an image is kept
+    async attachImages(node, files) {
+        const { invoke } = window.__TAURI__.core;
+        const error = node.querySelector('.image-error');
+        const errors = [];
+        for (const file of files) {
+            try {
+                const data = await this.readFileAsDataUrl(file);
+                const image = await invoke('check_image', { name: file.name || 'pasted image', data });
+                this.nodeImages.get(node.id).push(image);
+            } catch (e) {
+                errors.push(String(e));
+            }
+        }
+        error.textContent = errors.join('\n');
+        this.renderNodeImages(node);
+        await this.checkVisionSupport(node);
+    }
+
+    renderNodeImages(node) {
+        const list = node.querySelector('.image-list');
+        list.innerHTML = '';
+        this.nodeImages.get(node.id).forEach((image, index) => {
+            const item = document.createElement('div');
+            item.className = 'image-item';
+            item.title = `${image.name} (${(image.size / 1024).toFixed(1)} KB)`;
+            item.innerHTML = `
+                <img src="data:${image.media_type};base64,${image.data}" alt="">
+                <button class="image-remove-btn" title="Remove">×</button>
+            `;
+            item.querySelector('.image-remove-btn').addEventListener('click', () => {
+                this.nodeImages.get(node.id).splice(index, 1);
+                this.renderNodeImages(node);
+                this.checkVisionSupport(node);
+            });
+            list.appendChild(item);
+        });
+    }
+
+    // Warn when images are attached to a model that cannot see them
+    async checkVisionSupport(node) {
+        const [serverSelect, modelSelect] = node.querySelectorAll('.node-select');
+        const error = node.querySelector('.image-error');
+        node.querySelector('.image-warning')?.remove();
+        if (!this.nodeImages.get(node.id)?.length || !serverSelect?.value || !modelSelect?.value) return;
+        try {
+            const { invoke } = window.__TAURI__.core;
+            const info = await invoke('get_model_info', { serverUrl: serverSelect.value, model: modelSelect.value });
+            if (info.vision === false) {
+                const warning = document.createElement('div');
+                warning.className = 'image-warning';
+                warning.textContent = `${modelSelect.value} does not accept images`;
+                error.before(warning);
+            }
+        } catch (e) {
+            console.warn('Could not check vision support:', e);
+        }
+    }
+
+    // Reference a prompt from the library instead of typing one in
+    createPromptSection() {
+        const section = document.createElement('div');
+        section.className = 'params-container prompt-ref-settings';
+        section.innerHTML = `
+            <div class="params-header">Library Prompt</div>
+            <select class="node-select prompt-ref-id">
+                <option value="">None</option>
+            </select>
+            <select class="node-select prompt-ref-version" style="display: none;">
+                <option value="">Latest</option>
+            </select>
+            <div class="prompt-ref-vars"></div>
+        `;
+        const select = section.querySelector('.prompt-ref-id');
+        const version = section.querySelector('.prompt-ref-version');
+        select.addEventListener('focus', () => this.loadPromptOptions(select));
+        select.addEventListener('change', () => this.selectNodePrompt(section, true));
+        version.addEventListener('change', () => this.selectNodePrompt(section, false));
+        return section;
+    }
+
+    async loadPromptOptions(select) {
+        try {
+            const { invoke } = window.__TAURI__.core;
+            const prompts = await invoke('list_prompts', { search: null, tag: null });
+            const current = select.value;
+            select.innerHTML = '<option value="">None</option>';
+            for (const prompt of prompts) {
+                const option = document.createElement('option');
+                option.value = prompt.id;
+                option.textContent = `${prompt.name} (v${prompt.version})`;
+                select.appendChild(option);
+            }
+            select.value = current;
+        } catch (error) {
+            console.error('Failed to list prompts:', error);
+        }
+    }
+
+    // Show the versions of the chosen prompt and one input per variable
+    async selectNodePrompt(section, reloadVersions) {
+        const id = section.querySelector('.prompt-ref-id').value;
+        const versionSelect = section.querySelector('.prompt-ref-version');
+        const vars = section.querySelector('.prompt-ref-vars');
+        const values = Object.fromEntries([...vars.querySelectorAll('input')].map(input => [input.dataset.name, input.value]));
+        versionSelect.style.display = id ? 'block' : 'none';
+        vars.innerHTML = '';
+        if (!id) return;
+
+        try {
+            const { invoke } = window.__TAURI__.core;
+            if (reloadVersions) {
+                const versions = await invoke('list_prompt_versions', { id });
+                versionSelect.innerHTML = '<option value="">Latest</option>';
+                for (const prompt of versions) {
+                    const option = document.createElement('option');
+                    option.value = prompt.version;
+                    option.textContent = `v${prompt.version} · ${new Date(prompt.created_at * 1000).toLocaleDateString()}`;
+                    versionSelect.appendChild(option);
+                }
+            }
+            const version = parseInt(versionSelect.value, 10) || null;
+            const prompt = await invoke('get_prompt', { id, version });
+            for (const variable of prompt.variables) {
+                const row = document.createElement('div');
+                row.className = 'param-item';
+                const label = document.createElement('label');
+                label.textContent = `${variable.name}:`;
+                label.title = variable.description;
+                const input = document.createElement('input');
+                input.type = 'text';
+                input.className = 'param-input';
+                input.dataset.name = variable.name;
+                input.placeholder = variable.default ?? 'required';
+                input.value = values[variable.name] || '';
+                row.appendChild(label);
+                row.appendChild(input);
+                vars.appendChild(row);
+            }
+        } catch (error) {
+            console.error('Failed to load prompt:', error);
+        }
+    }
+
+    readPromptRef(node) {
+        const id = node.querySelector('.prompt-ref-id')?.value;
+        if (!id) return null;
+        const variables = {};
+        node.querySelectorAll('.prompt-ref-vars input').forEach(input => {
+            if (input.value) variables[input.dataset.name] = input.value;
+        });
+        return {
+            id,
+            version: parseInt(node.querySelector('.prompt-ref-version').value, 10) || null,
+            variables
+        };
+    }
+
+    // What to do when a request would overflow the model's context window
+    createContextSection() {
+        const section = document.createElement('div');
+        section.className = 'params-container context-settings';
+        section.innerHTML = `
+            <div class="params-header">Context Window</div>
+            <select class="node-select context-strategy">
+                <option value="drop_oldest">Drop oldest turns</option>
+                <option value="summarize_middle">Summarize middle turns</option>
+                <option value="truncate_chunks">Truncate retrieved chunks</option>
+            </select>
+            <div class="param-item">
+                <label>Reserve Tokens:</label>
+                <input type="number" value="1024" min="0" class="param-input context-reserve">
+            </div>
+            <div class="param-item">
+                <label>Context Length:</label>
+                <input type="number" min="256" placeholder="From model" class="param-input context-length">
+            </div>
+        `;
+        return section;
+    }
+
+    readContextConfig(node) {
+        const reserve = parseInt(node.querySelector('.context-reserve')?.value, 10);
+        return {
+            strategy: node.querySelector('.context-strategy')?.value || 'drop_oldest',
+            reserve_tokens: Number.isNaN(reserve) ? 1024 : reserve,
+            context_length: parseInt(node.querySelector('.context-length')?.value, 10) || null
+        };
+    }
+
+    readOutputSchema(node) {
+        const text = node.querySelector('.output-schema')?.value.trim();
+        if (!text) return null;
+        try {
+            return JSON.parse(text);
+        } catch (e) {
+            this.setNodeStatus(node, 'error', 'Output schema is not valid JSON');
+            throw new Error(`Output schema of node ${node.id} is not valid JSON: ${e.message}`);
+        }
+    }
+
+    // One checkbox per connected MCP server, enabling all of its tools
+    async addMcpToolGroups(list) {
+        try {
+            const { invoke } = window.__TAURI__.core;
+            const tools = await invoke('list_tools');
+            const groups = [...new Set(tools.map(tool => tool.group).filter(group => group.startsWith('mcp:')))];
+            const steps = list.querySelector('.tool-steps').closest('label');
+            for (const group of groups) {
+                const label = document.createElement('label');
+                const checkbox = document.createElement('input');
+                checkbox.type = 'checkbox';
+                checkbox.value = group;
+                label.append(checkbox, ` MCP: ${group.slice('mcp:'.length)}`);
+                list.insertBefore(label, steps);
+            }
+        } catch (error) {
+            console.error('Failed to list MCP tools:', error);
+        }
+    }
+
     createParametersSection() {
         const paramsContainer = document.createElement('div');
         paramsContainer.className = 'params-container';
@@ -562,6 +1395,7 @@ class ReteEditor {
                 const node = document.createElement('div');
                 node.className = 'rete-node';
                 node.id = `node-${this.nextId++}`;
+                node.dataset.type = type;
                 
                 // Set position
                 node.style.left = `${Math.random() * (this.container.clientWidth - 300)}px`;
@@ -580,6 +1414,7 @@ class ReteEditor {
                 controls.className = 'node-controls';
                 controls.innerHTML = `
                     <button class="node-btn run-btn" title="Run Node">▶</button>
+                    <button class="node-btn pin-btn" title="Keep this node's output when resuming">📌</button>
                     <button class="node-btn delete-btn" title="Delete Node">×</button>
                 `;
 
@@ -607,8 +1442,13 @@ class ReteEditor {
 
                 // Setup event listeners
                 controls.querySelector('.run-btn').addEventListener('click', () => this.runNode(node));
+                controls.querySelector('.pin-btn').addEventListener('click', () => node.classList.toggle('pinned'));
                 controls.querySelector('.delete-btn').addEventListener('click', () => this.deleteNode(node));
 
+                if (['llm', 'map', 'while'].includes(type)) {
+                    this.setupImageInput(node);
+                }
+
                 // Make node draggable
                 this.makeDraggable(node);
 
@@ -675,6 +1515,7 @@ class ReteEditor {
 
         outputPoint.addEventListener('mousedown', (e) => {
             isConnecting = true;
+            this.pendingConnection = { from: node.id };
             const rect = outputPoint.getBoundingClientRect();
             const startX = rect.left + rect.width / 2 - this.container.getBoundingClientRect().left;
             const startY = rect.top + rect.height / 2 - this.container.getBoundingClientRect().top;
@@ -696,20 +1537,24 @@ class ReteEditor {
                 }
                 isConnecting = false;
                 tempLine = null;
+                this.pendingConnection = null;
             };
 
             document.addEventListener('mouseup', cleanup, { once: true });
         });
 
         inputPoint.addEventListener('mouseup', (e) => {
-            if (isConnecting && tempLine) {
+            const pending = this.pendingConnection;
+            if (pending && pending.from !== node.id &&
+                !this.connections.some(conn => conn.from === pending.from && conn.to === node.id)) {
                 // Create permanent connection
                 const connection = {
-                    from: tempLine.dataset.fromNode,
+                    from: pending.from,
                     to: node.id
                 };
                 this.connections.push(connection);
                 this.drawConnections();
+                this.refreshRouterBranches(pending.from);
             }
         });
     }
@@ -887,17 +1732,1339 @@ class ReteEditor {
         this.outputBuffers.delete(nodeId);
     }
 
-    deleteNode(node) {
-        // Remove connections involving this node
+    // Describe a node in the shape the backend workflow executor expects
+    serializeNode(node) {
+        const type = node.dataset.type || 'llm';
+
+        if (type === 'router') {
+            const mode = node.querySelector('.router-mode').value;
+            const classifier = mode === 'classifier' ? {
+                server_url: node.querySelector('.classifier-server').value,
+                model: node.querySelector('.classifier-model').value,
+                labels: node.querySelector('.classifier-labels').value
+                    .split(',')
+                    .map(label => label.trim())
+                    .filter(Boolean)
+            } : null;
+            return { id: node.id, type, classifier };
+        }
+
+        if (type === 'retriever') {
+            return {
+                id: node.id,
+                type,
+                knowledge_base: node.querySelector('.retriever-kb').value,
+                query: node.querySelector('.retriever-query').value,
+                top_k: parseInt(node.querySelector('.retriever-top-k').value, 10) || 5,
+                mode: node.querySelector('.retriever-mode').value,
+                rerank: this.readRerankConfig(node)
+            };
+        }
+
+        if (type === 'memory') {
+            const server = node.querySelector('.memory-embedding-server').value;
+            return {
+                id: node.id,
+                type,
+                operation: node.querySelector('.memory-operation').value,
+                scope: node.querySelector('.memory-scope').value,
+                kind: node.querySelector('.memory-kind').value,
+                key: node.querySelector('.memory-key').value,
+                query: node.querySelector('.memory-query').value,
+                kinds: [...node.querySelectorAll('.memory-kinds input:checked')].map(box => box.value),
+                top_k: parseInt(node.querySelector('.memory-top-k').value, 10) || 5,
+                embedding: server
+                    ? { server_url: server, model: node.querySelector('.memory-embedding-model').value.trim() }
+                    : null
+            };
+        }
+
+        const [serverSelect, modelSelect] = node.querySelectorAll('.node-select');
+        const taskInput = node.querySelector('.node-input');
+        const tempSlider = node.querySelector('.param-slider');
+
+        switch (type) {
+            case 'input':
+                return { id: node.id, type, value: taskInput?.value || '' };
+            case 'output':
+                return { id: node.id, type };
+        }
+
+        const llm = {
+            id: node.id,
+            type: 'llm',
+            server_url: serverSelect?.value || '',
+            model: modelSelect?.value || '',
+            prompt: taskInput?.value || '',
+            system: node.querySelector('.node-system')?.value || '',
+            prompt_ref: this.readPromptRef(node),
+            images: this.nodeImages.get(node.id) || [],
+            temperature: parseFloat(tempSlider?.value ?? '70') / 100,
+            tools: [...node.querySelectorAll('.tools-list input[type="checkbox"]:checked')].map(cb => cb.value),
+            max_tool_steps: parseInt(node.querySelector('.tool-steps')?.value, 10) || 8,
+            output_schema: this.readOutputSchema(node),
+            max_repairs: parseInt(node.querySelector('.schema-repairs')?.value, 10) || 0,
+            context: this.readContextConfig(node)
+        };
+        const limit = parseInt(node.querySelector('.iter-limit')?.value, 10) || 10;
+
+        switch (type) {
+            case 'map':
+                return {
+                    ...llm,
+                    type,
+                    concurrency: parseInt(node.querySelector('.iter-concurrency').value, 10) || 1,
+                    max_items: limit
+                };
+            case 'while':
+                return {
+                    ...llm,
+                    type,
+                    until: this.readBranchCondition(
+                        node.querySelector('.until-kind').value,
+                        node.querySelector('.until-pattern').value,
+                        node.querySelector('.until-op').value,
+                        node.querySelector('.until-value').value
+                    ),
+                    max_iterations: limit
+                };
+            default:
+                return llm;
+        }
+    }
+
+    serializeWorkflow() {
+        return {
+            id: 'canvas',
+            name: 'Canvas workflow',
+            nodes: this.nodes.map(node => this.serializeNode(node)),
+            edges: this.connections.map(conn => ({
+                from: conn.from,
+                to: conn.to,
+                condition: this.taskRoutes.get(`${conn.from}-${conn.to}`)?.condition || null
+            }))
+        };
+    }
+
+    // Copy the backend's per-iteration buffer of a Map/While node into the buffer log
+    async logIterations(node, runId) {
+        if (!['map', 'while'].includes(node.dataset.type)) return;
+
+        try {
+            const { invoke } = window.__TAURI__.core;
+            const records = await invoke('get_iteration_buffer', { nodeId: node.id });
+            records
+                .filter(record => record.run_id === runId)
+                .forEach(record => this.logBufferOperation(node.id, 'iteration', record));
+        } catch (error) {
+            console.error('Failed to load iteration buffer:', error);
+        }
+    }
+
+    setNodeStatus(node, status, text) {
+        node.querySelector('.node-status')?.remove();
+        node.classList.remove('processing', 'skipped');
+        if (status === 'running') {
+            node.classList.add('processing');
+        } else if (status === 'skipped') {
+            node.classList.add('skipped');
+        }
+
+        const statusIndicator = document.createElement('div');
+        statusIndicator.className = `node-status ${status}`;
+        statusIndicator.textContent = text;
+        node.appendChild(statusIndicator);
+    }
+
+    // Run the whole canvas in the backend executor; routers pick branches there
+    async runWorkflow() {
+        const workflow = this.serializeWorkflow();
+        if (workflow.nodes.length === 0) return;
+
+        this.nodes.forEach(node => this.setNodeStatus(node, 'running', 'Queued...'));
+
+        this.startApprovalPolling();
+        try {
+            const { invoke } = window.__TAURI__.core;
+            const run = await invoke('execute_workflow', { workflow });
+            await this.applyRunResults(run);
+        } catch (error) {
+            console.error('Workflow execution error:', error);
+            this.nodes.forEach(node => this.setNodeStatus(node, 'error', `Error: ${error.message || error}`));
+        } finally {
+            this.stopApprovalPolling();
+        }
+    }
+
+    // Re-run a node and everything after it, reusing the last run's other outputs
+    async resumeWorkflow(fromNode) {
+        if (!this.lastRunId) return;
+
+        const workflow = this.serializeWorkflow();
+        const pinned = this.nodes.filter(n => n.classList.contains('pinned')).map(n => n.id);
+        this.setNodeStatus(fromNode, 'running', 'Resuming...');
+
+        this.startApprovalPolling();
+        try {
+            const { invoke } = window.__TAURI__.core;
+            const run = await invoke('resume_workflow', {
+                runId: this.lastRunId,
+                fromNode: fromNode.id,
+                pinned,
+                workflow
+            });
+            await this.applyRunResults(run);
+        } catch (error) {
+            console.error('Workflow resume error:', error);
+            this.setNodeStatus(fromNode, 'error', `Error: ${error.message || error}`);
+        } finally {
+            this.stopApprovalPolling();
+        }
+    }
+
+    // Tool actions such as file writes wait in the backend until answered here
+    startApprovalPolling() {
+        if (this.approvalPoll) return;
+        this.approvalPoll = setInterval(() => this.refreshApprovals(), 1000);
+    }
+
+    stopApprovalPolling() {
+        clearInterval(this.approvalPoll);
+        this.approvalPoll = null;
+        this.refreshApprovals();
+    }
+
+    async refreshApprovals() {
+        const content = this.modals.get('approvals')?.querySelector('.approvals-content');
+        if (!content) return;
+
+        let requests = [];
+        try {
+            const { invoke } = window.__TAURI__.core;
+            requests = await invoke('list_approvals');
+        } catch (error) {
+            console.error('Failed to list approvals:', error);
+            return;
+        }
+
+        const shown = [...content.querySelectorAll('.approval-request')].map(el => el.dataset.id);
+        if (content.children.length && shown.join() === requests.map(r => r.id).join()) return;
+
+        content.innerHTML = '';
+        if (requests.length === 0) {
+            content.innerHTML = '<div class="approvals-empty">Nothing is waiting for approval</div>';
+            return;
+        }
+
+        for (const request of requests) {
+            const item = document.createElement('div');
+            item.className = 'approval-request';
+            item.dataset.id = request.id;
+            item.innerHTML = `
+                <div class="approval-title"></div>
+                <div class="approval-meta"></div>
+                <pre class="approval-detail"></pre>
+                <div class="approval-actions">
+                    <button class="approve-btn">Approve</button>
+                    <button class="reject-btn">Reject</button>
+                </div>
+            `;
+            item.querySelector('.approval-title').textContent = request.title;
+            const nodeName = this.nodes.find(n => n.id === request.node_id)?.querySelector('.node-header span')?.textContent || request.node_id;
+            item.querySelector('.approval-meta').textContent = `${request.tool} · ${nodeName}`;
+            item.querySelector('.approval-detail').textContent = request.detail;
+            item.querySelector('.approve-btn').addEventListener('click', () => this.resolveApproval(request.id, true));
+            item.querySelector('.reject-btn').addEventListener('click', () => this.resolveApproval(request.id, false));
+            content.appendChild(item);
+        }
+
+        if (!this.isModalVisible('approvals')) {
+            this.toggleModal('approvals');
+        }
+    }
+
+    async resolveApproval(id, approved) {
+        try {
+            const { invoke } = window.__TAURI__.core;
+            await invoke('resolve_approval', { id, approved });
+        } catch (error) {
+            console.error('Failed to resolve approval:', error);
+        }
+        this.refreshApprovals();
+    }
+
+    // Save the canvas as a named workflow; published ones are served as MCP tools
+    setupWorkflowsModal(modal) {
+        const content = modal.querySelector('.modal-content-body');
+        content.innerHTML = `
+            <div class="workflow-save">
+                <input type="text" class="workflow-name" placeholder="Workflow name">
+                <textarea class="workflow-description" rows="2" placeholder="What the workflow does (shown to MCP clients)"></textarea>
+                <label><input type="checkbox" class="workflow-published"> Publish as MCP tool</label>
+                <button class="workflow-save-btn">Save Canvas</button>
+                <div class="workflow-error"></div>
+            </div>
+            <div class="workflow-list"></div>
+        `;
+        content.querySelector('.workflow-save-btn').addEventListener('click', () => this.saveWorkflow(content));
+        this.refreshWorkflows();
+    }
+
+    async saveWorkflow(content) {
+        const name = content.querySelector('.workflow-name').value.trim();
+        const error = content.querySelector('.workflow-error');
+        if (!name || this.nodes.length === 0) {
+            error.textContent = name ? 'The canvas is empty' : 'Give the workflow a name';
+            return;
+        }
+
+        const slug = name.toLowerCase().replace(/[^a-z0-9]+/g, '-').replace(/^-|-$/g, '');
+        const workflow = { ...this.serializeWorkflow(), id: `wf-${slug || Date.now()}`, name };
+        try {
+            const { invoke } = window.__TAURI__.core;
+            await invoke('save_workflow', {
+                workflow: {
+                    workflow,
+                    description: content.querySelector('.workflow-description').value.trim(),
+                    published: content.querySelector('.workflow-published').checked
+                }
+            });
+            error.textContent = '';
+        } catch (e) {
+            error.textContent = `Failed to save: ${e.message || e}`;
+        }
+        this.refreshWorkflows();
+    }
+
+    async refreshWorkflows() {
+        const list = this.modals.get('workflows')?.querySelector('.workflow-list');
+        if (!list) return;
+
+        let saved = [];
+        try {
+            const { invoke } = window.__TAURI__.core;
+            saved = await invoke('list_workflows');
+        } catch (error) {
+            console.error('Failed to list workflows:', error);
+        }
+
+        list.innerHTML = saved.length ? '' : '<div class="approvals-empty">No saved workflows</div>';
+        for (const entry of saved) {
+            const item = document.createElement('div');
+            item.className = 'workflow-item';
+            item.innerHTML = `
+                <div class="workflow-item-name"></div>
+                <div class="workflow-item-meta"></div>
+                <div class="workflow-item-actions">
+                    <label><input type="checkbox" class="workflow-publish-toggle"> MCP tool</label>
+                    <button class="workflow-delete-btn">Delete</button>
+                </div>
+            `;
+            item.querySelector('.workflow-item-name').textContent = entry.workflow.name;
+            item.querySelector('.workflow-item-meta').textContent =
+                `${entry.workflow.nodes.length} nodes · ${entry.description || 'No description'}`;
+            const toggle = item.querySelector('.workflow-publish-toggle');
+            toggle.checked = entry.published;
+            toggle.addEventListener('change', () => this.publishWorkflow(entry, toggle.checked));
+            item.querySelector('.workflow-delete-btn').addEventListener('click', () => this.deleteWorkflow(entry.workflow.id));
+            list.appendChild(item);
+        }
+    }
+
+    async publishWorkflow(entry, published) {
+        try {
+            const { invoke } = window.__TAURI__.core;
+            await invoke('save_workflow', { workflow: { ...entry, published } });
+        } catch (error) {
+            console.error('Failed to update workflow:', error);
+        }
+        this.refreshWorkflows();
+    }
+
+    async deleteWorkflow(id) {
+        try {
+            const { invoke } = window.__TAURI__.core;
+            await invoke('delete_workflow', { id });
+        } catch (error) {
+            console.error('Failed to delete workflow:', error);
+        }
+        this.refreshWorkflows();
+    }
+
+    // Folders of documents chunked and embedded for Retriever nodes
+    setupKnowledgeModal(modal) {
+        const content = modal.querySelector('.modal-content-body');
+        content.innerHTML = `
+            <div class="knowledge-form">
+                <input type="text" class="kb-name" placeholder="Name">
+                <textarea class="kb-folders" rows="2" placeholder="Folders, one per line"></textarea>
+                <select class="kb-server">
+                    <option value="http://localhost:11434">Ollama</option>
+                    <option value="http://localhost:1234/v1">LM Studio</option>
+                </select>
+                <input type="text" class="kb-model" placeholder="Embedding model, e.g. nomic-embed-text">
+                <div class="kb-chunking">
+                    <label>Chunk size <input type="number" class="kb-chunk-size" value="1200" min="100"></label>
+                    <label>Overlap <input type="number" class="kb-chunk-overlap" value="200" min="0"></label>
+                </div>
+                <button class="kb-save-btn">Add Knowledge Base</button>
+                <div class="workflow-error kb-error"></div>
+            </div>
+            <div class="knowledge-list"></div>
+        `;
+        content.querySelector('.kb-save-btn').addEventListener('click', () => this.saveKnowledgeBase(content));
+        this.refreshKnowledgeBases();
+    }
+
+    async saveKnowledgeBase(content) {
+        const error = content.querySelector('.kb-error');
+        const config = {
+            name: content.querySelector('.kb-name').value.trim(),
+            folders: content.querySelector('.kb-folders').value.split('\n').map(f => f.trim()).filter(Boolean),
+            server_url: content.querySelector('.kb-server').value,
+            embedding_model: content.querySelector('.kb-model').value.trim(),
+            chunk_size: parseInt(content.querySelector('.kb-chunk-size').value, 10) || 1200,
+            chunk_overlap: parseInt(content.querySelector('.kb-chunk-overlap').value, 10) || 0
+        };
+        if (!config.embedding_model || config.folders.length === 0) {
+            error.textContent = 'Add at least one folder and an embedding model';
+            return;
+        }
+        try {
+            const { invoke } = window.__TAURI__.core;
+            const saved = await invoke('save_knowledge_base', { config });
+            error.textContent = '';
+            content.querySelector('.kb-name').value = '';
+            content.querySelector('.kb-folders').value = '';
+            await this.refreshKnowledgeBases();
+            await this.ingestKnowledgeBase(saved.id);
+        } catch (e) {
+            error.textContent = `Failed to save: ${e.message || e}`;
+        }
+    }
+
+    async refreshKnowledgeBases() {
+        const list = this.modals.get('knowledge')?.querySelector('.knowledge-list');
+        if (!list) return;
+
+        let bases = [];
+        try {
+            const { invoke } = window.__TAURI__.core;
+            bases = await invoke('list_knowledge_bases');
+        } catch (error) {
+            console.error('Failed to list knowledge bases:', error);
+        }
+
+        list.innerHTML = bases.length ? '' : '<div class="approvals-empty">No knowledge bases</div>';
+        for (const base of bases) {
+            const item = document.createElement('div');
+            item.className = 'workflow-item';
+            item.innerHTML = `
+                <div class="workflow-item-name"></div>
+                <div class="workflow-item-meta kb-meta"></div>
+                <div class="workflow-item-meta kb-folders-list"></div>
+                <div class="kb-search">
+                    <input type="text" class="kb-query" placeholder="Try a query">
+                    <button class="kb-search-btn">Search</button>
+                </div>
+                <div class="kb-results"></div>
+                <div class="workflow-item-actions">
+                    <button class="kb-ingest-btn">Sync</button>
+                    <button class="workflow-delete-btn">Delete</button>
+                </div>
+            `;
+            item.querySelector('.workflow-item-name').textContent = base.name;
+            const indexed = base.indexed_at ? new Date(base.indexed_at).toLocaleString() : 'never';
+            item.querySelector('.kb-meta').textContent =
+                `${base.documents} documents · ${base.chunks} chunks · ${base.embedding_model} · indexed ${indexed}`;
+            item.querySelector('.kb-folders-list').textContent = base.folders.join(', ');
+            item.querySelector('.kb-ingest-btn').addEventListener('click', () => this.ingestKnowledgeBase(base.id));
+            item.querySelector('.workflow-delete-btn').addEventListener('click', () => this.deleteKnowledgeBase(base.id));
+            item.querySelector('.kb-search-btn').addEventListener('click', () =>
+                this.searchKnowledgeBase(base.id, item.querySelector('.kb-query').value, item.querySelector('.kb-results')));
+            list.appendChild(item);
+        }
+        document.querySelectorAll('.retriever-kb').forEach(select => this.fillKnowledgeBaseSelect(select));
+    }
+
+    async ingestKnowledgeBase(id) {
+        const error = this.modals.get('knowledge')?.querySelector('.kb-error');
+        if (error) error.textContent = 'Indexing...';
+        try {
+            const { invoke } = window.__TAURI__.core;
+            const report = await invoke('ingest_knowledge_base', { id });
+            if (error) {
+                const summary = `${report.documents} updated, ${report.unchanged} unchanged, ${report.removed} removed`;
+                error.textContent = report.failed.length
+                    ? `${summary}; ${report.failed.length} file(s) failed, see System Logs`
+                    : summary;
+            }
+        } catch (e) {
+            if (error) error.textContent = `Indexing failed: ${e.message || e}`;
+        }
+        this.refreshKnowledgeBases();
+    }
+
+    async searchKnowledgeBase(id, query, results) {
+        if (!query.trim()) return;
+        results.textContent = 'Searching...';
+        try {
+            const { invoke } = window.__TAURI__.core;
+            const hits = await invoke('search_knowledge_base', { id, query, topK: 3 });
+            results.innerHTML = '';
+            hits.forEach((hit, index) => {
+                const entry = document.createElement('div');
+                entry.className = 'kb-hit';
+                entry.textContent = `${this.formatLogData('retrieval', { index, ...hit })} · ${this.truncateText(hit.text)}`;
+                results.appendChild(entry);
+            });
+        } catch (e) {
+            results.textContent = `${e.message || e}`;
+        }
+    }
+
+    async deleteKnowledgeBase(id) {
+        try {
+            const { invoke } = window.__TAURI__.core;
+            await invoke('delete_knowledge_base', { id });
+        } catch (error) {
+            console.error('Failed to delete knowledge base:', error);
+        }
+        this.refreshKnowledgeBases();
+    }
+
+    // Browse, edit and purge what Memory nodes have stored
+    setupMemoryModal(modal) {
+        const content = modal.querySelector('.modal-content-body');
+        content.innerHTML = `
+            <div class="memory-filters">
+                <select class="memory-filter-namespace">
+                    <option value="">All namespaces</option>
+                </select>
+                <select class="memory-filter-kind">
+                    <option value="">All kinds</option>
+                    <option value="fact">Facts</option>
+                    <option value="episode">Episodes</option>
+                    <option value="note">Notes</option>
+                </select>
+                <input type="text" class="memory-filter-search" placeholder="Search">
+            </div>
+            <div class="memory-purge">
+                <label>Older than <input type="number" class="memory-purge-days" min="0" placeholder="any"> days</label>
+                <button class="memory-purge-btn">Purge matching</button>
+            </div>
+            <div class="memory-list"></div>
+        `;
+        content.querySelectorAll('.memory-filters select').forEach(select =>
+            select.addEventListener('change', () => this.refreshMemories()));
+        content.querySelector('.memory-filter-search').addEventListener('input', () => this.refreshMemories());
+        content.querySelector('.memory-purge-btn').addEventListener('click', () => this.purgeMemories());
+        this.refreshMemories();
+    }
+
+    memoryFilter(withAge = false) {
+        const content = this.modals.get('memory').querySelector('.modal-content-body');
+        const days = parseInt(content.querySelector('.memory-purge-days').value, 10);
+        return {
+            namespace: content.querySelector('.memory-filter-namespace').value || null,
+            kind: content.querySelector('.memory-filter-kind').value || null,
+            search: content.querySelector('.memory-filter-search').value || null,
+            older_than_days: withAge && !isNaN(days) ? days : null
+        };
+    }
+
+    async refreshMemories() {
+        const content = this.modals.get('memory')?.querySelector('.modal-content-body');
+        if (!content) return;
+        const { invoke } = window.__TAURI__.core;
+
+        try {
+            const select = content.querySelector('.memory-filter-namespace');
+            const current = select.value;
+            const namespaces = await invoke('list_memory_namespaces');
+            select.innerHTML = '<option value="">All namespaces</option>';
+            for (const info of namespaces) {
+                const option = document.createElement('option');
+                option.value = info.namespace;
+                option.textContent = `${info.namespace} (${info.memories})`;
+                select.appendChild(option);
+            }
+            select.value = namespaces.some(info => info.namespace === current) ? current : '';
+        } catch (error) {
+            console.error('Failed to list memory namespaces:', error);
+        }
+
+        let memories = [];
+        try {
+            memories = await invoke('list_memories', { filter: this.memoryFilter() });
+        } catch (error) {
+            console.error('Failed to list memories:', error);
+        }
+
+        const list = content.querySelector('.memory-list');
+        list.innerHTML = memories.length ? '' : '<div class="approvals-empty">No memories</div>';
+        for (const memory of memories) {
+            const item = document.createElement('div');
+            item.className = 'workflow-item memory-item';
+            item.innerHTML = `
+                <div class="workflow-item-meta memory-meta"></div>
+                <input type="text" class="memory-key-input" placeholder="Key">
+                <textarea class="memory-content-input" rows="2"></textarea>
+                <div class="workflow-item-actions">
+                    <button class="memory-save-btn">Save</button>
+                    <button class="workflow-delete-btn">Delete</button>
+                </div>
+            `;
+            const updated = new Date(memory.updated_at * 1000).toLocaleString();
+            item.querySelector('.memory-meta').textContent = `${memory.kind} · ${memory.namespace} · ${updated}`;
+            const key = item.querySelector('.memory-key-input');
+            key.value = memory.key || '';
+            key.style.display = memory.kind === 'note' ? 'block' : 'none';
+            const text = item.querySelector('.memory-content-input');
+            text.value = memory.content;
+            item.querySelector('.memory-save-btn').addEventListener('click', () =>
+                this.updateMemory(memory.id, text.value, key.value, item));
+            item.querySelector('.workflow-delete-btn').addEventListener('click', () => this.deleteMemory(memory.id));
+            list.appendChild(item);
+        }
+    }
+
+    async updateMemory(id, content, key, item) {
+        try {
+            const { invoke } = window.__TAURI__.core;
+            await invoke('update_memory', { id, content, key: key || null });
+            item.classList.add('saved');
+            setTimeout(() => item.classList.remove('saved'), 1000);
+        } catch (error) {
+            item.querySelector('.memory-meta').textContent = `Failed to save: ${error.message || error}`;
+        }
+    }
+
+    async deleteMemory(id) {
+        try {
+            const { invoke } = window.__TAURI__.core;
+            await invoke('delete_memory', { id });
+        } catch (error) {
+            console.error('Failed to delete memory:', error);
+        }
+        this.refreshMemories();
+    }
+
+    // The first click arms the button, the second purges
+    async purgeMemories() {
+        const button = this.modals.get('memory').querySelector('.memory-purge-btn');
+        const filter = this.memoryFilter(true);
+        if (!button.classList.contains('armed')) {
+            button.classList.add('armed');
+            button.textContent = `Confirm purge of ${filter.namespace || 'all namespaces'}`;
+            setTimeout(() => {
+                button.classList.remove('armed');
+                button.textContent = 'Purge matching';
+            }, 3000);
+            return;
+        }
+        button.classList.remove('armed');
+        button.textContent = 'Purge matching';
+        try {
+            const { invoke } = window.__TAURI__.core;
+            const removed = await invoke('purge_memories', { filter });
+            console.log(`Purged ${removed} memories`);
+        } catch (error) {
+            console.error('Failed to purge memories:', error);
+        }
+        this.refreshMemories();
+    }
+
+    // Edit library prompts; saving a changed prompt adds a new version
+    setupPromptsModal(modal) {
+        const content = modal.querySelector('.modal-content-body');
+        content.innerHTML = `
+            <div class="memory-filters">
+                <input type="text" class="prompt-filter-search" placeholder="Search">
+                <select class="prompt-filter-tag">
+                    <option value="">All tags</option>
+                </select>
+                <button class="prompt-new-btn">New</button>
+            </div>
+            <div class="prompt-list"></div>
+            <div class="prompt-editor">
+                <input type="text" class="prompt-name" placeholder="Name">
+                <input type="text" class="prompt-description" placeholder="Description">
+                <textarea class="prompt-system" rows="3" placeholder="System prompt, may use {{variables}}"></textarea>
+                <textarea class="prompt-user" rows="3" placeholder="User prompt, may use {{input}} and {{variables}}"></textarea>
+                <input type="text" class="prompt-tags" placeholder="Tags, comma separated">
+                <div class="prompt-variables"></div>
+                <div class="workflow-item-actions">
+                    <button class="prompt-save-btn">Save</button>
+                    <button class="workflow-delete-btn prompt-delete-btn">Delete</button>
+                </div>
+                <div class="prompt-status"></div>
+                <div class="prompt-versions"></div>
+                <div class="prompt-usages"></div>
+            </div>
+        `;
+        this.editingPrompt = null;
+        content.querySelector('.prompt-filter-search').addEventListener('input', () => this.refreshPrompts());
+        content.querySelector('.prompt-filter-tag').addEventListener('change', () => this.refreshPrompts());
+        content.querySelector('.prompt-new-btn').addEventListener('click', () => this.editPrompt(null));
+        content.querySelector('.prompt-save-btn').addEventListener('click', () => this.savePrompt());
+        content.querySelector('.prompt-delete-btn').addEventListener('click', () => this.deletePrompt());
+        this.editPrompt(null);
+        this.refreshPrompts();
+    }
+
+    async refreshPrompts() {
+        const content = this.modals.get('prompts')?.querySelector('.modal-content-body');
+        if (!content) return;
+        const { invoke } = window.__TAURI__.core;
+        const tagSelect = content.querySelector('.prompt-filter-tag');
+
+        let prompts = [];
+        try {
+            const all = await invoke('list_prompts', { search: null, tag: null });
+            const tags = [...new Set(all.flatMap(prompt => prompt.tags))].sort();
+            const current = tagSelect.value;
+            tagSelect.innerHTML = '<option value="">All tags</option>';
+            for (const tag of tags) {
+                const option = document.createElement('option');
+                option.value = tag;
+                option.textContent = tag;
+                tagSelect.appendChild(option);
+            }
+            tagSelect.value = tags.includes(current) ? current : '';
+            prompts = await invoke('list_prompts', {
+                search: content.querySelector('.prompt-filter-search').value || null,
+                tag: tagSelect.value || null
+            });
+        } catch (error) {
+            console.error('Failed to list prompts:', error);
+        }
+
+        const list = content.querySelector('.prompt-list');
+        list.innerHTML = prompts.length ? '' : '<div class="approvals-empty">No prompts</div>';
+        for (const prompt of prompts) {
+            const item = document.createElement('div');
+            item.className = 'workflow-item prompt-item';
+            item.classList.toggle('active', prompt.id === this.editingPrompt?.id);
+            item.innerHTML = `
+                <div class="workflow-item-name"></div>
+                <div class="workflow-item-meta"></div>
+            `;
+            item.querySelector('.workflow-item-name').textContent = `${prompt.name} · v${prompt.version}`;
+            const tags = prompt.tags.length ? ` · ${prompt.tags.join(', ')}` : '';
+            item.querySelector('.workflow-item-meta').textContent =
+                `${prompt.versions} versions · ${prompt.total_uses} uses${tags}`;
+            item.addEventListener('click', () => this.editPrompt(prompt));
+            list.appendChild(item);
+        }
+    }
+
+    // Load a prompt version into the editor, or clear it for a new prompt
+    async editPrompt(prompt) {
+        const content = this.modals.get('prompts').querySelector('.modal-content-body');
+        this.editingPrompt = prompt;
+        content.querySelector('.prompt-name').value = prompt?.name || '';
+        content.querySelector('.prompt-description').value = prompt?.description || '';
+        content.querySelector('.prompt-system').value = prompt?.system || '';
+        content.querySelector('.prompt-user').value = prompt?.user || '';
+        content.querySelector('.prompt-tags').value = (prompt?.tags || []).join(', ');
+        content.querySelector('.prompt-status').textContent = '';
+        content.querySelector('.prompt-delete-btn').style.display = prompt ? '' : 'none';
+        content.querySelectorAll('.prompt-item').forEach(item => item.classList.remove('active'));
+
+        const vars = content.querySelector('.prompt-variables');
+        vars.innerHTML = '';
+        for (const variable of prompt?.variables || []) {
+            const row = document.createElement('div');
+            row.className = 'prompt-variable';
+            row.dataset.name = variable.name;
+            row.innerHTML = `
+                <code></code>
+                <input type="text" class="prompt-variable-description" placeholder="Description">
+                <input type="text" class="prompt-variable-default" placeholder="No default">
+            `;
+            row.querySelector('code').textContent = `{{${variable.name}}}`;
+            row.querySelector('.prompt-variable-description').value = variable.description;
+            row.querySelector('.prompt-variable-default').value = variable.default ?? '';
+            vars.appendChild(row);
+        }
+
+        const versions = content.querySelector('.prompt-versions');
+        const usages = content.querySelector('.prompt-usages');
+        versions.innerHTML = '';
+        usages.innerHTML = '';
+        if (!prompt) return;
+
+        try {
+            const { invoke } = window.__TAURI__.core;
+            const [history, used] = await Promise.all([
+                invoke('list_prompt_versions', { id: prompt.id }),
+                invoke('list_prompt_usages', { id: prompt.id })
+            ]);
+            versions.innerHTML = '<div class="params-header">Versions</div>';
+            for (const version of history) {
+                const row = document.createElement('div');
+                row.className = 'prompt-version';
+                row.classList.toggle('active', version.version === prompt.version);
+                const lastUsed = version.last_used_at ? `, last ${new Date(version.last_used_at * 1000).toLocaleString()}` : '';
+                row.textContent = `v${version.version} · ${new Date(version.created_at * 1000).toLocaleString()} · ${version.uses} uses${lastUsed}`;
+                row.addEventListener('click', () => this.editPrompt(version));
+                versions.appendChild(row);
+            }
+            usages.innerHTML = '<div class="params-header">Used by</div>';
+            if (!used.length) {
+                usages.innerHTML += '<div class="approvals-empty">No saved workflows</div>';
+            }
+            for (const usage of used) {
+                const row = document.createElement('div');
+                row.className = 'prompt-usage';
+                row.textContent = `${usage.workflow_name} · node ${usage.node_id} · ${usage.version ? `v${usage.version}` : 'latest'}`;
+                usages.appendChild(row);
+            }
+        } catch (error) {
+            console.error('Failed to load prompt details:', error);
+        }
+    }
+
+    async savePrompt() {
+        const content = this.modals.get('prompts').querySelector('.modal-content-body');
+        const status = content.querySelector('.prompt-status');
+        const draft = {
+            id: this.editingPrompt?.id || null,
+            name: content.querySelector('.prompt-name').value.trim(),
+            description: content.querySelector('.prompt-description').value.trim(),
+            system: content.querySelector('.prompt-system').value,
+            user: content.querySelector('.prompt-user').value,
+            tags: content.querySelector('.prompt-tags').value.split(',').map(tag => tag.trim()).filter(Boolean),
+            variables: [...content.querySelectorAll('.prompt-variable')].map(row => ({
+                name: row.dataset.name,
+                description: row.querySelector('.prompt-variable-description').value,
+                default: row.querySelector('.prompt-variable-default').value || null
+            }))
+        };
+        try {
+            const { invoke } = window.__TAURI__.core;
+            const saved = await invoke('save_prompt', { draft });
+            const created = saved.version !== this.editingPrompt?.version || !this.editingPrompt;
+            await this.editPrompt(saved);
+            status.textContent = created ? `Saved as v${saved.version}` : 'No changes';
+        } catch (error) {
+            status.textContent = `Failed to save: ${error.message || error}`;
+        }
+        this.refreshPrompts();
+    }
+
+    // The first click arms the button, the second deletes every version
+    async deletePrompt() {
+        const button = this.modals.get('prompts').querySelector('.prompt-delete-btn');
+        if (!this.editingPrompt) return;
+        if (!button.classList.contains('armed')) {
+            button.classList.add('armed');
+            button.textContent = 'Delete all versions?';
+            setTimeout(() => {
+                button.classList.remove('armed');
+                button.textContent = 'Delete';
+            }, 3000);
+            return;
+        }
+        button.classList.remove('armed');
+        button.textContent = 'Delete';
+        try {
+            const { invoke } = window.__TAURI__.core;
+            await invoke('delete_prompt', { id: this.editingPrompt.id });
+        } catch (error) {
+            console.error('Failed to delete prompt:', error);
+        }
+        this.editPrompt(null);
+        this.refreshPrompts();
+    }
+
+    // Compare models, parameters and prompts over a JSONL dataset
+    setupEvalsModal(modal) {
+        const content = modal.querySelector('.modal-content-body');
+        content.innerHTML = `
+            <div class="eval-section">
+                <select class="eval-target">
+                    <option value="">Whole workflow</option>
+                </select>
+                <label class="eval-file-btn">
+                    Load dataset
+                    <input type="file" class="eval-file" accept=".jsonl,.json,.txt" hidden>
+                </label>
+            </div>
+            <textarea class="eval-dataset" rows="4" placeholder='{"input": "...", "expected": "..."} per line'></textarea>
+            <div class="params-header">Variants</div>
+            <div class="eval-variants"></div>
+            <button class="eval-add-variant">Add variant</button>
+            <div class="params-header">Scorers</div>
+            <div class="eval-scorers">
+                <div class="eval-scorer">
+                    <label><input type="checkbox" class="eval-exact"> Exact match</label>
+                    <label><input type="checkbox" class="eval-exact-case"> ignore case</label>
+                </div>
+                <div class="eval-scorer">
+                    <label><input type="checkbox" class="eval-regex"> Regex</label>
+                    <input type="text" class="eval-regex-pattern" placeholder="Pattern">
+                </div>
+                <div class="eval-scorer">
+                    <label><input type="checkbox" class="eval-schema"> JSON schema</label>
+                    <textarea class="eval-schema-text" rows="2" placeholder='{"type": "object"}'></textarea>
+                </div>
+                <div class="eval-scorer">
+                    <label><input type="checkbox" class="eval-judge"> LLM judge</label>
+                    <select class="eval-judge-server">
+                        <option value="http://localhost:11434">Ollama</option>
+                        <option value="http://localhost:1234/v1">LM Studio</option>
+                        <option value="mock://">Mock</option>
+                    </select>
+                    <input type="text" class="eval-judge-model" placeholder="Judge model">
+                    <input type="number" class="eval-judge-threshold" min="0" max="1" step="0.05" value="0.7" title="Pass threshold">
+                </div>
+                <input type="text" class="eval-judge-criteria" placeholder="Judge criteria (optional)">
+            </div>
+            <div class="workflow-item-actions">
+                <button class="eval-run-btn">Run evaluation</button>
+                <button class="eval-copy-btn" disabled>Copy table</button>
+            </div>
+            <div class="eval-status"></div>
+            <div class="eval-results"></div>
+        `;
+        content.querySelector('.eval-target').addEventListener('focus', () => this.refreshEvalTargets());
+        content.querySelector('.eval-file').addEventListener('change', async (e) => {
+            const file = e.target.files[0];
+            if (file) content.querySelector('.eval-dataset').value = await file.text();
+            e.target.value = '';
+        });
+        content.querySelector('.eval-add-variant').addEventListener('click', () => this.addEvalVariant());
+        content.querySelector('.eval-run-btn').addEventListener('click', () => this.runEvaluation());
+        content.querySelector('.eval-copy-btn').addEventListener('click', () => this.copyEvalTable());
+        this.addEvalVariant();
+    }
+
+    refreshEvalTargets() {
+        const select = this.modals.get('evals').querySelector('.eval-target');
+        const current = select.value;
+        select.innerHTML = '<option value="">Whole workflow</option>';
+        for (const node of this.nodes.filter(n => ['llm', 'map', 'while'].includes(n.dataset.type))) {
+            const option = document.createElement('option');
+            option.value = node.id;
+            option.textContent = node.querySelector('.node-header span')?.textContent || node.id;
+            select.appendChild(option);
+        }
+        select.value = [...select.options].some(option => option.value === current) ? current : '';
+    }
+
+    addEvalVariant() {
+        const list = this.modals.get('evals').querySelector('.eval-variants');
+        const row = document.createElement('div');
+        row.className = 'eval-variant';
+        row.innerHTML = `
+            <input type="text" class="eval-variant-label" placeholder="Label">
+            <select class="eval-variant-server">
+                <option value="">Node's server</option>
+                <option value="http://localhost:11434">Ollama</option>
+                <option value="http://localhost:1234/v1">LM Studio</option>
+                <option value="mock://">Mock</option>
+            </select>
+            <input type="text" class="eval-variant-model" placeholder="Node's model">
+            <input type="number" class="eval-variant-temperature" min="0" max="2" step="0.1" placeholder="Temp">
+            <select class="eval-variant-prompt">
+                <option value="">Node's prompt</option>
+            </select>
+            <button class="eval-variant-remove" title="Remove">×</button>
+        `;
+        const prompt = row.querySelector('.eval-variant-prompt');
+        prompt.addEventListener('focus', () => this.loadPromptOptions(prompt));
+        row.querySelector('.eval-variant-remove').addEventListener('click', () => row.remove());
+        list.appendChild(row);
+    }
+
+    readEvalSpec() {
+        const content = this.modals.get('evals').querySelector('.modal-content-body');
+        const checked = selector => content.querySelector(selector).checked;
+        const scorers = [];
+        if (checked('.eval-exact')) {
+            scorers.push({ kind: 'exact_match', ignore_case: checked('.eval-exact-case') });
+        }
+        if (checked('.eval-regex')) {
+            scorers.push({ kind: 'regex', pattern: content.querySelector('.eval-regex-pattern').value });
+        }
+        if (checked('.eval-schema')) {
+            try {
+                scorers.push({ kind: 'json_schema', schema: JSON.parse(content.querySelector('.eval-schema-text').value) });
+            } catch (e) {
+                throw new Error(`Scorer schema is not valid JSON: ${e.message}`);
+            }
+        }
+        if (checked('.eval-judge')) {
+            scorers.push({
+                kind: 'judge',
+                server_url: content.querySelector('.eval-judge-server').value,
+                model: content.querySelector('.eval-judge-model').value.trim(),
+                criteria: content.querySelector('.eval-judge-criteria').value,
+                threshold: parseFloat(content.querySelector('.eval-judge-threshold').value) || 0.7
+            });
+        }
+
+        const variants = [...content.querySelectorAll('.eval-variant')].map(row => {
+            const temperature = parseFloat(row.querySelector('.eval-variant-temperature').value);
+            const prompt = row.querySelector('.eval-variant-prompt').value;
+            return {
+                label: row.querySelector('.eval-variant-label').value.trim(),
+                server_url: row.querySelector('.eval-variant-server').value || null,
+                model: row.querySelector('.eval-variant-model').value.trim() || null,
+                temperature: Number.isNaN(temperature) ? null : temperature,
+                prompt_ref: prompt ? { id: prompt } : null
+            };
+        });
+
+        return {
+            name: '',
+            workflow: this.serializeWorkflow(),
+            node: content.querySelector('.eval-target').value || null,
+            dataset: content.querySelector('.eval-dataset').value,
+            variants,
+            scorers
+        };
+    }
+
+    async runEvaluation() {
+        const content = this.modals.get('evals').querySelector('.modal-content-body');
+        const status = content.querySelector('.eval-status');
+        const button = content.querySelector('.eval-run-btn');
+        button.disabled = true;
+        status.textContent = 'Running...';
+        try {
+            const { invoke } = window.__TAURI__.core;
+            this.evalReport = await invoke('run_evaluation', { spec: this.readEvalSpec() });
+            status.textContent = `${this.evalReport.cases} cases · ${this.evalReport.summary.length} variants`;
+            this.renderEvalReport(this.evalReport);
+            content.querySelector('.eval-copy-btn').disabled = false;
+        } catch (error) {
+            status.textContent = `Evaluation failed: ${error.message || error}`;
+        } finally {
+            button.disabled = false;
+        }
+    }
+
+    renderEvalReport(report) {
+        const results = this.modals.get('evals').querySelector('.eval-results');
+        const scorers = [...new Set(report.summary.flatMap(variant => variant.scores.map(score => score.scorer)))];
+        const table = document.createElement('table');
+        table.className = 'eval-table';
+        const header = table.createTHead().insertRow();
+        ['Variant', 'Model', 'Failed', ...scorers, 'Latency (mean / p95)', 'Tokens (in / out)', 'Judge tokens']
+            .forEach(title => header.appendChild(Object.assign(document.createElement('th'), { textContent: title })));
+        const body = table.createTBody();
+        for (const variant of report.summary) {
+            const row = body.insertRow();
+            const cells = [
+                variant.variant,
+                variant.model || '-',
+                `${variant.failed}/${variant.cases}`,
+                ...scorers.map(scorer => {
+                    const score = variant.scores.find(s => s.scorer === scorer);
+                    return score?.mean != null ? `${score.mean.toFixed(2)} (${score.passed}/${score.graded})` : '-';
+                }),
+                `${variant.mean_latency_ms} / ${variant.p95_latency_ms} ms`,
+                `${variant.prompt_tokens} / ${variant.completion_tokens}`,
+                variant.judge_tokens
+            ];
+            cells.forEach(text => { row.insertCell().textContent = text; });
+        }
+
+        const cases = document.createElement('div');
+        cases.className = 'eval-cases';
+        for (const result of report.results) {
+            const item = document.createElement('div');
+            item.className = `eval-case ${result.error ? 'failed' : ''}`;
+            const scores = result.scores
+                .map(score => `${score.scorer} ${score.score != null ? score.score.toFixed(2) : '-'}${score.detail ? ` (${score.detail})` : ''}`)
+                .join(' · ');
+            item.textContent = `#${result.case + 1} ${result.variant}: ${this.truncateText(result.error || result.output || '')}${scores ? ` — ${scores}` : ''}`;
+            cases.appendChild(item);
+        }
+        results.innerHTML = '';
+        results.appendChild(table);
+        results.appendChild(cases);
+    }
+
+    async copyEvalTable() {
+        if (!this.evalReport) return;
+        try {
+            const { invoke } = window.__TAURI__.core;
+            const table = await invoke('eval_report_table', { report: this.evalReport });
+            await navigator.clipboard.writeText(table);
+        } catch (error) {
+            console.error('Failed to copy table:', error);
+        }
+    }
+
+    // Mark runs as golden and re-run suites of them against a changed model
+    setupRegressionModal(modal) {
+        const content = modal.querySelector('.modal-content-body');
+        content.innerHTML = `
+            <div class="eval-section">
+                <input type="text" class="golden-suite" placeholder="Suite">
+                <input type="text" class="golden-name" placeholder="Name (workflow name)">
+                <button class="golden-mark-btn">Mark last run golden</button>
+            </div>
+            <div class="golden-error"></div>
+            <div class="golden-list"></div>
+            <div class="params-header">Re-run</div>
+            <div class="eval-section">
+                <select class="regression-suite">
+                    <option value="">All suites</option>
+                </select>
+                <select class="regression-server">
+                    <option value="">Recorded server</option>
+                    <option value="http://localhost:11434">Ollama</option>
+                    <option value="http://localhost:1234/v1">LM Studio</option>
+                    <option value="mock://">Mock</option>
+                </select>
+                <input type="text" class="regression-model" placeholder="Recorded model">
+            </div>
+            <div class="eval-section">
+                <select class="regression-comparison">
+                    <option value="exact">Exact match</option>
+                    <option value="semantic">Semantic similarity</option>
+                </select>
+                <select class="regression-embed-server">
+                    <option value="http://localhost:11434">Ollama</option>
+                    <option value="http://localhost:1234/v1">LM Studio</option>
+                    <option value="mock://">Mock</option>
+                </select>
+                <input type="text" class="regression-embed-model" placeholder="Embedding model">
+                <input type="number" class="regression-threshold" min="0" max="1" step="0.01" value="0.9" title="Similarity threshold">
+            </div>
+            <div class="workflow-item-actions">
+                <button class="regression-run-btn">Run suite</button>
+            </div>
+            <div class="eval-status regression-status"></div>
+            <div class="regression-results"></div>
+        `;
+        const comparison = content.querySelector('.regression-comparison');
+        const toggleSemantic = () => {
+            const semantic = comparison.value === 'semantic';
+            content.querySelectorAll('.regression-embed-server, .regression-embed-model, .regression-threshold')
+                .forEach(input => { input.style.display = semantic ? '' : 'none'; });
+        };
+        comparison.addEventListener('change', toggleSemantic);
+        toggleSemantic();
+        content.querySelector('.golden-mark-btn').addEventListener('click', () => this.markGoldenRun());
+        content.querySelector('.regression-run-btn').addEventListener('click', () => this.runRegression());
+        this.refreshGoldenRuns();
+    }
+
+    async refreshGoldenRuns() {
+        const content = this.modals.get('regression')?.querySelector('.modal-content-body');
+        if (!content) return;
+
+        let goldens = [];
+        try {
+            const { invoke } = window.__TAURI__.core;
+            goldens = await invoke('list_golden_runs', { suite: null });
+        } catch (error) {
+            console.error('Failed to list golden runs:', error);
+        }
+
+        const select = content.querySelector('.regression-suite');
+        const current = select.value;
+        const suites = [...new Set(goldens.map(golden => golden.suite))];
+        select.innerHTML = '<option value="">All suites</option>';
+        for (const suite of suites) {
+            const option = document.createElement('option');
+            option.value = suite;
+            option.textContent = `${suite} (${goldens.filter(golden => golden.suite === suite).length})`;
+            select.appendChild(option);
+        }
+        select.value = suites.includes(current) ? current : '';
+
+        const list = content.querySelector('.golden-list');
+        list.innerHTML = goldens.length ? '' : '<div class="approvals-empty">No golden runs</div>';
+        for (const golden of goldens) {
+            const item = document.createElement('div');
+            item.className = 'workflow-item';
+            item.innerHTML = `
+                <div class="workflow-item-name"></div>
+                <div class="workflow-item-meta"></div>
+                <div class="workflow-item-actions">
+                    <button class="workflow-delete-btn">Remove</button>
+                </div>
+            `;
+            item.querySelector('.workflow-item-name').textContent = `${golden.suite} / ${golden.name}`;
+            item.querySelector('.workflow-item-meta').textContent =
+                `${golden.workflow_name || golden.workflow_id} · run ${golden.run_id.slice(0, 8)} · ${new Date(golden.marked_at).toLocaleString()}`;
+            item.querySelector('.workflow-delete-btn').addEventListener('click', () => this.unmarkGoldenRun(golden.run_id));
+            list.appendChild(item);
+        }
+    }
+
+    async markGoldenRun() {
+        const content = this.modals.get('regression').querySelector('.modal-content-body');
+        const error = content.querySelector('.golden-error');
+        if (!this.lastRunId) {
+            error.textContent = 'Run the workflow first';
+            return;
+        }
+        try {
+            const { invoke } = window.__TAURI__.core;
+            await invoke('mark_golden_run', {
+                runId: this.lastRunId,
+                suite: content.querySelector('.golden-suite').value.trim(),
+                name: content.querySelector('.golden-name').value.trim() || null
+            });
+            error.textContent = '';
+        } catch (e) {
+            error.textContent = `Failed to mark run: ${e.message || e}`;
+        }
+        this.refreshGoldenRuns();
+    }
+
+    async unmarkGoldenRun(runId) {
+        try {
+            const { invoke } = window.__TAURI__.core;
+            await invoke('unmark_golden_run', { runId });
+        } catch (error) {
+            console.error('Failed to remove golden run:', error);
+        }
+        this.refreshGoldenRuns();
+    }
+
+    async runRegression() {
+        const content = this.modals.get('regression').querySelector('.modal-content-body');
+        const status = content.querySelector('.regression-status');
+        const button = content.querySelector('.regression-run-btn');
+        const semantic = content.querySelector('.regression-comparison').value === 'semantic';
+        const spec = {
+            suite: content.querySelector('.regression-suite').value || null,
+            overrides: {
+                server_url: content.querySelector('.regression-server').value || null,
+                model: content.querySelector('.regression-model').value.trim() || null
+            },
+            comparison: semantic
+                ? {
+                    mode: 'semantic',
+                    server_url: content.querySelector('.regression-embed-server').value,
+                    model: content.querySelector('.regression-embed-model').value.trim(),
+                    threshold: parseFloat(content.querySelector('.regression-threshold').value) || 0.9
+                }
+                : { mode: 'exact' }
+        };
+
+        button.disabled = true;
+        status.textContent = 'Running...';
+        try {
+            const { invoke } = window.__TAURI__.core;
+            const report = await invoke('run_regression_suite', { spec });
+            status.textContent = `${report.passed} passed, ${report.failed} failed`;
+            this.renderRegressionReport(report);
+        } catch (error) {
+            status.textContent = `Suite failed to run: ${error.message || error}`;
+        } finally {
+            button.disabled = false;
+        }
+    }
+
+    renderRegressionReport(report) {
+        const results = this.modals.get('regression').querySelector('.regression-results');
+        results.innerHTML = '';
+        for (const result of report.results) {
+            const item = document.createElement('div');
+            item.className = `regression-result ${result.passed ? 'passed' : 'failed'}`;
+            const title = document.createElement('div');
+            title.className = 'regression-result-title';
+            title.textContent = `${result.passed ? 'PASS' : 'FAIL'} ${result.golden.suite} / ${result.golden.name} · ${result.latency_ms} ms · ${result.tokens} tokens`;
+            item.appendChild(title);
+            if (result.error) {
+                item.appendChild(Object.assign(document.createElement('div'), { textContent: result.error }));
+            }
+            for (const check of result.checks.filter(check => !check.passed || check.similarity != null)) {
+                const similarity = check.similarity != null ? ` · similarity ${check.similarity.toFixed(3)}` : '';
+                item.appendChild(Object.assign(document.createElement('div'), {
+                    textContent: `Node ${check.node_id}${similarity}${check.error ? ` · ${check.error}` : ''}`
+                }));
+                if (check.diff && !check.passed) {
+                    item.appendChild(Object.assign(document.createElement('pre'), { className: 'regression-diff', textContent: check.diff }));
+                }
+            }
+            results.appendChild(item);
+        }
+    }
+
+    async applyRunResults(run) {
+        this.lastRunId = run.run_id;
+
+        for (const result of run.nodes) {
+            const node = this.nodes.find(n => n.id === result.node_id);
+            if (!node) continue;
+
+            switch (result.status) {
+                case 'completed': {
+                    const label = result.label ? ` (${result.label})` : '';
+                    if (result.reused_from) {
+                        this.setNodeStatus(node, 'success', `Reused${label}`);
+                        break;
+                    }
+                    const cached = result.cache_hits ? ` · ${result.cache_hits} cached` : '';
+                    this.setNodeStatus(node, 'success', `Completed${label}${cached}`);
+                    if (!this.outputBuffers.has(node.id)) {
+                        this.outputBuffers.set(node.id, []);
+                    }
+                    this.outputBuffers.get(node.id).push({
+                        timestamp: run.finished_at,
+                        input: result.input,
+                        output: result.output,
+                        metadata: { run_id: run.run_id, label: result.label }
+                    });
+                    await this.logIterations(node, run.run_id);
+                    if (node.dataset.type === 'retriever' && Array.isArray(result.value)) {
+                        result.value.forEach((hit, index) => this.logBufferOperation(node.id, 'retrieval', { index, ...hit }));
+                    }
+                    if (node.dataset.type === 'memory' && Array.isArray(result.value)) {
+                        result.value.forEach(memory => this.logBufferOperation(node.id, 'memory', memory));
+                    }
+                    if (result.context) {
+                        this.logBufferOperation(node.id, 'context', result.context);
+                    }
+                    this.logBufferOperation(node.id, 'output', { output: result.output || '', value: result.value });
+                    (result.artifacts || []).forEach(artifact => this.logBufferOperation(node.id, 'artifact', artifact));
+                    break;
+                }
+                case 'skipped':
+                    this.setNodeStatus(node, 'skipped', 'Skipped');
+                    break;
+                default: {
+                    this.setNodeStatus(node, 'error', `Error: ${result.error}`);
+                    this.logBufferOperation(node.id, 'error', { message: result.error });
+
+                    const resume = document.createElement('button');
+                    resume.className = 'resume-btn';
+                    resume.textContent = 'Resume from here';
+                    resume.addEventListener('click', () => this.resumeWorkflow(node));
+                    node.querySelector('.node-status').appendChild(resume);
+                }
+            }
+        }
+    }
+
+    deleteNode(node) {
+        const upstream = this.connections
+            .filter(conn => conn.to === node.id)
+            .map(conn => conn.from);
+
+        // Remove connections involving this node
         this.connections = this.connections.filter(conn => 
             conn.from !== node.id && conn.to !== node.id);
         
         // Remove the node
         node.remove();
         this.nodes = this.nodes.filter(n => n !== node);
+        this.nodeImages.delete(node.id);
         
         // Redraw remaining connections
         this.drawConnections();
+        upstream.forEach(id => this.refreshRouterBranches(id));
     }
 
     makeDraggable(node) {
@@ -909,7 +3076,7 @@ class ReteEditor {
         let rafId = null;
 
         node.addEventListener('mousedown', (e) => {
-            if (e.target.closest('.node-controls, .node-select, .node-input, .tools-list, .params-container')) {
+            if (e.target.closest('.node-controls, .node-select, .node-input, .tools-list, .params-container, .router-branches, .classifier-settings, .iteration-settings')) {
                 return;
             }
             isDragging = true;
@@ -1116,9 +3283,35 @@ class ReteEditor {
             case 'input':
                 return `📥 Input: ${this.truncateText(data.input)}`;
             case 'output':
-                return `📤 Output: ${this.truncateText(data.output)}`;
+                return data.value != null
+                    ? `📤 Output (JSON): ${this.truncateText(JSON.stringify(data.value))}`
+                    : `📤 Output: ${this.truncateText(data.output)}`;
             case 'error':
                 return `❌ Error: ${data.message}`;
+            case 'iteration':
+                return data.error
+                    ? `🔁 #${data.index} ❌ ${data.error}`
+                    : `🔁 #${data.index}${data.cached ? ' (cached)' : ''}: ${this.truncateText(data.output || '')}`;
+            case 'retrieval': {
+                const scores = [
+                    data.vector_score != null ? `vector ${data.vector_score.toFixed(3)}` : null,
+                    data.keyword_score != null ? `bm25 ${data.keyword_score.toFixed(2)}` : null,
+                    data.rerank_score != null ? `rerank ${data.rerank_score.toFixed(2)}` : null
+                ].filter(Boolean).join(' · ');
+                return `🔎 #${data.index + 1} ${data.score.toFixed(3)} ${data.source}:${data.start_line}-${data.end_line}${scores ? ` (${scores})` : ''}`;
+            }
+            case 'memory': {
+                const similarity = data.similarity != null ? ` · similarity ${data.similarity.toFixed(2)}` : '';
+                const key = data.key ? `${data.key}: ` : '';
+                return `🧠 ${data.score.toFixed(3)} [${data.kind}] ${key}${this.truncateText(data.content)} (recency ${data.recency.toFixed(2)}${similarity})`;
+            }
+            case 'context': {
+                const requests = data.requests > 1 ? ` in ${data.requests} requests` : '';
+                const truncated = data.truncated ? ', text cut' : '';
+                return `📏 ${data.strategy.replace(/_/g, ' ')}${requests}: ${data.tokens_before} → ${data.tokens_after} of ${data.context_length} tokens (${data.removed} removed${truncated})`;
+            }
+            case 'artifact':
+                return `📎 ${data.name} (${(data.size / 1024).toFixed(1)} KB) <button class="artifact-btn" data-artifact-id="${data.id}">Download</button>`;
             default:
                 return JSON.stringify(data);
         }
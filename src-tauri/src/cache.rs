@@ -0,0 +1,304 @@
+// Opt-in on-disk cache of chat replies, keyed on everything that shapes the reply.
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use std::sync::Mutex;
+use tauri::State;
+
+use crate::provider::{self, ChatMessage, ChatOptions, ChatReply, ChunkSender, Provider, Usage};
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS responses (
+        key TEXT PRIMARY KEY,
+        provider TEXT NOT NULL,
+        model TEXT NOT NULL,
+        reply TEXT NOT NULL,
+        size INTEGER NOT NULL,
+        created_at INTEGER NOT NULL,
+        last_hit_at INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS settings (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );
+";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheSettings {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Entries older than this are treated as misses and pruned.
+    #[serde(default = "default_ttl_secs")]
+    pub ttl_secs: u64,
+    /// Least recently used entries are evicted once stored replies exceed this.
+    #[serde(default = "default_max_bytes")]
+    pub max_bytes: u64,
+}
+
+fn default_ttl_secs() -> u64 {
+    7 * 24 * 60 * 60
+}
+
+fn default_max_bytes() -> u64 {
+    50 * 1024 * 1024
+}
+
+impl Default for CacheSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            ttl_secs: default_ttl_secs(),
+            max_bytes: default_max_bytes(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheStats {
+    pub entries: u64,
+    pub bytes: u64,
+    pub settings: CacheSettings,
+}
+
+pub struct ResponseCache {
+    conn: Mutex<Connection>,
+    settings: Mutex<CacheSettings>,
+}
+
+/// Hash of the server, provider, model, messages and generation parameters.
+pub fn cache_key(server_url: &str, provider: Provider, model: &str, messages: &[ChatMessage], options: &ChatOptions) -> String {
+    let request = json!({
+        "server": server_url.trim().trim_end_matches('/').to_lowercase(),
+        "provider": provider.as_str(),
+        "model": model,
+        "messages": messages,
+        "params": options,
+    });
+    format!("{:x}", Sha256::digest(request.to_string().as_bytes()))
+}
+
+impl ResponseCache {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let conn = Connection::open(path).map_err(|e| format!("Failed to open response cache: {}", e))?;
+        Self::init(conn)
+    }
+
+    #[cfg(test)]
+    pub fn open_in_memory() -> Result<Self, String> {
+        Self::init(Connection::open_in_memory().map_err(|e| e.to_string())?)
+    }
+
+    fn init(conn: Connection) -> Result<Self, String> {
+        conn.execute_batch(SCHEMA)
+            .map_err(|e| format!("Failed to initialise response cache: {}", e))?;
+        let settings = conn
+            .query_row("SELECT value FROM settings WHERE key = 'cache'", [], |row| row.get::<_, String>(0))
+            .optional()
+            .map_err(|e| e.to_string())?
+            .and_then(|value| serde_json::from_str(&value).ok())
+            .unwrap_or_default();
+        Ok(Self {
+            conn: Mutex::new(conn),
+            settings: Mutex::new(settings),
+        })
+    }
+
+    fn conn(&self) -> Result<std::sync::MutexGuard<'_, Connection>, String> {
+        self.conn.lock().map_err(|e| e.to_string())
+    }
+
+    pub fn settings(&self) -> CacheSettings {
+        self.settings.lock().map(|s| s.clone()).unwrap_or_default()
+    }
+
+    pub fn update_settings(&self, settings: CacheSettings) -> Result<(), String> {
+        let value = serde_json::to_string(&settings).map_err(|e| e.to_string())?;
+        let conn = self.conn()?;
+        conn.execute(
+            "INSERT OR REPLACE INTO settings (key, value) VALUES ('cache', ?1)",
+            params![value],
+        )
+        .map_err(|e| e.to_string())?;
+        prune(&conn, &settings).map_err(|e| e.to_string())?;
+        *self.settings.lock().map_err(|e| e.to_string())? = settings;
+        Ok(())
+    }
+
+    pub fn stats(&self) -> Result<CacheStats, String> {
+        let (entries, bytes) = self.conn()?
+            .query_row("SELECT COUNT(*), COALESCE(SUM(size), 0) FROM responses", [], |row| {
+                Ok((row.get::<_, i64>(0)? as u64, row.get::<_, i64>(1)? as u64))
+            })
+            .map_err(|e| e.to_string())?;
+        Ok(CacheStats {
+            entries,
+            bytes,
+            settings: self.settings(),
+        })
+    }
+
+    pub fn clear(&self) -> Result<(), String> {
+        self.conn()?
+            .execute("DELETE FROM responses", [])
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    fn lookup(&self, key: &str, settings: &CacheSettings) -> Result<Option<ChatReply>, String> {
+        let conn = self.conn()?;
+        let now = chrono::Utc::now().timestamp();
+        let reply = conn
+            .query_row(
+                "SELECT reply FROM responses WHERE key = ?1 AND created_at >= ?2",
+                params![key, now - settings.ttl_secs as i64],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?;
+        let Some(reply) = reply else {
+            return Ok(None);
+        };
+        conn.execute("UPDATE responses SET last_hit_at = ?2 WHERE key = ?1", params![key, now])
+            .map_err(|e| e.to_string())?;
+        let mut reply: ChatReply = serde_json::from_str(&reply).map_err(|e| e.to_string())?;
+        reply.cached = true;
+        reply.usage = Usage::default();
+        Ok(Some(reply))
+    }
+
+    fn store(&self, key: &str, provider: Provider, model: &str, reply: &ChatReply, settings: &CacheSettings) -> Result<(), String> {
+        let value = serde_json::to_string(reply).map_err(|e| e.to_string())?;
+        let now = chrono::Utc::now().timestamp();
+        let conn = self.conn()?;
+        conn.execute(
+            "INSERT OR REPLACE INTO responses (key, provider, model, reply, size, created_at, last_hit_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?6)",
+            params![key, provider.as_str(), model, value, value.len() as i64, now],
+        )
+        .map_err(|e| e.to_string())?;
+        prune(&conn, settings).map_err(|e| e.to_string())
+    }
+
+    /// Chat through the cache when it is enabled. Hits come back with `cached`
+    /// set and empty usage, since no tokens were spent on them.
+    pub async fn chat(&self, server_url: &str, model: &str, messages: &[ChatMessage], options: &ChatOptions) -> Result<ChatReply, String> {
+        self.chat_streaming(server_url, model, messages, options, None).await
+    }
+
+    /// Like `chat`, also passing the reply's text to `chunks` as it arrives.
+    /// A hit arrives as a single chunk.
+    pub async fn chat_streaming(
+        &self,
+        server_url: &str,
+        model: &str,
+        messages: &[ChatMessage],
+        options: &ChatOptions,
+        chunks: Option<&ChunkSender>,
+    ) -> Result<ChatReply, String> {
+        let settings = self.settings();
+        if !settings.enabled {
+            return provider::chat_streaming(server_url, model, messages, options, chunks).await;
+        }
+
+        let provider = Provider::detect(server_url);
+        let key = cache_key(server_url, provider, model, messages, options);
+        match self.lookup(&key, &settings) {
+            Ok(Some(reply)) => {
+                if let Some(chunks) = chunks.filter(|_| !reply.content.is_empty()) {
+                    let _ = chunks.send(reply.content.clone());
+                }
+                return Ok(reply);
+            }
+            Ok(None) => {}
+            Err(e) => log::warn!("Response cache lookup failed: {}", e),
+        }
+
+        let reply = provider::chat_streaming(server_url, model, messages, options, chunks).await?;
+        if let Err(e) = self.store(&key, provider, model, &reply, &settings) {
+            log::warn!("Failed to cache response: {}", e);
+        }
+        Ok(reply)
+    }
+}
+
+/// Drop expired entries, then the least recently used ones beyond the size limit.
+fn prune(conn: &Connection, settings: &CacheSettings) -> rusqlite::Result<()> {
+    let now = chrono::Utc::now().timestamp();
+    conn.execute(
+        "DELETE FROM responses WHERE created_at < ?1",
+        params![now - settings.ttl_secs as i64],
+    )?;
+    conn.execute(
+        "DELETE FROM responses WHERE key IN (
+            SELECT key FROM (
+                SELECT key, SUM(size) OVER (ORDER BY last_hit_at DESC, created_at DESC, key) AS running
+                FROM responses
+            ) WHERE running > ?1
+        )",
+        params![settings.max_bytes as i64],
+    )?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_cache_stats(cache: State<'_, ResponseCache>) -> Result<CacheStats, String> {
+    cache.stats()
+}
+
+#[tauri::command]
+pub async fn set_cache_settings(settings: CacheSettings, cache: State<'_, ResponseCache>) -> Result<(), String> {
+    cache.update_settings(settings)
+}
+
+#[tauri::command]
+pub async fn clear_response_cache(cache: State<'_, ResponseCache>) -> Result<(), String> {
+    cache.clear()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::mock::MockLlmServer;
+
+    #[tokio::test]
+    async fn hits_and_eviction() {
+        let server = MockLlmServer::start().await.unwrap();
+        let cache = ResponseCache::open_in_memory().unwrap();
+        let messages = [ChatMessage::user("hi")];
+        let options = ChatOptions::with_temperature(0.0);
+
+        assert!(!cache.chat(&server.base_url, "m", &messages, &options).await.unwrap().cached);
+        cache.chat(&server.base_url, "m", &messages, &options).await.unwrap();
+        assert_eq!(server.requests().len(), 2, "a disabled cache passes requests through");
+
+        cache.update_settings(CacheSettings { enabled: true, ..Default::default() }).unwrap();
+        assert!(!cache.chat(&server.base_url, "m", &messages, &options).await.unwrap().cached);
+        let reply = cache.chat(&format!("{}/", server.base_url), "m", &messages, &options).await.unwrap();
+        assert!(reply.cached);
+        assert_eq!(reply.usage.total(), 0);
+        assert_eq!(server.requests().len(), 3);
+        assert!(!cache.chat(&server.base_url, "m", &messages, &ChatOptions::with_temperature(0.5)).await.unwrap().cached);
+        assert_eq!(cache.stats().unwrap().entries, 2);
+
+        cache.update_settings(CacheSettings { enabled: true, max_bytes: 120, ..Default::default() }).unwrap();
+        assert_eq!(cache.stats().unwrap().entries, 1);
+        cache.clear().unwrap();
+        assert_eq!(cache.stats().unwrap().entries, 0);
+    }
+
+    #[tokio::test]
+    async fn servers_do_not_share_entries() {
+        let (a, b) = (MockLlmServer::start().await.unwrap(), MockLlmServer::start().await.unwrap());
+        let cache = ResponseCache::open_in_memory().unwrap();
+        cache.update_settings(CacheSettings { enabled: true, ..Default::default() }).unwrap();
+        let messages = [ChatMessage::user("hi")];
+        let options = ChatOptions::default();
+
+        cache.chat(&a.base_url, "m", &messages, &options).await.unwrap();
+        assert!(!cache.chat(&b.base_url, "m", &messages, &options).await.unwrap().cached);
+        assert!(cache.chat(&a.base_url, "m", &messages, &options).await.unwrap().cached);
+        assert_eq!((a.requests().len(), b.requests().len()), (1, 1));
+    }
+}
@@ -0,0 +1,89 @@
+// The LLM configuration edited in the settings panel, saved with the app data.
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::{Mutex, RwLock};
+use tauri::State;
+
+use crate::mcp::{McpManager, McpServerConfig};
+use crate::tools::ToolRegistry;
+use crate::{add_log_entry, LogState};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerConfig {
+    pub id: String,
+    pub name: String,
+    pub url: String,
+    pub provider: String,
+    #[serde(default)]
+    pub selected_model: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelConfig {
+    pub name: String,
+    pub provider: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LlmConfig {
+    #[serde(default)]
+    pub ws_url: String,
+    #[serde(default)]
+    pub servers: Vec<ServerConfig>,
+    #[serde(default)]
+    pub available_models: Vec<ModelConfig>,
+    #[serde(default)]
+    pub selected_model: String,
+    /// MCP servers whose tools, resources and prompts nodes can use.
+    #[serde(default)]
+    pub mcp_servers: Vec<McpServerConfig>,
+}
+
+/// The saved configuration, or `None` until the settings are first saved.
+pub struct ConfigStore {
+    config: RwLock<Option<LlmConfig>>,
+    store: PathBuf,
+}
+
+impl ConfigStore {
+    pub fn load(store: PathBuf) -> Self {
+        let config = std::fs::read_to_string(&store)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok());
+        Self {
+            config: RwLock::new(config),
+            store,
+        }
+    }
+
+    pub fn get(&self) -> Option<LlmConfig> {
+        self.config.read().ok().and_then(|c| c.clone())
+    }
+
+    pub fn update(&self, config: LlmConfig) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(&config).map_err(|e| e.to_string())?;
+        std::fs::write(&self.store, json).map_err(|e| format!("Failed to save configuration: {}", e))?;
+        *self.config.write().map_err(|e| e.to_string())? = Some(config);
+        Ok(())
+    }
+}
+
+#[tauri::command]
+pub async fn load_llm_config(store: State<'_, ConfigStore>) -> Result<Option<LlmConfig>, String> {
+    Ok(store.get())
+}
+
+/// Save the configuration and bring the MCP connections in line with it.
+#[tauri::command]
+pub async fn save_llm_config(
+    config: LlmConfig,
+    store: State<'_, ConfigStore>,
+    mcp: State<'_, McpManager>,
+    registry: State<'_, ToolRegistry>,
+    log_state: State<'_, Mutex<LogState>>,
+) -> Result<(), String> {
+    let mcp_servers = config.mcp_servers.clone();
+    store.update(config)?;
+    add_log_entry(&log_state, "info", "Saved LLM configuration", "system")?;
+    mcp.sync(&mcp_servers, &registry, &log_state).await
+}
@@ -0,0 +1,480 @@
+// Evaluation runs: a node or workflow over a dataset, once per variant of
+// model and parameters, scored and summarized side by side.
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::time::Instant;
+use tauri::AppHandle;
+
+use crate::prompts::PromptRef;
+use crate::provider::Usage;
+use crate::workflow::{self, NodeKind, NodeResult, NodeStatus, Services, Workflow, WorkflowEdge, WorkflowNode};
+use crate::{add_log_entry, broadcast_update};
+
+pub mod scorer;
+
+use scorer::{Graded, Score, Scorer};
+
+/// Most cases a single eval runs.
+const MAX_CASES: usize = 1000;
+
+/// One line of a dataset.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvalCase {
+    pub input: String,
+    #[serde(default)]
+    pub expected: Option<Value>,
+}
+
+/// Overrides applied to the evaluated LLM nodes. Unset fields keep the
+/// node's own settings.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EvalVariant {
+    #[serde(default)]
+    pub label: String,
+    #[serde(default)]
+    pub server_url: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub temperature: Option<f32>,
+    #[serde(default)]
+    pub system: Option<String>,
+    #[serde(default)]
+    pub prompt: Option<String>,
+    #[serde(default)]
+    pub prompt_ref: Option<PromptRef>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvalSpec {
+    #[serde(default)]
+    pub name: String,
+    pub workflow: Workflow,
+    /// Evaluate only this node, fed each case's input directly. Without it
+    /// the whole workflow runs with the input in place of its input nodes.
+    #[serde(default)]
+    pub node: Option<String>,
+    /// JSONL: one case per line, either an object with `input` and an
+    /// optional `expected`, or a bare string input.
+    pub dataset: String,
+    /// An empty list evaluates the nodes as they are.
+    #[serde(default)]
+    pub variants: Vec<EvalVariant>,
+    #[serde(default)]
+    pub scorers: Vec<Scorer>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaseResult {
+    pub case: usize,
+    pub variant: String,
+    pub run_id: String,
+    pub output: Option<String>,
+    pub error: Option<String>,
+    pub scores: Vec<Score>,
+    #[serde(flatten)]
+    pub usage: Usage,
+    pub latency_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScorerSummary {
+    pub scorer: String,
+    /// Mean over the cases the scorer could grade.
+    pub mean: Option<f64>,
+    pub passed: usize,
+    pub graded: usize,
+}
+
+/// One row of the comparison table.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VariantSummary {
+    pub variant: String,
+    pub model: Option<String>,
+    pub cases: usize,
+    pub failed: usize,
+    pub scores: Vec<ScorerSummary>,
+    pub mean_latency_ms: u64,
+    pub p95_latency_ms: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    /// Tokens spent by judge scorers, kept apart from the variant's own.
+    pub judge_tokens: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvalReport {
+    pub name: String,
+    pub workflow_id: String,
+    pub node: Option<String>,
+    pub started_at: String,
+    pub finished_at: String,
+    pub cases: usize,
+    pub summary: Vec<VariantSummary>,
+    pub results: Vec<CaseResult>,
+}
+
+pub fn parse_dataset(text: &str) -> Result<Vec<EvalCase>, String> {
+    let mut cases = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let value: Value = serde_json::from_str(line).map_err(|e| format!("Dataset line {}: {}", number + 1, e))?;
+        let case = match value {
+            Value::String(input) => EvalCase { input, expected: None },
+            Value::Object(mut fields) => {
+                let input = match fields.remove("input") {
+                    Some(Value::String(input)) => input,
+                    Some(other) => other.to_string(),
+                    None => return Err(format!("Dataset line {} has no \"input\"", number + 1)),
+                };
+                EvalCase {
+                    input,
+                    expected: fields.remove("expected").filter(|expected| !expected.is_null()),
+                }
+            }
+            _ => return Err(format!("Dataset line {} is neither an object nor a string", number + 1)),
+        };
+        cases.push(case);
+    }
+    if cases.is_empty() {
+        return Err("Dataset has no cases".to_string());
+    }
+    if cases.len() > MAX_CASES {
+        return Err(format!("Dataset has {} cases, more than the limit of {}", cases.len(), MAX_CASES));
+    }
+    Ok(cases)
+}
+
+impl EvalVariant {
+    pub(crate) fn apply(&self, kind: &mut NodeKind) {
+        let Some(config) = kind.llm_config_mut() else { return };
+        if let Some(server_url) = &self.server_url {
+            config.server_url = server_url.clone();
+        }
+        if let Some(model) = &self.model {
+            config.model = model.clone();
+        }
+        if let Some(temperature) = self.temperature {
+            config.temperature = temperature;
+        }
+        if let Some(system) = &self.system {
+            config.system = system.clone();
+        }
+        if let Some(prompt) = &self.prompt {
+            config.prompt = prompt.clone();
+        }
+        if self.prompt_ref.is_some() {
+            config.prompt_ref = self.prompt_ref.clone();
+        }
+    }
+
+    fn name(&self, index: usize) -> String {
+        if !self.label.trim().is_empty() {
+            return self.label.trim().to_string();
+        }
+        match (&self.model, self.temperature) {
+            (Some(model), Some(temperature)) => format!("{} @ {}", model, temperature),
+            (Some(model), None) => model.clone(),
+            _ => format!("Variant {}", index + 1),
+        }
+    }
+}
+
+/// The workflow a case runs: the whole graph with the case input in its
+/// entry input nodes, or the evaluated node fed by a single input node.
+fn case_workflow(spec: &EvalSpec, variant: &EvalVariant, case: &EvalCase) -> Result<Workflow, String> {
+    let input = NodeKind::Input { value: case.input.clone() };
+    let mut workflow = match &spec.node {
+        Some(node_id) => {
+            let node = spec.workflow.nodes.iter()
+                .find(|node| &node.id == node_id)
+                .ok_or_else(|| format!("Node '{}' is not part of the workflow", node_id))?;
+            Workflow {
+                id: spec.workflow.id.clone(),
+                name: spec.workflow.name.clone(),
+                nodes: vec![WorkflowNode { id: "eval_input".to_string(), kind: input }, node.clone()],
+                edges: vec![WorkflowEdge { from: "eval_input".to_string(), to: node_id.clone(), condition: None }],
+            }
+        }
+        None => {
+            let mut workflow = spec.workflow.clone();
+            let entries: Vec<String> = workflow.nodes.iter()
+                .filter(|node| matches!(node.kind, NodeKind::Input { .. }))
+                .filter(|node| !workflow.edges.iter().any(|edge| edge.to == node.id))
+                .map(|node| node.id.clone())
+                .collect();
+            if entries.is_empty() {
+                return Err("Workflow has no input node to feed the dataset into".to_string());
+            }
+            for node in workflow.nodes.iter_mut().filter(|node| entries.contains(&node.id)) {
+                node.kind = input.clone();
+            }
+            workflow
+        }
+    };
+    for node in workflow.nodes.iter_mut() {
+        if spec.node.as_ref().is_none_or(|id| id == &node.id) {
+            variant.apply(&mut node.kind);
+        }
+    }
+    Ok(workflow)
+}
+
+/// The nodes whose output counts as the run's result: `node` if given,
+/// otherwise the output nodes, or the last completed node if there are none.
+pub(crate) fn final_nodes<'a>(nodes: &'a [NodeResult], node: Option<&str>) -> Vec<&'a NodeResult> {
+    let completed: Vec<_> = nodes.iter().filter(|result| result.status == NodeStatus::Completed).collect();
+    match node {
+        Some(node_id) => completed.into_iter().filter(|result| result.node_id == node_id).collect(),
+        None => {
+            let outputs: Vec<_> = completed.iter().copied().filter(|result| result.node_type == "output").collect();
+            if outputs.is_empty() { completed.last().copied().into_iter().collect() } else { outputs }
+        }
+    }
+}
+
+/// What a case run produced, joined when several nodes count.
+fn case_output(spec: &EvalSpec, run: &workflow::WorkflowRun) -> Result<(String, Option<Value>), String> {
+    if let Some(failed) = run.nodes.iter().find(|node| node.status == NodeStatus::Failed) {
+        return Err(format!("Node {} failed: {}", failed.node_id, failed.error.as_deref().unwrap_or_default()));
+    }
+    match final_nodes(&run.nodes, spec.node.as_deref()).as_slice() {
+        [] => Err("Run produced no output".to_string()),
+        [only] => Ok((only.output.clone().unwrap_or_default(), only.value.clone())),
+        many => Ok((many.iter().filter_map(|node| node.output.as_deref()).collect::<Vec<_>>().join("\n\n"), None)),
+    }
+}
+
+fn summarize(variant: String, model: Option<String>, results: &[&CaseResult], judge_usage: Usage) -> VariantSummary {
+    let mut scorers: Vec<String> = Vec::new();
+    for score in results.iter().flat_map(|result| &result.scores) {
+        if !scorers.contains(&score.scorer) {
+            scorers.push(score.scorer.clone());
+        }
+    }
+    let scores = scorers.into_iter().map(|scorer| {
+        let graded: Vec<&Score> = results.iter()
+            .flat_map(|result| &result.scores)
+            .filter(|score| score.scorer == scorer && score.score.is_some())
+            .collect();
+        ScorerSummary {
+            mean: (!graded.is_empty()).then(|| graded.iter().filter_map(|score| score.score).sum::<f64>() / graded.len() as f64),
+            passed: graded.iter().filter(|score| score.passed).count(),
+            graded: graded.len(),
+            scorer,
+        }
+    }).collect();
+
+    let mut latencies: Vec<u64> = results.iter().map(|result| result.latency_ms).collect();
+    latencies.sort_unstable();
+    let p95 = latencies.get((latencies.len() * 95).div_ceil(100).saturating_sub(1)).copied().unwrap_or(0);
+    let mut usage = Usage::default();
+    results.iter().for_each(|result| usage.add(result.usage));
+    VariantSummary {
+        variant,
+        model,
+        cases: results.len(),
+        failed: results.iter().filter(|result| result.error.is_some()).count(),
+        scores,
+        mean_latency_ms: latencies.iter().sum::<u64>() / latencies.len().max(1) as u64,
+        p95_latency_ms: p95,
+        prompt_tokens: usage.prompt_tokens.unwrap_or(0),
+        completion_tokens: usage.completion_tokens.unwrap_or(0),
+        judge_tokens: judge_usage.total(),
+    }
+}
+
+pub async fn run_eval(spec: &EvalSpec, services: Services<'_>) -> Result<EvalReport, String> {
+    let cases = parse_dataset(&spec.dataset)?;
+    let variants = if spec.variants.is_empty() { vec![EvalVariant::default()] } else { spec.variants.clone() };
+    let name = if spec.name.trim().is_empty() { spec.workflow.name.clone() } else { spec.name.clone() };
+    let started_at = chrono::Local::now().to_rfc3339();
+    let total = cases.len() * variants.len();
+    add_log_entry(
+        services.log_state,
+        "info",
+        &format!("Evaluating '{}' on {} cases across {} variant(s)", name, cases.len(), variants.len()),
+        "eval",
+    )?;
+
+    let mut results = Vec::with_capacity(total);
+    let mut summary = Vec::with_capacity(variants.len());
+    for (v, variant) in variants.iter().enumerate() {
+        let label = variant.name(v);
+        let mut judge_usage = Usage::default();
+        let mut model = None;
+        for (c, case) in cases.iter().enumerate() {
+            let workflow = case_workflow(spec, variant, case)?;
+            model = model.or_else(|| {
+                workflow.nodes.iter()
+                    .filter(|node| spec.node.as_ref().is_none_or(|id| id == &node.id))
+                    .find_map(|node| node.kind.llm_config().map(|config| config.model.clone()))
+            });
+            let started = Instant::now();
+            let run = workflow::run_workflow(&workflow, services).await?;
+            let latency_ms = started.elapsed().as_millis() as u64;
+            let mut usage = Usage::default();
+            run.nodes.iter().for_each(|node| usage.add(node.usage));
+
+            let (output, error, scores) = match case_output(spec, &run) {
+                Ok((output, value)) => {
+                    let graded = Graded {
+                        input: &case.input,
+                        output: &output,
+                        value: value.as_ref(),
+                        expected: case.expected.as_ref(),
+                    };
+                    let mut scores = Vec::with_capacity(spec.scorers.len());
+                    for scorer in &spec.scorers {
+                        scores.push(scorer.score(&graded, services.cache, &mut judge_usage).await);
+                    }
+                    (Some(output), None, scores)
+                }
+                Err(e) => (None, Some(e), Vec::new()),
+            };
+            let result = CaseResult {
+                case: c,
+                variant: label.clone(),
+                run_id: run.run_id,
+                output,
+                error,
+                scores,
+                usage,
+                latency_ms,
+            };
+            broadcast_update(json!({
+                "type": "eval_progress",
+                "data": {
+                    "done": results.len() + 1,
+                    "total": total,
+                    "result": result,
+                }
+            })).await;
+            results.push(result);
+        }
+        let rows: Vec<&CaseResult> = results.iter().filter(|result| result.variant == label).collect();
+        summary.push(summarize(label, model, &rows, judge_usage));
+    }
+
+    add_log_entry(services.log_state, "info", &format!("Evaluation of '{}' finished", name), "eval")?;
+    Ok(EvalReport {
+        name,
+        workflow_id: spec.workflow.id.clone(),
+        node: spec.node.clone(),
+        started_at,
+        finished_at: chrono::Local::now().to_rfc3339(),
+        cases: cases.len(),
+        summary,
+        results,
+    })
+}
+
+impl EvalReport {
+    /// The comparison table as Markdown, one row per variant.
+    pub fn table(&self) -> String {
+        let mut scorers: Vec<&str> = Vec::new();
+        for score in self.summary.iter().flat_map(|variant| &variant.scores) {
+            if !scorers.contains(&score.scorer.as_str()) {
+                scorers.push(&score.scorer);
+            }
+        }
+        let mut table = format!("| Variant | Model | Failed | {}Mean latency | p95 latency | Prompt tokens | Completion tokens |\n", scorers.iter().map(|scorer| format!("{} | ", scorer)).collect::<String>());
+        table.push_str(&format!("|---|---|---|{}---|---|---|---|\n", "---|".repeat(scorers.len())));
+        for variant in &self.summary {
+            let scores: String = scorers.iter().map(|scorer| {
+                match variant.scores.iter().find(|score| score.scorer == *scorer) {
+                    Some(ScorerSummary { mean: Some(mean), passed, graded, .. }) => format!("{:.2} ({}/{}) | ", mean, passed, graded),
+                    _ => "- | ".to_string(),
+                }
+            }).collect();
+            table.push_str(&format!(
+                "| {} | {} | {}/{} | {}{} ms | {} ms | {} | {} |\n",
+                variant.variant,
+                variant.model.as_deref().unwrap_or("-"),
+                variant.failed,
+                variant.cases,
+                scores,
+                variant.mean_latency_ms,
+                variant.p95_latency_ms,
+                variant.prompt_tokens,
+                variant.completion_tokens,
+            ));
+        }
+        table
+    }
+}
+
+#[tauri::command]
+pub async fn run_evaluation(app: AppHandle, spec: EvalSpec) -> Result<EvalReport, String> {
+    run_eval(&spec, Services::from_app(&app)).await
+}
+
+#[tauri::command]
+pub async fn eval_report_table(report: EvalReport) -> Result<String, String> {
+    Ok(report.table())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::mock::{Fixture, MockLlmServer};
+    use crate::workflow::testing::TestServices;
+
+    #[test]
+    fn datasets_take_objects_and_strings() {
+        let cases = parse_dataset("{\"input\": \"France\", \"expected\": \"Paris\"}\n\n\"Spain\"\n").unwrap();
+        assert_eq!((cases[0].input.as_str(), cases[0].expected.clone()), ("France", Some(json!("Paris"))));
+        assert_eq!((cases[1].input.as_str(), cases[1].expected.clone()), ("Spain", None));
+        assert_eq!(parse_dataset("{\"expected\": 1}").unwrap_err(), "Dataset line 1 has no \"input\"");
+        assert_eq!(parse_dataset("\n").unwrap_err(), "Dataset has no cases");
+    }
+
+    #[tokio::test]
+    async fn models_are_compared_over_the_dataset() {
+        let fixture: Fixture = serde_json::from_value(json!({"responses": [
+            {"model": "good", "match": "France", "content": "Paris"},
+            {"model": "good", "match": "Italy", "content": "Rome"},
+            {"model": "good", "match": "Spain", "content": "Madrid"},
+            {"model": "bad", "match": "France", "content": "Paris"},
+            {"model": "bad", "match": "Italy", "error": "model overloaded"},
+            {"model": "bad", "content": "Lyon"}
+        ]})).unwrap();
+        let server = MockLlmServer::with_fixture(fixture).await.unwrap();
+        let dataset = [("France", "Paris"), ("Italy", "Rome"), ("Spain", "Madrid")]
+            .map(|(input, expected)| json!({"input": input, "expected": expected}).to_string())
+            .join("\n");
+        let spec: EvalSpec = serde_json::from_value(json!({
+            "name": "capitals",
+            "workflow": {"id": "w", "name": "Capitals", "nodes": [
+                {"id": "in", "type": "input"},
+                {"id": "ask", "type": "llm", "server_url": server.base_url, "model": "good", "prompt": "Capital of {{input}}?"}
+            ], "edges": [{"from": "in", "to": "ask"}]},
+            "dataset": dataset,
+            "variants": [{"model": "good"}, {"model": "bad"}],
+            "scorers": [{"kind": "exact_match"}]
+        })).unwrap();
+
+        let services = TestServices::new();
+        let report = run_eval(&spec, services.services()).await.unwrap();
+        assert_eq!((report.cases, report.results.len()), (3, 6));
+        assert!(report.results[4].error.as_deref().unwrap().starts_with("Node ask failed: "));
+        let rows: Vec<_> = report.summary.iter()
+            .map(|row| (row.variant.as_str(), row.model.as_deref(), row.failed, row.scores[0].mean, row.scores[0].passed, row.scores[0].graded))
+            .collect();
+        assert_eq!(rows, [
+            ("good", Some("good"), 0, Some(1.0), 3, 3),
+            ("bad", Some("bad"), 1, Some(0.5), 1, 2),
+        ]);
+
+        let table = report.table();
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines[0], "| Variant | Model | Failed | exact_match | Mean latency | p95 latency | Prompt tokens | Completion tokens |");
+        assert_eq!(lines[1], "|---|---|---|---|---|---|---|---|");
+        assert!(lines[2].starts_with("| good | good | 0/3 | 1.00 (3/3) | "), "{}", lines[2]);
+        assert!(lines[3].starts_with("| bad | bad | 1/3 | 0.50 (1/2) | "), "{}", lines[3]);
+        assert_eq!(lines.len(), 4);
+    }
+}
@@ -0,0 +1,247 @@
+// Scorers grading an eval case's output, each on a 0 to 1 scale.
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::cache::ResponseCache;
+use crate::provider::{ChatMessage, ChatOptions, Usage};
+use crate::workflow::schema;
+
+const JUDGE_PROMPT: &str = "You grade answers. Compare the answer with the task and, when given, the reference answer. \
+Reply with JSON: {\"score\": <integer 0-10>, \"reason\": \"<one sentence>\"}.";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Scorer {
+    /// The output equals the expected output. Typed outputs are compared as
+    /// JSON, text after trimming.
+    ExactMatch {
+        #[serde(default)]
+        ignore_case: bool,
+    },
+    /// The output matches `pattern`.
+    Regex { pattern: String },
+    /// The output is JSON satisfying `schema`.
+    JsonSchema { schema: Value },
+    /// A model grades the output from 0 to 10.
+    Judge {
+        server_url: String,
+        model: String,
+        /// What the judge should look for, beyond agreeing with the reference.
+        #[serde(default)]
+        criteria: String,
+        /// Lowest normalized score that counts as a pass.
+        #[serde(default = "default_threshold")]
+        threshold: f64,
+    },
+}
+
+fn default_threshold() -> f64 {
+    0.7
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Score {
+    pub scorer: String,
+    /// None when the scorer could not grade the case, e.g. without an
+    /// expected output.
+    pub score: Option<f64>,
+    pub passed: bool,
+    #[serde(default)]
+    pub detail: Option<String>,
+}
+
+/// What a scorer looks at.
+pub struct Graded<'a> {
+    pub input: &'a str,
+    pub output: &'a str,
+    pub value: Option<&'a Value>,
+    pub expected: Option<&'a Value>,
+}
+
+impl Scorer {
+    pub fn name(&self) -> String {
+        match self {
+            Scorer::ExactMatch { .. } => "exact_match".to_string(),
+            Scorer::Regex { .. } => "regex".to_string(),
+            Scorer::JsonSchema { .. } => "json_schema".to_string(),
+            Scorer::Judge { model, .. } => format!("judge:{}", model),
+        }
+    }
+
+    /// Grade one output. Judge requests add their tokens to `usage`.
+    pub async fn score(&self, graded: &Graded<'_>, cache: &ResponseCache, usage: &mut Usage) -> Score {
+        let result = match self {
+            Scorer::ExactMatch { ignore_case } => Ok(exact_match(graded, *ignore_case)),
+            Scorer::Regex { pattern } => regex::Regex::new(pattern)
+                .map(|re| Some((re.is_match(graded.output) as u8 as f64, None)))
+                .map_err(|e| format!("Invalid pattern: {}", e)),
+            Scorer::JsonSchema { schema: expected } => Ok(Some(match graded.value.cloned().map_or_else(|| schema::extract_json(graded.output), Ok) {
+                Ok(value) => match schema::validate(expected, &value) {
+                    Ok(()) => (1.0, None),
+                    Err(errors) => (0.0, Some(errors.join("\n"))),
+                },
+                Err(e) => (0.0, Some(e)),
+            })),
+            Scorer::Judge { server_url, model, criteria, .. } => judge(graded, server_url, model, criteria, cache, usage).await.map(Some),
+        };
+        let threshold = match self {
+            Scorer::Judge { threshold, .. } => *threshold,
+            _ => 1.0,
+        };
+        match result {
+            Ok(Some((score, detail))) => Score {
+                scorer: self.name(),
+                score: Some(score),
+                passed: score >= threshold,
+                detail,
+            },
+            Ok(None) => Score {
+                scorer: self.name(),
+                score: None,
+                passed: false,
+                detail: Some("No expected output".to_string()),
+            },
+            Err(e) => Score {
+                scorer: self.name(),
+                score: None,
+                passed: false,
+                detail: Some(e),
+            },
+        }
+    }
+}
+
+fn exact_match(graded: &Graded<'_>, ignore_case: bool) -> Option<(f64, Option<String>)> {
+    let expected = graded.expected?;
+    let equal = match (expected, graded.value) {
+        (Value::String(_), _) | (_, None) => {
+            let expected = match expected {
+                Value::String(text) => text.clone(),
+                other => other.to_string(),
+            };
+            let (a, b) = (graded.output.trim(), expected.trim());
+            if ignore_case { a.to_lowercase() == b.to_lowercase() } else { a == b }
+        }
+        (expected, Some(value)) => expected == value,
+    };
+    Some((equal as u8 as f64, None))
+}
+
+async fn judge(
+    graded: &Graded<'_>,
+    server_url: &str,
+    model: &str,
+    criteria: &str,
+    cache: &ResponseCache,
+    usage: &mut Usage,
+) -> Result<(f64, Option<String>), String> {
+    let mut request = format!("Task:\n{}\n\nAnswer:\n{}", graded.input, graded.output);
+    if let Some(expected) = graded.expected {
+        let expected = match expected {
+            Value::String(text) => text.clone(),
+            other => other.to_string(),
+        };
+        request.push_str(&format!("\n\nReference answer:\n{}", expected));
+    }
+    if !criteria.trim().is_empty() {
+        request.push_str(&format!("\n\nCriteria:\n{}", criteria.trim()));
+    }
+    let grade_schema = json!({
+        "type": "object",
+        "properties": { "score": { "type": "number" }, "reason": { "type": "string" } },
+        "required": ["score"]
+    });
+    let options = ChatOptions {
+        temperature: 0.0,
+        response_schema: Some(grade_schema.clone()),
+        ..Default::default()
+    };
+    let reply = cache.chat(server_url, model, &[ChatMessage::system(JUDGE_PROMPT), ChatMessage::user(request)], &options).await?;
+    usage.add(reply.usage);
+    let grade = schema::check_reply(&grade_schema, &reply.content).map_err(|e| format!("Judge reply unusable: {}", e))?;
+    let score = grade["score"].as_f64().unwrap_or_default().clamp(0.0, 10.0) / 10.0;
+    Ok((score, grade["reason"].as_str().map(str::to_string)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::mock::{Fixture, MockLlmServer};
+
+    fn graded<'a>(output: &'a str, value: Option<&'a Value>, expected: Option<&'a Value>) -> Graded<'a> {
+        Graded { input: "What is the capital of France?", output, value, expected }
+    }
+
+    async fn score(scorer: Value, graded: &Graded<'_>) -> Score {
+        let scorer: Scorer = serde_json::from_value(scorer).unwrap();
+        let cache = ResponseCache::open_in_memory().unwrap();
+        scorer.score(graded, &cache, &mut Usage::default()).await
+    }
+
+    #[tokio::test]
+    async fn exact_match_compares_text_and_typed_outputs() {
+        let exact = json!({"kind": "exact_match"});
+        let paris = json!("Paris");
+        assert!(score(exact.clone(), &graded(" Paris\n", None, Some(&paris))).await.passed);
+        assert_eq!(score(exact.clone(), &graded("paris", None, Some(&paris))).await.score, Some(0.0));
+        assert!(score(json!({"kind": "exact_match", "ignore_case": true}), &graded("PARIS", None, Some(&paris))).await.passed);
+
+        let expected = json!({"city": "Paris", "country": "France"});
+        let same = json!({"country": "France", "city": "Paris"});
+        let other = json!({"city": "Lyon", "country": "France"});
+        assert!(score(exact.clone(), &graded("{…}", Some(&same), Some(&expected))).await.passed);
+        assert!(!score(exact.clone(), &graded("{…}", Some(&other), Some(&expected))).await.passed);
+        // Text output is compared with the expected JSON's text
+        assert!(score(exact.clone(), &graded("[1,2]", None, Some(&json!([1, 2])))).await.passed);
+
+        let ungraded = score(exact, &graded("Paris", None, None)).await;
+        assert_eq!((ungraded.score, ungraded.passed), (None, false));
+        assert_eq!(ungraded.detail.as_deref(), Some("No expected output"));
+    }
+
+    #[tokio::test]
+    async fn regex_and_schema_failures_are_reported() {
+        let matched = score(json!({"kind": "regex", "pattern": "(?i)^paris$"}), &graded("PARIS", None, None)).await;
+        assert_eq!((matched.scorer.as_str(), matched.score), ("regex", Some(1.0)));
+        let invalid = score(json!({"kind": "regex", "pattern": "(unclosed"}), &graded("Paris", None, None)).await;
+        assert_eq!((invalid.score, invalid.passed), (None, false));
+        assert!(invalid.detail.unwrap().starts_with("Invalid pattern: "));
+
+        let schema = json!({"kind": "json_schema", "schema": {
+            "type": "object", "properties": {"population": {"type": "integer"}}, "required": ["population"]
+        }});
+        assert!(score(schema.clone(), &graded("```json\n{\"population\": 2102650}\n```", None, None)).await.passed);
+        let wrong_type = score(schema.clone(), &graded("{\"population\": \"many\"}", None, None)).await;
+        assert_eq!(wrong_type.score, Some(0.0));
+        assert!(wrong_type.detail.unwrap().contains("population"));
+        let not_json = score(schema, &graded("About two million", None, None)).await;
+        assert_eq!((not_json.score, not_json.passed), (Some(0.0), false));
+        assert!(not_json.detail.is_some());
+    }
+
+    #[tokio::test]
+    async fn judge_grades_through_the_model() {
+        let fixture: Fixture = serde_json::from_value(json!({"responses": [
+            {"match": "Answer:\nParis", "content": "{\"score\": 8, \"reason\": \"Correct but terse\"}"},
+            {"content": "{\"score\": 15}"}
+        ]})).unwrap();
+        let server = MockLlmServer::with_fixture(fixture).await.unwrap();
+        let scorer: Scorer = serde_json::from_value(json!({
+            "kind": "judge", "server_url": server.base_url, "model": "grader", "criteria": "Be brief."
+        })).unwrap();
+        let cache = ResponseCache::open_in_memory().unwrap();
+        let mut usage = Usage::default();
+        let expected = json!("Paris");
+
+        let grade = scorer.score(&graded("Paris", None, Some(&expected)), &cache, &mut usage).await;
+        assert_eq!((grade.scorer.as_str(), grade.score, grade.passed), ("judge:grader", Some(0.8), true));
+        assert_eq!(grade.detail.as_deref(), Some("Correct but terse"));
+        assert!(usage.total() > 0);
+        let request = server.requests()[0]["messages"][1]["content"].as_str().unwrap().to_string();
+        assert!(request.ends_with("Reference answer:\nParis\n\nCriteria:\nBe brief."), "{}", request);
+
+        // Out-of-range grades are clamped
+        let grade = scorer.score(&graded("Lyon", None, Some(&expected)), &cache, &mut usage).await;
+        assert_eq!(grade.score, Some(1.0));
+    }
+}
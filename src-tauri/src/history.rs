@@ -0,0 +1,718 @@
+// Persistent history of workflow runs, and the saved workflows themselves,
+// kept in SQLite under the app data dir.
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use similar::TextDiff;
+use std::path::Path;
+use std::sync::Mutex;
+use tauri::State;
+
+use crate::provider::Usage;
+use crate::workflow::{NodeResult, NodeStatus, RunStatus, Workflow};
+
+/// Schema migrations, applied in order and tracked with `PRAGMA user_version`.
+const MIGRATIONS: &[&str] = &["
+    CREATE TABLE IF NOT EXISTS runs (
+        id TEXT PRIMARY KEY,
+        workflow_id TEXT NOT NULL,
+        workflow_name TEXT NOT NULL,
+        workflow_json TEXT NOT NULL,
+        status TEXT NOT NULL,
+        started_at TEXT NOT NULL,
+        finished_at TEXT
+    );
+    CREATE INDEX IF NOT EXISTS runs_by_workflow ON runs (workflow_id, started_at);
+    CREATE TABLE IF NOT EXISTS node_runs (
+        run_id TEXT NOT NULL REFERENCES runs (id) ON DELETE CASCADE,
+        seq INTEGER NOT NULL,
+        node_id TEXT NOT NULL,
+        node_type TEXT NOT NULL,
+        status TEXT NOT NULL,
+        input TEXT,
+        output TEXT,
+        error TEXT,
+        label TEXT,
+        model TEXT,
+        params TEXT NOT NULL,
+        prompt_tokens INTEGER,
+        completion_tokens INTEGER,
+        latency_ms INTEGER NOT NULL,
+        started_at TEXT NOT NULL,
+        PRIMARY KEY (run_id, node_id)
+    );
+", "
+    ALTER TABLE runs ADD COLUMN resumed_from TEXT;
+    ALTER TABLE node_runs ADD COLUMN reused_from TEXT;
+", "
+    ALTER TABLE node_runs ADD COLUMN cache_hits INTEGER NOT NULL DEFAULT 0;
+", "
+    CREATE TABLE IF NOT EXISTS artifacts (
+        id TEXT PRIMARY KEY,
+        run_id TEXT NOT NULL REFERENCES runs (id) ON DELETE CASCADE,
+        node_id TEXT NOT NULL,
+        name TEXT NOT NULL,
+        media_type TEXT NOT NULL,
+        size INTEGER NOT NULL,
+        content BLOB NOT NULL,
+        created_at TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS artifacts_by_run ON artifacts (run_id, node_id);
+", "
+    CREATE TABLE IF NOT EXISTS workflows (
+        id TEXT PRIMARY KEY,
+        name TEXT NOT NULL,
+        description TEXT NOT NULL,
+        workflow_json TEXT NOT NULL,
+        published INTEGER NOT NULL DEFAULT 0,
+        updated_at TEXT NOT NULL
+    );
+", "
+    ALTER TABLE node_runs ADD COLUMN value TEXT;
+", "
+    ALTER TABLE node_runs ADD COLUMN context TEXT;
+", "
+    CREATE TABLE IF NOT EXISTS golden_runs (
+        run_id TEXT PRIMARY KEY REFERENCES runs (id) ON DELETE CASCADE,
+        suite TEXT NOT NULL,
+        name TEXT NOT NULL,
+        marked_at TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS golden_runs_by_suite ON golden_runs (suite);
+"];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunSummary {
+    pub run_id: String,
+    pub workflow_id: String,
+    pub workflow_name: String,
+    pub status: RunStatus,
+    pub started_at: String,
+    pub finished_at: Option<String>,
+    pub node_count: u64,
+    pub total_tokens: u64,
+    pub total_latency_ms: u64,
+    /// The run this one resumed from, if any.
+    pub resumed_from: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunDetail {
+    #[serde(flatten)]
+    pub summary: RunSummary,
+    pub workflow: Workflow,
+    pub nodes: Vec<NodeResult>,
+}
+
+/// A file a node produced alongside its output, e.g. a full query result.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Artifact {
+    pub id: String,
+    pub run_id: String,
+    pub node_id: String,
+    pub name: String,
+    pub media_type: String,
+    pub size: u64,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArtifactContent {
+    #[serde(flatten)]
+    pub artifact: Artifact,
+    pub content: String,
+}
+
+/// A run whose outputs later runs of its workflow are checked against.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GoldenRun {
+    pub run_id: String,
+    pub suite: String,
+    pub name: String,
+    pub marked_at: String,
+    pub workflow_id: String,
+    pub workflow_name: String,
+}
+
+/// A workflow saved from the canvas. Published ones are served as MCP tools.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedWorkflow {
+    pub workflow: Workflow,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub published: bool,
+    #[serde(default)]
+    pub updated_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NodeDiff {
+    pub node_id: String,
+    pub status_a: Option<NodeStatus>,
+    pub status_b: Option<NodeStatus>,
+    pub output_changed: bool,
+    /// Unified diff of the two outputs, present when they differ.
+    pub output_diff: Option<String>,
+    pub latency_ms_a: Option<u64>,
+    pub latency_ms_b: Option<u64>,
+    pub tokens_a: Option<u64>,
+    pub tokens_b: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunDiff {
+    pub run_a: RunSummary,
+    pub run_b: RunSummary,
+    pub nodes: Vec<NodeDiff>,
+}
+
+pub struct RunHistory {
+    conn: Mutex<Connection>,
+}
+
+impl RunHistory {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let conn = Connection::open(path).map_err(|e| format!("Failed to open run history: {}", e))?;
+        Self::init(conn)
+    }
+
+    #[cfg(test)]
+    pub fn open_in_memory() -> Result<Self, String> {
+        Self::init(Connection::open_in_memory().map_err(|e| e.to_string())?)
+    }
+
+    fn init(conn: Connection) -> Result<Self, String> {
+        migrate(&conn).map_err(|e| format!("Failed to initialise run history: {}", e))?;
+        Ok(Self { conn: Mutex::new(conn) })
+    }
+
+    fn conn(&self) -> Result<std::sync::MutexGuard<'_, Connection>, String> {
+        self.conn.lock().map_err(|e| e.to_string())
+    }
+
+    pub fn start_run(&self, run_id: &str, workflow: &Workflow, started_at: &str, resumed_from: Option<&str>) -> Result<(), String> {
+        let workflow_json = serde_json::to_string(workflow).map_err(|e| e.to_string())?;
+        self.conn()?
+            .execute(
+                "INSERT INTO runs (id, workflow_id, workflow_name, workflow_json, status, started_at, resumed_from)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![run_id, workflow.id, workflow.name, workflow_json, RunStatus::Running.as_str(), started_at, resumed_from],
+            )
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    pub fn record_node(&self, run_id: &str, seq: usize, node: &NodeResult) -> Result<(), String> {
+        let params_json = serde_json::to_string(&node.params).map_err(|e| e.to_string())?;
+        let value_json = node.value.as_ref().map(Value::to_string);
+        let context_json = node.context.as_ref().map(serde_json::to_string).transpose().map_err(|e| e.to_string())?;
+        self.conn()?
+            .execute(
+                "INSERT OR REPLACE INTO node_runs (
+                    run_id, seq, node_id, node_type, status, input, output, error, label, model,
+                    params, prompt_tokens, completion_tokens, latency_ms, started_at, reused_from, cache_hits, value, context
+                 ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19)",
+                params![
+                    run_id,
+                    seq as i64,
+                    node.node_id,
+                    node.node_type,
+                    node.status.as_str(),
+                    node.input,
+                    node.output,
+                    node.error,
+                    node.label,
+                    node.model,
+                    params_json,
+                    node.usage.prompt_tokens.map(|t| t as i64),
+                    node.usage.completion_tokens.map(|t| t as i64),
+                    node.latency_ms as i64,
+                    node.started_at,
+                    node.reused_from,
+                    node.cache_hits,
+                    value_json,
+                    context_json,
+                ],
+            )
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    pub fn finish_run(&self, run_id: &str, status: RunStatus, finished_at: &str) -> Result<(), String> {
+        self.conn()?
+            .execute(
+                "UPDATE runs SET status = ?2, finished_at = ?3 WHERE id = ?1",
+                params![run_id, status.as_str(), finished_at],
+            )
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    pub fn list_runs(&self, workflow_id: Option<&str>, limit: u32) -> Result<Vec<RunSummary>, String> {
+        let conn = self.conn()?;
+        let mut stmt = conn
+            .prepare(&format!(
+                "{} WHERE ?1 IS NULL OR r.workflow_id = ?1 GROUP BY r.id ORDER BY r.started_at DESC LIMIT ?2",
+                SUMMARY_QUERY
+            ))
+            .map_err(|e| e.to_string())?;
+        let runs = stmt
+            .query_map(params![workflow_id, limit], summary_from_row)
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        Ok(runs)
+    }
+
+    pub fn get_run(&self, run_id: &str) -> Result<RunDetail, String> {
+        let conn = self.conn()?;
+        let (summary, workflow_json) = conn
+            .query_row(
+                &format!("{} WHERE r.id = ?1 GROUP BY r.id", SUMMARY_QUERY),
+                params![run_id],
+                |row| Ok((summary_from_row(row)?, row.get::<_, String>(10)?)),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("Run {} not found", run_id))?;
+        let workflow = serde_json::from_str(&workflow_json).map_err(|e| e.to_string())?;
+
+        let mut stmt = conn
+            .prepare(
+                "SELECT node_id, node_type, status, input, output, error, label, model, params,
+                        prompt_tokens, completion_tokens, latency_ms, started_at, reused_from, cache_hits, value, context
+                 FROM node_runs WHERE run_id = ?1 ORDER BY seq",
+            )
+            .map_err(|e| e.to_string())?;
+        let mut nodes: Vec<NodeResult> = stmt
+            .query_map(params![run_id], |row| {
+                let status: String = row.get(2)?;
+                let params: String = row.get(8)?;
+                let value: Option<String> = row.get(15)?;
+                let context: Option<String> = row.get(16)?;
+                Ok(NodeResult {
+                    node_id: row.get(0)?,
+                    node_type: row.get(1)?,
+                    status: NodeStatus::parse(&status).unwrap_or(NodeStatus::Failed),
+                    input: row.get(3)?,
+                    output: row.get(4)?,
+                    value: value.and_then(|value| serde_json::from_str(&value).ok()),
+                    error: row.get(5)?,
+                    label: row.get(6)?,
+                    model: row.get(7)?,
+                    params: serde_json::from_str(&params).unwrap_or(Value::Null),
+                    usage: Usage {
+                        prompt_tokens: row.get::<_, Option<i64>>(9)?.map(|t| t as u64),
+                        completion_tokens: row.get::<_, Option<i64>>(10)?.map(|t| t as u64),
+                    },
+                    latency_ms: row.get::<_, i64>(11)? as u64,
+                    started_at: row.get(12)?,
+                    reused_from: row.get(13)?,
+                    cache_hits: row.get(14)?,
+                    artifacts: Vec::new(),
+                    context: context.and_then(|context| serde_json::from_str(&context).ok()),
+                })
+            })
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+
+        drop(stmt);
+        drop(conn);
+        let artifacts = self.artifacts(run_id, None)?;
+        for node in &mut nodes {
+            node.artifacts = artifacts.iter().filter(|a| a.node_id == node.node_id).cloned().collect();
+        }
+
+        Ok(RunDetail { summary, workflow, nodes })
+    }
+
+    pub fn save_artifact(&self, run_id: &str, node_id: &str, name: &str, media_type: &str, content: &[u8]) -> Result<Artifact, String> {
+        let artifact = Artifact {
+            id: uuid::Uuid::new_v4().to_string(),
+            run_id: run_id.to_string(),
+            node_id: node_id.to_string(),
+            name: name.to_string(),
+            media_type: media_type.to_string(),
+            size: content.len() as u64,
+            created_at: chrono::Local::now().to_rfc3339(),
+        };
+        self.conn()?
+            .execute(
+                "INSERT INTO artifacts (id, run_id, node_id, name, media_type, size, content, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![
+                    artifact.id,
+                    artifact.run_id,
+                    artifact.node_id,
+                    artifact.name,
+                    artifact.media_type,
+                    artifact.size as i64,
+                    content,
+                    artifact.created_at,
+                ],
+            )
+            .map_err(|e| format!("Failed to save artifact: {}", e))?;
+        Ok(artifact)
+    }
+
+    /// Artifacts of a run, or of one of its nodes, oldest first.
+    pub fn artifacts(&self, run_id: &str, node_id: Option<&str>) -> Result<Vec<Artifact>, String> {
+        let conn = self.conn()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, run_id, node_id, name, media_type, size, created_at FROM artifacts
+                 WHERE run_id = ?1 AND (?2 IS NULL OR node_id = ?2) ORDER BY created_at, rowid",
+            )
+            .map_err(|e| e.to_string())?;
+        let artifacts = stmt
+            .query_map(params![run_id, node_id], artifact_from_row)
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        Ok(artifacts)
+    }
+
+    pub fn artifact_content(&self, id: &str) -> Result<ArtifactContent, String> {
+        self.conn()?
+            .query_row(
+                "SELECT id, run_id, node_id, name, media_type, size, created_at, content FROM artifacts WHERE id = ?1",
+                params![id],
+                |row| Ok(ArtifactContent {
+                    artifact: artifact_from_row(row)?,
+                    content: String::from_utf8_lossy(&row.get::<_, Vec<u8>>(7)?).into_owned(),
+                }),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("Artifact {} not found", id))
+    }
+
+    /// Save a workflow under its id, replacing an earlier version.
+    pub fn save_workflow(&self, saved: &SavedWorkflow) -> Result<SavedWorkflow, String> {
+        let workflow_json = serde_json::to_string(&saved.workflow).map_err(|e| e.to_string())?;
+        let updated_at = chrono::Local::now().to_rfc3339();
+        self.conn()?
+            .execute(
+                "INSERT INTO workflows (id, name, description, workflow_json, published, updated_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+                 ON CONFLICT (id) DO UPDATE SET name = ?2, description = ?3, workflow_json = ?4, published = ?5, updated_at = ?6",
+                params![saved.workflow.id, saved.workflow.name, saved.description, workflow_json, saved.published, updated_at],
+            )
+            .map_err(|e| format!("Failed to save workflow: {}", e))?;
+        Ok(SavedWorkflow { updated_at, ..saved.clone() })
+    }
+
+    /// Saved workflows by name, or only the published ones.
+    pub fn list_workflows(&self, published_only: bool) -> Result<Vec<SavedWorkflow>, String> {
+        let conn = self.conn()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT workflow_json, description, published, updated_at FROM workflows
+                 WHERE published = 1 OR ?1 = 0 ORDER BY name, id",
+            )
+            .map_err(|e| e.to_string())?;
+        let workflows = stmt
+            .query_map(params![published_only], saved_workflow_from_row)
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        Ok(workflows)
+    }
+
+    pub fn delete_workflow(&self, id: &str) -> Result<(), String> {
+        self.conn()?
+            .execute("DELETE FROM workflows WHERE id = ?1", params![id])
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    /// Mark a completed run as golden, or move it to another suite.
+    pub fn mark_golden(&self, run_id: &str, suite: &str, name: &str) -> Result<GoldenRun, String> {
+        let run = self.get_run(run_id)?;
+        if run.summary.status != RunStatus::Completed {
+            return Err(format!("Run {} did not complete and cannot be golden", run_id));
+        }
+        let suite = suite.trim();
+        if suite.is_empty() {
+            return Err("Golden runs need a suite".to_string());
+        }
+        let name = if name.trim().is_empty() { run.summary.workflow_name.as_str() } else { name.trim() };
+        self.conn()?
+            .execute(
+                "INSERT OR REPLACE INTO golden_runs (run_id, suite, name, marked_at) VALUES (?1, ?2, ?3, ?4)",
+                params![run_id, suite, name, chrono::Local::now().to_rfc3339()],
+            )
+            .map_err(|e| e.to_string())?;
+        self.golden_runs(Some(suite))?
+            .into_iter()
+            .find(|golden| golden.run_id == run_id)
+            .ok_or_else(|| format!("Run {} not found", run_id))
+    }
+
+    pub fn unmark_golden(&self, run_id: &str) -> Result<(), String> {
+        self.conn()?
+            .execute("DELETE FROM golden_runs WHERE run_id = ?1", params![run_id])
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    pub fn golden_runs(&self, suite: Option<&str>) -> Result<Vec<GoldenRun>, String> {
+        let conn = self.conn()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT g.run_id, g.suite, g.name, g.marked_at, r.workflow_id, r.workflow_name
+                 FROM golden_runs g JOIN runs r ON r.id = g.run_id
+                 WHERE ?1 IS NULL OR g.suite = ?1 ORDER BY g.suite, g.marked_at",
+            )
+            .map_err(|e| e.to_string())?;
+        let goldens = stmt
+            .query_map(params![suite], |row| {
+                Ok(GoldenRun {
+                    run_id: row.get(0)?,
+                    suite: row.get(1)?,
+                    name: row.get(2)?,
+                    marked_at: row.get(3)?,
+                    workflow_id: row.get(4)?,
+                    workflow_name: row.get(5)?,
+                })
+            })
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        Ok(goldens)
+    }
+}
+
+fn artifact_from_row(row: &Row) -> rusqlite::Result<Artifact> {
+    Ok(Artifact {
+        id: row.get(0)?,
+        run_id: row.get(1)?,
+        node_id: row.get(2)?,
+        name: row.get(3)?,
+        media_type: row.get(4)?,
+        size: row.get::<_, i64>(5)? as u64,
+        created_at: row.get(6)?,
+    })
+}
+
+const SUMMARY_QUERY: &str = "
+    SELECT r.id, r.workflow_id, r.workflow_name, r.status, r.started_at, r.finished_at,
+           COUNT(n.node_id),
+           COALESCE(SUM(COALESCE(n.prompt_tokens, 0) + COALESCE(n.completion_tokens, 0)), 0),
+           COALESCE(SUM(n.latency_ms), 0),
+           r.resumed_from,
+           r.workflow_json
+    FROM runs r LEFT JOIN node_runs n ON n.run_id = r.id";
+
+fn saved_workflow_from_row(row: &Row<'_>) -> rusqlite::Result<SavedWorkflow> {
+    let workflow_json: String = row.get(0)?;
+    let workflow = serde_json::from_str(&workflow_json)
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(e)))?;
+    Ok(SavedWorkflow {
+        workflow,
+        description: row.get(1)?,
+        published: row.get(2)?,
+        updated_at: row.get(3)?,
+    })
+}
+
+fn summary_from_row(row: &Row<'_>) -> rusqlite::Result<RunSummary> {
+    let status: String = row.get(3)?;
+    Ok(RunSummary {
+        run_id: row.get(0)?,
+        workflow_id: row.get(1)?,
+        workflow_name: row.get(2)?,
+        status: RunStatus::parse(&status).unwrap_or(RunStatus::Failed),
+        started_at: row.get(4)?,
+        finished_at: row.get(5)?,
+        node_count: row.get::<_, i64>(6)? as u64,
+        total_tokens: row.get::<_, i64>(7)? as u64,
+        total_latency_ms: row.get::<_, i64>(8)? as u64,
+        resumed_from: row.get(9)?,
+    })
+}
+
+fn migrate(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch("PRAGMA foreign_keys = ON;")?;
+    let version: usize = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    for (i, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        // Dropping the transaction on error rolls the migration back
+        let tx = conn.unchecked_transaction()?;
+        tx.execute_batch(migration)?;
+        tx.pragma_update(None, "user_version", i + 1)?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+/// Compare two runs node by node, in the order nodes ran in `a` followed by
+/// any nodes only present in `b`.
+pub fn compare_runs(a: &RunDetail, b: &RunDetail) -> RunDiff {
+    let mut node_ids: Vec<&str> = a.nodes.iter().map(|n| n.node_id.as_str()).collect();
+    for node in &b.nodes {
+        if !node_ids.contains(&node.node_id.as_str()) {
+            node_ids.push(&node.node_id);
+        }
+    }
+
+    let nodes = node_ids.into_iter()
+        .map(|node_id| {
+            let left = a.nodes.iter().find(|n| n.node_id == node_id);
+            let right = b.nodes.iter().find(|n| n.node_id == node_id);
+            let output_a = left.and_then(|n| n.output.as_deref()).unwrap_or_default();
+            let output_b = right.and_then(|n| n.output.as_deref()).unwrap_or_default();
+            let output_changed = output_a != output_b;
+            NodeDiff {
+                node_id: node_id.to_string(),
+                status_a: left.map(|n| n.status),
+                status_b: right.map(|n| n.status),
+                output_changed,
+                output_diff: output_changed.then(|| {
+                    TextDiff::from_lines(output_a, output_b)
+                        .unified_diff()
+                        .header(&a.summary.run_id, &b.summary.run_id)
+                        .to_string()
+                }),
+                latency_ms_a: left.map(|n| n.latency_ms),
+                latency_ms_b: right.map(|n| n.latency_ms),
+                tokens_a: left.map(|n| n.usage.total()),
+                tokens_b: right.map(|n| n.usage.total()),
+            }
+        })
+        .collect();
+
+    RunDiff {
+        run_a: a.summary.clone(),
+        run_b: b.summary.clone(),
+        nodes,
+    }
+}
+
+#[tauri::command]
+pub async fn list_runs(workflow_id: Option<String>, limit: Option<u32>, history: State<'_, RunHistory>) -> Result<Vec<RunSummary>, String> {
+    history.list_runs(workflow_id.as_deref(), limit.unwrap_or(50))
+}
+
+#[tauri::command]
+pub async fn get_run(run_id: String, history: State<'_, RunHistory>) -> Result<RunDetail, String> {
+    history.get_run(&run_id)
+}
+
+#[tauri::command]
+pub async fn get_artifact(id: String, history: State<'_, RunHistory>) -> Result<ArtifactContent, String> {
+    history.artifact_content(&id)
+}
+
+#[tauri::command]
+pub async fn diff_runs(run_a: String, run_b: String, history: State<'_, RunHistory>) -> Result<RunDiff, String> {
+    let a = history.get_run(&run_a)?;
+    let b = history.get_run(&run_b)?;
+    Ok(compare_runs(&a, &b))
+}
+
+#[tauri::command]
+pub async fn save_workflow(workflow: SavedWorkflow, history: State<'_, RunHistory>) -> Result<SavedWorkflow, String> {
+    history.save_workflow(&workflow)
+}
+
+#[tauri::command]
+pub async fn list_workflows(history: State<'_, RunHistory>) -> Result<Vec<SavedWorkflow>, String> {
+    history.list_workflows(false)
+}
+
+#[tauri::command]
+pub async fn delete_workflow(id: String, history: State<'_, RunHistory>) -> Result<(), String> {
+    history.delete_workflow(&id)
+}
+
+#[tauri::command]
+pub async fn mark_golden_run(run_id: String, suite: String, name: Option<String>, history: State<'_, RunHistory>) -> Result<GoldenRun, String> {
+    history.mark_golden(&run_id, &suite, name.as_deref().unwrap_or_default())
+}
+
+#[tauri::command]
+pub async fn unmark_golden_run(run_id: String, history: State<'_, RunHistory>) -> Result<(), String> {
+    history.unmark_golden(&run_id)
+}
+
+#[tauri::command]
+pub async fn list_golden_runs(suite: Option<String>, history: State<'_, RunHistory>) -> Result<Vec<GoldenRun>, String> {
+    history.golden_runs(suite.as_deref())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn workflow() -> Workflow {
+        serde_json::from_value(json!({
+            "id": "w", "name": "W",
+            "nodes": [{ "id": "a", "type": "input", "value": "hi" }],
+            "edges": []
+        }))
+        .unwrap()
+    }
+
+    fn node(output: &str) -> NodeResult {
+        serde_json::from_value(json!({
+            "node_id": "a", "node_type": "input", "status": "completed",
+            "input": "", "output": output, "error": null, "label": null, "model": null,
+            "params": null, "prompt_tokens": 3, "completion_tokens": null,
+            "latency_ms": 5, "started_at": "t", "cache_hits": 2
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn records_and_compares_runs() {
+        let history = RunHistory::open_in_memory().unwrap();
+        history.start_run("r1", &workflow(), "2025-01-01", None).unwrap();
+        history.record_node("r1", 0, &node("hi\nthere")).unwrap();
+        history.finish_run("r1", RunStatus::Completed, "2025-01-02").unwrap();
+        history.start_run("r2", &workflow(), "2025-01-03", Some("r1")).unwrap();
+        history.record_node("r2", 0, &node("hi\nthem")).unwrap();
+
+        let runs = history.list_runs(None, 10).unwrap();
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[1].total_tokens, 3);
+        assert!(history.list_runs(Some("other"), 10).unwrap().is_empty());
+
+        let (a, b) = (history.get_run("r1").unwrap(), history.get_run("r2").unwrap());
+        assert_eq!(a.nodes[0].cache_hits, 2);
+        assert_eq!(b.summary.resumed_from.as_deref(), Some("r1"));
+        let diff = compare_runs(&a, &b);
+        assert!(diff.nodes[0].output_changed);
+        assert!(diff.nodes[0].output_diff.as_deref().unwrap().contains("+them"));
+    }
+
+    #[test]
+    fn golden_runs_need_a_completed_run() {
+        let history = RunHistory::open_in_memory().unwrap();
+        history.start_run("r1", &workflow(), "2025-01-01", None).unwrap();
+        assert!(history.mark_golden("r1", "smoke", "").is_err());
+        history.finish_run("r1", RunStatus::Completed, "2025-01-02").unwrap();
+        assert!(history.mark_golden("r1", " ", "").is_err());
+
+        let golden = history.mark_golden("r1", "smoke", "").unwrap();
+        assert_eq!(golden.name, "W");
+        assert_eq!(history.golden_runs(Some("smoke")).unwrap().len(), 1);
+        history.unmark_golden("r1").unwrap();
+        assert!(history.golden_runs(None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn failed_migration_rolls_back() {
+        let conn = Connection::open_in_memory().unwrap();
+        // A table in the way of the artifacts migration's index
+        conn.execute_batch("CREATE TABLE artifacts (id TEXT PRIMARY KEY);").unwrap();
+        assert!(migrate(&conn).is_err());
+
+        let version: usize = conn.query_row("PRAGMA user_version", [], |row| row.get(0)).unwrap();
+        assert_eq!(version, 3);
+        assert!(conn.is_autocommit());
+        conn.execute("INSERT INTO artifacts (id) VALUES ('x')", []).unwrap();
+    }
+}
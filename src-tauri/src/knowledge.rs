@@ -0,0 +1,783 @@
+// Knowledge bases: folders of documents split into chunks, embedded, and kept
+// in an on-disk index that Retriever nodes search.
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tauri::State;
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::provider;
+use crate::{add_log_entry, LogState};
+
+pub mod bm25;
+pub mod chunk;
+pub mod watch;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS knowledge_bases (
+        id TEXT PRIMARY KEY,
+        config TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS documents (
+        kb_id TEXT NOT NULL,
+        path TEXT NOT NULL,
+        hash TEXT NOT NULL,
+        indexed_at TEXT NOT NULL,
+        PRIMARY KEY (kb_id, path)
+    );
+    CREATE TABLE IF NOT EXISTS chunks (
+        kb_id TEXT NOT NULL,
+        path TEXT NOT NULL,
+        ordinal INTEGER NOT NULL,
+        start_line INTEGER NOT NULL,
+        end_line INTEGER NOT NULL,
+        text TEXT NOT NULL,
+        vector BLOB NOT NULL,
+        PRIMARY KEY (kb_id, path, ordinal)
+    );
+";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KnowledgeBaseConfig {
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub folders: Vec<PathBuf>,
+    /// Server and model the chunks and queries are embedded with.
+    pub server_url: String,
+    pub embedding_model: String,
+    /// Maximum chunk length in characters.
+    #[serde(default = "default_chunk_size")]
+    pub chunk_size: usize,
+    /// Characters of whole lines repeated at the start of the next chunk.
+    #[serde(default = "default_chunk_overlap")]
+    pub chunk_overlap: usize,
+}
+
+fn default_chunk_size() -> usize {
+    1200
+}
+
+fn default_chunk_overlap() -> usize {
+    200
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KnowledgeBaseInfo {
+    #[serde(flatten)]
+    pub config: KnowledgeBaseConfig,
+    pub documents: u64,
+    pub chunks: u64,
+    pub indexed_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IngestReport {
+    /// Documents chunked and embedded, and the chunks written for them.
+    pub documents: usize,
+    pub chunks: usize,
+    /// Documents skipped because their content hash had not changed.
+    #[serde(default)]
+    pub unchanged: usize,
+    /// Documents dropped because their file is gone.
+    #[serde(default)]
+    pub removed: usize,
+    /// Files that could not be indexed, with the reason.
+    pub failed: Vec<String>,
+}
+
+impl IngestReport {
+    fn summary(&self) -> String {
+        format!(
+            "{} document(s) indexed into {} chunk(s), {} unchanged, {} removed, {} failed",
+            self.documents, self.chunks, self.unchanged, self.removed, self.failed.len()
+        )
+    }
+}
+
+/// Totals over every knowledge base, shown in the status bar.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IndexStatus {
+    pub knowledge_bases: u64,
+    pub documents: u64,
+    pub chunks: u64,
+    /// Changed files waiting to be re-indexed.
+    pub pending: usize,
+    pub last_error: Option<String>,
+}
+
+#[derive(Default)]
+struct IndexActivity {
+    pending: HashSet<PathBuf>,
+    last_error: Option<String>,
+}
+
+enum Refresh {
+    Unchanged,
+    Indexed(usize),
+}
+
+/// A chunk matching a query, with where it came from.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchHit {
+    pub source: String,
+    pub chunk: usize,
+    pub start_line: usize,
+    pub end_line: usize,
+    pub text: String,
+    /// Score the hits are ordered by: the fused or rerank score in hybrid
+    /// search or after reranking, otherwise the method's own score.
+    pub score: f32,
+    /// Cosine similarity to the query.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vector_score: Option<f32>,
+    /// BM25 score, when the chunk contains any query term.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keyword_score: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rerank_score: Option<f32>,
+}
+
+/// How chunks are matched against a query.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchMode {
+    Vector,
+    Keyword,
+    /// Vector and keyword rankings merged by reciprocal rank fusion.
+    #[default]
+    Hybrid,
+}
+
+/// Damps the weight of top ranks in reciprocal rank fusion.
+const RRF_K: f32 = 60.0;
+
+struct IndexedChunk {
+    source: String,
+    ordinal: usize,
+    start_line: usize,
+    end_line: usize,
+    text: String,
+    vector: Vec<f32>,
+}
+
+/// The chunks of a knowledge base and a keyword index over them.
+struct LoadedIndex {
+    chunks: Vec<IndexedChunk>,
+    keywords: bm25::Bm25,
+}
+
+pub struct KnowledgeBase {
+    conn: Mutex<Connection>,
+    /// Index of each knowledge base, loaded on first search.
+    loaded: Mutex<HashMap<String, Arc<LoadedIndex>>>,
+    activity: Mutex<IndexActivity>,
+    /// Set once `watch_folders` runs; follows the folders of every knowledge base.
+    watcher: Mutex<Option<watch::FolderWatcher>>,
+}
+
+pub(crate) fn vector_bytes(vector: &[f32]) -> Vec<u8> {
+    vector.iter().flat_map(|x| x.to_le_bytes()).collect()
+}
+
+pub(crate) fn vector_from_bytes(bytes: &[u8]) -> Vec<f32> {
+    bytes.chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect()
+}
+
+pub fn content_hash(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+impl KnowledgeBase {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let conn = Connection::open(path).map_err(|e| format!("Failed to open knowledge base index: {}", e))?;
+        Self::init(conn)
+    }
+
+    #[cfg(test)]
+    pub fn open_in_memory() -> Result<Self, String> {
+        Self::init(Connection::open_in_memory().map_err(|e| e.to_string())?)
+    }
+
+    fn init(conn: Connection) -> Result<Self, String> {
+        conn.execute_batch(SCHEMA)
+            .map_err(|e| format!("Failed to initialise knowledge base index: {}", e))?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+            loaded: Mutex::new(HashMap::new()),
+            activity: Mutex::new(IndexActivity::default()),
+            watcher: Mutex::new(None),
+        })
+    }
+
+    fn conn(&self) -> Result<std::sync::MutexGuard<'_, Connection>, String> {
+        self.conn.lock().map_err(|e| e.to_string())
+    }
+
+    /// Forget the loaded chunks of a knowledge base after it changed.
+    fn invalidate(&self, id: &str) {
+        if let Ok(mut loaded) = self.loaded.lock() {
+            loaded.remove(id);
+        }
+    }
+
+    pub fn config(&self, id: &str) -> Result<KnowledgeBaseConfig, String> {
+        let config: String = self.conn()?
+            .query_row("SELECT config FROM knowledge_bases WHERE id = ?1", params![id], |row| row.get(0))
+            .optional()
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("Knowledge base '{}' not found", id))?;
+        serde_json::from_str(&config).map_err(|e| e.to_string())
+    }
+
+    /// Create or update a knowledge base. A new id is assigned when empty.
+    /// Changing how documents are chunked or embedded drops the index.
+    pub fn save(&self, mut config: KnowledgeBaseConfig) -> Result<KnowledgeBaseInfo, String> {
+        if config.name.trim().is_empty() {
+            return Err("Knowledge base needs a name".to_string());
+        }
+        if config.chunk_overlap >= config.chunk_size {
+            return Err("Chunk overlap must be smaller than the chunk size".to_string());
+        }
+        if config.id.is_empty() {
+            config.id = uuid::Uuid::new_v4().to_string();
+        }
+        let stale = self.config(&config.id).ok().is_some_and(|previous| {
+            previous.server_url != config.server_url
+                || previous.embedding_model != config.embedding_model
+                || previous.chunk_size != config.chunk_size
+                || previous.chunk_overlap != config.chunk_overlap
+        });
+
+        let json = serde_json::to_string(&config).map_err(|e| e.to_string())?;
+        {
+            let conn = self.conn()?;
+            conn.execute(
+                "INSERT INTO knowledge_bases (id, config) VALUES (?1, ?2)
+                 ON CONFLICT (id) DO UPDATE SET config = excluded.config",
+                params![config.id, json],
+            )
+            .map_err(|e| e.to_string())?;
+            if stale {
+                clear_index(&conn, &config.id).map_err(|e| e.to_string())?;
+            }
+        }
+        self.invalidate(&config.id);
+        self.rewatch()?;
+        self.info(config)
+    }
+
+    pub fn delete(&self, id: &str) -> Result<(), String> {
+        {
+            let conn = self.conn()?;
+            clear_index(&conn, id).map_err(|e| e.to_string())?;
+            conn.execute("DELETE FROM knowledge_bases WHERE id = ?1", params![id])
+                .map_err(|e| e.to_string())?;
+        }
+        self.invalidate(id);
+        self.rewatch()
+    }
+
+    fn info(&self, config: KnowledgeBaseConfig) -> Result<KnowledgeBaseInfo, String> {
+        let conn = self.conn()?;
+        let (documents, indexed_at) = conn
+            .query_row(
+                "SELECT COUNT(*), MAX(indexed_at) FROM documents WHERE kb_id = ?1",
+                params![config.id],
+                |row| Ok((row.get::<_, i64>(0)? as u64, row.get(1)?)),
+            )
+            .map_err(|e| e.to_string())?;
+        let chunks = conn
+            .query_row("SELECT COUNT(*) FROM chunks WHERE kb_id = ?1", params![config.id], |row| row.get::<_, i64>(0))
+            .map_err(|e| e.to_string())? as u64;
+        Ok(KnowledgeBaseInfo { config, documents, chunks, indexed_at })
+    }
+
+    fn configs(&self) -> Result<Vec<KnowledgeBaseConfig>, String> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare("SELECT config FROM knowledge_bases").map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map([], |row| row.get::<_, String>(0))
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        Ok(rows.iter().filter_map(|config| serde_json::from_str(config).ok()).collect())
+    }
+
+    pub fn list(&self) -> Result<Vec<KnowledgeBaseInfo>, String> {
+        let mut infos = self.configs()?.into_iter().map(|config| self.info(config)).collect::<Result<Vec<_>, _>>()?;
+        infos.sort_by(|a, b| a.config.name.cmp(&b.config.name));
+        Ok(infos)
+    }
+
+    /// Bring the index up to date with the knowledge base's folders:
+    /// changed and new documents are re-indexed, unchanged ones skipped by
+    /// content hash, and documents whose file is gone dropped.
+    pub async fn ingest(&self, id: &str, log_state: &Mutex<LogState>) -> Result<IngestReport, String> {
+        let config = self.config(id)?;
+        let folders = config.folders.clone();
+        let files = tokio::task::spawn_blocking(move || chunk::document_files(&folders))
+            .await
+            .map_err(|e| e.to_string())?;
+        add_log_entry(
+            log_state,
+            "info",
+            &format!("Checking {} document(s) in knowledge base '{}'", files.len(), config.name),
+            "knowledge",
+        )?;
+
+        let mut report = IngestReport::default();
+        let current: HashSet<String> = files.iter().map(|path| path.to_string_lossy().into_owned()).collect();
+        for source in self.stored_paths(id)? {
+            if !current.contains(&source) {
+                report.removed += self.remove_documents(id, Path::new(&source))?;
+            }
+        }
+        for path in files {
+            self.refresh_into(&config, &path, &mut report, log_state).await?;
+        }
+        if report.failed.is_empty() {
+            self.activity.lock().map_err(|e| e.to_string())?.last_error = None;
+        }
+        add_log_entry(
+            log_state,
+            "info",
+            &format!("Knowledge base '{}' indexed: {}", config.name, report.summary()),
+            "knowledge",
+        )?;
+        Ok(report)
+    }
+
+    /// Re-index changed paths in every knowledge base whose folders contain
+    /// them. Directories are rescanned; paths that no longer exist are
+    /// dropped from the index.
+    pub async fn update_paths(&self, paths: &[PathBuf], log_state: &Mutex<LogState>) {
+        if let Err(e) = self.try_update_paths(paths, log_state).await {
+            self.record_error(&e);
+            let _ = add_log_entry(log_state, "error", &e, "knowledge");
+        }
+        if let Ok(mut activity) = self.activity.lock() {
+            for path in paths {
+                activity.pending.remove(path);
+            }
+        }
+    }
+
+    async fn try_update_paths(&self, paths: &[PathBuf], log_state: &Mutex<LogState>) -> Result<(), String> {
+        for config in self.configs()? {
+            let mut report = IngestReport::default();
+            for path in paths {
+                let Some(folder) = config.folders.iter().find(|folder| path.starts_with(folder)) else {
+                    continue;
+                };
+                if path.strip_prefix(folder).is_ok_and(chunk::in_skipped_dir) {
+                    continue;
+                }
+                if !path.exists() {
+                    report.removed += self.remove_documents(&config.id, path)?;
+                    continue;
+                }
+                let files = if path.is_dir() {
+                    let dir = path.clone();
+                    tokio::task::spawn_blocking(move || chunk::document_files(&[dir]))
+                        .await
+                        .map_err(|e| e.to_string())?
+                } else {
+                    vec![path.clone()]
+                };
+                for file in files.iter().filter(|file| chunk::is_indexable(folder, file)) {
+                    self.refresh_into(&config, file, &mut report, log_state).await?;
+                }
+            }
+            if report.documents + report.removed + report.failed.len() > 0 {
+                add_log_entry(
+                    log_state,
+                    "info",
+                    &format!("Knowledge base '{}' updated: {}", config.name, report.summary()),
+                    "knowledge",
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Refresh one document, counting the result in `report`.
+    async fn refresh_into(
+        &self,
+        config: &KnowledgeBaseConfig,
+        path: &Path,
+        report: &mut IngestReport,
+        log_state: &Mutex<LogState>,
+    ) -> Result<(), String> {
+        match self.refresh_document(config, path).await {
+            Ok(Refresh::Unchanged) => report.unchanged += 1,
+            Ok(Refresh::Indexed(chunks)) => {
+                report.documents += 1;
+                report.chunks += chunks;
+            }
+            Err(e) => {
+                add_log_entry(log_state, "warn", &e, "knowledge")?;
+                self.record_error(&e);
+                report.failed.push(e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Chunk, embed and store one document, replacing what was stored for
+    /// it, unless its content hash matches the stored one.
+    async fn refresh_document(&self, config: &KnowledgeBaseConfig, path: &Path) -> Result<Refresh, String> {
+        let bytes = tokio::fs::read(path).await.map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let hash = content_hash(&bytes);
+        let source = path.to_string_lossy().into_owned();
+        let stored: Option<String> = self.conn()?
+            .query_row(
+                "SELECT hash FROM documents WHERE kb_id = ?1 AND path = ?2",
+                params![config.id, source],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?;
+        if stored.as_deref() == Some(hash.as_str()) {
+            return Ok(Refresh::Unchanged);
+        }
+
+        let file = path.to_path_buf();
+        let text = tokio::task::spawn_blocking(move || chunk::document_text(&file, &bytes))
+            .await
+            .map_err(|e| e.to_string())??;
+        let chunks = chunk::split(&text, config.chunk_size, config.chunk_overlap);
+        let inputs: Vec<String> = chunks.iter().map(|chunk| chunk.text.clone()).collect();
+        let embeddings = if inputs.is_empty() {
+            Vec::new()
+        } else {
+            provider::embed(&config.server_url, &config.embedding_model, &inputs)
+                .await
+                .map_err(|e| format!("Failed to embed {}: {}", path.display(), e))?
+                .vectors
+        };
+
+        {
+            let mut conn = self.conn()?;
+            let tx = conn.transaction().map_err(|e| e.to_string())?;
+            tx.execute("DELETE FROM chunks WHERE kb_id = ?1 AND path = ?2", params![config.id, source])
+                .map_err(|e| e.to_string())?;
+            tx.execute(
+                "INSERT OR REPLACE INTO documents (kb_id, path, hash, indexed_at) VALUES (?1, ?2, ?3, ?4)",
+                params![config.id, source, hash, chrono::Local::now().to_rfc3339()],
+            )
+            .map_err(|e| e.to_string())?;
+            for (ordinal, (chunk, vector)) in chunks.iter().zip(&embeddings).enumerate() {
+                tx.execute(
+                    "INSERT INTO chunks (kb_id, path, ordinal, start_line, end_line, text, vector)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                    params![
+                        config.id,
+                        source,
+                        ordinal as i64,
+                        chunk.start_line as i64,
+                        chunk.end_line as i64,
+                        chunk.text,
+                        vector_bytes(vector),
+                    ],
+                )
+                .map_err(|e| e.to_string())?;
+            }
+            tx.commit().map_err(|e| e.to_string())?;
+        }
+        self.invalidate(&config.id);
+        Ok(Refresh::Indexed(chunks.len()))
+    }
+
+    fn stored_paths(&self, id: &str) -> Result<Vec<String>, String> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare("SELECT path FROM documents WHERE kb_id = ?1").map_err(|e| e.to_string())?;
+        let paths = stmt
+            .query_map(params![id], |row| row.get(0))
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        Ok(paths)
+    }
+
+    /// Drop the document at `path`, or every document under it if it was a
+    /// directory. Returns the number dropped.
+    fn remove_documents(&self, id: &str, path: &Path) -> Result<usize, String> {
+        let source = path.to_string_lossy().into_owned();
+        let prefix = format!("{}{}", source.trim_end_matches(std::path::MAIN_SEPARATOR), std::path::MAIN_SEPARATOR);
+        let removed = {
+            let conn = self.conn()?;
+            let matching = "kb_id = ?1 AND (path = ?2 OR substr(path, 1, length(?3)) = ?3)";
+            conn.execute(&format!("DELETE FROM chunks WHERE {}", matching), params![id, source, prefix])
+                .map_err(|e| e.to_string())?;
+            conn.execute(&format!("DELETE FROM documents WHERE {}", matching), params![id, source, prefix])
+                .map_err(|e| e.to_string())?
+        };
+        if removed > 0 {
+            self.invalidate(id);
+        }
+        Ok(removed)
+    }
+
+    fn record_error(&self, error: &str) {
+        if let Ok(mut activity) = self.activity.lock() {
+            activity.last_error = Some(error.to_string());
+        }
+    }
+
+    /// Count changed paths as waiting for re-indexing, leaving out files the
+    /// index would never hold.
+    pub fn mark_pending(&self, paths: &[PathBuf]) {
+        if let Ok(mut activity) = self.activity.lock() {
+            activity.pending.extend(paths.iter()
+                .filter(|path| !chunk::in_skipped_dir(path) && (chunk::is_document(path) || !path.is_file()))
+                .cloned());
+        }
+    }
+
+    pub fn status(&self) -> Result<IndexStatus, String> {
+        let (knowledge_bases, documents, chunks) = self.conn()?
+            .query_row(
+                "SELECT (SELECT COUNT(*) FROM knowledge_bases), (SELECT COUNT(*) FROM documents), (SELECT COUNT(*) FROM chunks)",
+                [],
+                |row| Ok((row.get::<_, i64>(0)? as u64, row.get::<_, i64>(1)? as u64, row.get::<_, i64>(2)? as u64)),
+            )
+            .map_err(|e| e.to_string())?;
+        let activity = self.activity.lock().map_err(|e| e.to_string())?;
+        Ok(IndexStatus {
+            knowledge_bases,
+            documents,
+            chunks,
+            pending: activity.pending.len(),
+            last_error: activity.last_error.clone(),
+        })
+    }
+
+    /// Watch the folders of every knowledge base, now and as they are edited,
+    /// sending changed paths to `changes` for `watch::process`.
+    pub fn watch_folders(&self, changes: UnboundedSender<PathBuf>) -> Result<(), String> {
+        *self.watcher.lock().map_err(|e| e.to_string())? = Some(watch::FolderWatcher::new(changes)?);
+        self.rewatch()
+    }
+
+    fn rewatch(&self) -> Result<(), String> {
+        let folders: HashSet<PathBuf> = self.configs()?.into_iter().flat_map(|config| config.folders).collect();
+        let errors = match self.watcher.lock().map_err(|e| e.to_string())?.as_mut() {
+            Some(watcher) => watcher.watch(&folders),
+            None => Vec::new(),
+        };
+        if let Some(error) = errors.last() {
+            self.record_error(error);
+        }
+        Ok(())
+    }
+
+    fn index(&self, id: &str) -> Result<Arc<LoadedIndex>, String> {
+        if let Some(index) = self.loaded.lock().map_err(|e| e.to_string())?.get(id) {
+            return Ok(index.clone());
+        }
+        let chunks: Vec<IndexedChunk> = {
+            let conn = self.conn()?;
+            let mut stmt = conn
+                .prepare(
+                    "SELECT path, ordinal, start_line, end_line, text, vector FROM chunks
+                     WHERE kb_id = ?1 ORDER BY path, ordinal",
+                )
+                .map_err(|e| e.to_string())?;
+            let rows = stmt
+                .query_map(params![id], |row| {
+                    Ok(IndexedChunk {
+                        source: row.get(0)?,
+                        ordinal: row.get::<_, i64>(1)? as usize,
+                        start_line: row.get::<_, i64>(2)? as usize,
+                        end_line: row.get::<_, i64>(3)? as usize,
+                        text: row.get(4)?,
+                        vector: vector_from_bytes(&row.get::<_, Vec<u8>>(5)?),
+                    })
+                })
+                .map_err(|e| e.to_string())?
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| e.to_string())?;
+            rows
+        };
+        let keywords = bm25::Bm25::build(chunks.iter().map(|chunk: &IndexedChunk| chunk.text.as_str()));
+        let index = Arc::new(LoadedIndex { chunks, keywords });
+        self.loaded.lock().map_err(|e| e.to_string())?.insert(id.to_string(), index.clone());
+        Ok(index)
+    }
+
+    /// Every chunk's cosine similarity to `query`, in chunk order.
+    async fn vector_scores(&self, config: &KnowledgeBaseConfig, index: &LoadedIndex, query: &str) -> Result<Vec<f32>, String> {
+        let embedding = provider::embed(&config.server_url, &config.embedding_model, &[query.to_string()]).await?;
+        let query_vector = embedding.vectors.into_iter().next().ok_or("No embedding returned for the query")?;
+        let dimension = index.chunks[0].vector.len();
+        if query_vector.len() != dimension {
+            return Err(format!(
+                "Query embedding has {} dimensions but the index has {}; re-index the knowledge base",
+                query_vector.len(),
+                dimension
+            ));
+        }
+        Ok(index.chunks.iter().map(|chunk| provider::similarity(&query_vector, &chunk.vector)).collect())
+    }
+
+    /// The `top_k` chunks best matching `query`.
+    pub async fn search(&self, id: &str, query: &str, top_k: usize, mode: SearchMode) -> Result<Vec<SearchHit>, String> {
+        let config = self.config(id)?;
+        let index = self.index(id)?;
+        if index.chunks.is_empty() {
+            return Err(format!("Knowledge base '{}' has not been indexed", config.name));
+        }
+
+        let vector_scores = match mode {
+            SearchMode::Keyword => None,
+            _ => Some(self.vector_scores(&config, &index, query).await?),
+        };
+        let by_keyword = match mode {
+            SearchMode::Vector => Vec::new(),
+            _ => index.keywords.search(query),
+        };
+        let keyword_scores: HashMap<usize, f32> = by_keyword.iter().copied().collect();
+        let mut by_vector: Vec<(usize, f32)> = vector_scores.iter().flatten().copied().enumerate().collect();
+        by_vector.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        let mut ranked = match mode {
+            SearchMode::Vector => by_vector,
+            SearchMode::Keyword => by_keyword,
+            SearchMode::Hybrid => reciprocal_rank_fusion(&[by_vector, by_keyword]),
+        };
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+
+        Ok(ranked.into_iter()
+            .take(top_k)
+            .map(|(i, score)| {
+                let chunk = &index.chunks[i];
+                SearchHit {
+                    source: chunk.source.clone(),
+                    chunk: chunk.ordinal,
+                    start_line: chunk.start_line,
+                    end_line: chunk.end_line,
+                    text: chunk.text.clone(),
+                    score,
+                    vector_score: vector_scores.as_ref().map(|scores| scores[i]),
+                    keyword_score: keyword_scores.get(&i).copied(),
+                    rerank_score: None,
+                }
+            })
+            .collect())
+    }
+}
+
+/// Merge rankings of the same items, each best first, by summing
+/// `1 / (RRF_K + rank)` over the rankings an item appears in. The result is
+/// best first, ties going to the lower item.
+pub fn reciprocal_rank_fusion(rankings: &[Vec<(usize, f32)>]) -> Vec<(usize, f32)> {
+    let mut fused: HashMap<usize, f32> = HashMap::new();
+    for ranking in rankings {
+        for (rank, &(item, _)) in ranking.iter().enumerate() {
+            *fused.entry(item).or_default() += 1.0 / (RRF_K + rank as f32 + 1.0);
+        }
+    }
+    let mut fused: Vec<(usize, f32)> = fused.into_iter().collect();
+    fused.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+    fused
+}
+
+fn clear_index(conn: &Connection, id: &str) -> rusqlite::Result<()> {
+    conn.execute("DELETE FROM chunks WHERE kb_id = ?1", params![id])?;
+    conn.execute("DELETE FROM documents WHERE kb_id = ?1", params![id])?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn list_knowledge_bases(knowledge: State<'_, KnowledgeBase>) -> Result<Vec<KnowledgeBaseInfo>, String> {
+    knowledge.list()
+}
+
+#[tauri::command]
+pub async fn save_knowledge_base(config: KnowledgeBaseConfig, knowledge: State<'_, KnowledgeBase>) -> Result<KnowledgeBaseInfo, String> {
+    knowledge.save(config)
+}
+
+#[tauri::command]
+pub async fn delete_knowledge_base(id: String, knowledge: State<'_, KnowledgeBase>) -> Result<(), String> {
+    knowledge.delete(&id)
+}
+
+#[tauri::command]
+pub async fn ingest_knowledge_base(
+    id: String,
+    knowledge: State<'_, KnowledgeBase>,
+    log_state: State<'_, Mutex<LogState>>,
+) -> Result<IngestReport, String> {
+    knowledge.ingest(&id, &log_state).await
+}
+
+#[tauri::command]
+pub async fn search_knowledge_base(
+    id: String,
+    query: String,
+    top_k: Option<usize>,
+    mode: Option<SearchMode>,
+    knowledge: State<'_, KnowledgeBase>,
+) -> Result<Vec<SearchHit>, String> {
+    knowledge.search(&id, &query, top_k.unwrap_or(5), mode.unwrap_or_default()).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn ingest_search_and_reingest() {
+        let dir = std::env::temp_dir().join(format!("knowledge-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("pets.md"), "# Pets\nThe cat sat on the mat.\nA dog barked.").unwrap();
+        std::fs::write(dir.join("garden.txt"), "The garden has a red flower.").unwrap();
+
+        let knowledge = KnowledgeBase::open_in_memory().unwrap();
+        let log_state = Mutex::new(LogState { entries: Default::default() });
+        let info = knowledge.save(KnowledgeBaseConfig {
+            id: String::new(),
+            name: "notes".into(),
+            folders: vec![dir.clone()],
+            server_url: provider::mock::SCHEME.into(),
+            embedding_model: provider::mock::MOCK_MODEL.into(),
+            chunk_size: 200,
+            chunk_overlap: 20,
+        }).unwrap();
+        let id = info.config.id;
+
+        let report = knowledge.ingest(&id, &log_state).await.unwrap();
+        assert_eq!((report.documents, report.chunks), (2, 2));
+        for mode in [SearchMode::Vector, SearchMode::Keyword, SearchMode::Hybrid] {
+            let hits = knowledge.search(&id, "red flower garden", 1, mode).await.unwrap();
+            assert!(hits[0].source.ends_with("garden.txt"), "{:?}", hits);
+        }
+
+        let report = knowledge.ingest(&id, &log_state).await.unwrap();
+        assert_eq!((report.documents, report.unchanged), (0, 2));
+        assert!(knowledge.search("missing", "cat", 1, SearchMode::Keyword).await.is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn fused_order_is_deterministic() {
+        let by_vector = vec![(3, 0.9), (1, 0.8), (2, 0.5)];
+        let by_keyword = vec![(1, 7.0), (3, 4.0), (4, 1.0)];
+        let fused = reciprocal_rank_fusion(&[by_vector.clone(), by_keyword.clone()]);
+        let order: Vec<usize> = fused.iter().map(|&(item, _)| item).collect();
+        // 1 and 3 tie on rank 1 + rank 2, so the lower item comes first;
+        // 2 and 4 tie on a single rank 3.
+        assert_eq!(order, [1, 3, 2, 4]);
+        for _ in 0..20 {
+            assert_eq!(reciprocal_rank_fusion(&[by_vector.clone(), by_keyword.clone()]), fused);
+        }
+    }
+}
@@ -0,0 +1,128 @@
+// BM25 keyword scoring for knowledge base search, with identifier-aware tokens.
+use std::collections::HashMap;
+
+/// Term frequency saturation and document length normalisation.
+const K1: f32 = 1.2;
+const B: f32 = 0.75;
+
+/// Lowercased words of `text`. Identifiers are kept whole, so an exact
+/// `snake_case` or `camelCase` name scores above its parts, and their parts
+/// are added as well.
+pub fn tokenize(text: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    for word in text.split(|c: char| !c.is_alphanumeric() && c != '_') {
+        let word = word.trim_matches('_');
+        if word.is_empty() {
+            continue;
+        }
+        let parts = identifier_parts(word);
+        tokens.push(word.to_lowercase());
+        if parts.len() > 1 {
+            tokens.extend(parts.into_iter().map(|part| part.to_lowercase()));
+        }
+    }
+    tokens
+}
+
+/// `parse_config` and `parseConfig` both give `parse`, `config`.
+fn identifier_parts(word: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    for piece in word.split('_').filter(|piece| !piece.is_empty()) {
+        let mut start = 0;
+        let chars: Vec<(usize, char)> = piece.char_indices().collect();
+        for window in chars.windows(2) {
+            let ((_, a), (i, b)) = (window[0], window[1]);
+            if a.is_lowercase() && b.is_uppercase() {
+                parts.push(&piece[start..i]);
+                start = i;
+            }
+        }
+        parts.push(&piece[start..]);
+    }
+    parts
+}
+
+/// Okapi BM25 keyword index over a fixed list of documents.
+#[derive(Default)]
+pub struct Bm25 {
+    /// Documents containing each term, with the term's count in them.
+    postings: HashMap<String, Vec<(usize, u32)>>,
+    lengths: Vec<u32>,
+    average_length: f32,
+}
+
+impl Bm25 {
+    pub fn build<'a>(documents: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut index = Self::default();
+        for (doc, text) in documents.into_iter().enumerate() {
+            let tokens = tokenize(text);
+            let mut counts: HashMap<String, u32> = HashMap::new();
+            for token in &tokens {
+                *counts.entry(token.clone()).or_default() += 1;
+            }
+            for (term, count) in counts {
+                index.postings.entry(term).or_default().push((doc, count));
+            }
+            index.lengths.push(tokens.len() as u32);
+        }
+        let total: u64 = index.lengths.iter().map(|&length| length as u64).sum();
+        index.average_length = total as f32 / index.lengths.len().max(1) as f32;
+        index
+    }
+
+    /// Documents matching any query term, best first, with their scores.
+    pub fn search(&self, query: &str) -> Vec<(usize, f32)> {
+        let mut terms = tokenize(query);
+        terms.sort();
+        terms.dedup();
+
+        let documents = self.lengths.len() as f32;
+        let mut scores: HashMap<usize, f32> = HashMap::new();
+        for term in &terms {
+            let Some(postings) = self.postings.get(term) else {
+                continue;
+            };
+            let frequency = postings.len() as f32;
+            let idf = ((documents - frequency + 0.5) / (frequency + 0.5) + 1.0).ln();
+            for &(doc, count) in postings {
+                let count = count as f32;
+                let length = self.lengths[doc] as f32 / self.average_length.max(1.0);
+                *scores.entry(doc).or_default() += idf * count * (K1 + 1.0) / (count + K1 * (1.0 - B + B * length));
+            }
+        }
+        let mut ranked: Vec<(usize, f32)> = scores.into_iter().collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        ranked
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identifiers_are_split_into_parts() {
+        assert_eq!(tokenize("call parse_config, then parseConfig!"), [
+            "call", "parse_config", "parse", "config", "then", "parseconfig", "parse", "config",
+        ]);
+    }
+
+    #[test]
+    fn exact_identifiers_outrank_their_parts() {
+        let index = Bm25::build([
+            "Parse the config file before anything else.",
+            "fn parse_config(path: &Path) -> Config",
+            "Nothing relevant here at all.",
+        ]);
+        let ranked = index.search("parse_config");
+        assert_eq!(ranked.iter().map(|&(doc, _)| doc).collect::<Vec<_>>(), [1, 0]);
+        assert!(ranked[0].1 > ranked[1].1);
+    }
+
+    #[test]
+    fn equal_scores_rank_by_document() {
+        let index = Bm25::build(["red apple", "green pear", "red apple"]);
+        assert_eq!(index.search("apple").iter().map(|&(doc, _)| doc).collect::<Vec<_>>(), [0, 2]);
+        assert!(index.search("banana").is_empty());
+    }
+}
@@ -0,0 +1,116 @@
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use crate::tools::files::{is_skipped, SKIPPED_DIRS};
+
+/// Markdown, plain text, PDF and source files worth indexing.
+const DOCUMENT_EXTENSIONS: &[&str] = &[
+    "md", "markdown", "mdx", "txt", "rst", "adoc", "org", "pdf",
+    "rs", "py", "js", "jsx", "ts", "tsx", "go", "java", "kt", "c", "h", "cpp", "hpp", "cs",
+    "rb", "php", "swift", "scala", "sh", "sql", "html", "css", "toml", "yaml", "yml", "json",
+];
+const MAX_DOCUMENT_BYTES: u64 = 10 * 1024 * 1024;
+
+/// A piece of a document, with the lines it spans (1-based, inclusive).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chunk {
+    pub text: String,
+    pub start_line: usize,
+    pub end_line: usize,
+}
+
+pub fn is_document(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| DOCUMENT_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+}
+
+/// Whether any directory along `path` is one that indexing skips.
+pub fn in_skipped_dir(path: &Path) -> bool {
+    path.components().any(|part| part.as_os_str().to_str().is_some_and(|name| SKIPPED_DIRS.contains(&name)))
+}
+
+/// Whether `path`, inside `folder`, is a document that belongs in the index:
+/// the right kind, not too large and not under a skipped directory.
+pub fn is_indexable(folder: &Path, path: &Path) -> bool {
+    !path.strip_prefix(folder).is_ok_and(in_skipped_dir)
+        && is_document(path)
+        && std::fs::metadata(path).is_ok_and(|m| m.is_file() && m.len() <= MAX_DOCUMENT_BYTES)
+}
+
+/// Indexable files under the folders, in a stable order.
+pub fn document_files(folders: &[PathBuf]) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = folders.iter()
+        .flat_map(|folder| WalkDir::new(folder)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_entry(|entry| !is_skipped(entry))
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_type().is_file() && is_document(entry.path()))
+            .filter(|entry| entry.metadata().is_ok_and(|m| m.len() <= MAX_DOCUMENT_BYTES))
+            .map(|entry| entry.into_path()))
+        .collect();
+    files.dedup();
+    files
+}
+
+/// The text of a document read from `path`. PDFs have their text layer
+/// extracted.
+pub fn document_text(path: &Path, bytes: &[u8]) -> Result<String, String> {
+    if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("pdf")) {
+        return pdf_extract::extract_text_from_mem(bytes)
+            .map_err(|e| format!("Failed to extract text from {}: {}", path.display(), e));
+    }
+    Ok(String::from_utf8_lossy(bytes).into_owned())
+}
+
+/// Split text into chunks of at most `size` characters, breaking between
+/// lines where possible. Consecutive chunks share up to `overlap` characters
+/// of whole lines.
+pub fn split(text: &str, size: usize, overlap: usize) -> Vec<Chunk> {
+    let size = size.max(1);
+    let overlap = overlap.min(size / 2);
+
+    // Lines, with any longer than a chunk cut into chunk-sized pieces
+    let mut pieces: Vec<(usize, String)> = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let chars: Vec<char> = line.chars().collect();
+        if chars.len() <= size {
+            pieces.push((i + 1, line.to_string()));
+        } else {
+            pieces.extend(chars.chunks(size).map(|part| (i + 1, part.iter().collect())));
+        }
+    }
+    let length = |piece: &(usize, String)| piece.1.chars().count() + 1;
+
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    while start < pieces.len() {
+        let mut end = start;
+        let mut used = 0;
+        while end < pieces.len() && (end == start || used + length(&pieces[end]) <= size + 1) {
+            used += length(&pieces[end]);
+            end += 1;
+        }
+        let text = pieces[start..end].iter().map(|(_, line)| line.as_str()).collect::<Vec<_>>().join("\n");
+        if !text.trim().is_empty() {
+            chunks.push(Chunk {
+                text,
+                start_line: pieces[start].0,
+                end_line: pieces[end - 1].0,
+            });
+        }
+        if end == pieces.len() {
+            break;
+        }
+
+        let mut next = end;
+        let mut shared = 0;
+        while next > start + 1 && shared + length(&pieces[next - 1]) <= overlap {
+            shared += length(&pieces[next - 1]);
+            next -= 1;
+        }
+        start = next;
+    }
+    chunks
+}
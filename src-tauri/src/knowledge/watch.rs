@@ -0,0 +1,148 @@
+// Folder watching: re-indexing knowledge base files as they change on disk.
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+
+use super::KnowledgeBase;
+use crate::LogState;
+
+/// Quiet period after the last change before re-indexing, so an editor's
+/// burst of writes is handled once.
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Watches knowledge base folders and reports changed paths on a channel.
+pub struct FolderWatcher {
+    watcher: RecommendedWatcher,
+    watched: HashSet<PathBuf>,
+}
+
+impl FolderWatcher {
+    pub fn new(changes: UnboundedSender<PathBuf>) -> Result<Self, String> {
+        let watcher = notify::recommended_watcher(move |result: notify::Result<notify::Event>| {
+            let Ok(event) = result else {
+                return;
+            };
+            if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)) {
+                for path in event.paths {
+                    let _ = changes.send(path);
+                }
+            }
+        })
+        .map_err(|e| format!("Failed to start folder watcher: {}", e))?;
+        Ok(Self { watcher, watched: HashSet::new() })
+    }
+
+    /// Watch exactly `folders`. Returns the folders that could not be watched.
+    pub fn watch(&mut self, folders: &HashSet<PathBuf>) -> Vec<String> {
+        for folder in self.watched.difference(folders) {
+            let _ = self.watcher.unwatch(folder);
+        }
+        self.watched.retain(|folder| folders.contains(folder));
+
+        let mut errors = Vec::new();
+        for folder in folders {
+            if self.watched.contains(folder) {
+                continue;
+            }
+            match self.watcher.watch(folder, RecursiveMode::Recursive) {
+                Ok(()) => {
+                    self.watched.insert(folder.clone());
+                }
+                Err(e) => errors.push(format!("Failed to watch {}: {}", folder.display(), e)),
+            }
+        }
+        errors
+    }
+}
+
+/// Bring indexed knowledge bases up to date, then re-index files as changes
+/// arrive, until the channel closes.
+pub async fn process(knowledge: &KnowledgeBase, mut changes: UnboundedReceiver<PathBuf>, log_state: &Mutex<LogState>) {
+    // Catch up on changes made while the app was not running
+    for info in knowledge.list().unwrap_or_default().into_iter().filter(|info| info.documents > 0) {
+        if let Err(e) = knowledge.ingest(&info.config.id, log_state).await {
+            knowledge.record_error(&e);
+        }
+    }
+
+    while let Some(first) = changes.recv().await {
+        let mut paths = vec![first];
+        knowledge.mark_pending(&paths);
+        while let Ok(Some(path)) = tokio::time::timeout(DEBOUNCE, changes.recv()).await {
+            knowledge.mark_pending(std::slice::from_ref(&path));
+            paths.push(path);
+        }
+        paths.sort();
+        paths.dedup();
+        knowledge.update_paths(&paths, log_state).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::knowledge::{KnowledgeBaseConfig, SearchMode};
+    use crate::provider;
+
+    #[tokio::test]
+    async fn changes_are_reindexed_by_content_hash() {
+        let dir = std::env::temp_dir().join(format!("watch-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let dir = dir.canonicalize().unwrap();
+        let (cats, dogs, broken) = (dir.join("cats.md"), dir.join("dogs.md"), dir.join("broken.pdf"));
+        std::fs::write(&cats, "Cats purr.").unwrap();
+        std::fs::write(&dogs, "Dogs bark.").unwrap();
+
+        let knowledge = KnowledgeBase::open_in_memory().unwrap();
+        let log_state = Mutex::new(LogState { entries: Default::default() });
+        let id = knowledge.save(KnowledgeBaseConfig {
+            id: String::new(),
+            name: "pets".into(),
+            folders: vec![dir.clone()],
+            server_url: provider::mock::SCHEME.into(),
+            embedding_model: provider::mock::MOCK_MODEL.into(),
+            chunk_size: 200,
+            chunk_overlap: 20,
+        }).unwrap().config.id;
+        knowledge.ingest(&id, &log_state).await.unwrap();
+
+        let (changes, received) = tokio::sync::mpsc::unbounded_channel();
+        let logged = |prefix: &str| log_state.lock().unwrap().entries.iter().filter(|e| e.message.starts_with(prefix)).count();
+        let driver = async {
+            // Let the catch-up pass finish before changing anything
+            while logged("Knowledge base 'pets' indexed") < 2 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            std::fs::write(&dogs, "Dogs bark at birds.").unwrap();
+            std::fs::write(&broken, "not a pdf").unwrap();
+            for path in [&cats, &dogs, &broken] {
+                changes.send(path.clone()).unwrap();
+            }
+            // Still inside the debounce window
+            tokio::time::sleep(DEBOUNCE / 5).await;
+            assert_eq!(knowledge.status().unwrap().pending, 3);
+            while knowledge.status().unwrap().pending > 0 {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+            drop(changes);
+        };
+        tokio::join!(process(&knowledge, received, &log_state), driver);
+
+        let updated = log_state.lock().unwrap().entries.iter()
+            .any(|e| e.message == "Knowledge base 'pets' updated: 1 document(s) indexed into 1 chunk(s), 1 unchanged, 0 removed, 1 failed");
+        assert!(updated, "{:?}", log_state.lock().unwrap().entries.iter().map(|e| &e.message).collect::<Vec<_>>());
+        let hits = knowledge.search(&id, "birds", 1, SearchMode::Keyword).await.unwrap();
+        assert_eq!(hits[0].source, dogs.to_string_lossy());
+        assert!(knowledge.status().unwrap().last_error.is_some());
+
+        // A clean pass clears the error
+        std::fs::remove_file(&broken).unwrap();
+        let report = knowledge.ingest(&id, &log_state).await.unwrap();
+        assert_eq!((report.documents, report.unchanged), (0, 2));
+        assert_eq!(knowledge.status().unwrap().last_error, None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -16,6 +16,21 @@ use serde_json::Value;
 use tokio::sync::mpsc;
 use sysinfo::System;
 
+mod cache;
+mod config;
+mod eval;
+mod history;
+mod knowledge;
+mod mcp;
+mod memory;
+mod prompts;
+mod provider;
+mod regression;
+mod tools;
+mod workflow;
+
+use provider::ChatMessage;
+
 // Define WebSocket types
 type WebSocket = WebSocketStream<TcpStream>;
 type WsSender = mpsc::Sender<Message>;
@@ -119,7 +134,7 @@ async fn clear_logs(log_state: State<'_, Mutex<LogState>>) -> Result<(), String>
     Ok(())
 }
 
-fn add_log_entry(log_state: &State<'_, Mutex<LogState>>, level: &str, message: &str, target: &str) -> Result<(), String> {
+fn add_log_entry(log_state: &Mutex<LogState>, level: &str, message: &str, target: &str) -> Result<(), String> {
     let mut state = log_state.lock().map_err(|e| e.to_string())?;
     state.add_entry(level, message, target)
 }
@@ -163,12 +178,6 @@ struct ChatRequest {
     stream: bool,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-struct ChatMessage {
-    role: String,
-    content: String,
-}
-
 #[derive(Debug, Serialize, Deserialize)]
 struct ChatResponse {
     message: Option<ChatMessage>,
@@ -196,17 +205,24 @@ struct SystemStatus {
     memory_usage: f64,
     cpu_usage: f32,
     processes: Vec<ProcessInfo>,
+    index: knowledge::IndexStatus,
 }
 
 #[tauri::command]
-async fn get_system_status(system_state: State<'_, SystemState>) -> Result<SystemStatus, String> {
+async fn get_system_status(
+    system_state: State<'_, SystemState>,
+    running_commands: State<'_, Arc<tools::shell::RunningCommands>>,
+    knowledge: State<'_, knowledge::KnowledgeBase>,
+) -> Result<SystemStatus, String> {
     let mut sys = System::new();
     
-    // Refresh only what we need
+    // Refresh only what we need: this process and the commands tools are running
     sys.refresh_memory();
     sys.refresh_cpu_all();
+    let mut pids = vec![sysinfo::Pid::from(std::process::id() as usize)];
+    pids.extend(running_commands.pids().into_iter().map(sysinfo::Pid::from_u32));
     sys.refresh_processes_specifics(
-        sysinfo::ProcessesToUpdate::Some(&[sysinfo::Pid::from(std::process::id() as usize)]),
+        sysinfo::ProcessesToUpdate::Some(&pids),
         true,
         sysinfo::ProcessRefreshKind::everything()
     );
@@ -246,7 +262,8 @@ async fn get_system_status(system_state: State<'_, SystemState>) -> Result<Syste
             if let Some(parent_pid) = proc.parent() {
                 if parent_pid == sysinfo::Pid::from(current_pid) {
                     let child_info = ProcessInfo {
-                        name: proc.name().to_string_lossy().into_owned(),
+                        name: running_commands.command(pid.as_u32())
+                            .unwrap_or_else(|| proc.name().to_string_lossy().into_owned()),
                         pid: pid.as_u32(),
                         cpu_usage: proc.cpu_usage(),
                         memory_usage: proc.memory(),
@@ -264,11 +281,16 @@ async fn get_system_status(system_state: State<'_, SystemState>) -> Result<Syste
         memory_usage,
         cpu_usage,
         processes,
+        index: knowledge.status().unwrap_or_default(),
     })
 }
 
 #[tauri::command]
 async fn fetch_models_lmstudio(url: String, timeout: u64, log_state: State<'_, Mutex<LogState>>) -> Result<Result<Vec<String>, String>, String> {
+    if provider::Provider::detect(&url) == provider::Provider::Mock {
+        return Ok(provider::mock::models(&url));
+    }
+
     // Ensure we have the correct models endpoint
     let base_url = url.trim_end_matches('/');
     let models_url = if base_url.ends_with("/v1") {
@@ -382,62 +404,6 @@ fn greet(name: &str) -> String {
     format!("Hello, {}! You've been greeted from Rust!", name)
 }
 
-async fn ollama_chat(server_url: &str, model: &str, message: &str, temperature: f32) -> Result<String, String> {
-    let client = Client::new();
-    let url = format!("{}/api/chat", server_url.trim_end_matches('/'));
-    
-    let response = client.post(&url)
-        .json(&json!({
-            "model": model,
-            "messages": [{
-                "role": "user",
-                "content": message
-            }],
-            "temperature": temperature,
-            "stream": false
-        }))
-        .send()
-        .await
-        .map_err(|e| e.to_string())?;
-
-    if response.status().is_success() {
-        let result: Value = response.json().await.map_err(|e| e.to_string())?;
-        Ok(result["message"]["content"].as_str()
-            .ok_or_else(|| "Invalid response format".to_string())?
-            .to_string())
-    } else {
-        Err(format!("Request failed: {}", response.status()))
-    }
-}
-
-async fn lmstudio_chat(server_url: &str, model: &str, message: &str, temperature: f32) -> Result<String, String> {
-    let client = Client::new();
-    let url = format!("{}/chat/completions", server_url.trim_end_matches('/'));
-    
-    let response = client.post(&url)
-        .json(&json!({
-            "model": model,
-            "messages": [{
-                "role": "user",
-                "content": message
-            }],
-            "temperature": temperature,
-            "stream": false
-        }))
-        .send()
-        .await
-        .map_err(|e| e.to_string())?;
-
-    if response.status().is_success() {
-        let result: Value = response.json().await.map_err(|e| e.to_string())?;
-        Ok(result["choices"][0]["message"]["content"].as_str()
-            .ok_or_else(|| "Invalid response format".to_string())?
-            .to_string())
-    } else {
-        Err(format!("Request failed: {}", response.status()))
-    }
-}
-
 #[tauri::command]
 async fn chat_completion(
     server_url: String,
@@ -445,31 +411,80 @@ async fn chat_completion(
     message: String,
     temperature: f32,
 ) -> Result<String, String> {
-    let response = match server_url.contains("11434") {
-        true => ollama_chat(&server_url, &model, &message, temperature).await,
-        false => lmstudio_chat(&server_url, &model, &message, temperature).await,
+    let options = provider::ChatOptions::with_temperature(temperature);
+    let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+    let chat = async {
+        let sender = sender;
+        provider::chat_streaming(&server_url, &model, &[ChatMessage::user(message.clone())], &options, Some(&sender)).await
     };
+    // Pass the reply on as it arrives, then in full below
+    let forward = async {
+        while let Some(chunk) = receiver.recv().await {
+            broadcast_update(json!({
+                "type": "chat_chunk",
+                "data": { "model": model, "chunk": chunk }
+            })).await;
+        }
+    };
+    let (reply, ()) = tokio::join!(chat, forward);
+    let response = reply.map(|reply| reply.content);
 
     // Send the response through WebSocket for real-time updates
     if let Ok(response_text) = &response {
-        if let Some(tx) = WS_SENDER.lock().await.as_ref() {
-            let update = json!({
-                "type": "chat_response",
-                "data": {
-                    "model": model,
-                    "message": message,
-                    "response": response_text,
-                    "timestamp": chrono::Local::now().to_rfc3339()
-                }
-            });
-            
-            let _ = tx.send(Message::Text(update.to_string().into())).await;
-        }
+        broadcast_update(json!({
+            "type": "chat_response",
+            "data": {
+                "model": model,
+                "message": message,
+                "response": response_text,
+                "timestamp": chrono::Local::now().to_rfc3339()
+            }
+        })).await;
     }
 
     response
 }
 
+/// Embed texts with an embedding model, returning unit-length vectors.
+#[tauri::command]
+async fn embed(
+    server_url: String,
+    model: String,
+    inputs: Vec<String>,
+    log_state: State<'_, Mutex<LogState>>,
+) -> Result<provider::Embeddings, String> {
+    let embeddings = provider::embed(&server_url, &model, &inputs).await.map_err(|e| {
+        let _ = add_log_entry(&log_state, "error", &format!("Embedding with {} failed: {}", model, e), "provider");
+        e
+    })?;
+    add_log_entry(
+        &log_state,
+        "debug",
+        &format!("Embedded {} input(s) with {} ({} dimensions)", inputs.len(), model, embeddings.dimension),
+        "provider",
+    )?;
+    Ok(embeddings)
+}
+
+/// Validate an image before it is attached to a node.
+#[tauri::command]
+fn check_image(name: String, data: String) -> Result<provider::ImageInput, String> {
+    provider::ImageInput { name, media_type: String::new(), data, size: 0 }.checked()
+}
+
+/// Context length and vision support of a model, as far as its server says.
+#[tauri::command]
+async fn get_model_info(server_url: String, model: String) -> Result<provider::ModelInfo, String> {
+    provider::model_info(&server_url, &model).await
+}
+
+/// Push an update to the connected WebSocket client, if there is one.
+pub(crate) async fn broadcast_update(update: Value) {
+    if let Some(tx) = WS_SENDER.lock().await.as_ref() {
+        let _ = tx.send(Message::Text(update.to_string().into())).await;
+    }
+}
+
 // WebSocket handler for real-time updates
 pub async fn handle_ws_connection(ws: WebSocket) {
     let (mut tx, mut rx) = ws.split();
@@ -601,12 +616,24 @@ async fn handle_connection(stream: TcpStream) -> Result<(), String> {
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    let context = tauri::generate_context!();
+    // A regression run from the command line needs no window, so it runs and
+    // exits before the app is built
+    #[cfg(desktop)]
+    if let Some(code) = regression::run_from_args(&context.config().identifier) {
+        std::process::exit(code);
+    }
+    let mcp_stdio = std::env::args().any(|arg| arg == "--mcp-stdio");
     let log_state = LogState::new();
     let system_state = SystemState::new();
 
     tauri::Builder::default()
         .manage(Mutex::new(log_state))
         .manage(system_state)
+        .manage(workflow::iteration::OutputBuffers::default())
+        .manage(tools::ToolRegistry::default())
+        .manage(tools::approval::Approvals::default())
+        .manage(mcp::McpManager::default())
         .plugin(tauri_plugin_window_state::Builder::new().build())
         .plugin(tauri_plugin_websocket::init())
         .plugin(tauri_plugin_upload::init())
@@ -618,7 +645,12 @@ pub fn run() {
         .plugin(tauri_plugin_notification::init())
         .plugin(tauri_plugin_log::Builder::new()
             .targets([
-                Target::new(tauri_plugin_log::TargetKind::Stdout),
+                // Stdout carries the protocol when serving MCP over stdio
+                Target::new(if mcp_stdio {
+                    tauri_plugin_log::TargetKind::Stderr
+                } else {
+                    tauri_plugin_log::TargetKind::Stdout
+                }),
                 Target::new(tauri_plugin_log::TargetKind::Webview),
                 Target::new(tauri_plugin_log::TargetKind::LogDir { 
                     file_name: Some("nexa-ai.log".into()) 
@@ -640,6 +672,137 @@ pub fn run() {
         .plugin(tauri_plugin_cli::init())
         .plugin(tauri_plugin_opener::init())
         .setup(|app| {
+            let data_dir = app.path().app_data_dir()?;
+            std::fs::create_dir_all(&data_dir)?;
+            app.manage(history::RunHistory::open(&data_dir.join("history.db"))?);
+            app.manage(cache::ResponseCache::open(&data_dir.join("cache.db"))?);
+            app.manage(knowledge::KnowledgeBase::open(&data_dir.join("knowledge.db"))?);
+            app.manage(memory::MemoryStore::open(&data_dir.join("memory.db"))?);
+            app.manage(prompts::PromptLibrary::open(&data_dir.join("prompts.db"))?);
+            let (changes, changed) = tokio::sync::mpsc::unbounded_channel();
+            if let Err(e) = app.state::<knowledge::KnowledgeBase>().watch_folders(changes) {
+                let _ = add_log_entry(&app.state::<Mutex<LogState>>(), "error", &e, "knowledge");
+            }
+            let app_handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                let knowledge = app_handle.state::<knowledge::KnowledgeBase>();
+                let log_state = app_handle.state::<Mutex<LogState>>();
+                knowledge::watch::process(&knowledge, changed, &log_state).await;
+            });
+            let workspace = {
+                use tauri_plugin_fs::FsExt;
+                Arc::new(tools::files::Workspace::from_scope(&app.fs_scope(), data_dir.join("workspace.json")))
+            };
+            tools::files::register(&app.state::<tools::ToolRegistry>(), workspace.clone());
+            tools::code::register(&app.state::<tools::ToolRegistry>(), workspace.clone());
+            tools::data::register(&app.state::<tools::ToolRegistry>(), workspace.clone());
+            let shell_policy = Arc::new(tools::shell::ShellPolicy::load(data_dir.join("shell.json")));
+            let running_commands = Arc::new(tools::shell::RunningCommands::default());
+            tools::shell::register(
+                &app.state::<tools::ToolRegistry>(),
+                app.handle().clone(),
+                workspace.clone(),
+                shell_policy.clone(),
+                running_commands.clone(),
+            );
+            app.manage(workspace);
+            app.manage(shell_policy);
+            app.manage(running_commands);
+            let web_search = Arc::new(tools::web::WebSearch::load(data_dir.join("web.json")));
+            tools::web::register(&app.state::<tools::ToolRegistry>(), web_search.clone());
+            #[cfg(desktop)]
+            let cli_args = {
+                use tauri_plugin_cli::CliExt;
+                app.cli().matches().map(|matches| matches.args).unwrap_or_default()
+            };
+            #[cfg(desktop)]
+            {
+                let mock_search = cli_args.get("mock-search").is_some_and(|arg| arg.occurrences > 0);
+                if mock_search {
+                    let web_search = web_search.clone();
+                    let app_handle = app.handle().clone();
+                    tauri::async_runtime::spawn(async move {
+                        let log_state = app_handle.state::<Mutex<LogState>>();
+                        match tools::web::mock::MockSearchServer::start(tools::web::mock::default_pages()).await {
+                            Ok(server) => {
+                                web_search.override_endpoint(&server.base_url);
+                                let _ = add_log_entry(&log_state, "info", &format!("Mock search server listening on {}", server.base_url), "tools");
+                                // Keep the server alive for the rest of the session
+                                std::future::pending::<()>().await;
+                            }
+                            Err(e) => {
+                                let _ = add_log_entry(&log_state, "error", &format!("Failed to start mock search server: {}", e), "tools");
+                            }
+                        }
+                    });
+                }
+            }
+            app.manage(web_search);
+            let config_store = config::ConfigStore::load(data_dir.join("config.json"));
+            let mcp_servers = config_store.get().map(|c| c.mcp_servers).unwrap_or_default();
+            app.manage(config_store);
+            if !mcp_servers.is_empty() {
+                let app_handle = app.handle().clone();
+                tauri::async_runtime::spawn(async move {
+                    let mcp = app_handle.state::<mcp::McpManager>();
+                    let registry = app_handle.state::<tools::ToolRegistry>();
+                    let log_state = app_handle.state::<Mutex<LogState>>();
+                    if let Err(e) = mcp.sync(&mcp_servers, &registry, &log_state).await {
+                        let _ = add_log_entry(&log_state, "error", &format!("Failed to connect MCP servers: {}", e), "mcp");
+                    }
+                });
+            }
+            #[cfg(desktop)]
+            {
+                if cli_args.get("mcp-stdio").is_some_and(|arg| arg.occurrences > 0) {
+                    if let Some(window) = app.get_webview_window("main") {
+                        let _ = window.hide();
+                    }
+                    let app_handle = app.handle().clone();
+                    tauri::async_runtime::spawn(async move {
+                        mcp::server::serve_stdio(app_handle.clone()).await;
+                        app_handle.exit(0);
+                    });
+                }
+                let mock_llm = cli_args.get("mock-llm").is_some_and(|arg| arg.occurrences > 0);
+                let cli_value = |name: &str| cli_args.get(name)
+                    .and_then(|arg| arg.value.as_str())
+                    .map(str::to_string);
+                let mock_fixture = match cli_value("mock-fixture") {
+                    Some(path) => provider::mock::Fixture::load(std::path::Path::new(&path))?,
+                    None => provider::mock::Fixture::default(),
+                };
+                if mock_llm {
+                    let app_handle = app.handle().clone();
+                    tauri::async_runtime::spawn(async move {
+                        let log_state = app_handle.state::<Mutex<LogState>>();
+                        match provider::mock::MockLlmServer::with_fixture(mock_fixture).await {
+                            Ok(server) => {
+                                let _ = add_log_entry(&log_state, "info", &format!("Mock LLM server listening on {}", server.base_url), "provider");
+                                // Keep the server alive for the rest of the session
+                                std::future::pending::<()>().await;
+                            }
+                            Err(e) => {
+                                let _ = add_log_entry(&log_state, "error", &format!("Failed to start mock LLM server: {}", e), "provider");
+                            }
+                        }
+                    });
+                }
+                let mcp_http_port = cli_args.get("mcp-http")
+                    .and_then(|arg| arg.value.as_str())
+                    .and_then(|port| port.parse::<u16>().ok());
+                if let Some(port) = mcp_http_port {
+                    let app_handle = app.handle().clone();
+                    tauri::async_runtime::spawn(async move {
+                        let log_state = app_handle.state::<Mutex<LogState>>();
+                        let _ = add_log_entry(&log_state, "info", &format!("Serving workflows over MCP at http://127.0.0.1:{}/mcp", port), "mcp");
+                        if let Err(e) = mcp::server::serve_http(app_handle.clone(), port).await {
+                            let _ = add_log_entry(&log_state, "error", &e, "mcp");
+                        }
+                    });
+                }
+            }
+
             let log_state = app.state::<Mutex<LogState>>();
             let _system_state = app.state::<SystemState>();
             
@@ -652,7 +815,7 @@ pub fn run() {
                 );
                 let _ = state.add_entry(
                     "debug",
-                    "Registering Tauri commands: greet, get_logs, clear_logs, fetch_models_lmstudio, fetch_models_ollama, chat_completion, get_system_status",
+                    "Registering Tauri commands: greet, get_logs, clear_logs, fetch_models_lmstudio, fetch_models_ollama, chat_completion, embed, check_image, get_model_info, get_system_status, execute_workflow, resume_workflow, get_iteration_buffer, clear_iteration_buffer, list_runs, get_run, get_artifact, diff_runs, get_cache_stats, set_cache_settings, clear_response_cache, list_tools, list_approvals, resolve_approval, list_workspace_roots, add_workspace_root, remove_workspace_root, get_shell_settings, set_shell_settings, get_web_search_settings, set_web_search_settings, load_llm_config, save_llm_config, list_mcp_servers, reconnect_mcp_server, save_workflow, list_workflows, delete_workflow, list_knowledge_bases, save_knowledge_base, delete_knowledge_base, ingest_knowledge_base, search_knowledge_base, list_memory_namespaces, list_memories, update_memory, delete_memory, purge_memories, list_prompts, get_prompt, list_prompt_versions, save_prompt, delete_prompt, list_prompt_usages, run_evaluation, eval_report_table, mark_golden_run, unmark_golden_run, list_golden_runs, run_regression_suite",
                     "system"
                 );
             }
@@ -665,11 +828,64 @@ pub fn run() {
             fetch_models_lmstudio,
             fetch_models_ollama,
             chat_completion,
+            embed,
+            check_image,
+            get_model_info,
             get_system_status,
             register_connection,
-            unregister_connection
+            unregister_connection,
+            workflow::execute_workflow,
+            workflow::resume_workflow,
+            workflow::iteration::get_iteration_buffer,
+            workflow::iteration::clear_iteration_buffer,
+            history::list_runs,
+            history::get_run,
+            history::get_artifact,
+            history::diff_runs,
+            cache::get_cache_stats,
+            cache::set_cache_settings,
+            cache::clear_response_cache,
+            tools::list_tools,
+            tools::approval::list_approvals,
+            tools::approval::resolve_approval,
+            tools::files::list_workspace_roots,
+            tools::files::add_workspace_root,
+            tools::files::remove_workspace_root,
+            tools::shell::get_shell_settings,
+            tools::shell::set_shell_settings,
+            tools::web::get_web_search_settings,
+            tools::web::set_web_search_settings,
+            config::load_llm_config,
+            config::save_llm_config,
+            mcp::list_mcp_servers,
+            mcp::reconnect_mcp_server,
+            history::save_workflow,
+            history::list_workflows,
+            history::delete_workflow,
+            knowledge::list_knowledge_bases,
+            knowledge::save_knowledge_base,
+            knowledge::delete_knowledge_base,
+            knowledge::ingest_knowledge_base,
+            knowledge::search_knowledge_base,
+            memory::list_memory_namespaces,
+            memory::list_memories,
+            memory::update_memory,
+            memory::delete_memory,
+            memory::purge_memories,
+            prompts::list_prompts,
+            prompts::get_prompt,
+            prompts::list_prompt_versions,
+            prompts::save_prompt,
+            prompts::delete_prompt,
+            prompts::list_prompt_usages,
+            eval::run_evaluation,
+            eval::eval_report_table,
+            history::mark_golden_run,
+            history::unmark_golden_run,
+            history::list_golden_runs,
+            regression::run_regression_suite
         ])
-        .run(tauri::generate_context!())
+        .run(context)
         .expect("error while running tauri application");
 }
 
@@ -0,0 +1,701 @@
+// Model Context Protocol client. Connects to the configured MCP servers and
+// exposes their tools, resources and prompts through the tool registry.
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::State;
+
+use crate::config::ConfigStore;
+use crate::tools::{Tool, ToolContext, ToolRegistry};
+use crate::{add_log_entry, LogState};
+use transport::Connection;
+
+pub mod server;
+mod transport;
+
+const PROTOCOL_VERSION: &str = "2025-03-26";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+/// Resources and prompts named in the description of the tools that read them.
+const MAX_LISTED: usize = 25;
+/// Longest tool name models accept.
+const MAX_TOOL_NAME: usize = 64;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct McpServerConfig {
+    pub id: String,
+    pub name: String,
+    #[serde(flatten)]
+    pub transport: McpTransport,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "transport", rename_all = "snake_case")]
+pub enum McpTransport {
+    /// A child process speaking newline-delimited JSON-RPC.
+    Stdio {
+        command: String,
+        #[serde(default)]
+        args: Vec<String>,
+        #[serde(default)]
+        env: HashMap<String, String>,
+        #[serde(default)]
+        cwd: Option<String>,
+    },
+    /// A streamable HTTP endpoint.
+    Http {
+        url: String,
+        #[serde(default)]
+        headers: HashMap<String, String>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct McpToolInfo {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(rename = "inputSchema", default)]
+    pub input_schema: Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct McpResourceInfo {
+    pub uri: String,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(rename = "mimeType", default)]
+    pub mime_type: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct McpPromptArgument {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub required: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct McpPromptInfo {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub arguments: Vec<McpPromptArgument>,
+}
+
+/// An initialized session with one MCP server.
+pub struct McpClient {
+    connection: Connection,
+    next_id: AtomicU64,
+    server_info: Value,
+    capabilities: Value,
+}
+
+impl McpClient {
+    /// Start the server or open the endpoint, and run the initialization handshake.
+    pub async fn connect(transport: &McpTransport, client: Client) -> Result<Self, String> {
+        let mut mcp = Self {
+            connection: Connection::open(transport, client)?,
+            next_id: AtomicU64::new(1),
+            server_info: Value::Null,
+            capabilities: Value::Null,
+        };
+        let initialized = mcp.request("initialize", json!({
+            "protocolVersion": PROTOCOL_VERSION,
+            "capabilities": {},
+            "clientInfo": { "name": "nexa-ai-core", "version": env!("CARGO_PKG_VERSION") }
+        })).await;
+        let result = match initialized {
+            Ok(result) => result,
+            Err(e) => {
+                mcp.close().await;
+                return Err(e);
+            }
+        };
+        mcp.server_info = result["serverInfo"].clone();
+        mcp.capabilities = result["capabilities"].clone();
+        mcp.connection.notify(json!({ "jsonrpc": "2.0", "method": "notifications/initialized" })).await?;
+        Ok(mcp)
+    }
+
+    pub fn server_info(&self) -> &Value {
+        &self.server_info
+    }
+
+    pub async fn request(&self, method: &str, params: Value) -> Result<Value, String> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let response = self.connection
+            .request(json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }))
+            .await?;
+        if let Some(error) = response.get("error") {
+            return Err(format!("MCP {} failed: {}", method, error["message"].as_str().unwrap_or("unknown error")));
+        }
+        Ok(response["result"].clone())
+    }
+
+    /// All pages of a list method, e.g. every tool from `tools/list`.
+    async fn list<T: serde::de::DeserializeOwned>(&self, method: &str, key: &str) -> Result<Vec<T>, String> {
+        let capability = method.split('/').next().unwrap_or_default();
+        if self.capabilities.get(capability).is_none() {
+            return Ok(Vec::new());
+        }
+        let mut items = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let params = match &cursor {
+                Some(cursor) => json!({ "cursor": cursor }),
+                None => json!({}),
+            };
+            let result = self.request(method, params).await?;
+            let page: Vec<T> = serde_json::from_value(result[key].clone())
+                .map_err(|e| format!("Invalid {} response: {}", method, e))?;
+            items.extend(page);
+            match result["nextCursor"].as_str() {
+                Some(next) if !next.is_empty() => cursor = Some(next.to_string()),
+                _ => return Ok(items),
+            }
+        }
+    }
+
+    pub async fn tools(&self) -> Result<Vec<McpToolInfo>, String> {
+        self.list("tools/list", "tools").await
+    }
+
+    pub async fn resources(&self) -> Result<Vec<McpResourceInfo>, String> {
+        self.list("resources/list", "resources").await
+    }
+
+    pub async fn prompts(&self) -> Result<Vec<McpPromptInfo>, String> {
+        self.list("prompts/list", "prompts").await
+    }
+
+    pub async fn call_tool(&self, name: &str, arguments: Value) -> Result<String, String> {
+        let result = self.request("tools/call", json!({ "name": name, "arguments": arguments })).await?;
+        let mut text = render_content(result["content"].as_array().map(Vec::as_slice).unwrap_or_default());
+        if text.is_empty() {
+            if let Some(structured) = result.get("structuredContent") {
+                text = structured.to_string();
+            }
+        }
+        if result["isError"].as_bool().unwrap_or(false) {
+            return Err(text);
+        }
+        Ok(text)
+    }
+
+    pub async fn read_resource(&self, uri: &str) -> Result<String, String> {
+        let result = self.request("resources/read", json!({ "uri": uri })).await?;
+        let contents = result["contents"].as_array().map(Vec::as_slice).unwrap_or_default();
+        Ok(contents.iter().map(render_resource).collect::<Vec<_>>().join("\n\n"))
+    }
+
+    /// The prompt's messages, one `role: text` block each.
+    pub async fn get_prompt(&self, name: &str, arguments: Value) -> Result<String, String> {
+        let result = self.request("prompts/get", json!({ "name": name, "arguments": arguments })).await?;
+        let messages = result["messages"].as_array().map(Vec::as_slice).unwrap_or_default();
+        Ok(messages.iter()
+            .map(|message| format!(
+                "{}: {}",
+                message["role"].as_str().unwrap_or("user"),
+                render_content(std::slice::from_ref(&message["content"]))
+            ))
+            .collect::<Vec<_>>()
+            .join("\n\n"))
+    }
+
+    pub async fn close(&self) {
+        self.connection.close().await;
+    }
+}
+
+/// Text of a content list, with placeholders for what can't be shown as text.
+fn render_content(content: &[Value]) -> String {
+    content.iter()
+        .map(|item| match item["type"].as_str() {
+            Some("text") => item["text"].as_str().unwrap_or_default().to_string(),
+            Some("resource") => render_resource(&item["resource"]),
+            Some("resource_link") => format!("[resource {}]", item["uri"].as_str().unwrap_or_default()),
+            Some(kind) => format!("[{} content, {}]", kind, item["mimeType"].as_str().unwrap_or("unknown type")),
+            None => item.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn render_resource(resource: &Value) -> String {
+    let uri = resource["uri"].as_str().unwrap_or_default();
+    match resource["text"].as_str() {
+        Some(text) => text.to_string(),
+        None => format!("[binary resource {}, {}]", uri, resource["mimeType"].as_str().unwrap_or("unknown type")),
+    }
+}
+
+/// Tool group of a server's tools; nodes select it to use them.
+pub fn tool_group(server_id: &str) -> String {
+    format!("mcp:{}", server_id)
+}
+
+/// Registry name for something a server offers, unique across servers and
+/// within the 64 characters models accept. Names that had to be changed end
+/// in a hash of the original, so that two of them cannot come out the same.
+fn tool_name(server_id: &str, name: &str) -> String {
+    let full = format!("{}__{}", server_id, name);
+    let sanitized: String = full.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' || c == '-' { c } else { '_' })
+        .collect();
+    if sanitized == full && full.len() <= MAX_TOOL_NAME {
+        return full;
+    }
+    let hash = format!("{:x}", Sha256::digest(full.as_bytes()));
+    format!("{}_{}", &sanitized[..sanitized.len().min(MAX_TOOL_NAME - 9)], &hash[..8])
+}
+
+pub struct McpTool {
+    client: Arc<McpClient>,
+    name: String,
+    group: String,
+    info: McpToolInfo,
+}
+
+#[async_trait]
+impl Tool for McpTool {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn group(&self) -> &str {
+        &self.group
+    }
+
+    fn description(&self) -> &str {
+        &self.info.description
+    }
+
+    fn parameters(&self) -> Value {
+        if self.info.input_schema.is_object() {
+            self.info.input_schema.clone()
+        } else {
+            json!({ "type": "object", "properties": {} })
+        }
+    }
+
+    async fn call(&self, ctx: &ToolContext<'_>, arguments: Value) -> Result<String, String> {
+        add_log_entry(ctx.log_state, "info", &format!("MCP {} calling {}", self.group, self.info.name), "mcp")?;
+        self.client.call_tool(&self.info.name, arguments).await
+    }
+}
+
+pub struct McpReadResource {
+    client: Arc<McpClient>,
+    name: String,
+    group: String,
+    description: String,
+}
+
+#[async_trait]
+impl Tool for McpReadResource {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn group(&self) -> &str {
+        &self.group
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "uri": { "type": "string", "description": "URI of the resource" }
+            },
+            "required": ["uri"]
+        })
+    }
+
+    async fn call(&self, ctx: &ToolContext<'_>, arguments: Value) -> Result<String, String> {
+        let uri = arguments["uri"].as_str().ok_or("Missing string argument 'uri'")?;
+        add_log_entry(ctx.log_state, "info", &format!("MCP {} reading {}", self.group, uri), "mcp")?;
+        self.client.read_resource(uri).await
+    }
+}
+
+pub struct McpGetPrompt {
+    client: Arc<McpClient>,
+    name: String,
+    group: String,
+    description: String,
+}
+
+#[async_trait]
+impl Tool for McpGetPrompt {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn group(&self) -> &str {
+        &self.group
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "name": { "type": "string", "description": "Name of the prompt" },
+                "arguments": { "type": "object", "additionalProperties": { "type": "string" } }
+            },
+            "required": ["name"]
+        })
+    }
+
+    async fn call(&self, ctx: &ToolContext<'_>, arguments: Value) -> Result<String, String> {
+        let name = arguments["name"].as_str().ok_or("Missing string argument 'name'")?;
+        add_log_entry(ctx.log_state, "info", &format!("MCP {} getting prompt {}", self.group, name), "mcp")?;
+        let prompt_arguments = arguments.get("arguments").cloned().unwrap_or_else(|| json!({}));
+        self.client.get_prompt(name, prompt_arguments).await
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct McpServerStatus {
+    pub id: String,
+    pub name: String,
+    pub connected: bool,
+    pub error: Option<String>,
+    /// `serverInfo` from the handshake: the server's name and version.
+    pub server_info: Value,
+    pub tools: Vec<McpToolInfo>,
+    pub resources: Vec<McpResourceInfo>,
+    pub prompts: Vec<McpPromptInfo>,
+}
+
+struct Connected {
+    config: McpServerConfig,
+    client: Option<Arc<McpClient>>,
+    status: McpServerStatus,
+}
+
+/// Sessions with the configured MCP servers.
+#[derive(Default)]
+pub struct McpManager {
+    servers: tokio::sync::Mutex<HashMap<String, Connected>>,
+    http: Client,
+}
+
+impl McpManager {
+    /// Connect to enabled servers that aren't connected yet, and drop the ones
+    /// that were removed, disabled or changed since.
+    pub async fn sync(&self, configs: &[McpServerConfig], registry: &ToolRegistry, log_state: &Mutex<LogState>) -> Result<(), String> {
+        let mut servers = self.servers.lock().await;
+        let stale: Vec<String> = servers.values()
+            .filter(|server| !configs.iter().any(|config| config.enabled && *config == server.config))
+            .map(|server| server.config.id.clone())
+            .collect();
+        for id in stale {
+            if let Some(server) = servers.remove(&id) {
+                registry.unregister_group(&tool_group(&id));
+                if let Some(client) = server.client {
+                    client.close().await;
+                }
+                add_log_entry(log_state, "info", &format!("Disconnected MCP server {}", server.config.name), "mcp")?;
+            }
+        }
+
+        for config in configs.iter().filter(|config| config.enabled) {
+            if servers.contains_key(&config.id) {
+                continue;
+            }
+            let server = self.connect(config, registry, log_state).await?;
+            match &server.status.error {
+                Some(e) => add_log_entry(log_state, "error", &format!("MCP server {} failed: {}", config.name, e), "mcp")?,
+                None => add_log_entry(
+                    log_state,
+                    "info",
+                    &format!(
+                        "Connected MCP server {}: {} tools, {} resources, {} prompts",
+                        config.name,
+                        server.status.tools.len(),
+                        server.status.resources.len(),
+                        server.status.prompts.len()
+                    ),
+                    "mcp",
+                )?,
+            }
+            servers.insert(config.id.clone(), server);
+        }
+        Ok(())
+    }
+
+    /// Drop one server's session, so the next sync connects it afresh.
+    pub async fn disconnect(&self, id: &str, registry: &ToolRegistry) {
+        if let Some(server) = self.servers.lock().await.remove(id) {
+            registry.unregister_group(&tool_group(id));
+            if let Some(client) = server.client {
+                client.close().await;
+            }
+        }
+    }
+
+    pub async fn status(&self) -> Vec<McpServerStatus> {
+        let mut statuses: Vec<McpServerStatus> = self.servers.lock().await
+            .values()
+            .map(|server| server.status.clone())
+            .collect();
+        statuses.sort_by(|a, b| a.name.cmp(&b.name));
+        statuses
+    }
+
+    async fn connect(&self, config: &McpServerConfig, registry: &ToolRegistry, log_state: &Mutex<LogState>) -> Result<Connected, String> {
+        let mut status = McpServerStatus {
+            id: config.id.clone(),
+            name: config.name.clone(),
+            connected: false,
+            error: None,
+            server_info: Value::Null,
+            tools: Vec::new(),
+            resources: Vec::new(),
+            prompts: Vec::new(),
+        };
+        let client = match self.discover(config, &mut status).await {
+            Ok(client) => client,
+            Err(e) => {
+                status.error = Some(e);
+                return Ok(Connected { config: config.clone(), client: None, status });
+            }
+        };
+
+        let group = tool_group(&config.id);
+        // Keep the first of two tools whose names come out the same
+        let register = |tool: Arc<dyn Tool>| match registry.get(tool.name()) {
+            Some(_) => add_log_entry(
+                log_state,
+                "warn",
+                &format!("Skipped a tool of MCP server {}: another tool is already named {}", config.name, tool.name()),
+                "mcp",
+            ),
+            None => {
+                registry.register(tool);
+                Ok(())
+            }
+        };
+        for info in &status.tools {
+            register(Arc::new(McpTool {
+                client: client.clone(),
+                name: tool_name(&config.id, &info.name),
+                group: group.clone(),
+                info: info.clone(),
+            }))?;
+        }
+        if !status.resources.is_empty() {
+            let listed: Vec<String> = status.resources.iter()
+                .take(MAX_LISTED)
+                .map(|resource| format!("{} ({})", resource.uri, resource.name))
+                .collect();
+            register(Arc::new(McpReadResource {
+                client: client.clone(),
+                name: tool_name(&config.id, "read_resource"),
+                group: group.clone(),
+                description: format!("Read a resource from the {} MCP server. Resources: {}", config.name, listed.join(", ")),
+            }))?;
+        }
+        if !status.prompts.is_empty() {
+            let listed: Vec<String> = status.prompts.iter()
+                .take(MAX_LISTED)
+                .map(|prompt| {
+                    let arguments: Vec<&str> = prompt.arguments.iter().map(|a| a.name.as_str()).collect();
+                    format!("{}({})", prompt.name, arguments.join(", "))
+                })
+                .collect();
+            register(Arc::new(McpGetPrompt {
+                client: client.clone(),
+                name: tool_name(&config.id, "get_prompt"),
+                group,
+                description: format!("Get a prompt template from the {} MCP server. Prompts: {}", config.name, listed.join(", ")),
+            }))?;
+        }
+        status.connected = true;
+        Ok(Connected { config: config.clone(), client: Some(client), status })
+    }
+
+    async fn discover(&self, config: &McpServerConfig, status: &mut McpServerStatus) -> Result<Arc<McpClient>, String> {
+        let client = Arc::new(McpClient::connect(&config.transport, self.http.clone()).await?);
+        status.server_info = client.server_info().clone();
+        let listed = async {
+            status.tools = client.tools().await?;
+            status.resources = client.resources().await?;
+            status.prompts = client.prompts().await?;
+            Ok::<_, String>(())
+        };
+        if let Err(e) = listed.await {
+            client.close().await;
+            return Err(e);
+        }
+        Ok(client)
+    }
+}
+
+#[tauri::command]
+pub async fn list_mcp_servers(manager: State<'_, McpManager>) -> Result<Vec<McpServerStatus>, String> {
+    Ok(manager.status().await)
+}
+
+#[tauri::command]
+pub async fn reconnect_mcp_server(
+    id: String,
+    manager: State<'_, McpManager>,
+    config: State<'_, ConfigStore>,
+    registry: State<'_, ToolRegistry>,
+    log_state: State<'_, Mutex<LogState>>,
+) -> Result<(), String> {
+    manager.disconnect(&id, &registry).await;
+    let servers = config.get().map(|c| c.mcp_servers).unwrap_or_default();
+    manager.sync(&servers, &registry, &log_state).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::RunHistory;
+    use crate::tools::approval::Approvals;
+
+    /// Set when the test binary runs as the fixture server.
+    const FIXTURE_ENV: &str = "NEXA_MCP_FIXTURE";
+
+    /// A small stdio MCP server. The stdio test starts this test binary again
+    /// with `FIXTURE_ENV` set, running only this test, which then answers
+    /// requests on stdin until it closes. Lines libtest prints around it are
+    /// not JSON, so the client skips them.
+    #[test]
+    fn fixture_server() {
+        use std::io::BufRead;
+        if std::env::var_os(FIXTURE_ENV).is_none() {
+            return;
+        }
+        for line in std::io::stdin().lock().lines() {
+            let message: Value = serde_json::from_str(&line.unwrap()).unwrap();
+            let Some(id) = message.get("id") else {
+                continue;
+            };
+            let params = &message["params"];
+            let result = match message["method"].as_str().unwrap_or_default() {
+                "initialize" => json!({
+                    "protocolVersion": params["protocolVersion"],
+                    "capabilities": { "tools": {} },
+                    "serverInfo": { "name": "fixture", "version": "1" }
+                }),
+                // Two pages, to check the client follows the cursor
+                "tools/list" if params["cursor"] == "2" => json!({ "tools": [{ "name": "fail" }, { "name": "crash" }] }),
+                "tools/list" => json!({
+                    "tools": [{ "name": "echo", "description": "Echo text", "inputSchema": { "type": "object" } }],
+                    "nextCursor": "2"
+                }),
+                "tools/call" => match params["name"].as_str() {
+                    Some("echo") => json!({ "content": [{ "type": "text", "text": format!("echo: {}", params["arguments"]["text"].as_str().unwrap_or_default()) }] }),
+                    Some("fail") => json!({ "content": [{ "type": "text", "text": "boom" }], "isError": true }),
+                    _ => {
+                        eprintln!("fixture: out of cheese");
+                        std::process::exit(3);
+                    }
+                },
+                method => {
+                    println!("{}", json!({ "jsonrpc": "2.0", "id": id, "error": { "code": -32601, "message": format!("No {}", method) } }));
+                    continue;
+                }
+            };
+            println!("{}", json!({ "jsonrpc": "2.0", "id": id, "result": result }));
+        }
+    }
+
+    fn fixture_config() -> McpServerConfig {
+        let module = module_path!().split_once("::").map_or(module_path!(), |(_, module)| module);
+        McpServerConfig {
+            id: "fx".to_string(),
+            name: "Fixture".to_string(),
+            transport: McpTransport::Stdio {
+                command: std::env::current_exe().unwrap().display().to_string(),
+                args: ["--exact", &format!("{}::fixture_server", module), "--nocapture", "--test-threads=1", "-q"]
+                    .map(str::to_string)
+                    .to_vec(),
+                env: HashMap::from([(FIXTURE_ENV.to_string(), "1".to_string())]),
+                cwd: None,
+            },
+            enabled: true,
+        }
+    }
+
+    #[tokio::test]
+    async fn stdio_round_trip() {
+        let (manager, registry) = (McpManager::default(), ToolRegistry::default());
+        let log_state = Mutex::new(LogState { entries: Default::default() });
+        manager.sync(&[fixture_config()], &registry, &log_state).await.unwrap();
+        let status = manager.status().await;
+        assert!(status[0].connected, "{:?}", status[0].error);
+        assert_eq!(status[0].server_info["name"], "fixture");
+        let names: Vec<String> = registry.list().into_iter().map(|tool| tool.name).collect();
+        assert_eq!(names, ["fx__crash", "fx__echo", "fx__fail"]);
+
+        let (approvals, history) = (Approvals::default(), RunHistory::open_in_memory().unwrap());
+        let ctx = ToolContext { run_id: "r", node_id: "n", log_state: &log_state, approvals: &approvals, history: &history };
+        let ctx = &ctx;
+        let call = |name: &str, arguments: Value| {
+            let tool = registry.get(name).unwrap();
+            async move { tool.call(ctx, arguments).await }
+        };
+        assert_eq!(call("fx__echo", json!({ "text": "hi" })).await.unwrap(), "echo: hi");
+        assert_eq!(call("fx__fail", json!({})).await.unwrap_err(), "boom");
+
+        // A server that dies says why on stderr
+        let error = call("fx__crash", json!({})).await.unwrap_err();
+        assert_eq!(error, "MCP server closed the connection\nfixture: out of cheese");
+
+        manager.sync(&[], &registry, &log_state).await.unwrap();
+        assert!(registry.list().is_empty() && manager.status().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn unstartable_servers_report_an_error() {
+        let mut config = fixture_config();
+        config.transport = McpTransport::Stdio { command: "/nonexistent".to_string(), args: Vec::new(), env: HashMap::new(), cwd: None };
+        let (manager, registry) = (McpManager::default(), ToolRegistry::default());
+        let log_state = Mutex::new(LogState { entries: Default::default() });
+        manager.sync(&[config], &registry, &log_state).await.unwrap();
+        let status = manager.status().await;
+        assert!(!status[0].connected);
+        assert!(status[0].error.as_deref().unwrap().starts_with("Failed to start /nonexistent"));
+    }
+
+    #[test]
+    fn changed_tool_names_stay_distinct() {
+        assert_eq!(tool_name("fs", "read_file"), "fs__read_file");
+        let (dotted, underscored) = (tool_name("fs", "read.file"), tool_name("fs", "read_file"));
+        assert!(dotted.starts_with("fs__read_file_") && dotted != underscored);
+
+        let long = "x".repeat(80);
+        let (a, b) = (tool_name("fs", &format!("{}a", long)), tool_name("fs", &format!("{}b", long)));
+        assert_eq!((a.len(), b.len()), (MAX_TOOL_NAME, MAX_TOOL_NAME));
+        assert_ne!(a, b);
+    }
+}
@@ -0,0 +1,479 @@
+// Serves published workflows as MCP tools, over stdio or streamable HTTP, so
+// outside agents can run a whole pipeline with one tool call.
+use serde_json::{json, Map, Value};
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use tauri::AppHandle;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+use super::PROTOCOL_VERSION;
+use crate::add_log_entry;
+use crate::history::SavedWorkflow;
+use crate::workflow::{self, NodeKind, NodeStatus, RunStatus, Services, Workflow, WorkflowNode};
+
+const SUPPORTED_VERSIONS: &[&str] = &["2024-11-05", "2025-03-26", "2025-06-18"];
+const MAX_REQUEST_BYTES: usize = 4 * 1024 * 1024;
+
+/// Input nodes: their values become the tool's arguments.
+fn entry_nodes(workflow: &Workflow) -> Vec<&WorkflowNode> {
+    workflow.nodes.iter().filter(|node| matches!(node.kind, NodeKind::Input { .. })).collect()
+}
+
+/// Output nodes, or the nodes nothing leads on from when there are none.
+fn exit_nodes(workflow: &Workflow) -> Vec<&WorkflowNode> {
+    let outputs: Vec<&WorkflowNode> = workflow.nodes.iter().filter(|node| matches!(node.kind, NodeKind::Output)).collect();
+    if !outputs.is_empty() {
+        return outputs;
+    }
+    let sources: HashSet<&str> = workflow.edges.iter().map(|edge| edge.from.as_str()).collect();
+    workflow.nodes.iter().filter(|node| !sources.contains(node.id.as_str())).collect()
+}
+
+/// Argument name of an input node; `input` when the workflow has just one.
+fn argument_name(node: &WorkflowNode, entries: usize) -> String {
+    if entries == 1 {
+        "input".to_string()
+    } else {
+        node.id.clone()
+    }
+}
+
+/// Tool name of a workflow: its name with anything a model rejects replaced.
+fn tool_name(workflow: &Workflow) -> String {
+    let name = if workflow.name.trim().is_empty() { &workflow.id } else { &workflow.name };
+    name.trim()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' })
+        .take(64)
+        .collect()
+}
+
+/// Schema of an exit node's output: its own output schema, or that of the
+/// single node feeding it, and plain text otherwise.
+fn output_schema(workflow: &Workflow, node: &WorkflowNode) -> Value {
+    let mut upstream = workflow.edges.iter().filter(|edge| edge.to == node.id);
+    let feeding = match (upstream.next(), upstream.next()) {
+        (Some(edge), None) => workflow.nodes.iter().find(|n| n.id == edge.from),
+        _ => None,
+    };
+    node.kind.output_schema()
+        .or_else(|| feeding.and_then(|n| n.kind.output_schema()))
+        .unwrap_or_else(|| json!({ "type": "string" }))
+}
+
+pub fn tool_definition(saved: &SavedWorkflow) -> Value {
+    let workflow = &saved.workflow;
+    let entries = entry_nodes(workflow);
+    let mut properties = Map::new();
+    let mut required = Vec::new();
+    for node in &entries {
+        let NodeKind::Input { value } = &node.kind else {
+            continue;
+        };
+        let name = argument_name(node, entries.len());
+        let description = if value.trim().is_empty() {
+            required.push(name.clone());
+            format!("Text for input node {}", node.id)
+        } else {
+            format!("Text for input node {}. Defaults to: {}", node.id, value)
+        };
+        properties.insert(name, json!({ "type": "string", "description": description }));
+    }
+    let outputs: Map<String, Value> = exit_nodes(workflow)
+        .iter()
+        .map(|node| (node.id.clone(), output_schema(workflow, node)))
+        .collect();
+    let description = if saved.description.trim().is_empty() {
+        format!("Run the {} workflow ({} nodes)", workflow.name, workflow.nodes.len())
+    } else {
+        saved.description.clone()
+    };
+    json!({
+        "name": tool_name(workflow),
+        "description": description,
+        "inputSchema": { "type": "object", "properties": properties, "required": required },
+        "outputSchema": { "type": "object", "properties": outputs }
+    })
+}
+
+/// Run a workflow with the call's arguments in place of its input values,
+/// returning the MCP `tools/call` result.
+async fn call_workflow(saved: &SavedWorkflow, arguments: &Value, services: Services<'_>) -> Value {
+    let mut workflow = saved.workflow.clone();
+    let entries = entry_nodes(&saved.workflow).len();
+    for node in workflow.nodes.iter_mut() {
+        let name = argument_name(node, entries);
+        if let NodeKind::Input { value } = &mut node.kind {
+            if let Some(argument) = arguments.get(&name) {
+                *value = argument.as_str().map(str::to_string).unwrap_or_else(|| argument.to_string());
+            }
+        }
+    }
+
+    let run = match workflow::run_workflow(&workflow, services).await {
+        Ok(run) => run,
+        Err(e) => return error_result(&e),
+    };
+    if run.status != RunStatus::Completed {
+        let errors: Vec<String> = run.nodes.iter()
+            .filter(|node| node.status == NodeStatus::Failed)
+            .map(|node| format!("{}: {}", node.node_id, node.error.clone().unwrap_or_default()))
+            .collect();
+        return error_result(&format!("Workflow run {} failed\n{}", run.run_id, errors.join("\n")));
+    }
+
+    let exits = exit_nodes(&workflow);
+    let mut structured = Map::new();
+    let mut texts = Vec::new();
+    for node in &exits {
+        let result = run.nodes.iter().find(|result| result.node_id == node.id);
+        let output = result.and_then(|result| result.output.clone()).unwrap_or_default();
+        let value = result.and_then(|result| result.value.clone()).unwrap_or(Value::String(output.clone()));
+        texts.push((node.id.as_str(), output));
+        structured.insert(node.id.clone(), value);
+    }
+    let text = match texts.as_slice() {
+        [(_, only)] => only.clone(),
+        _ => texts.iter()
+            .map(|(id, output)| format!("## {}\n{}", id, output))
+            .collect::<Vec<_>>()
+            .join("\n\n"),
+    };
+    json!({
+        "content": [{ "type": "text", "text": text }],
+        "structuredContent": structured,
+        "isError": false
+    })
+}
+
+fn error_result(message: &str) -> Value {
+    json!({ "content": [{ "type": "text", "text": message }], "isError": true })
+}
+
+/// Answer one JSON-RPC message. Notifications and responses get no reply.
+pub async fn handle(message: &Value, services: Services<'_>) -> Option<Value> {
+    let id = message.get("id")?.clone();
+    let method = message["method"].as_str()?;
+    let params = &message["params"];
+    let result = match method {
+        "initialize" => {
+            let requested = params["protocolVersion"].as_str().unwrap_or(PROTOCOL_VERSION);
+            let version = if SUPPORTED_VERSIONS.contains(&requested) { requested } else { PROTOCOL_VERSION };
+            Ok(json!({
+                "protocolVersion": version,
+                "capabilities": { "tools": { "listChanged": false } },
+                "serverInfo": { "name": "nexa-ai-core", "version": env!("CARGO_PKG_VERSION") },
+                "instructions": "Each tool runs a saved nexa-ai-core workflow and returns the text of its output nodes."
+            }))
+        }
+        "ping" => Ok(json!({})),
+        "tools/list" => services.history
+            .list_workflows(true)
+            .map(|workflows| json!({ "tools": workflows.iter().map(tool_definition).collect::<Vec<_>>() }))
+            .map_err(|e| (-32603, e)),
+        "tools/call" => {
+            let name = params["name"].as_str().unwrap_or_default();
+            match services.history.list_workflows(true) {
+                Ok(workflows) => match workflows.iter().find(|saved| tool_name(&saved.workflow) == name) {
+                    Some(saved) => {
+                        let _ = add_log_entry(services.log_state, "info", &format!("MCP client running workflow {}", saved.workflow.name), "mcp");
+                        let arguments = params.get("arguments").cloned().unwrap_or_else(|| json!({}));
+                        Ok(call_workflow(saved, &arguments, services).await)
+                    }
+                    None => Err((-32602, format!("Unknown tool: {}", name))),
+                },
+                Err(e) => Err((-32603, e)),
+            }
+        }
+        _ => Err((-32601, format!("Method not found: {}", method))),
+    };
+    Some(match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err((code, message)) => json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } }),
+    })
+}
+
+/// Serve on stdin and stdout until stdin closes.
+pub async fn serve_stdio(app: AppHandle) {
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let mut stdout = tokio::io::stdout();
+    while let Ok(Some(line)) = lines.next_line().await {
+        let reply = match serde_json::from_str::<Value>(&line) {
+            Ok(message) => handle(&message, Services::from_app(&app)).await,
+            Err(e) => Some(json!({ "jsonrpc": "2.0", "id": null, "error": { "code": -32700, "message": e.to_string() } })),
+        };
+        if let Some(reply) = reply {
+            let mut bytes = reply.to_string().into_bytes();
+            bytes.push(b'\n');
+            if stdout.write_all(&bytes).await.is_err() || stdout.flush().await.is_err() {
+                break;
+            }
+        }
+    }
+}
+
+/// Sessions handed out by `initialize` and not yet ended with DELETE.
+#[derive(Default)]
+struct Sessions(Mutex<HashSet<String>>);
+
+impl Sessions {
+    fn start(&self) -> Option<String> {
+        let id = uuid::Uuid::new_v4().to_string();
+        self.0.lock().ok()?.insert(id.clone());
+        Some(id)
+    }
+
+    fn contains(&self, id: &str) -> bool {
+        self.0.lock().is_ok_and(|sessions| sessions.contains(id))
+    }
+
+    fn end(&self, id: &str) -> bool {
+        self.0.lock().is_ok_and(|mut sessions| sessions.remove(id))
+    }
+}
+
+/// Serve `POST /mcp` on a local port. Replies are plain JSON; nothing is
+/// streamed back, so GET has no event stream to offer.
+pub async fn serve_http(app: AppHandle, port: u16) -> Result<(), String> {
+    let listener = TcpListener::bind(("127.0.0.1", port))
+        .await
+        .map_err(|e| format!("Failed to listen on port {}: {}", port, e))?;
+    let sessions = Arc::new(Sessions::default());
+    while let Ok((stream, _)) = listener.accept().await {
+        let app = app.clone();
+        let sessions = sessions.clone();
+        tokio::spawn(async move {
+            let _ = respond(stream, &sessions, Services::from_app(&app)).await;
+        });
+    }
+    Ok(())
+}
+
+fn header<'a>(head: &'a str, name: &str) -> Option<&'a str> {
+    head.lines()
+        .skip(1)
+        .filter_map(|line| line.split_once(':'))
+        .find(|(key, _)| key.trim().eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.trim())
+}
+
+/// Browsers send an Origin with every cross-site POST. Only pages served from
+/// this machine may call in; anything else could be a DNS rebinding attack.
+fn local_origin(origin: &str) -> bool {
+    reqwest::Url::parse(origin)
+        .is_ok_and(|url| matches!(url.host_str(), Some("localhost" | "127.0.0.1" | "[::1]")))
+}
+
+async fn respond(mut stream: TcpStream, sessions: &Sessions, services: Services<'_>) -> std::io::Result<()> {
+    let mut request = Vec::new();
+    let mut buffer = [0u8; 8192];
+    let (head, body) = loop {
+        let read = stream.read(&mut buffer).await?;
+        request.extend_from_slice(&buffer[..read]);
+        if let Some(end) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+            let head = String::from_utf8_lossy(&request[..end]).into_owned();
+            let length: usize = header(&head, "content-length")
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(0);
+            if length > MAX_REQUEST_BYTES {
+                return write_response(&mut stream, "413 Payload Too Large", None, "").await;
+            }
+            while request.len() < end + 4 + length {
+                let read = stream.read(&mut buffer).await?;
+                if read == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buffer[..read]);
+            }
+            let body = String::from_utf8_lossy(&request[end + 4..]).into_owned();
+            break (head, body);
+        }
+        if read == 0 || request.len() > MAX_REQUEST_BYTES {
+            return Ok(());
+        }
+    };
+
+    let mut request_line = head.lines().next().unwrap_or_default().split_whitespace();
+    let method = request_line.next().unwrap_or_default();
+    let path = request_line.next().unwrap_or_default();
+    if path.split('?').next() != Some("/mcp") {
+        return write_response(&mut stream, "404 Not Found", None, "").await;
+    }
+    if header(&head, "origin").is_some_and(|origin| !local_origin(origin)) {
+        return write_response(&mut stream, "403 Forbidden", None, "").await;
+    }
+    let session = header(&head, "mcp-session-id");
+    match method {
+        "POST" => {}
+        "DELETE" => {
+            let status = if session.is_some_and(|id| sessions.end(id)) { "200 OK" } else { "404 Not Found" };
+            return write_response(&mut stream, status, None, "").await;
+        }
+        _ => return write_response(&mut stream, "405 Method Not Allowed", None, "").await,
+    }
+    let json = header(&head, "content-type")
+        .and_then(|value| value.split(';').next())
+        .is_some_and(|value| value.trim().eq_ignore_ascii_case("application/json"));
+    if !json {
+        return write_response(&mut stream, "415 Unsupported Media Type", None, "").await;
+    }
+
+    let message: Value = match serde_json::from_str(&body) {
+        Ok(message) => message,
+        Err(e) => {
+            let error = json!({ "jsonrpc": "2.0", "id": null, "error": { "code": -32700, "message": e.to_string() } });
+            return write_response(&mut stream, "400 Bad Request", None, &error.to_string()).await;
+        }
+    };
+    let new_session = if message["method"] == "initialize" {
+        sessions.start()
+    } else {
+        match session {
+            Some(id) if sessions.contains(id) => None,
+            Some(_) => return write_response(&mut stream, "404 Not Found", None, "").await,
+            None => return write_response(&mut stream, "400 Bad Request", None, "").await,
+        }
+    };
+    match handle(&message, services).await {
+        Some(reply) => write_response(&mut stream, "200 OK", new_session.as_deref(), &reply.to_string()).await,
+        None => write_response(&mut stream, "202 Accepted", None, "").await,
+    }
+}
+
+async fn write_response(stream: &mut TcpStream, status: &str, session: Option<&str>, body: &str) -> std::io::Result<()> {
+    let mut response = format!("HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n", status, body.len());
+    if !body.is_empty() {
+        response.push_str("Content-Type: application/json\r\n");
+    }
+    if let Some(session) = session {
+        response.push_str(&format!("Mcp-Session-Id: {}\r\n", session));
+    }
+    response.push_str("\r\n");
+    response.push_str(body);
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::workflow::testing::TestServices;
+
+    fn save(services: &TestServices, workflow: Value, published: bool) {
+        let saved = SavedWorkflow {
+            workflow: serde_json::from_value(workflow).unwrap(),
+            description: String::new(),
+            published,
+            updated_at: String::new(),
+        };
+        services.services().history.save_workflow(&saved).unwrap();
+    }
+
+    fn echo_workflow(services: &TestServices) {
+        save(services, json!({"id": "wf-echo", "name": "Echo text", "nodes": [
+            {"id": "in", "type": "input", "value": "default text"},
+            {"id": "out", "type": "output"}
+        ], "edges": [{"from": "in", "to": "out"}]}), true);
+    }
+
+    #[tokio::test]
+    async fn published_workflows_are_tools() {
+        let services = TestServices::new();
+        echo_workflow(&services);
+        save(&services, json!({"id": "wf-two", "name": "Two", "nodes": [
+            {"id": "a", "type": "input", "value": ""},
+            {"id": "b", "type": "input", "value": "B"}
+        ], "edges": []}), true);
+        save(&services, json!({"id": "wf-hidden", "name": "Hidden", "nodes": [], "edges": []}), false);
+        let request = |id: u64, method: &str, params: Value| json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params});
+
+        let init = handle(&request(1, "initialize", json!({"protocolVersion": "2025-06-18"})), services.services()).await.unwrap();
+        assert_eq!(init["result"]["protocolVersion"], "2025-06-18");
+        let init = handle(&request(1, "initialize", json!({"protocolVersion": "1999-01-01"})), services.services()).await.unwrap();
+        assert_eq!(init["result"]["protocolVersion"], PROTOCOL_VERSION);
+        assert!(handle(&json!({"jsonrpc": "2.0", "method": "notifications/initialized"}), services.services()).await.is_none());
+
+        let list = handle(&request(2, "tools/list", json!({})), services.services()).await.unwrap();
+        let tools = list["result"]["tools"].as_array().unwrap();
+        assert_eq!(tools.len(), 2);
+        assert_eq!(tools[0]["name"], "Echo_text");
+        assert_eq!(tools[0]["inputSchema"]["properties"]["input"]["type"], "string");
+        assert_eq!(tools[0]["outputSchema"]["properties"]["out"]["type"], "string");
+        assert_eq!(tools[1]["inputSchema"]["required"], json!(["a"]));
+
+        let call = handle(&request(3, "tools/call", json!({"name": "Echo_text", "arguments": {"input": "hello"}})), services.services()).await.unwrap();
+        assert_eq!(call["result"]["content"][0]["text"], "hello");
+        assert_eq!(call["result"]["structuredContent"]["out"], "hello");
+        let call = handle(&request(4, "tools/call", json!({"name": "Echo_text"})), services.services()).await.unwrap();
+        assert_eq!(call["result"]["content"][0]["text"], "default text");
+        let call = handle(&request(5, "tools/call", json!({"name": "Two", "arguments": {"a": "A"}})), services.services()).await.unwrap();
+        assert_eq!(call["result"]["content"][0]["text"], "## a\nA\n\n## b\nB");
+        let hidden = handle(&request(6, "tools/call", json!({"name": "Hidden"})), services.services()).await.unwrap();
+        assert_eq!(hidden["error"]["code"], -32602);
+        let unknown = handle(&request(7, "resources/list", json!({})), services.services()).await.unwrap();
+        assert_eq!(unknown["error"]["code"], -32601);
+    }
+
+    /// Send one raw HTTP request to `respond`, returning the status line and
+    /// the whole response.
+    async fn exchange(sessions: &Sessions, services: Services<'_>, request: String) -> (String, String) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = async {
+            let (stream, _) = listener.accept().await.unwrap();
+            respond(stream, sessions, services).await.unwrap();
+        };
+        let client = async {
+            let mut stream = TcpStream::connect(address).await.unwrap();
+            stream.write_all(request.as_bytes()).await.unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await.unwrap();
+            response
+        };
+        let ((), response) = tokio::join!(server, client);
+        (response.lines().next().unwrap_or_default().to_string(), response)
+    }
+
+    fn request(method: &str, headers: &[(&str, &str)], body: &Value) -> String {
+        let body = if body.is_null() { String::new() } else { body.to_string() };
+        let headers: String = headers.iter().map(|(name, value)| format!("{}: {}\r\n", name, value)).collect();
+        format!("{} /mcp HTTP/1.1\r\nHost: 127.0.0.1\r\n{}Content-Length: {}\r\n\r\n{}", method, headers, body.len(), body)
+    }
+
+    #[tokio::test]
+    async fn http_checks_origin_content_type_and_session() {
+        let services = TestServices::new();
+        echo_workflow(&services);
+        let sessions = Sessions::default();
+        let json_type = ("Content-Type", "application/json");
+        let initialize = json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}});
+        let call = json!({"jsonrpc": "2.0", "id": 2, "method": "tools/call", "params": {"name": "Echo_text", "arguments": {"input": "hi"}}});
+
+        // A page on another site cannot call in, whatever it sends
+        let (status, _) = exchange(&sessions, services.services(), request("POST", &[json_type, ("Origin", "http://evil.example")], &initialize)).await;
+        assert_eq!(status, "HTTP/1.1 403 Forbidden");
+        let (status, _) = exchange(&sessions, services.services(), request("POST", &[("Content-Type", "text/plain")], &initialize)).await;
+        assert_eq!(status, "HTTP/1.1 415 Unsupported Media Type");
+
+        let (status, response) = exchange(&sessions, services.services(), request("POST", &[json_type, ("Origin", "http://localhost:1420")], &initialize)).await;
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        let session = header(&response, "mcp-session-id").unwrap().to_string();
+
+        let (status, _) = exchange(&sessions, services.services(), request("POST", &[json_type], &call)).await;
+        assert_eq!(status, "HTTP/1.1 400 Bad Request");
+        let (status, _) = exchange(&sessions, services.services(), request("POST", &[json_type, ("Mcp-Session-Id", "made-up")], &call)).await;
+        assert_eq!(status, "HTTP/1.1 404 Not Found");
+        let with_session = [("Content-Type", "application/json; charset=utf-8"), ("Mcp-Session-Id", session.as_str())];
+        let (status, response) = exchange(&sessions, services.services(), request("POST", &with_session, &call)).await;
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        let reply: Value = serde_json::from_str(response.split("\r\n\r\n").nth(1).unwrap()).unwrap();
+        assert_eq!(reply["result"]["content"][0]["text"], "hi");
+
+        // Ending the session makes its id unknown
+        let (status, _) = exchange(&sessions, services.services(), request("DELETE", &[("Mcp-Session-Id", session.as_str())], &Value::Null)).await;
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        let (status, _) = exchange(&sessions, services.services(), request("POST", &with_session, &call)).await;
+        assert_eq!(status, "HTTP/1.1 404 Not Found");
+    }
+}
@@ -0,0 +1,246 @@
+// JSON-RPC connections to MCP servers: a child process speaking over stdio,
+// or a streamable HTTP endpoint.
+use reqwest::header::{ACCEPT, CONTENT_TYPE};
+use reqwest::Client;
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, Command};
+use tokio::sync::oneshot;
+
+use super::{McpTransport, PROTOCOL_VERSION, REQUEST_TIMEOUT};
+
+/// Lines of the server's stderr kept for error messages.
+const STDERR_LINES: usize = 20;
+/// How long to wait for stderr to close after stdout did.
+const STDERR_GRACE: Duration = Duration::from_secs(1);
+
+type Pending = Arc<Mutex<HashMap<u64, oneshot::Sender<Value>>>>;
+
+pub enum Connection {
+    Stdio(StdioConnection),
+    Http(HttpConnection),
+}
+
+impl Connection {
+    pub fn open(transport: &McpTransport, client: Client) -> Result<Self, String> {
+        match transport {
+            McpTransport::Stdio { command, args, env, cwd } => {
+                StdioConnection::spawn(command, args, env, cwd.as_deref()).map(Connection::Stdio)
+            }
+            McpTransport::Http { url, headers } => Ok(Connection::Http(HttpConnection {
+                url: url.clone(),
+                headers: headers.clone(),
+                client,
+                session: Mutex::new(None),
+            })),
+        }
+    }
+
+    /// Send a request and wait for the response with the same id.
+    pub async fn request(&self, message: Value) -> Result<Value, String> {
+        let reply = async {
+            match self {
+                Connection::Stdio(stdio) => stdio.request(message).await,
+                Connection::Http(http) => http.request(message).await,
+            }
+        };
+        tokio::time::timeout(REQUEST_TIMEOUT, reply)
+            .await
+            .map_err(|_| format!("MCP server did not answer within {}s", REQUEST_TIMEOUT.as_secs()))?
+    }
+
+    pub async fn notify(&self, message: Value) -> Result<(), String> {
+        match self {
+            Connection::Stdio(stdio) => write_message(&stdio.stdin, &message).await,
+            Connection::Http(http) => http.post(&message).await.map(|_| ()),
+        }
+    }
+
+    pub async fn close(&self) {
+        if let Connection::Stdio(stdio) = self {
+            let _ = stdio.child.lock().await.kill().await;
+        }
+    }
+}
+
+pub struct StdioConnection {
+    child: tokio::sync::Mutex<Child>,
+    stdin: Arc<tokio::sync::Mutex<ChildStdin>>,
+    pending: Pending,
+    stderr: Arc<Mutex<VecDeque<String>>>,
+}
+
+impl StdioConnection {
+    fn spawn(command: &str, args: &[String], env: &HashMap<String, String>, cwd: Option<&str>) -> Result<Self, String> {
+        let mut cmd = Command::new(command);
+        cmd.args(args)
+            .envs(env)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        if let Some(cwd) = cwd.filter(|cwd| !cwd.is_empty()) {
+            cmd.current_dir(cwd);
+        }
+        let mut child = cmd.spawn().map_err(|e| format!("Failed to start {}: {}", command, e))?;
+        let (Some(stdin), Some(stdout), Some(stderr)) = (child.stdin.take(), child.stdout.take(), child.stderr.take()) else {
+            return Err(format!("Failed to open the pipes of {}", command));
+        };
+
+        let stdin = Arc::new(tokio::sync::Mutex::new(stdin));
+        let pending = Pending::default();
+        let stderr_tail = Arc::new(Mutex::new(VecDeque::new()));
+
+        let tail = stderr_tail.clone();
+        let stderr_done = tokio::spawn(async move {
+            let mut lines = BufReader::new(stderr).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                if let Ok(mut tail) = tail.lock() {
+                    if tail.len() == STDERR_LINES {
+                        tail.pop_front();
+                    }
+                    tail.push_back(line);
+                }
+            }
+        });
+
+        let (writer, waiting) = (stdin.clone(), pending.clone());
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(stdout).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                let Ok(message) = serde_json::from_str::<Value>(&line) else {
+                    continue;
+                };
+                if let Some(method) = message["method"].as_str() {
+                    // A request from the server; answer pings, refuse the rest
+                    if let Some(id) = message.get("id") {
+                        let reply = if method == "ping" {
+                            json!({ "jsonrpc": "2.0", "id": id, "result": {} })
+                        } else {
+                            json!({ "jsonrpc": "2.0", "id": id, "error": { "code": -32601, "message": "Method not found" } })
+                        };
+                        let _ = write_message(&writer, &reply).await;
+                    }
+                    continue;
+                }
+                let sender = message["id"].as_u64().and_then(|id| waiting.lock().ok()?.remove(&id));
+                if let Some(sender) = sender {
+                    let _ = sender.send(message);
+                }
+            }
+            // The server is gone. Once its last words on stderr are in, fail
+            // whatever is still waiting
+            let _ = tokio::time::timeout(STDERR_GRACE, stderr_done).await;
+            if let Ok(mut waiting) = waiting.lock() {
+                waiting.clear();
+            }
+        });
+
+        Ok(Self {
+            child: tokio::sync::Mutex::new(child),
+            stdin,
+            pending,
+            stderr: stderr_tail,
+        })
+    }
+
+    async fn request(&self, message: Value) -> Result<Value, String> {
+        let id = message["id"].as_u64().ok_or("MCP requests need a numeric id")?;
+        let (sender, receiver) = oneshot::channel();
+        self.pending.lock().map_err(|e| e.to_string())?.insert(id, sender);
+        if let Err(e) = write_message(&self.stdin, &message).await {
+            self.pending.lock().map_err(|e| e.to_string())?.remove(&id);
+            return Err(self.exited(&e));
+        }
+        receiver.await.map_err(|_| self.exited("MCP server closed the connection"))
+    }
+
+    /// `error`, followed by the last lines the server wrote to stderr.
+    fn exited(&self, error: &str) -> String {
+        let tail = self.stderr.lock().map(|tail| tail.iter().cloned().collect::<Vec<_>>().join("\n")).unwrap_or_default();
+        if tail.is_empty() {
+            error.to_string()
+        } else {
+            format!("{}\n{}", error, tail)
+        }
+    }
+}
+
+async fn write_message(stdin: &tokio::sync::Mutex<ChildStdin>, message: &Value) -> Result<(), String> {
+    let mut line = serde_json::to_vec(message).map_err(|e| e.to_string())?;
+    line.push(b'\n');
+    let mut stdin = stdin.lock().await;
+    stdin.write_all(&line).await.map_err(|e| format!("Failed to write to MCP server: {}", e))?;
+    stdin.flush().await.map_err(|e| format!("Failed to write to MCP server: {}", e))
+}
+
+pub struct HttpConnection {
+    url: String,
+    headers: HashMap<String, String>,
+    client: Client,
+    /// Session id the server assigned on initialization.
+    session: Mutex<Option<String>>,
+}
+
+impl HttpConnection {
+    async fn request(&self, message: Value) -> Result<Value, String> {
+        self.post(&message).await?.ok_or_else(|| "MCP server sent no response".to_string())
+    }
+
+    /// POST one message. Responses come back as JSON or as an event stream
+    /// that carries the response among other messages.
+    async fn post(&self, message: &Value) -> Result<Option<Value>, String> {
+        let mut request = self.client.post(&self.url)
+            .header(ACCEPT, "application/json, text/event-stream")
+            .header("MCP-Protocol-Version", PROTOCOL_VERSION)
+            .json(message);
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+        if let Some(session) = self.session.lock().map_err(|e| e.to_string())?.clone() {
+            request = request.header("Mcp-Session-Id", session);
+        }
+        let mut response = request.send().await.map_err(|e| format!("MCP request to {} failed: {}", self.url, e))?;
+        if let Some(session) = response.headers().get("mcp-session-id").and_then(|v| v.to_str().ok()) {
+            *self.session.lock().map_err(|e| e.to_string())? = Some(session.to_string());
+        }
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(format!("MCP server returned {}: {}", status, body.trim()));
+        }
+        let Some(id) = message.get("id") else {
+            return Ok(None);
+        };
+
+        let is_stream = response.headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.starts_with("text/event-stream"));
+        if !is_stream {
+            return response.json().await.map(Some).map_err(|e| format!("Invalid MCP response: {}", e));
+        }
+
+        let mut buffer = String::new();
+        while let Some(chunk) = response.chunk().await.map_err(|e| e.to_string())? {
+            buffer.push_str(&String::from_utf8_lossy(&chunk).replace("\r\n", "\n"));
+            while let Some(end) = buffer.find("\n\n") {
+                let event: String = buffer.drain(..end + 2).collect();
+                let data: Vec<&str> = event.lines()
+                    .filter_map(|line| line.strip_prefix("data:"))
+                    .map(|data| data.strip_prefix(' ').unwrap_or(data))
+                    .collect();
+                if let Ok(message) = serde_json::from_str::<Value>(&data.join("\n")) {
+                    if message.get("id") == Some(id) && message.get("method").is_none() {
+                        return Ok(Some(message));
+                    }
+                }
+            }
+        }
+        Err("MCP event stream ended without a response".to_string())
+    }
+}
@@ -0,0 +1,529 @@
+// Long-term memory for agents: facts, episode summaries and key/value notes
+// kept across runs, per workflow or global, and recalled by recency and
+// similarity.
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Mutex;
+use tauri::State;
+
+use crate::knowledge::bm25::Bm25;
+use crate::knowledge::{vector_bytes, vector_from_bytes};
+use crate::provider;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS memories (
+        id TEXT PRIMARY KEY,
+        namespace TEXT NOT NULL,
+        kind TEXT NOT NULL,
+        key TEXT,
+        content TEXT NOT NULL,
+        run_id TEXT,
+        created_at INTEGER NOT NULL,
+        updated_at INTEGER NOT NULL,
+        embedding_model TEXT,
+        vector BLOB
+    );
+    CREATE INDEX IF NOT EXISTS idx_memories_namespace ON memories (namespace, updated_at);
+";
+
+const COLUMNS: &str = "id, namespace, kind, key, content, run_id, created_at, updated_at";
+
+/// Namespace shared by every workflow.
+pub const GLOBAL_NAMESPACE: &str = "global";
+
+/// Most recent memories considered by a recall.
+const MAX_CANDIDATES: usize = 2000;
+/// Share of a recall score that comes from recency rather than similarity.
+const RECENCY_WEIGHT: f32 = 0.3;
+/// Age at which a memory's recency score halves.
+const RECENCY_HALF_LIFE_DAYS: f32 = 7.0;
+
+pub fn workflow_namespace(workflow_id: &str) -> String {
+    format!("workflow:{}", workflow_id)
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MemoryKind {
+    /// A standalone statement worth keeping. Identical facts are stored once.
+    #[default]
+    Fact,
+    /// A summary of what happened in a run.
+    Episode,
+    /// A value stored under a key; writing the key again replaces it.
+    Note,
+}
+
+impl MemoryKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MemoryKind::Fact => "fact",
+            MemoryKind::Episode => "episode",
+            MemoryKind::Note => "note",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "fact" => Some(MemoryKind::Fact),
+            "episode" => Some(MemoryKind::Episode),
+            "note" => Some(MemoryKind::Note),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Memory {
+    pub id: String,
+    pub namespace: String,
+    pub kind: MemoryKind,
+    /// Key of a note.
+    pub key: Option<String>,
+    pub content: String,
+    /// Run that wrote the memory.
+    pub run_id: Option<String>,
+    /// Unix seconds.
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+/// A memory returned by `recall`, with how it scored.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecalledMemory {
+    #[serde(flatten)]
+    pub memory: Memory,
+    pub score: f32,
+    /// Similarity to the query: cosine with an embedding model, otherwise
+    /// BM25 relative to the best match. Absent without a query.
+    pub similarity: Option<f32>,
+    /// 1 for a memory updated now, halving every week.
+    pub recency: f32,
+}
+
+/// Model used to embed memories and queries for similarity.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryEmbedding {
+    pub server_url: String,
+    pub model: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NamespaceInfo {
+    pub namespace: String,
+    pub memories: u64,
+}
+
+/// Which memories `list` and `purge` act on. Unset fields match everything.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MemoryFilter {
+    #[serde(default)]
+    pub namespace: Option<String>,
+    #[serde(default)]
+    pub kind: Option<MemoryKind>,
+    /// Text the content or key contains.
+    #[serde(default)]
+    pub search: Option<String>,
+    /// Only memories last updated more than this many days ago.
+    #[serde(default)]
+    pub older_than_days: Option<u32>,
+}
+
+impl MemoryFilter {
+    /// SQL condition and its parameters.
+    fn clause(&self) -> (String, Vec<rusqlite::types::Value>) {
+        use rusqlite::types::Value;
+        let mut conditions = vec!["1 = 1".to_string()];
+        let mut values = Vec::new();
+        if let Some(namespace) = &self.namespace {
+            values.push(Value::Text(namespace.clone()));
+            conditions.push(format!("namespace = ?{}", values.len()));
+        }
+        if let Some(kind) = self.kind {
+            values.push(Value::Text(kind.as_str().to_string()));
+            conditions.push(format!("kind = ?{}", values.len()));
+        }
+        if let Some(search) = self.search.as_deref().filter(|search| !search.trim().is_empty()) {
+            values.push(Value::Text(search.trim().to_lowercase()));
+            conditions.push(format!(
+                "(instr(lower(content), ?{0}) > 0 OR instr(lower(coalesce(key, '')), ?{0}) > 0)",
+                values.len()
+            ));
+        }
+        if let Some(days) = self.older_than_days {
+            values.push(Value::Integer(now() - days as i64 * 24 * 60 * 60));
+            conditions.push(format!("updated_at < ?{}", values.len()));
+        }
+        (conditions.join(" AND "), values)
+    }
+}
+
+fn now() -> i64 {
+    chrono::Utc::now().timestamp()
+}
+
+fn memory_from_row(row: &Row) -> rusqlite::Result<Memory> {
+    let kind: String = row.get(2)?;
+    Ok(Memory {
+        id: row.get(0)?,
+        namespace: row.get(1)?,
+        kind: MemoryKind::parse(&kind).unwrap_or_default(),
+        key: row.get(3)?,
+        content: row.get(4)?,
+        run_id: row.get(5)?,
+        created_at: row.get(6)?,
+        updated_at: row.get(7)?,
+    })
+}
+
+pub struct MemoryStore {
+    conn: Mutex<Connection>,
+}
+
+impl MemoryStore {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let conn = Connection::open(path).map_err(|e| format!("Failed to open memory store: {}", e))?;
+        Self::init(conn)
+    }
+
+    #[cfg(test)]
+    pub fn open_in_memory() -> Result<Self, String> {
+        Self::init(Connection::open_in_memory().map_err(|e| e.to_string())?)
+    }
+
+    fn init(conn: Connection) -> Result<Self, String> {
+        conn.execute_batch(SCHEMA)
+            .map_err(|e| format!("Failed to initialise memory store: {}", e))?;
+        Ok(Self { conn: Mutex::new(conn) })
+    }
+
+    fn conn(&self) -> Result<std::sync::MutexGuard<'_, Connection>, String> {
+        self.conn.lock().map_err(|e| e.to_string())
+    }
+
+    pub fn get(&self, id: &str) -> Result<Memory, String> {
+        self.conn()?
+            .query_row(&format!("SELECT {} FROM memories WHERE id = ?1", COLUMNS), params![id], memory_from_row)
+            .optional()
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("Memory '{}' not found", id))
+    }
+
+    /// Store a memory. A note replaces the note with the same key, and a fact
+    /// identical to a stored one refreshes it instead of adding a copy.
+    pub fn remember(
+        &self,
+        namespace: &str,
+        kind: MemoryKind,
+        key: Option<&str>,
+        content: &str,
+        run_id: Option<&str>,
+    ) -> Result<Memory, String> {
+        let content = content.trim();
+        if content.is_empty() {
+            return Err("Memory content is empty".to_string());
+        }
+        let key = key.map(str::trim).filter(|key| !key.is_empty());
+        if kind == MemoryKind::Note && key.is_none() {
+            return Err("A note needs a key".to_string());
+        }
+
+        let lookup = match kind {
+            MemoryKind::Note => Some(("key = ?3", key.unwrap_or_default())),
+            MemoryKind::Fact => Some(("content = ?3", content)),
+            MemoryKind::Episode => None,
+        };
+        let existing: Option<String> = match lookup {
+            Some((condition, value)) => self.conn()?
+                .query_row(
+                    &format!("SELECT id FROM memories WHERE namespace = ?1 AND kind = ?2 AND {}", condition),
+                    params![namespace, kind.as_str(), value],
+                    |row| row.get(0),
+                )
+                .optional()
+                .map_err(|e| e.to_string())?,
+            None => None,
+        };
+
+        let id = match existing {
+            Some(id) => {
+                self.conn()?
+                    .execute(
+                        "UPDATE memories SET content = ?2, run_id = coalesce(?3, run_id), updated_at = ?4,
+                         embedding_model = CASE WHEN content = ?2 THEN embedding_model END,
+                         vector = CASE WHEN content = ?2 THEN vector END
+                         WHERE id = ?1",
+                        params![id, content, run_id, now()],
+                    )
+                    .map_err(|e| e.to_string())?;
+                id
+            }
+            None => {
+                let id = uuid::Uuid::new_v4().to_string();
+                let time = now();
+                self.conn()?
+                    .execute(
+                        "INSERT INTO memories (id, namespace, kind, key, content, run_id, created_at, updated_at)
+                         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?7)",
+                        params![id, namespace, kind.as_str(), key, content, run_id, time],
+                    )
+                    .map_err(|e| e.to_string())?;
+                id
+            }
+        };
+        self.get(&id)
+    }
+
+    /// Change a memory's content or key. Its embedding is recomputed on the
+    /// next recall.
+    pub fn update(&self, id: &str, content: &str, key: Option<&str>) -> Result<Memory, String> {
+        let memory = self.get(id)?;
+        let key = key.map(str::trim).filter(|key| !key.is_empty());
+        if memory.kind == MemoryKind::Note && key.is_none() {
+            return Err("A note needs a key".to_string());
+        }
+        if content.trim().is_empty() {
+            return Err("Memory content is empty".to_string());
+        }
+        self.conn()?
+            .execute(
+                "UPDATE memories SET content = ?2, key = ?3, updated_at = ?4, embedding_model = NULL, vector = NULL
+                 WHERE id = ?1",
+                params![id, content.trim(), key, now()],
+            )
+            .map_err(|e| e.to_string())?;
+        self.get(id)
+    }
+
+    pub fn delete(&self, id: &str) -> Result<(), String> {
+        self.conn()?
+            .execute("DELETE FROM memories WHERE id = ?1", params![id])
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    /// Delete the memories matching `filter`. Returns how many were deleted.
+    pub fn purge(&self, filter: &MemoryFilter) -> Result<usize, String> {
+        let (clause, values) = filter.clause();
+        self.conn()?
+            .execute(&format!("DELETE FROM memories WHERE {}", clause), rusqlite::params_from_iter(values))
+            .map_err(|e| e.to_string())
+    }
+
+    /// Memories matching `filter`, most recently updated first.
+    pub fn list(&self, filter: &MemoryFilter, limit: usize) -> Result<Vec<Memory>, String> {
+        let (clause, mut values) = filter.clause();
+        values.push(rusqlite::types::Value::Integer(limit as i64));
+        let conn = self.conn()?;
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT {} FROM memories WHERE {} ORDER BY updated_at DESC, created_at DESC LIMIT ?{}",
+                COLUMNS,
+                clause,
+                values.len()
+            ))
+            .map_err(|e| e.to_string())?;
+        let memories = stmt
+            .query_map(rusqlite::params_from_iter(values), memory_from_row)
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        Ok(memories)
+    }
+
+    pub fn namespaces(&self) -> Result<Vec<NamespaceInfo>, String> {
+        let conn = self.conn()?;
+        let mut stmt = conn
+            .prepare("SELECT namespace, COUNT(*) FROM memories GROUP BY namespace ORDER BY namespace")
+            .map_err(|e| e.to_string())?;
+        let namespaces = stmt
+            .query_map([], |row| Ok(NamespaceInfo { namespace: row.get(0)?, memories: row.get::<_, i64>(1)? as u64 }))
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        Ok(namespaces)
+    }
+
+    /// Embed the memories that have no vector for `embedding.model` yet.
+    async fn backfill(&self, embedding: &MemoryEmbedding, ids: &[String]) -> Result<(), String> {
+        let missing: Vec<(String, String)> = {
+            let conn = self.conn()?;
+            let mut stmt = conn
+                .prepare("SELECT id, content FROM memories WHERE id = ?1 AND (vector IS NULL OR embedding_model IS NOT ?2)")
+                .map_err(|e| e.to_string())?;
+            let mut missing = Vec::new();
+            for id in ids {
+                if let Some(row) = stmt
+                    .query_row(params![id, embedding.model], |row| Ok((row.get(0)?, row.get(1)?)))
+                    .optional()
+                    .map_err(|e| e.to_string())?
+                {
+                    missing.push(row);
+                }
+            }
+            missing
+        };
+        if missing.is_empty() {
+            return Ok(());
+        }
+        let inputs: Vec<String> = missing.iter().map(|(_, content)| content.clone()).collect();
+        let vectors = provider::embed(&embedding.server_url, &embedding.model, &inputs).await?.vectors;
+        let conn = self.conn()?;
+        for ((id, _), vector) in missing.iter().zip(vectors) {
+            conn.execute(
+                "UPDATE memories SET embedding_model = ?2, vector = ?3 WHERE id = ?1",
+                params![id, embedding.model, vector_bytes(&vector)],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    fn vectors(&self, ids: &[String], model: &str) -> Result<Vec<Option<Vec<f32>>>, String> {
+        let conn = self.conn()?;
+        let mut stmt = conn
+            .prepare("SELECT vector FROM memories WHERE id = ?1 AND embedding_model = ?2")
+            .map_err(|e| e.to_string())?;
+        ids.iter()
+            .map(|id| {
+                stmt.query_row(params![id, model], |row| row.get::<_, Vec<u8>>(0))
+                    .optional()
+                    .map(|bytes| bytes.map(|bytes| vector_from_bytes(&bytes)))
+                    .map_err(|e| e.to_string())
+            })
+            .collect()
+    }
+
+    /// The `top_k` memories in `namespaces` best matching `query`, scoring
+    /// similarity and recency together. Without a query only recency counts.
+    pub async fn recall(
+        &self,
+        namespaces: &[String],
+        kinds: &[MemoryKind],
+        query: &str,
+        top_k: usize,
+        embedding: Option<&MemoryEmbedding>,
+    ) -> Result<Vec<RecalledMemory>, String> {
+        let mut candidates = Vec::new();
+        for namespace in namespaces {
+            let filter = MemoryFilter { namespace: Some(namespace.clone()), ..Default::default() };
+            candidates.extend(self.list(&filter, MAX_CANDIDATES)?
+                .into_iter()
+                .filter(|memory| kinds.is_empty() || kinds.contains(&memory.kind)));
+        }
+        if candidates.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let query = query.trim();
+        let similarities: Vec<Option<f32>> = match embedding {
+            _ if query.is_empty() => vec![None; candidates.len()],
+            Some(embedding) => {
+                let ids: Vec<String> = candidates.iter().map(|memory| memory.id.clone()).collect();
+                self.backfill(embedding, &ids).await?;
+                let query_vector = provider::embed(&embedding.server_url, &embedding.model, &[query.to_string()])
+                    .await?
+                    .vectors
+                    .into_iter()
+                    .next()
+                    .ok_or("No embedding returned for the query")?;
+                self.vectors(&ids, &embedding.model)?
+                    .into_iter()
+                    .map(|vector| Some(vector.map_or(0.0, |vector| provider::similarity(&query_vector, &vector))))
+                    .collect()
+            }
+            None => {
+                let index = Bm25::build(candidates.iter().map(|memory| memory.content.as_str()));
+                let scores = index.search(query);
+                let best = scores.first().map_or(1.0, |(_, score)| *score);
+                let mut similarities = vec![Some(0.0); candidates.len()];
+                for (i, score) in scores {
+                    similarities[i] = Some(score / best);
+                }
+                similarities
+            }
+        };
+
+        let time = now();
+        let mut recalled: Vec<RecalledMemory> = candidates.into_iter()
+            .zip(similarities)
+            .map(|(memory, similarity)| {
+                let age_days = (time - memory.updated_at).max(0) as f32 / (24.0 * 60.0 * 60.0);
+                let recency = 0.5f32.powf(age_days / RECENCY_HALF_LIFE_DAYS);
+                let score = match similarity {
+                    Some(similarity) => (1.0 - RECENCY_WEIGHT) * similarity + RECENCY_WEIGHT * recency,
+                    None => recency,
+                };
+                RecalledMemory { memory, score, similarity, recency }
+            })
+            .collect();
+        recalled.sort_by(|a, b| b.score.total_cmp(&a.score).then(b.memory.updated_at.cmp(&a.memory.updated_at)));
+        recalled.truncate(top_k);
+        Ok(recalled)
+    }
+}
+
+#[tauri::command]
+pub async fn list_memory_namespaces(memory: State<'_, MemoryStore>) -> Result<Vec<NamespaceInfo>, String> {
+    memory.namespaces()
+}
+
+#[tauri::command]
+pub async fn list_memories(
+    filter: Option<MemoryFilter>,
+    limit: Option<usize>,
+    memory: State<'_, MemoryStore>,
+) -> Result<Vec<Memory>, String> {
+    memory.list(&filter.unwrap_or_default(), limit.unwrap_or(200))
+}
+
+#[tauri::command]
+pub async fn update_memory(
+    id: String,
+    content: String,
+    key: Option<String>,
+    memory: State<'_, MemoryStore>,
+) -> Result<Memory, String> {
+    memory.update(&id, &content, key.as_deref())
+}
+
+#[tauri::command]
+pub async fn delete_memory(id: String, memory: State<'_, MemoryStore>) -> Result<(), String> {
+    memory.delete(&id)
+}
+
+#[tauri::command]
+pub async fn purge_memories(filter: MemoryFilter, memory: State<'_, MemoryStore>) -> Result<usize, String> {
+    memory.purge(&filter)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn remember_recall_and_purge() {
+        let store = MemoryStore::open_in_memory().unwrap();
+        let namespace = workflow_namespace("wf");
+        let first = store.remember(&namespace, MemoryKind::Fact, None, "The deploy target is eu-west-1", Some("r1")).unwrap();
+        let again = store.remember(&namespace, MemoryKind::Fact, None, " The deploy target is eu-west-1 ", Some("r2")).unwrap();
+        assert_eq!(first.id, again.id);
+        assert_eq!(again.run_id.as_deref(), Some("r2"));
+        let owner = store.remember(&namespace, MemoryKind::Note, Some("owner"), "alice", None).unwrap();
+        assert_eq!(store.remember(&namespace, MemoryKind::Note, Some("owner"), "bob", None).unwrap().id, owner.id);
+        assert!(store.remember(&namespace, MemoryKind::Note, None, "no key", None).is_err());
+        store.remember(GLOBAL_NAMESPACE, MemoryKind::Fact, None, "User prefers metric units", None).unwrap();
+
+        let namespaces = [namespace.clone(), GLOBAL_NAMESPACE.to_string()];
+        let recalled = store.recall(&namespaces, &[], "where is the deploy target", 1, None).await.unwrap();
+        assert!(recalled[0].memory.content.contains("eu-west-1"));
+        let notes = store.recall(&namespaces, &[MemoryKind::Note], "", 5, None).await.unwrap();
+        assert_eq!(notes.len(), 1);
+        assert_eq!(notes[0].memory.content, "bob");
+
+        let filter = MemoryFilter { namespace: Some(namespace), ..Default::default() };
+        assert_eq!(store.purge(&filter).unwrap(), 2);
+        assert_eq!(store.list(&MemoryFilter::default(), 10).unwrap().len(), 1);
+    }
+}
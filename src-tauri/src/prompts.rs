@@ -0,0 +1,504 @@
+// Prompt library: named, versioned system and user prompts with variables,
+// referenced by workflow nodes.
+use rusqlite::{params, Connection, OptionalExtension, Row, TransactionBehavior};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::{LazyLock, Mutex};
+use tauri::State;
+
+use crate::history::{RunHistory, SavedWorkflow};
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS prompts (
+        id TEXT NOT NULL,
+        version INTEGER NOT NULL,
+        name TEXT NOT NULL,
+        description TEXT NOT NULL,
+        system TEXT NOT NULL,
+        user TEXT NOT NULL,
+        variables TEXT NOT NULL,
+        tags TEXT NOT NULL,
+        created_at INTEGER NOT NULL,
+        PRIMARY KEY (id, version)
+    );
+    CREATE TABLE IF NOT EXISTS prompt_usage (
+        id TEXT NOT NULL,
+        version INTEGER NOT NULL,
+        uses INTEGER NOT NULL,
+        last_used_at INTEGER NOT NULL,
+        PRIMARY KEY (id, version)
+    );
+";
+
+const COLUMNS: &str = "p.id, p.version, p.name, p.description, p.system, p.user, p.variables, p.tags, p.created_at,
+    coalesce(u.uses, 0), u.last_used_at";
+
+const FROM: &str = "prompts p LEFT JOIN prompt_usage u ON u.id = p.id AND u.version = p.version";
+
+/// A `{{name}}` slot filled in from the referencing node, or its default.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PromptVariable {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub default: Option<String>,
+}
+
+/// One version of a prompt. Versions never change once saved.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Prompt {
+    pub id: String,
+    pub version: u32,
+    pub name: String,
+    pub description: String,
+    pub system: String,
+    /// Task text; `{{input}}` works as in a node's own task.
+    pub user: String,
+    pub variables: Vec<PromptVariable>,
+    pub tags: Vec<String>,
+    /// Unix seconds.
+    pub created_at: i64,
+    /// Model requests made with this version.
+    pub uses: u64,
+    pub last_used_at: Option<i64>,
+}
+
+/// The latest version of a prompt, with stats over all versions.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptSummary {
+    #[serde(flatten)]
+    pub prompt: Prompt,
+    pub versions: u32,
+    pub total_uses: u64,
+}
+
+/// What the editor sends. Without an id a new prompt is created, otherwise
+/// a new version of that prompt.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PromptDraft {
+    #[serde(default)]
+    pub id: Option<String>,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub system: String,
+    #[serde(default)]
+    pub user: String,
+    #[serde(default)]
+    pub variables: Vec<PromptVariable>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// A node's reference to a library prompt.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptRef {
+    pub id: String,
+    /// Pinned version; the latest one when unset.
+    #[serde(default)]
+    pub version: Option<u32>,
+    #[serde(default)]
+    pub variables: BTreeMap<String, String>,
+}
+
+/// A prompt with its variables filled in.
+#[derive(Debug, Clone)]
+pub struct RenderedPrompt {
+    pub name: String,
+    pub version: u32,
+    pub system: String,
+    pub user: String,
+}
+
+/// A saved workflow node that references a prompt.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptUsage {
+    pub workflow_id: String,
+    pub workflow_name: String,
+    pub node_id: String,
+    /// Pinned version, or none when the node follows the latest.
+    pub version: Option<u32>,
+}
+
+/// A `{{name}}` placeholder, spaces inside the braces allowed.
+static PLACEHOLDER: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"\{\{\s*([A-Za-z_][\w.]*)\s*\}\}").unwrap());
+
+fn now() -> i64 {
+    chrono::Utc::now().timestamp()
+}
+
+/// Variable names in `text`, in order of first appearance. `{{input}}` and
+/// `{{input.path}}` belong to the node and are left out.
+pub fn placeholders(text: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for caps in PLACEHOLDER.captures_iter(text) {
+        let name = &caps[1];
+        if name != "input" && !name.starts_with("input.") && !names.iter().any(|known| known == name) {
+            names.push(name.to_string());
+        }
+    }
+    names
+}
+
+fn fill(text: &str, values: &BTreeMap<String, String>) -> String {
+    PLACEHOLDER.replace_all(text, |caps: &regex::Captures| match values.get(&caps[1]) {
+        Some(value) => value.clone(),
+        None => caps[0].to_string(),
+    })
+    .into_owned()
+}
+
+fn prompt_from_row(row: &Row) -> rusqlite::Result<Prompt> {
+    let variables: String = row.get(6)?;
+    let tags: String = row.get(7)?;
+    Ok(Prompt {
+        id: row.get(0)?,
+        version: row.get(1)?,
+        name: row.get(2)?,
+        description: row.get(3)?,
+        system: row.get(4)?,
+        user: row.get(5)?,
+        variables: serde_json::from_str(&variables).unwrap_or_default(),
+        tags: serde_json::from_str(&tags).unwrap_or_default(),
+        created_at: row.get(8)?,
+        uses: row.get::<_, i64>(9)? as u64,
+        last_used_at: row.get(10)?,
+    })
+}
+
+/// A version of a prompt, or its latest version, read through `conn`.
+fn read_prompt(conn: &Connection, id: &str, version: Option<u32>) -> Result<Prompt, String> {
+    let found = match version {
+        Some(version) => conn
+            .query_row(
+                &format!("SELECT {} FROM {} WHERE p.id = ?1 AND p.version = ?2", COLUMNS, FROM),
+                params![id, version],
+                prompt_from_row,
+            ),
+        None => conn
+            .query_row(
+                &format!("SELECT {} FROM {} WHERE p.id = ?1 ORDER BY p.version DESC LIMIT 1", COLUMNS, FROM),
+                params![id],
+                prompt_from_row,
+            ),
+    };
+    found.optional()
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| match version {
+            Some(version) => format!("Prompt '{}' has no version {}", id, version),
+            None => format!("Prompt '{}' not found", id),
+        })
+}
+
+pub struct PromptLibrary {
+    conn: Mutex<Connection>,
+}
+
+impl PromptLibrary {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let conn = Connection::open(path).map_err(|e| format!("Failed to open prompt library: {}", e))?;
+        Self::init(conn)
+    }
+
+    #[cfg(test)]
+    pub fn open_in_memory() -> Result<Self, String> {
+        Self::init(Connection::open_in_memory().map_err(|e| e.to_string())?)
+    }
+
+    fn init(conn: Connection) -> Result<Self, String> {
+        conn.execute_batch(SCHEMA)
+            .map_err(|e| format!("Failed to initialise prompt library: {}", e))?;
+        Ok(Self { conn: Mutex::new(conn) })
+    }
+
+    fn conn(&self) -> Result<std::sync::MutexGuard<'_, Connection>, String> {
+        self.conn.lock().map_err(|e| e.to_string())
+    }
+
+    /// A version of a prompt, or its latest version.
+    pub fn get(&self, id: &str, version: Option<u32>) -> Result<Prompt, String> {
+        read_prompt(&*self.conn()?, id, version)
+    }
+
+    /// Latest version of every prompt, optionally only those with `tag` or
+    /// whose name, description or text contains `search`.
+    pub fn list(&self, search: Option<&str>, tag: Option<&str>) -> Result<Vec<PromptSummary>, String> {
+        let conn = self.conn()?;
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT {}, v.versions, v.total_uses FROM {}
+                 JOIN (SELECT p.id, max(p.version) AS latest, count(*) AS versions, coalesce(sum(u.uses), 0) AS total_uses
+                       FROM {} GROUP BY p.id) v ON v.id = p.id AND v.latest = p.version
+                 ORDER BY lower(p.name)",
+                COLUMNS, FROM, FROM
+            ))
+            .map_err(|e| e.to_string())?;
+        let summaries = stmt
+            .query_map([], |row| {
+                Ok(PromptSummary {
+                    prompt: prompt_from_row(row)?,
+                    versions: row.get(11)?,
+                    total_uses: row.get::<_, i64>(12)? as u64,
+                })
+            })
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+
+        let search = search.map(|search| search.trim().to_lowercase()).filter(|search| !search.is_empty());
+        Ok(summaries.into_iter()
+            .filter(|summary| tag.is_none_or(|tag| summary.prompt.tags.iter().any(|t| t.eq_ignore_ascii_case(tag))))
+            .filter(|summary| search.as_deref().is_none_or(|search| {
+                let prompt = &summary.prompt;
+                [&prompt.name, &prompt.description, &prompt.system, &prompt.user]
+                    .iter()
+                    .any(|text| text.to_lowercase().contains(search))
+            }))
+            .collect())
+    }
+
+    /// Every version of a prompt, newest first.
+    pub fn versions(&self, id: &str) -> Result<Vec<Prompt>, String> {
+        let conn = self.conn()?;
+        let mut stmt = conn
+            .prepare(&format!("SELECT {} FROM {} WHERE p.id = ?1 ORDER BY p.version DESC", COLUMNS, FROM))
+            .map_err(|e| e.to_string())?;
+        let versions = stmt
+            .query_map(params![id], prompt_from_row)
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        Ok(versions)
+    }
+
+    /// Store a draft as a new prompt or as the next version of an existing
+    /// one. Variables follow the placeholders in the text, keeping the
+    /// descriptions and defaults given for them. A draft identical to the
+    /// latest version returns that version.
+    pub fn save(&self, draft: PromptDraft) -> Result<Prompt, String> {
+        let name = draft.name.trim();
+        if name.is_empty() {
+            return Err("Prompt needs a name".to_string());
+        }
+        if draft.system.trim().is_empty() && draft.user.trim().is_empty() {
+            return Err("Prompt needs a system or user part".to_string());
+        }
+        let variables: Vec<PromptVariable> = placeholders(&format!("{}\n{}", draft.system, draft.user))
+            .into_iter()
+            .map(|name| {
+                let given = draft.variables.iter().find(|variable| variable.name == name);
+                PromptVariable {
+                    description: given.map(|variable| variable.description.trim().to_string()).unwrap_or_default(),
+                    default: given.and_then(|variable| variable.default.clone()),
+                    name,
+                }
+            })
+            .collect();
+        let mut tags: Vec<String> = Vec::new();
+        for tag in draft.tags.iter().map(|tag| tag.trim()).filter(|tag| !tag.is_empty()) {
+            if !tags.iter().any(|known| known.eq_ignore_ascii_case(tag)) {
+                tags.push(tag.to_string());
+            }
+        }
+
+        // Read the latest version and add the next one under one write lock,
+        // so concurrent saves cannot both take the same version number
+        let mut conn = self.conn()?;
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(|e| e.to_string())?;
+        let (id, version) = match draft.id.as_deref().filter(|id| !id.is_empty()) {
+            Some(id) => {
+                let latest = read_prompt(&tx, id, None)?;
+                let unchanged = latest.name == name
+                    && latest.description == draft.description.trim()
+                    && latest.system == draft.system
+                    && latest.user == draft.user
+                    && latest.variables == variables
+                    && latest.tags == tags;
+                if unchanged {
+                    return Ok(latest);
+                }
+                (id.to_string(), latest.version + 1)
+            }
+            None => (uuid::Uuid::new_v4().to_string(), 1),
+        };
+        tx.execute(
+            "INSERT INTO prompts (id, version, name, description, system, user, variables, tags, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![
+                id,
+                version,
+                name,
+                draft.description.trim(),
+                draft.system,
+                draft.user,
+                serde_json::to_string(&variables).map_err(|e| e.to_string())?,
+                serde_json::to_string(&tags).map_err(|e| e.to_string())?,
+                now(),
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+        let saved = read_prompt(&tx, &id, Some(version))?;
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(saved)
+    }
+
+    /// Remove a prompt with all its versions and stats.
+    pub fn delete(&self, id: &str) -> Result<(), String> {
+        let conn = self.conn()?;
+        conn.execute("DELETE FROM prompt_usage WHERE id = ?1", params![id]).map_err(|e| e.to_string())?;
+        let removed = conn.execute("DELETE FROM prompts WHERE id = ?1", params![id]).map_err(|e| e.to_string())?;
+        if removed == 0 {
+            return Err(format!("Prompt '{}' not found", id));
+        }
+        Ok(())
+    }
+
+    /// Fill in the prompt a node references and count the use.
+    pub fn render(&self, reference: &PromptRef) -> Result<RenderedPrompt, String> {
+        let prompt = self.get(&reference.id, reference.version)?;
+        let mut values = BTreeMap::new();
+        for variable in &prompt.variables {
+            let value = reference.variables.get(&variable.name)
+                .filter(|value| !value.is_empty())
+                .or(variable.default.as_ref())
+                .ok_or_else(|| format!("Prompt '{}' needs a value for {{{{{}}}}}", prompt.name, variable.name))?;
+            values.insert(variable.name.clone(), value.clone());
+        }
+
+        self.conn()?
+            .execute(
+                "INSERT INTO prompt_usage (id, version, uses, last_used_at) VALUES (?1, ?2, 1, ?3)
+                 ON CONFLICT (id, version) DO UPDATE SET uses = uses + 1, last_used_at = ?3",
+                params![prompt.id, prompt.version, now()],
+            )
+            .map_err(|e| e.to_string())?;
+        Ok(RenderedPrompt {
+            system: fill(&prompt.system, &values),
+            user: fill(&prompt.user, &values),
+            name: prompt.name,
+            version: prompt.version,
+        })
+    }
+}
+
+/// Saved workflow nodes that reference prompt `id`.
+pub fn usages(id: &str, workflows: &[SavedWorkflow]) -> Vec<PromptUsage> {
+    workflows.iter()
+        .flat_map(|saved| {
+            saved.workflow.nodes.iter().filter_map(move |node| {
+                let reference = node.kind.llm_config()?.prompt_ref.as_ref().filter(|reference| reference.id == id)?;
+                Some(PromptUsage {
+                    workflow_id: saved.workflow.id.clone(),
+                    workflow_name: saved.workflow.name.clone(),
+                    node_id: node.id.clone(),
+                    version: reference.version,
+                })
+            })
+        })
+        .collect()
+}
+
+#[tauri::command]
+pub async fn list_prompts(
+    search: Option<String>,
+    tag: Option<String>,
+    prompts: State<'_, PromptLibrary>,
+) -> Result<Vec<PromptSummary>, String> {
+    prompts.list(search.as_deref(), tag.as_deref())
+}
+
+#[tauri::command]
+pub async fn get_prompt(id: String, version: Option<u32>, prompts: State<'_, PromptLibrary>) -> Result<Prompt, String> {
+    prompts.get(&id, version)
+}
+
+#[tauri::command]
+pub async fn list_prompt_versions(id: String, prompts: State<'_, PromptLibrary>) -> Result<Vec<Prompt>, String> {
+    prompts.versions(&id)
+}
+
+#[tauri::command]
+pub async fn save_prompt(draft: PromptDraft, prompts: State<'_, PromptLibrary>) -> Result<Prompt, String> {
+    prompts.save(draft)
+}
+
+#[tauri::command]
+pub async fn delete_prompt(id: String, prompts: State<'_, PromptLibrary>) -> Result<(), String> {
+    prompts.delete(&id)
+}
+
+#[tauri::command]
+pub async fn list_prompt_usages(id: String, history: State<'_, RunHistory>) -> Result<Vec<PromptUsage>, String> {
+    Ok(usages(&id, &history.list_workflows(false)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn draft(id: Option<&str>, user: &str) -> PromptDraft {
+        PromptDraft {
+            id: id.map(str::to_string),
+            name: "Summary".to_string(),
+            description: String::new(),
+            system: "You write {{ style }} summaries.".to_string(),
+            user: user.to_string(),
+            variables: vec![PromptVariable {
+                name: "style".to_string(),
+                description: "Tone".to_string(),
+                default: Some("short".to_string()),
+            }],
+            tags: vec!["docs".to_string(), "Docs".to_string()],
+        }
+    }
+
+    #[test]
+    fn versions_and_rendering() {
+        let library = PromptLibrary::open_in_memory().unwrap();
+        let first = library.save(draft(None, "Summarise {{input}} for {{audience}}")).unwrap();
+        assert_eq!(first.version, 1);
+        assert_eq!(first.tags, ["docs"]);
+        let names: Vec<&str> = first.variables.iter().map(|variable| variable.name.as_str()).collect();
+        assert_eq!(names, ["style", "audience"]);
+        assert_eq!(library.save(draft(Some(&first.id), "Summarise {{input}} for {{audience}}")).unwrap().version, 1);
+        assert_eq!(library.save(draft(Some(&first.id), "Summarise {{input}}")).unwrap().version, 2);
+
+        let mut reference = PromptRef { id: first.id.clone(), version: Some(1), variables: BTreeMap::new() };
+        assert!(library.render(&reference).is_err());
+        reference.variables.insert("audience".to_string(), "engineers".to_string());
+        let rendered = library.render(&reference).unwrap();
+        assert_eq!(rendered.system, "You write short summaries.");
+        assert_eq!(rendered.user, "Summarise {{input}} for engineers");
+        assert_eq!(library.get(&first.id, Some(1)).unwrap().uses, 1);
+        assert_eq!(library.get(&first.id, None).unwrap().version, 2);
+    }
+
+    #[test]
+    fn concurrent_saves_take_distinct_versions() {
+        let path = std::env::temp_dir().join(format!("prompts-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let id = PromptLibrary::open(&path).unwrap().save(draft(None, "v1")).unwrap().id;
+        let savers: Vec<_> = (0..4)
+            .map(|thread| {
+                let (path, id) = (path.clone(), id.clone());
+                std::thread::spawn(move || {
+                    let library = PromptLibrary::open(&path).unwrap();
+                    for i in 0..5 {
+                        library.save(draft(Some(&id), &format!("thread {} edit {}", thread, i))).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for saver in savers {
+            saver.join().unwrap();
+        }
+        let versions = PromptLibrary::open(&path).unwrap().versions(&id).unwrap();
+        assert_eq!(versions.len(), 21);
+        assert_eq!(versions[0].version, 21);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,770 @@
+// Chat backends for the LLM servers the app talks to.
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+
+pub mod mock;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ChatMessage {
+    pub role: String,
+    pub content: String,
+    /// Calls requested by the assistant in this turn.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+    /// For `tool` messages, the call this is the result of.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub images: Vec<ImageInput>,
+}
+
+impl ChatMessage {
+    fn new(role: &str, content: String) -> Self {
+        Self {
+            role: role.to_string(),
+            content,
+            tool_calls: Vec::new(),
+            tool_call_id: None,
+            images: Vec::new(),
+        }
+    }
+
+    pub fn with_images(self, images: Vec<ImageInput>) -> Self {
+        Self { images, ..self }
+    }
+
+    pub fn system(content: impl Into<String>) -> Self {
+        Self::new("system", content.into())
+    }
+
+    pub fn user(content: impl Into<String>) -> Self {
+        Self::new("user", content.into())
+    }
+
+    pub fn assistant(content: impl Into<String>, tool_calls: Vec<ToolCall>) -> Self {
+        Self {
+            tool_calls,
+            ..Self::new("assistant", content.into())
+        }
+    }
+
+    pub fn tool(call: &ToolCall, content: impl Into<String>) -> Self {
+        Self {
+            tool_call_id: Some(call.id.clone()),
+            ..Self::new("tool", content.into())
+        }
+    }
+}
+
+/// Largest image accepted, the limit OpenAI sets per image.
+pub const MAX_IMAGE_BYTES: usize = 20 * 1024 * 1024;
+
+/// An image sent along with a message.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ImageInput {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub media_type: String,
+    /// Base64 of the file. A `data:` URL prefix is accepted and dropped.
+    pub data: String,
+    #[serde(default)]
+    pub size: usize,
+}
+
+impl ImageInput {
+    /// Check the image is a PNG, JPEG, GIF or WebP within the size limit.
+    /// The media type and size are taken from the file itself.
+    pub fn checked(self) -> Result<Self, String> {
+        let data = match self.data.split_once(";base64,") {
+            Some((prefix, data)) if prefix.starts_with("data:") => data,
+            _ => self.data.as_str(),
+        };
+        let bytes = BASE64.decode(data.trim()).map_err(|e| format!("Image {} is not valid base64: {}", self.name, e))?;
+        if bytes.len() > MAX_IMAGE_BYTES {
+            return Err(format!(
+                "Image {} is {:.1} MB, over the {} MB limit",
+                self.name,
+                bytes.len() as f64 / (1024.0 * 1024.0),
+                MAX_IMAGE_BYTES / (1024 * 1024)
+            ));
+        }
+        let media_type = image_format(&bytes).ok_or_else(|| format!("Image {} is not a PNG, JPEG, GIF or WebP file", self.name))?;
+        Ok(Self {
+            media_type: media_type.to_string(),
+            data: data.trim().to_string(),
+            size: bytes.len(),
+            ..self
+        })
+    }
+}
+
+/// Media type from the file signature, for the formats vision models accept.
+fn image_format(bytes: &[u8]) -> Option<&'static str> {
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some("image/jpeg")
+    } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+        Some("image/gif")
+    } else if bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        Some("image/webp")
+    } else {
+        None
+    }
+}
+
+/// A function the model may call, described by a JSON schema.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolDefinition {
+    pub name: String,
+    pub description: String,
+    pub parameters: Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolCall {
+    pub id: String,
+    pub name: String,
+    pub arguments: Value,
+}
+
+/// Generation parameters sent along with the messages.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChatOptions {
+    pub temperature: f32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<ToolDefinition>,
+    /// JSON schema the reply must follow: Ollama's `format`, OpenAI's
+    /// `response_format`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response_schema: Option<Value>,
+}
+
+impl ChatOptions {
+    pub fn with_temperature(temperature: f32) -> Self {
+        Self {
+            temperature,
+            ..Default::default()
+        }
+    }
+}
+
+/// Token counts reported by the server, when it reports them.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Usage {
+    pub prompt_tokens: Option<u64>,
+    pub completion_tokens: Option<u64>,
+}
+
+impl Usage {
+    pub fn add(&mut self, other: Usage) {
+        fn sum(a: Option<u64>, b: Option<u64>) -> Option<u64> {
+            match (a, b) {
+                (None, None) => None,
+                _ => Some(a.unwrap_or(0) + b.unwrap_or(0)),
+            }
+        }
+        self.prompt_tokens = sum(self.prompt_tokens, other.prompt_tokens);
+        self.completion_tokens = sum(self.completion_tokens, other.completion_tokens);
+    }
+
+    pub fn total(&self) -> u64 {
+        self.prompt_tokens.unwrap_or(0) + self.completion_tokens.unwrap_or(0)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatReply {
+    pub content: String,
+    #[serde(default)]
+    pub tool_calls: Vec<ToolCall>,
+    pub usage: Usage,
+    /// Served from the response cache rather than the server.
+    #[serde(default)]
+    pub cached: bool,
+}
+
+/// The API flavour spoken by a server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Provider {
+    Ollama,
+    /// OpenAI-compatible servers such as LM Studio.
+    OpenAi,
+    /// The built-in mock, answering from a fixture file.
+    Mock,
+}
+
+impl Provider {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Provider::Ollama => "ollama",
+            Provider::OpenAi => "openai",
+            Provider::Mock => "mock",
+        }
+    }
+
+    pub fn detect(server_url: &str) -> Self {
+        if server_url.starts_with(mock::SCHEME) {
+            Provider::Mock
+        } else if server_url.contains("11434") {
+            Provider::Ollama
+        } else {
+            Provider::OpenAi
+        }
+    }
+}
+
+/// Receives the text of a reply as it arrives.
+pub type ChunkSender = tokio::sync::mpsc::UnboundedSender<String>;
+
+/// Send a non-streaming chat request to whichever server `server_url` points at.
+pub async fn chat(server_url: &str, model: &str, messages: &[ChatMessage], options: &ChatOptions) -> Result<ChatReply, String> {
+    chat_streaming(server_url, model, messages, options, None).await
+}
+
+/// Like `chat`, also passing the reply's text to `chunks` as it arrives. The
+/// mock sends its scripted chunks one by one; real servers are still asked
+/// for whole replies, which arrive as a single chunk.
+pub async fn chat_streaming(
+    server_url: &str,
+    model: &str,
+    messages: &[ChatMessage],
+    options: &ChatOptions,
+    chunks: Option<&ChunkSender>,
+) -> Result<ChatReply, String> {
+    let reply = match Provider::detect(server_url) {
+        Provider::Ollama => ollama_chat(server_url, model, messages, options).await?,
+        Provider::OpenAi => lmstudio_chat(server_url, model, messages, options).await?,
+        Provider::Mock => return mock::chat(server_url, model, messages, options, chunks).await,
+    };
+    if let Some(chunks) = chunks.filter(|_| !reply.content.is_empty()) {
+        let _ = chunks.send(reply.content.clone());
+    }
+    Ok(reply)
+}
+
+/// Messages as the server expects them. The two APIs differ in how tool
+/// calls are spelled: OpenAI wants ids and string-encoded arguments.
+fn wire_messages(provider: Provider, messages: &[ChatMessage]) -> Vec<Value> {
+    messages.iter()
+        .map(|message| {
+            let mut wire = json!({ "role": message.role, "content": message.content });
+            if !message.tool_calls.is_empty() {
+                wire["tool_calls"] = message.tool_calls.iter()
+                    .map(|call| match provider {
+                        Provider::Ollama => json!({
+                            "function": { "name": call.name, "arguments": call.arguments }
+                        }),
+                        Provider::OpenAi | Provider::Mock => json!({
+                            "id": call.id,
+                            "type": "function",
+                            "function": { "name": call.name, "arguments": call.arguments.to_string() }
+                        }),
+                    })
+                    .collect();
+            }
+            if let (Provider::OpenAi | Provider::Mock, Some(id)) = (provider, &message.tool_call_id) {
+                wire["tool_call_id"] = json!(id);
+            }
+            if !message.images.is_empty() {
+                match provider {
+                    Provider::Ollama => {
+                        wire["images"] = message.images.iter().map(|image| json!(image.data)).collect();
+                    }
+                    Provider::OpenAi | Provider::Mock => {
+                        let mut parts = vec![json!({ "type": "text", "text": message.content })];
+                        parts.extend(message.images.iter().map(|image| json!({
+                            "type": "image_url",
+                            "image_url": { "url": format!("data:{};base64,{}", image.media_type, image.data) }
+                        })));
+                        wire["content"] = Value::Array(parts);
+                    }
+                }
+            }
+            wire
+        })
+        .collect()
+}
+
+fn request_body(provider: Provider, model: &str, messages: &[ChatMessage], options: &ChatOptions) -> Value {
+    let mut body = json!({
+        "model": model,
+        "messages": wire_messages(provider, messages),
+        "temperature": options.temperature,
+        "stream": false
+    });
+    if !options.tools.is_empty() {
+        body["tools"] = options.tools.iter()
+            .map(|tool| json!({
+                "type": "function",
+                "function": {
+                    "name": tool.name,
+                    "description": tool.description,
+                    "parameters": tool.parameters,
+                }
+            }))
+            .collect();
+    }
+    if let Some(schema) = &options.response_schema {
+        match provider {
+            Provider::Ollama => body["format"] = schema.clone(),
+            Provider::OpenAi | Provider::Mock => body["response_format"] = json!({
+                "type": "json_schema",
+                "json_schema": { "name": "response", "schema": schema, "strict": true }
+            }),
+        }
+    }
+    body
+}
+
+/// Read the reply text and any tool calls out of a response `message` object.
+fn parse_message(message: &Value) -> Result<(String, Vec<ToolCall>), String> {
+    let tool_calls: Vec<ToolCall> = message["tool_calls"].as_array()
+        .map(|calls| calls.iter()
+            .enumerate()
+            .filter_map(|(i, call)| {
+                let function = &call["function"];
+                let arguments = match &function["arguments"] {
+                    Value::String(raw) => serde_json::from_str(raw).unwrap_or_else(|_| json!({})),
+                    Value::Null => json!({}),
+                    other => other.clone(),
+                };
+                Some(ToolCall {
+                    id: call["id"].as_str().map(str::to_string).unwrap_or_else(|| format!("call_{}", i)),
+                    name: function["name"].as_str()?.to_string(),
+                    arguments,
+                })
+            })
+            .collect())
+        .unwrap_or_default();
+
+    let content = match message["content"].as_str() {
+        Some(content) => content.to_string(),
+        None if !tool_calls.is_empty() => String::new(),
+        None => return Err("Invalid response format".to_string()),
+    };
+    Ok((content, tool_calls))
+}
+
+async fn ollama_chat(server_url: &str, model: &str, messages: &[ChatMessage], options: &ChatOptions) -> Result<ChatReply, String> {
+    let client = Client::new();
+    let url = format!("{}/api/chat", server_url.trim_end_matches('/'));
+
+    let response = client.post(&url)
+        .json(&request_body(Provider::Ollama, model, messages, options))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if response.status().is_success() {
+        let result: Value = response.json().await.map_err(|e| e.to_string())?;
+        let (content, tool_calls) = parse_message(&result["message"])?;
+        Ok(ChatReply {
+            content,
+            tool_calls,
+            usage: Usage {
+                prompt_tokens: result["prompt_eval_count"].as_u64(),
+                completion_tokens: result["eval_count"].as_u64(),
+            },
+            cached: false,
+        })
+    } else {
+        Err(format!("Request failed: {}", response.status()))
+    }
+}
+
+async fn lmstudio_chat(server_url: &str, model: &str, messages: &[ChatMessage], options: &ChatOptions) -> Result<ChatReply, String> {
+    let client = Client::new();
+    let url = format!("{}/chat/completions", server_url.trim_end_matches('/'));
+
+    let response = client.post(&url)
+        .json(&request_body(Provider::OpenAi, model, messages, options))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if response.status().is_success() {
+        let result: Value = response.json().await.map_err(|e| e.to_string())?;
+        let (content, tool_calls) = parse_message(&result["choices"][0]["message"])?;
+        Ok(ChatReply {
+            content,
+            tool_calls,
+            usage: Usage {
+                prompt_tokens: result["usage"]["prompt_tokens"].as_u64(),
+                completion_tokens: result["usage"]["completion_tokens"].as_u64(),
+            },
+            cached: false,
+        })
+    } else {
+        Err(format!("Request failed: {}", response.status()))
+    }
+}
+
+/// What a server's model metadata says about a model. Fields the server
+/// does not report are `None`.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ModelInfo {
+    pub context_length: Option<usize>,
+    /// Whether the model accepts images.
+    pub vision: Option<bool>,
+}
+
+/// Metadata already fetched, by server and model.
+static MODEL_INFO: LazyLock<Mutex<HashMap<(String, String), ModelInfo>>> = LazyLock::new(Default::default);
+
+/// Metadata of `model` from the server, fetched once per server and model.
+pub async fn model_info(server_url: &str, model: &str) -> Result<ModelInfo, String> {
+    let key = (server_url.to_string(), model.to_string());
+    if let Some(info) = MODEL_INFO.lock().ok().and_then(|cache| cache.get(&key).copied()) {
+        return Ok(info);
+    }
+    let mut info = match Provider::detect(server_url) {
+        Provider::Ollama => ollama_model_info(server_url, model).await?,
+        Provider::OpenAi => openai_model_info(server_url, model).await?,
+        Provider::Mock => mock::model_info(server_url)?,
+    };
+    if info.vision.is_none() && looks_like_vision_model(model) {
+        info.vision = Some(true);
+    }
+    if let Ok(mut cache) = MODEL_INFO.lock() {
+        cache.insert(key, info);
+    }
+    Ok(info)
+}
+
+/// Names of well-known vision model families, for servers that do not say.
+fn looks_like_vision_model(model: &str) -> bool {
+    let model = model.to_lowercase();
+    ["llava", "vision", "-vl", "vl-", "moondream", "minicpm-v", "pixtral", "gpt-4o", "gpt-4.1", "gemma3", "qwen2.5vl"]
+        .iter()
+        .any(|family| model.contains(family))
+}
+
+async fn ollama_model_info(server_url: &str, model: &str) -> Result<ModelInfo, String> {
+    let response = Client::new().post(format!("{}/api/show", server_url.trim_end_matches('/')))
+        .json(&json!({ "model": model }))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("Model info request failed: {}", response.status()));
+    }
+    let info: Value = response.json().await.map_err(|e| e.to_string())?;
+
+    // A `num_ctx` set in the Modelfile is what the server actually runs with
+    let configured = info["parameters"].as_str().and_then(|parameters| {
+        parameters.lines()
+            .filter_map(|line| line.trim().strip_prefix("num_ctx"))
+            .find_map(|value| value.trim().parse().ok())
+    });
+    let trained = info["model_info"].as_object().and_then(|fields| {
+        fields.iter()
+            .find(|(key, _)| key.ends_with(".context_length"))
+            .and_then(|(_, value)| value.as_u64())
+            .map(|length| length as usize)
+    });
+    // Older servers list no capabilities, but a vision model has a projector
+    let vision = match info["capabilities"].as_array() {
+        Some(capabilities) => capabilities.iter().any(|capability| capability == "vision"),
+        None => info["projector_info"].is_object()
+            || info["details"]["families"].as_array().is_some_and(|families| {
+                families.iter().any(|family| family == "clip" || family == "mllama")
+            }),
+    };
+    Ok(ModelInfo {
+        context_length: configured.or(trained),
+        vision: Some(vision),
+    })
+}
+
+async fn openai_model_info(server_url: &str, model: &str) -> Result<ModelInfo, String> {
+    let client = Client::new();
+    let base = server_url.trim_end_matches('/');
+
+    // LM Studio's own API knows the length the model was loaded with
+    let native = format!("{}/api/v0/models/{}", base.trim_end_matches("/v1"), model);
+    if let Ok(response) = client.get(&native).send().await {
+        if response.status().is_success() {
+            let info: Value = response.json().await.unwrap_or_default();
+            if let Some(kind) = info["type"].as_str() {
+                return Ok(ModelInfo {
+                    context_length: info["loaded_context_length"].as_u64()
+                        .or(info["max_context_length"].as_u64())
+                        .map(|length| length as usize),
+                    vision: Some(kind == "vlm"),
+                });
+            }
+        }
+    }
+
+    let response = client.get(format!("{}/models", base)).send().await.map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("Model list request failed: {}", response.status()));
+    }
+    let list: Value = response.json().await.map_err(|e| e.to_string())?;
+    let Some(entry) = list["data"].as_array().and_then(|models| models.iter().find(|entry| entry["id"] == model)) else {
+        return Ok(ModelInfo::default());
+    };
+    Ok(ModelInfo {
+        context_length: ["context_length", "max_context_length", "max_model_len", "context_window"]
+            .iter()
+            .find_map(|field| entry[field].as_u64())
+            .map(|length| length as usize),
+        vision: entry["architecture"]["input_modalities"]
+            .as_array()
+            .map(|modalities| modalities.iter().any(|modality| modality == "image")),
+    })
+}
+
+/// Texts sent per embedding request.
+const EMBED_BATCH_SIZE: usize = 64;
+
+/// Vectors for a list of texts, in input order and scaled to unit length so
+/// a dot product is their cosine similarity.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Embeddings {
+    pub model: String,
+    pub dimension: usize,
+    pub vectors: Vec<Vec<f32>>,
+    pub prompt_tokens: Option<u64>,
+}
+
+/// Embed `inputs` with `model`, in batches, on whichever server `server_url`
+/// points at.
+pub async fn embed(server_url: &str, model: &str, inputs: &[String]) -> Result<Embeddings, String> {
+    embed_with(Provider::detect(server_url), server_url, model, inputs).await
+}
+
+async fn embed_with(provider: Provider, server_url: &str, model: &str, inputs: &[String]) -> Result<Embeddings, String> {
+    let client = Client::new();
+    let mut embeddings = Embeddings {
+        model: model.to_string(),
+        dimension: 0,
+        vectors: Vec::with_capacity(inputs.len()),
+        prompt_tokens: None,
+    };
+    for batch in inputs.chunks(EMBED_BATCH_SIZE) {
+        let (vectors, prompt_tokens) = match provider {
+            Provider::Ollama => ollama_embed(&client, server_url, model, batch).await?,
+            Provider::OpenAi => openai_embed(&client, server_url, model, batch).await?,
+            Provider::Mock => (batch.iter().map(|text| mock::embedding(text)).collect(), None),
+        };
+        if vectors.len() != batch.len() {
+            return Err(format!("Server returned {} embeddings for {} inputs", vectors.len(), batch.len()));
+        }
+        for mut vector in vectors {
+            if embeddings.dimension == 0 {
+                embeddings.dimension = vector.len();
+            } else if vector.len() != embeddings.dimension {
+                return Err(format!("Embedding dimension changed from {} to {}", embeddings.dimension, vector.len()));
+            }
+            normalize(&mut vector);
+            embeddings.vectors.push(vector);
+        }
+        if let Some(tokens) = prompt_tokens {
+            embeddings.prompt_tokens = Some(embeddings.prompt_tokens.unwrap_or(0) + tokens);
+        }
+    }
+    Ok(embeddings)
+}
+
+/// Scale a vector to unit length. Zero vectors are left alone.
+pub fn normalize(vector: &mut [f32]) {
+    let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|x| *x /= norm);
+    }
+}
+
+/// Cosine similarity of two normalized vectors.
+pub fn similarity(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+fn parse_vector(value: &Value) -> Option<Vec<f32>> {
+    value.as_array()?.iter().map(|x| x.as_f64().map(|x| x as f32)).collect()
+}
+
+async fn ollama_embed(client: &Client, server_url: &str, model: &str, batch: &[String]) -> Result<(Vec<Vec<f32>>, Option<u64>), String> {
+    let url = format!("{}/api/embed", server_url.trim_end_matches('/'));
+    let response = client.post(&url)
+        .json(&json!({ "model": model, "input": batch }))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("Embedding request failed: {}", response.status()));
+    }
+
+    let result: Value = response.json().await.map_err(|e| e.to_string())?;
+    let vectors = result["embeddings"].as_array()
+        .and_then(|vectors| vectors.iter().map(parse_vector).collect::<Option<Vec<_>>>())
+        .ok_or("Invalid embedding response format")?;
+    Ok((vectors, result["prompt_eval_count"].as_u64()))
+}
+
+async fn openai_embed(client: &Client, server_url: &str, model: &str, batch: &[String]) -> Result<(Vec<Vec<f32>>, Option<u64>), String> {
+    let url = format!("{}/embeddings", server_url.trim_end_matches('/'));
+    let response = client.post(&url)
+        .json(&json!({ "model": model, "input": batch, "encoding_format": "float" }))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("Embedding request failed: {}", response.status()));
+    }
+
+    let result: Value = response.json().await.map_err(|e| e.to_string())?;
+    let mut data: Vec<(u64, Vec<f32>)> = result["data"].as_array()
+        .and_then(|data| data.iter()
+            .enumerate()
+            .map(|(i, item)| Some((item["index"].as_u64().unwrap_or(i as u64), parse_vector(&item["embedding"])?)))
+            .collect())
+        .ok_or("Invalid embedding response format")?;
+    data.sort_by_key(|(index, _)| *index);
+    Ok((data.into_iter().map(|(_, vector)| vector).collect(), result["usage"]["prompt_tokens"].as_u64()))
+}
+
+/// Relevance of each document to `query` from a cross-encoder behind a
+/// `/rerank` endpoint (llama.cpp, Jina, Cohere or Text Embeddings Inference),
+/// in document order.
+pub async fn rerank(server_url: &str, model: &str, query: &str, documents: &[String]) -> Result<Vec<f32>, String> {
+    let url = format!("{}/rerank", server_url.trim_end_matches('/'));
+    let response = Client::new().post(&url)
+        .json(&json!({
+            "model": model,
+            "query": query,
+            "documents": documents,
+            "texts": documents,
+            "top_n": documents.len(),
+        }))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("Rerank request failed: {}", response.status()));
+    }
+
+    let result: Value = response.json().await.map_err(|e| e.to_string())?;
+    let results = result["results"].as_array().or(result.as_array()).ok_or("Invalid rerank response format")?;
+    let mut scores = vec![None; documents.len()];
+    for item in results {
+        let index = item["index"].as_u64().ok_or("Rerank result without an index")? as usize;
+        let score = item["relevance_score"].as_f64().or(item["score"].as_f64()).ok_or("Rerank result without a score")?;
+        if let Some(slot) = scores.get_mut(index) {
+            *slot = Some(score as f32);
+        }
+    }
+    scores.into_iter()
+        .collect::<Option<Vec<f32>>>()
+        .ok_or_else(|| "Rerank response is missing documents".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mock::{Fixture, MockLlmServer};
+
+    fn texts(count: usize) -> Vec<String> {
+        (0..count).map(|i| format!("document {} about cats", i)).collect()
+    }
+
+    /// The URL `provider` is reached at on `server`.
+    fn url(server: &MockLlmServer, provider: Provider) -> String {
+        match provider {
+            Provider::Ollama => server.base_url.trim_end_matches("/v1").to_string(),
+            _ => server.base_url.clone(),
+        }
+    }
+
+    #[tokio::test]
+    async fn embeds_in_normalized_batches() {
+        for provider in [Provider::Ollama, Provider::OpenAi] {
+            let server = MockLlmServer::start().await.unwrap();
+            let inputs = texts(130);
+            let embeddings = embed_with(provider, &url(&server, provider), "m", &inputs).await.unwrap();
+            assert_eq!((embeddings.dimension, embeddings.vectors.len()), (64, 130), "{:?}", provider);
+            for vector in &embeddings.vectors {
+                assert!((similarity(vector, vector) - 1.0).abs() < 1e-5, "{:?}", provider);
+            }
+            let batches: Vec<usize> = server.embed_requests().iter()
+                .map(|body| body["input"].as_array().unwrap().len())
+                .collect();
+            assert_eq!(batches, [64, 64, 2], "{:?}", provider);
+            assert_eq!(server.embed_requests()[2]["input"][1], inputs[129]);
+        }
+    }
+
+    #[tokio::test]
+    async fn dimension_changes_are_errors() {
+        for provider in [Provider::Ollama, Provider::OpenAi] {
+            let fixture: Fixture = serde_json::from_value(json!({"embedding_dimensions": [64, 32]})).unwrap();
+            let server = MockLlmServer::with_fixture(fixture).await.unwrap();
+            let error = embed_with(provider, &url(&server, provider), "m", &texts(65)).await.unwrap_err();
+            assert_eq!(error, "Embedding dimension changed from 64 to 32", "{:?}", provider);
+        }
+    }
+
+    #[test]
+    fn zero_vectors_stay_zero() {
+        let mut vector = vec![0.0; 3];
+        normalize(&mut vector);
+        assert_eq!(vector, [0.0; 3]);
+        let mut vector = vec![3.0, 4.0];
+        normalize(&mut vector);
+        assert_eq!(vector, [0.6, 0.8]);
+    }
+
+    fn image(name: &str, bytes: &[u8]) -> ImageInput {
+        ImageInput { name: name.into(), media_type: String::new(), data: BASE64.encode(bytes), size: 0 }
+    }
+
+    #[test]
+    fn images_are_checked_by_signature_and_size() {
+        let png = b"\x89PNG\r\n\x1a\nrest of the file";
+        let checked = image("a.png", png).checked().unwrap();
+        assert_eq!((checked.media_type.as_str(), checked.size), ("image/png", png.len()));
+
+        let data_url = ImageInput {
+            data: format!("data:image/jpeg;base64,{}", BASE64.encode(b"GIF89a...")),
+            ..image("b.gif", b"")
+        };
+        let checked = data_url.checked().unwrap();
+        assert_eq!(checked.media_type, "image/gif");
+        assert_eq!(checked.data, BASE64.encode(b"GIF89a..."));
+        assert_eq!(image("c.webp", b"RIFF\0\0\0\0WEBPVP8 ").checked().unwrap().media_type, "image/webp");
+
+        assert_eq!(image("notes.txt", b"just text").checked().unwrap_err(), "Image notes.txt is not a PNG, JPEG, GIF or WebP file");
+        let bad = ImageInput { data: "not base64!".into(), ..image("x.png", b"") };
+        assert!(bad.checked().unwrap_err().starts_with("Image x.png is not valid base64: "));
+        let mut huge = vec![0u8; MAX_IMAGE_BYTES + 1];
+        huge[..3].copy_from_slice(&[0xFF, 0xD8, 0xFF]);
+        assert_eq!(image("big.jpg", &huge).checked().unwrap_err(), "Image big.jpg is 20.0 MB, over the 20 MB limit");
+    }
+
+    #[test]
+    fn images_are_wired_per_provider() {
+        let png = image("a.png", b"\x89PNG\r\n\x1a\n").checked().unwrap();
+        let messages = [
+            ChatMessage::system("Describe images."),
+            ChatMessage::user("What is this?").with_images(vec![png.clone()]),
+        ];
+
+        let ollama = wire_messages(Provider::Ollama, &messages);
+        assert_eq!(ollama[0], json!({"role": "system", "content": "Describe images."}));
+        assert_eq!(ollama[1], json!({"role": "user", "content": "What is this?", "images": [png.data]}));
+
+        let openai = wire_messages(Provider::OpenAi, &messages);
+        assert_eq!(openai[0], json!({"role": "system", "content": "Describe images."}));
+        assert_eq!(openai[1], json!({"role": "user", "content": [
+            {"type": "text", "text": "What is this?"},
+            {"type": "image_url", "image_url": {"url": format!("data:image/png;base64,{}", png.data)}}
+        ]}));
+    }
+}
@@ -0,0 +1,666 @@
+// Scripted stand-in for an LLM server, for running workflows offline. It is
+// reachable in-process through `mock://` server URLs and over HTTP, where it
+// speaks both the Ollama and the OpenAI-compatible API.
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, SystemTime};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use super::{ChatMessage, ChatOptions, ChatReply, ChunkSender, ModelInfo, Provider, ToolCall, Usage};
+
+/// Prefix of the server URLs answered by the mock. The rest of the URL is
+/// the path of a fixture file; without one every request is echoed.
+pub const SCHEME: &str = "mock://";
+/// Model offered when the fixture lists none.
+pub const MOCK_MODEL: &str = "mock";
+/// Dimension of the mock's embeddings.
+const EMBEDDING_DIMENSION: usize = 64;
+
+/// Scripted replies, read from a JSON fixture file.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Fixture {
+    /// Models the mock offers, `mock` when empty.
+    #[serde(default)]
+    pub models: Vec<String>,
+    #[serde(default)]
+    pub context_length: Option<usize>,
+    #[serde(default)]
+    pub vision: Option<bool>,
+    /// Tried in order; a request nothing answers is echoed back.
+    #[serde(default)]
+    pub responses: Vec<MockResponse>,
+    /// Dimension of the vectors in successive embedding requests to the
+    /// HTTP server, the last one repeating; 64 when empty.
+    #[serde(default)]
+    pub embedding_dimensions: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MockResponse {
+    /// Regex the last user message must match; any request when unset.
+    #[serde(default, rename = "match")]
+    pub pattern: Option<String>,
+    /// Only answer requests for this model.
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Number of requests answered before the next response takes over;
+    /// unlimited when unset.
+    #[serde(default)]
+    pub times: Option<usize>,
+    /// Reply text, when it is not given in chunks.
+    #[serde(default)]
+    pub content: Option<String>,
+    /// Pieces the reply is streamed in, joined when it is not streamed.
+    #[serde(default)]
+    pub chunks: Vec<String>,
+    #[serde(default)]
+    pub tool_calls: Vec<MockToolCall>,
+    /// Wait before answering.
+    #[serde(default)]
+    pub delay_ms: u64,
+    /// Wait between streamed chunks.
+    #[serde(default)]
+    pub chunk_delay_ms: u64,
+    /// Fail the request with this message.
+    #[serde(default)]
+    pub error: Option<String>,
+    /// HTTP status of the failure, 500 when unset.
+    #[serde(default)]
+    pub status: Option<u16>,
+    /// Token counts to report; words are counted when unset.
+    #[serde(default)]
+    pub usage: Option<Usage>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MockToolCall {
+    #[serde(default)]
+    pub id: Option<String>,
+    pub name: String,
+    #[serde(default)]
+    pub arguments: Value,
+}
+
+impl Fixture {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read mock fixture {}: {}", path.display(), e))?;
+        let fixture: Fixture = serde_json::from_str(&text)
+            .map_err(|e| format!("Invalid mock fixture {}: {}", path.display(), e))?;
+        for response in &fixture.responses {
+            if let Some(pattern) = &response.pattern {
+                regex::Regex::new(pattern).map_err(|e| format!("Invalid match pattern '{}': {}", pattern, e))?;
+            }
+        }
+        Ok(fixture)
+    }
+
+    pub fn models(&self) -> Vec<String> {
+        if self.models.is_empty() {
+            vec![MOCK_MODEL.to_string()]
+        } else {
+            self.models.clone()
+        }
+    }
+}
+
+/// How the mock answers one chat request.
+struct Reply {
+    chunks: Vec<String>,
+    tool_calls: Vec<ToolCall>,
+    usage: Usage,
+    delay: Duration,
+    chunk_delay: Duration,
+    error: Option<(u16, String)>,
+}
+
+impl Reply {
+    fn content(&self) -> String {
+        self.chunks.concat()
+    }
+}
+
+/// A fixture and how many requests each of its responses has answered.
+#[derive(Default)]
+struct Script {
+    fixture: Fixture,
+    used: Vec<usize>,
+    /// Request bodies, recorded by the HTTP server only.
+    #[cfg(test)]
+    requests: Vec<Value>,
+    #[cfg(test)]
+    embed_requests: Vec<Value>,
+    embeds: usize,
+    modified: Option<SystemTime>,
+}
+
+impl Script {
+    fn new(fixture: Fixture) -> Self {
+        Self {
+            used: vec![0; fixture.responses.len()],
+            fixture,
+            ..Default::default()
+        }
+    }
+
+    /// Dimension of the vectors answering an embedding request to `path`,
+    /// or `None` for other paths.
+    fn embedding_dimension(&mut self, path: &str) -> Option<usize> {
+        if !matches!(path, "/api/embed" | "/v1/embeddings" | "/embeddings") {
+            return None;
+        }
+        let dimensions = &self.fixture.embedding_dimensions;
+        let dimension = dimensions.get(self.embeds).or(dimensions.last()).copied().unwrap_or(EMBEDDING_DIMENSION);
+        self.embeds += 1;
+        Some(dimension)
+    }
+
+    /// Pick the reply to an OpenAI-style request body.
+    fn reply(&mut self, body: &Value) -> Reply {
+        let messages = body["messages"].as_array().cloned().unwrap_or_default();
+        let last_user = messages.iter().rev()
+            .find(|message| message["role"] == "user")
+            .map(message_text)
+            .unwrap_or_default();
+        let prompt_tokens = messages.iter().map(|message| word_count(&message_text(message))).sum();
+
+        let chosen = self.fixture.responses.iter().enumerate().position(|(i, response)| {
+            response.times.is_none_or(|times| self.used[i] < times)
+                && response.model.as_ref().is_none_or(|model| body["model"] == model.as_str())
+                && response.pattern.as_ref().is_none_or(|pattern| {
+                    regex::Regex::new(pattern).is_ok_and(|re| re.is_match(&last_user))
+                })
+        });
+        let Some(i) = chosen else {
+            let completion_tokens = word_count(&last_user);
+            return Reply {
+                chunks: vec![last_user],
+                tool_calls: Vec::new(),
+                usage: Usage { prompt_tokens: Some(prompt_tokens), completion_tokens: Some(completion_tokens) },
+                delay: Duration::ZERO,
+                chunk_delay: Duration::ZERO,
+                error: None,
+            };
+        };
+        self.used[i] += 1;
+
+        let response = &self.fixture.responses[i];
+        let chunks = if response.chunks.is_empty() {
+            vec![response.content.clone().unwrap_or_default()]
+        } else {
+            response.chunks.clone()
+        };
+        let completion_tokens = word_count(&chunks.concat());
+        Reply {
+            chunks,
+            tool_calls: response.tool_calls.iter()
+                .enumerate()
+                .map(|(n, call)| ToolCall {
+                    id: call.id.clone().unwrap_or_else(|| format!("call_{}", n)),
+                    name: call.name.clone(),
+                    arguments: if call.arguments.is_null() { json!({}) } else { call.arguments.clone() },
+                })
+                .collect(),
+            usage: response.usage.unwrap_or(Usage {
+                prompt_tokens: Some(prompt_tokens),
+                completion_tokens: Some(completion_tokens),
+            }),
+            delay: Duration::from_millis(response.delay_ms),
+            chunk_delay: Duration::from_millis(response.chunk_delay_ms),
+            error: response.error.clone().map(|message| (response.status.unwrap_or(500), message)),
+        }
+    }
+}
+
+fn message_text(message: &Value) -> String {
+    match &message["content"] {
+        Value::String(text) => text.clone(),
+        Value::Array(parts) => parts.iter().filter_map(|part| part["text"].as_str()).collect::<Vec<_>>().join("\n"),
+        _ => String::new(),
+    }
+}
+
+fn word_count(text: &str) -> u64 {
+    text.split_whitespace().count() as u64
+}
+
+/// Scripts of the `mock://` servers, by fixture path.
+static SCRIPTS: LazyLock<Mutex<HashMap<String, Script>>> = LazyLock::new(Default::default);
+
+/// Run `f` on the script behind a `mock://` URL, loading the fixture on
+/// first use and again whenever the file changes.
+fn with_script<T>(server_url: &str, f: impl FnOnce(&mut Script) -> T) -> Result<T, String> {
+    let path = server_url.strip_prefix(SCHEME).unwrap_or_default().trim_end_matches('/');
+    let mut scripts = SCRIPTS.lock().map_err(|e| e.to_string())?;
+    let modified = if path.is_empty() {
+        None
+    } else {
+        Some(std::fs::metadata(path).and_then(|meta| meta.modified())
+            .map_err(|e| format!("Failed to read mock fixture {}: {}", path, e))?)
+    };
+    let stale = scripts.get(path).is_none_or(|script| script.modified != modified);
+    if stale {
+        let fixture = if path.is_empty() { Fixture::default() } else { Fixture::load(Path::new(path))? };
+        scripts.insert(path.to_string(), Script { modified, ..Script::new(fixture) });
+    }
+    Ok(f(scripts.get_mut(path).expect("script was just loaded")))
+}
+
+/// Answer a chat request from the fixture behind `server_url`, sending each
+/// chunk to `chunks` as its delay runs out.
+pub(super) async fn chat(
+    server_url: &str,
+    model: &str,
+    messages: &[ChatMessage],
+    options: &ChatOptions,
+    chunks: Option<&ChunkSender>,
+) -> Result<ChatReply, String> {
+    let body = super::request_body(Provider::OpenAi, model, messages, options);
+    let reply = with_script(server_url, |script| script.reply(&body))?;
+    tokio::time::sleep(reply.delay).await;
+    if let Some((status, message)) = &reply.error {
+        return Err(format!("Request failed: {} {}", status, message));
+    }
+    for (i, chunk) in reply.chunks.iter().enumerate() {
+        if i > 0 {
+            tokio::time::sleep(reply.chunk_delay).await;
+        }
+        if let Some(chunks) = chunks.filter(|_| !chunk.is_empty()) {
+            let _ = chunks.send(chunk.clone());
+        }
+    }
+    Ok(ChatReply {
+        content: reply.content(),
+        tool_calls: reply.tool_calls,
+        usage: reply.usage,
+        cached: false,
+    })
+}
+
+pub fn models(server_url: &str) -> Result<Vec<String>, String> {
+    with_script(server_url, |script| script.fixture.models())
+}
+
+pub(super) fn model_info(server_url: &str) -> Result<ModelInfo, String> {
+    with_script(server_url, |script| ModelInfo {
+        context_length: script.fixture.context_length,
+        vision: script.fixture.vision,
+    })
+}
+
+/// Hashed bag of words: texts sharing words get similar vectors.
+pub(super) fn embedding(text: &str) -> Vec<f32> {
+    embedding_of(text, EMBEDDING_DIMENSION)
+}
+
+fn embedding_of(text: &str, dimension: usize) -> Vec<f32> {
+    let mut vector = vec![0.0f32; dimension];
+    for word in text.split(|c: char| !c.is_alphanumeric()).filter(|word| !word.is_empty()) {
+        let hash = word.to_lowercase().bytes().fold(0xcbf29ce484222325u64, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3));
+        vector[(hash % dimension as u64) as usize] += 1.0;
+    }
+    vector
+}
+
+pub struct MockLlmServer {
+    /// OpenAI-style base URL, ending in `/v1`.
+    pub base_url: String,
+    script: Arc<Mutex<Script>>,
+    handle: tokio::task::JoinHandle<()>,
+}
+
+impl MockLlmServer {
+    /// Echo every request, on a free local port until dropped.
+    #[cfg(test)]
+    pub async fn start() -> Result<Self, String> {
+        Self::with_fixture(Fixture::default()).await
+    }
+
+    /// Answer from `fixture`, on a free local port until dropped.
+    pub async fn with_fixture(fixture: Fixture) -> Result<Self, String> {
+        let listener = TcpListener::bind("127.0.0.1:0").await.map_err(|e| e.to_string())?;
+        let base_url = format!("http://{}/v1", listener.local_addr().map_err(|e| e.to_string())?);
+        let script = Arc::new(Mutex::new(Script::new(fixture)));
+        let served = script.clone();
+        let handle = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let script = served.clone();
+                tokio::spawn(async move {
+                    let _ = respond(stream, &script).await;
+                });
+            }
+        });
+        Ok(Self { base_url, script, handle })
+    }
+
+    /// Bodies of the chat requests received so far.
+    #[cfg(test)]
+    pub fn requests(&self) -> Vec<Value> {
+        self.script.lock().map(|script| script.requests.clone()).unwrap_or_default()
+    }
+
+    /// Bodies of the embedding requests received so far.
+    #[cfg(test)]
+    pub fn embed_requests(&self) -> Vec<Value> {
+        self.script.lock().map(|script| script.embed_requests.clone()).unwrap_or_default()
+    }
+}
+
+impl Drop for MockLlmServer {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+async fn read_request(stream: &mut TcpStream) -> std::io::Result<(String, Value)> {
+    let mut request = Vec::new();
+    let mut buffer = [0u8; 8192];
+    loop {
+        if let Some(end) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+            let head = String::from_utf8_lossy(&request[..end]).to_string();
+            let length = head.lines()
+                .find_map(|line| line.split_once(':').filter(|(name, _)| name.eq_ignore_ascii_case("content-length")))
+                .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+                .unwrap_or(0);
+            if request.len() >= end + 4 + length {
+                let body = serde_json::from_slice(&request[end + 4..end + 4 + length]).unwrap_or(Value::Null);
+                let path = head.split_whitespace().nth(1).unwrap_or("/").to_string();
+                return Ok((path, body));
+            }
+        }
+        let read = stream.read(&mut buffer).await?;
+        if read == 0 {
+            return Ok((String::new(), Value::Null));
+        }
+        request.extend_from_slice(&buffer[..read]);
+    }
+}
+
+fn status_line(status: u16) -> String {
+    let reason = reqwest::StatusCode::from_u16(status).ok()
+        .and_then(|status| status.canonical_reason())
+        .unwrap_or("Error");
+    format!("{} {}", status, reason)
+}
+
+async fn respond(mut stream: TcpStream, script: &Mutex<Script>) -> std::io::Result<()> {
+    let (path, body) = read_request(&mut stream).await?;
+    let path = path.trim_end_matches('/');
+    let provider = match path {
+        "/api/chat" => Some(Provider::Ollama),
+        "/v1/chat/completions" | "/chat/completions" => Some(Provider::OpenAi),
+        _ => None,
+    };
+    let (status, body) = match provider {
+        Some(provider) => {
+            let reply = match script.lock() {
+                Ok(mut script) => {
+                    #[cfg(test)]
+                    script.requests.push(body.clone());
+                    script.reply(&body)
+                }
+                Err(_) => return Ok(()),
+            };
+            tokio::time::sleep(reply.delay).await;
+            if let Some((status, message)) = &reply.error {
+                (status_line(*status), json!({ "error": { "message": message } }))
+            } else if body["stream"] == true {
+                return stream_reply(stream, provider, &body["model"], &reply).await;
+            } else {
+                ("200 OK".to_string(), chat_response(provider, &body["model"], &reply))
+            }
+        }
+        None => {
+            let (models, dimension) = match script.lock() {
+                Ok(mut script) => {
+                    let dimension = script.embedding_dimension(path);
+                    #[cfg(test)]
+                    if dimension.is_some() {
+                        script.embed_requests.push(body.clone());
+                    }
+                    (script.fixture.models(), dimension.unwrap_or(EMBEDDING_DIMENSION))
+                }
+                Err(_) => return Ok(()),
+            };
+            match path {
+                "/api/embed" => ("200 OK".to_string(), json!({ "embeddings": inputs(&body).iter().map(|text| embedding_of(text, dimension)).collect::<Vec<_>>() })),
+                "/v1/embeddings" | "/embeddings" => ("200 OK".to_string(), json!({
+                    "data": inputs(&body).iter().enumerate()
+                        .map(|(index, text)| json!({ "index": index, "embedding": embedding_of(text, dimension) }))
+                        .collect::<Vec<_>>(),
+                })),
+                "/api/tags" => ("200 OK".to_string(), json!({ "models": models.iter().map(|name| json!({ "name": name })).collect::<Vec<_>>() })),
+                "/v1/models" | "/models" => ("200 OK".to_string(), json!({ "data": models.iter().map(|id| json!({ "id": id })).collect::<Vec<_>>() })),
+                _ => ("404 Not Found".to_string(), json!({ "error": "Not found" })),
+            }
+        }
+    };
+    let body = body.to_string();
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status, body.len(), body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// Tool calls spelled the way `provider` spells them.
+fn wire_tool_calls(provider: Provider, calls: &[ToolCall]) -> Vec<Value> {
+    calls.iter()
+        .enumerate()
+        .map(|(index, call)| match provider {
+            Provider::Ollama => json!({ "function": { "name": call.name, "arguments": call.arguments } }),
+            _ => json!({
+                "index": index,
+                "id": call.id,
+                "type": "function",
+                "function": { "name": call.name, "arguments": call.arguments.to_string() }
+            }),
+        })
+        .collect()
+}
+
+fn chat_response(provider: Provider, model: &Value, reply: &Reply) -> Value {
+    let mut message = json!({ "role": "assistant", "content": reply.content() });
+    if !reply.tool_calls.is_empty() {
+        message["tool_calls"] = json!(wire_tool_calls(provider, &reply.tool_calls));
+    }
+    match provider {
+        Provider::Ollama => json!({
+            "model": model,
+            "message": message,
+            "done": true,
+            "prompt_eval_count": reply.usage.prompt_tokens,
+            "eval_count": reply.usage.completion_tokens,
+        }),
+        _ => json!({
+            "model": model,
+            "choices": [{
+                "index": 0,
+                "message": message,
+                "finish_reason": if reply.tool_calls.is_empty() { "stop" } else { "tool_calls" },
+            }],
+            "usage": { "prompt_tokens": reply.usage.prompt_tokens, "completion_tokens": reply.usage.completion_tokens },
+        }),
+    }
+}
+
+/// Send the reply chunk by chunk: newline-delimited JSON for Ollama, server-
+/// sent events for OpenAI. Tool calls and token counts come with the last event.
+async fn stream_reply(mut stream: TcpStream, provider: Provider, model: &Value, reply: &Reply) -> std::io::Result<()> {
+    let content_type = match provider {
+        Provider::Ollama => "application/x-ndjson",
+        _ => "text/event-stream",
+    };
+    stream.write_all(format!("HTTP/1.1 200 OK\r\nContent-Type: {}\r\nConnection: close\r\n\r\n", content_type).as_bytes()).await?;
+
+    let frame = |event: Value| match provider {
+        Provider::Ollama => format!("{}\n", event),
+        _ => format!("data: {}\n\n", event),
+    };
+    for (i, chunk) in reply.chunks.iter().enumerate() {
+        if i > 0 {
+            tokio::time::sleep(reply.chunk_delay).await;
+        }
+        let event = match provider {
+            Provider::Ollama => json!({ "model": model, "message": { "role": "assistant", "content": chunk }, "done": false }),
+            _ => json!({ "model": model, "choices": [{ "index": 0, "delta": { "content": chunk }, "finish_reason": null }] }),
+        };
+        stream.write_all(frame(event).as_bytes()).await?;
+    }
+
+    let tool_calls = wire_tool_calls(provider, &reply.tool_calls);
+    let last = match provider {
+        Provider::Ollama => json!({
+            "model": model,
+            "message": { "role": "assistant", "content": "", "tool_calls": tool_calls },
+            "done": true,
+            "prompt_eval_count": reply.usage.prompt_tokens,
+            "eval_count": reply.usage.completion_tokens,
+        }),
+        _ => json!({
+            "model": model,
+            "choices": [{
+                "index": 0,
+                "delta": { "tool_calls": tool_calls },
+                "finish_reason": if reply.tool_calls.is_empty() { "stop" } else { "tool_calls" },
+            }],
+            "usage": { "prompt_tokens": reply.usage.prompt_tokens, "completion_tokens": reply.usage.completion_tokens },
+        }),
+    };
+    stream.write_all(frame(last).as_bytes()).await?;
+    if provider != Provider::Ollama {
+        stream.write_all(b"data: [DONE]\n\n").await?;
+    }
+    stream.shutdown().await
+}
+
+fn inputs(body: &Value) -> Vec<String> {
+    match &body["input"] {
+        Value::String(text) => vec![text.clone()],
+        Value::Array(texts) => texts.iter().map(|text| text.as_str().unwrap_or_default().to_string()).collect(),
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider;
+    use std::path::PathBuf;
+    use std::time::Instant;
+
+    fn fixture_json() -> Value {
+        json!({
+            "models": ["scripted"],
+            "context_length": 4096,
+            "responses": [
+                {"match": "weather", "times": 1, "tool_calls": [{"name": "echo", "arguments": {"text": "hi"}}]},
+                {"match": "weather", "content": "sunny", "usage": {"prompt_tokens": 3, "completion_tokens": 1}},
+                {"match": "explode", "error": "model overloaded", "status": 503},
+                {"match": "slow", "chunks": ["a ", "b"], "delay_ms": 100, "chunk_delay_ms": 50}
+            ]
+        })
+    }
+
+    fn fixture_file(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("mock-{}-{}.json", name, std::process::id()));
+        std::fs::write(&path, fixture_json().to_string()).unwrap();
+        path
+    }
+
+    #[tokio::test]
+    async fn in_process_chat_follows_the_fixture() {
+        let path = fixture_file("chat");
+        let url = format!("{}{}", SCHEME, path.display());
+        let options = ChatOptions::default();
+        assert_eq!(Provider::detect(&url), Provider::Mock);
+        assert_eq!(models(&url).unwrap(), ["scripted"]);
+        assert_eq!(models(SCHEME).unwrap(), [MOCK_MODEL]);
+        assert_eq!(provider::model_info(&url, "scripted").await.unwrap().context_length, Some(4096));
+
+        let reply = provider::chat(&url, "scripted", &[ChatMessage::user("weather?")], &options).await.unwrap();
+        assert_eq!(reply.tool_calls[0].name, "echo");
+        assert_eq!(reply.tool_calls[0].arguments, json!({"text": "hi"}));
+        let reply = provider::chat(&url, "scripted", &[ChatMessage::user("weather?")], &options).await.unwrap();
+        assert_eq!((reply.content.as_str(), reply.usage.completion_tokens), ("sunny", Some(1)));
+        let error = provider::chat(&url, "scripted", &[ChatMessage::user("explode")], &options).await.unwrap_err();
+        assert_eq!(error, "Request failed: 503 model overloaded");
+        let reply = provider::chat(SCHEME, MOCK_MODEL, &[ChatMessage::user("echo me")], &options).await.unwrap();
+        assert_eq!(reply.content, "echo me");
+
+        // Edits to the fixture are picked up and restart the script
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        std::fs::write(&path, json!({"responses": [{"content": "changed"}]}).to_string()).unwrap();
+        let reply = provider::chat(&url, "m", &[ChatMessage::user("weather?")], &options).await.unwrap();
+        assert_eq!(reply.content, "changed");
+        std::fs::write(&path, "{").unwrap();
+        let error = provider::chat(&url, "m", &[ChatMessage::user("x")], &options).await.unwrap_err();
+        assert!(error.contains("Invalid mock fixture"), "{}", error);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn in_process_chunks_arrive_one_by_one() {
+        let path = fixture_file("stream");
+        let url = format!("{}{}", SCHEME, path.display());
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+        let started = Instant::now();
+        let (messages, options) = ([ChatMessage::user("slow please")], ChatOptions::default());
+        let mut chat = Box::pin(provider::chat_streaming(&url, "scripted", &messages, &options, Some(&sender)));
+
+        // The first chunk comes after the reply's delay, before the reply is done
+        let first = tokio::select! {
+            chunk = receiver.recv() => chunk.unwrap(),
+            _ = &mut chat => panic!("reply finished before its first chunk arrived"),
+        };
+        assert_eq!(first, "a ");
+        assert!(started.elapsed() >= Duration::from_millis(100));
+        let reply = chat.await.unwrap();
+        assert_eq!(receiver.recv().await.as_deref(), Some("b"));
+        assert!(started.elapsed() >= Duration::from_millis(150));
+        assert_eq!(reply.content, "a b");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn http_server_speaks_both_apis() {
+        let server = MockLlmServer::start().await.unwrap();
+        let reply = provider::chat(&server.base_url, "m", &[ChatMessage::user("echo me")], &ChatOptions::default()).await.unwrap();
+        assert_eq!(reply.content, "echo me");
+        assert_eq!(server.requests().len(), 1);
+
+        let server = MockLlmServer::with_fixture(serde_json::from_value(fixture_json()).unwrap()).await.unwrap();
+        let root = server.base_url.trim_end_matches("/v1").to_string();
+        let reply = provider::chat(&server.base_url, "scripted", &[ChatMessage::user("weather?")], &ChatOptions::default()).await.unwrap();
+        assert_eq!(reply.tool_calls[0].name, "echo");
+        assert_eq!(reply.tool_calls[0].arguments, json!({"text": "hi"}));
+        let error = provider::chat(&server.base_url, "scripted", &[ChatMessage::user("explode")], &ChatOptions::default()).await.unwrap_err();
+        assert!(error.contains("503"), "{}", error);
+
+        let client = reqwest::Client::new();
+        let body = json!({"model": "scripted", "stream": true, "messages": [{"role": "user", "content": "slow"}]});
+        let sse = client.post(format!("{}/chat/completions", server.base_url)).json(&body).send().await.unwrap().text().await.unwrap();
+        let events: Vec<&str> = sse.split("\n\n").filter(|event| !event.is_empty()).collect();
+        assert_eq!(events.len(), 4, "{}", sse);
+        assert!(events[0].contains("\"content\":\"a \""));
+        assert!(events[2].contains("\"finish_reason\":\"stop\""));
+        assert_eq!(events[3], "data: [DONE]");
+
+        let ndjson = client.post(format!("{}/api/chat", root)).json(&body).send().await.unwrap().text().await.unwrap();
+        let lines: Vec<Value> = ndjson.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[1]["message"]["content"], "b");
+        assert_eq!(lines[2]["done"], true);
+
+        let tags: Value = client.get(format!("{}/api/tags", root)).send().await.unwrap().json().await.unwrap();
+        assert_eq!(tags["models"][0]["name"], "scripted");
+        let requests = server.requests();
+        assert_eq!(requests.len(), 4);
+        assert_eq!(requests[0]["messages"][0]["content"], "weather?");
+    }
+}
@@ -0,0 +1,485 @@
+// Regression suites: golden runs re-run against a changed model or prompt,
+// their final outputs compared with the golden ones.
+use serde::{Deserialize, Serialize};
+use similar::TextDiff;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tauri::AppHandle;
+
+use crate::cache::ResponseCache;
+use crate::config::ConfigStore;
+use crate::eval::{self, EvalVariant};
+use crate::history::{GoldenRun, RunHistory};
+use crate::knowledge::KnowledgeBase;
+use crate::mcp::McpManager;
+use crate::memory::MemoryStore;
+use crate::prompts::PromptLibrary;
+use crate::provider::{self, mock::{Fixture, MockLlmServer}, Usage};
+use crate::tools::approval::Approvals;
+use crate::tools::web::{self, mock::MockSearchServer, WebSearch};
+use crate::tools::ToolRegistry;
+use crate::workflow::iteration::OutputBuffers;
+use crate::workflow::{self, NodeStatus, Services};
+use crate::{add_log_entry, LogState};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum Comparison {
+    /// Outputs must be identical.
+    #[default]
+    Exact,
+    /// Outputs must embed close to each other.
+    Semantic {
+        server_url: String,
+        model: String,
+        /// Lowest cosine similarity that counts as a match.
+        #[serde(default = "default_threshold")]
+        threshold: f32,
+    },
+}
+
+fn default_threshold() -> f32 {
+    0.9
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RegressionSpec {
+    /// Suite to run; every golden run when unset.
+    #[serde(default)]
+    pub suite: Option<String>,
+    /// Changes to check the golden runs against, applied to every LLM node.
+    #[serde(default)]
+    pub overrides: EvalVariant,
+    #[serde(default)]
+    pub comparison: Comparison,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutputCheck {
+    pub node_id: String,
+    pub passed: bool,
+    pub similarity: Option<f32>,
+    /// Unified diff from the golden output, present when they differ.
+    pub diff: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GoldenResult {
+    pub golden: GoldenRun,
+    pub run_id: Option<String>,
+    pub passed: bool,
+    pub error: Option<String>,
+    pub checks: Vec<OutputCheck>,
+    pub latency_ms: u64,
+    pub tokens: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegressionReport {
+    pub suite: Option<String>,
+    pub started_at: String,
+    pub finished_at: String,
+    pub passed: usize,
+    pub failed: usize,
+    pub results: Vec<GoldenResult>,
+}
+
+impl RegressionReport {
+    /// Plain-text pass/fail report, one line per golden run and the diffs of
+    /// those that failed.
+    pub fn summary(&self) -> String {
+        let mut text = format!(
+            "Regression suite {}: {} passed, {} failed\n",
+            self.suite.as_deref().unwrap_or("(all)"),
+            self.passed,
+            self.failed
+        );
+        for result in &self.results {
+            text.push_str(&format!(
+                "{} {} / {} ({} ms, {} tokens)\n",
+                if result.passed { "PASS" } else { "FAIL" },
+                result.golden.suite,
+                result.golden.name,
+                result.latency_ms,
+                result.tokens
+            ));
+            if let Some(error) = &result.error {
+                text.push_str(&format!("  {}\n", error));
+            }
+            for check in result.checks.iter().filter(|check| !check.passed) {
+                let similarity = check.similarity.map(|s| format!(" (similarity {:.3})", s)).unwrap_or_default();
+                text.push_str(&format!("  node {}{}: {}\n", check.node_id, similarity, check.error.as_deref().unwrap_or("output changed")));
+                if let Some(diff) = &check.diff {
+                    text.extend(diff.lines().map(|line| format!("    {}\n", line)));
+                }
+            }
+        }
+        text
+    }
+}
+
+async fn check_output(
+    comparison: &Comparison,
+    node_id: &str,
+    golden: &str,
+    actual: Option<&str>,
+) -> OutputCheck {
+    let mut check = OutputCheck {
+        node_id: node_id.to_string(),
+        passed: false,
+        similarity: None,
+        diff: None,
+        error: None,
+    };
+    let Some(actual) = actual else {
+        check.error = Some("Node did not complete".to_string());
+        return check;
+    };
+    if golden != actual {
+        check.diff = Some(TextDiff::from_lines(golden, actual).unified_diff().header("golden", "actual").to_string());
+    }
+    match comparison {
+        Comparison::Exact => check.passed = golden == actual,
+        Comparison::Semantic { server_url, model, threshold } => {
+            match provider::embed(server_url, model, &[golden.to_string(), actual.to_string()]).await {
+                Ok(embeddings) => {
+                    let similarity = provider::similarity(&embeddings.vectors[0], &embeddings.vectors[1]);
+                    check.similarity = Some(similarity);
+                    check.passed = golden == actual || similarity >= *threshold;
+                }
+                Err(e) => check.error = Some(format!("Could not embed outputs: {}", e)),
+            }
+        }
+    }
+    check
+}
+
+async fn run_golden(spec: &RegressionSpec, golden: GoldenRun, services: Services<'_>) -> GoldenResult {
+    let mut result = GoldenResult {
+        golden,
+        run_id: None,
+        passed: false,
+        error: None,
+        checks: Vec::new(),
+        latency_ms: 0,
+        tokens: 0,
+    };
+    let source = match services.history.get_run(&result.golden.run_id) {
+        Ok(source) => source,
+        Err(e) => {
+            result.error = Some(e);
+            return result;
+        }
+    };
+    let mut workflow = source.workflow.clone();
+    for node in workflow.nodes.iter_mut() {
+        spec.overrides.apply(&mut node.kind);
+    }
+
+    let started = Instant::now();
+    let run = match workflow::run_workflow(&workflow, services).await {
+        Ok(run) => run,
+        Err(e) => {
+            result.error = Some(e);
+            return result;
+        }
+    };
+    result.latency_ms = started.elapsed().as_millis() as u64;
+    let mut usage = Usage::default();
+    run.nodes.iter().for_each(|node| usage.add(node.usage));
+    result.tokens = usage.total();
+    result.run_id = Some(run.run_id.clone());
+
+    for expected in eval::final_nodes(&source.nodes, None) {
+        let actual = run.nodes.iter()
+            .find(|node| node.node_id == expected.node_id && node.status == NodeStatus::Completed)
+            .map(|node| node.output.as_deref().unwrap_or_default());
+        let golden = expected.output.as_deref().unwrap_or_default();
+        result.checks.push(check_output(&spec.comparison, &expected.node_id, golden, actual).await);
+    }
+    if let Some(failed) = run.nodes.iter().find(|node| node.status == NodeStatus::Failed) {
+        result.error = Some(format!("Node {} failed: {}", failed.node_id, failed.error.as_deref().unwrap_or_default()));
+    }
+    result.passed = result.error.is_none() && !result.checks.is_empty() && result.checks.iter().all(|check| check.passed);
+    result
+}
+
+pub async fn run_regression(spec: &RegressionSpec, services: Services<'_>) -> Result<RegressionReport, String> {
+    let goldens = services.history.golden_runs(spec.suite.as_deref())?;
+    if goldens.is_empty() {
+        return Err(match &spec.suite {
+            Some(suite) => format!("Suite '{}' has no golden runs", suite),
+            None => "There are no golden runs".to_string(),
+        });
+    }
+    let started_at = chrono::Local::now().to_rfc3339();
+    add_log_entry(services.log_state, "info", &format!("Running {} golden run(s)", goldens.len()), "regression")?;
+
+    let mut results = Vec::with_capacity(goldens.len());
+    for golden in goldens {
+        let result = run_golden(spec, golden, services).await;
+        let level = if result.passed { "info" } else { "warn" };
+        add_log_entry(
+            services.log_state,
+            level,
+            &format!("Golden run '{}' {}", result.golden.name, if result.passed { "passed" } else { "failed" }),
+            "regression",
+        )?;
+        results.push(result);
+    }
+    let passed = results.iter().filter(|result| result.passed).count();
+    Ok(RegressionReport {
+        suite: spec.suite.clone(),
+        started_at,
+        finished_at: chrono::Local::now().to_rfc3339(),
+        passed,
+        failed: results.len() - passed,
+        results,
+    })
+}
+
+/// Logs to stderr in a headless run, leaving stdout to the report.
+struct StderrLogger;
+
+impl log::Log for StderrLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!(
+                "{} [{}] [{}] {}",
+                chrono::Local::now().format("%Y-%m-%d %H:%M:%S%.3f"),
+                record.level(),
+                record.target(),
+                record.args()
+            );
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static STDERR_LOGGER: StderrLogger = StderrLogger;
+
+/// The value of `--name value` or `--name=value` in `args`.
+fn arg_value(args: &[String], name: &str) -> Option<String> {
+    let flag = format!("--{}", name);
+    args.iter().enumerate().find_map(|(i, arg)| {
+        if *arg == flag {
+            args.get(i + 1).cloned()
+        } else {
+            arg.strip_prefix(&flag)?.strip_prefix('=').map(str::to_string)
+        }
+    })
+}
+
+/// What a headless run borrows through `Services`, opened from the app's
+/// data directory without starting the app.
+struct Headless {
+    log_state: Mutex<LogState>,
+    buffers: OutputBuffers,
+    history: RunHistory,
+    cache: ResponseCache,
+    tools: ToolRegistry,
+    approvals: Approvals,
+    knowledge: KnowledgeBase,
+    memory: MemoryStore,
+    prompts: PromptLibrary,
+    mcp: McpManager,
+}
+
+impl Headless {
+    /// Open the stores and register the tools that work without a window:
+    /// web search and MCP servers. The file, code, data and shell tools need
+    /// the app's fs scope or shell plugin and are left out.
+    async fn open(data_dir: &Path, mock_search: bool) -> Result<(Self, Option<MockSearchServer>), String> {
+        let headless = Self {
+            log_state: Mutex::new(LogState::new()),
+            buffers: OutputBuffers::default(),
+            history: RunHistory::open(&data_dir.join("history.db"))?,
+            cache: ResponseCache::open(&data_dir.join("cache.db"))?,
+            tools: ToolRegistry::default(),
+            approvals: Approvals::default(),
+            knowledge: KnowledgeBase::open(&data_dir.join("knowledge.db"))?,
+            memory: MemoryStore::open(&data_dir.join("memory.db"))?,
+            prompts: PromptLibrary::open(&data_dir.join("prompts.db"))?,
+            mcp: McpManager::default(),
+        };
+        let web_search = Arc::new(WebSearch::load(data_dir.join("web.json")));
+        let search_server = if mock_search {
+            let server = MockSearchServer::start(web::mock::default_pages()).await?;
+            web_search.override_endpoint(&server.base_url);
+            Some(server)
+        } else {
+            None
+        };
+        web::register(&headless.tools, web_search);
+        let mcp_servers = ConfigStore::load(data_dir.join("config.json")).get().map(|c| c.mcp_servers).unwrap_or_default();
+        if !mcp_servers.is_empty() {
+            headless.mcp.sync(&mcp_servers, &headless.tools, &headless.log_state).await?;
+        }
+        Ok((headless, search_server))
+    }
+
+    fn services(&self) -> Services<'_> {
+        Services {
+            log_state: &self.log_state,
+            buffers: &self.buffers,
+            history: &self.history,
+            cache: &self.cache,
+            tools: &self.tools,
+            approvals: &self.approvals,
+            knowledge: &self.knowledge,
+            memory: &self.memory,
+            prompts: &self.prompts,
+        }
+    }
+}
+
+/// Run a regression suite when the command line has `--regression <suite>`,
+/// returning the process exit code: 0 when every golden run passed, 1 when
+/// any failed, 2 when the suite could not run. This happens before the app
+/// is built, so no window or webview is needed; the stores are opened in the
+/// data directory of the app with bundle `identifier`. Logs go to stderr and
+/// the report to stdout.
+pub fn run_from_args(identifier: &str) -> Option<i32> {
+    let args: Vec<String> = std::env::args().collect();
+    let suite = arg_value(&args, "regression")?;
+    if log::set_logger(&STDERR_LOGGER).is_ok() {
+        log::set_max_level(log::LevelFilter::Info);
+    }
+    let spec = RegressionSpec {
+        suite: Some(suite),
+        overrides: EvalVariant {
+            server_url: arg_value(&args, "regression-server"),
+            model: arg_value(&args, "regression-model"),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let flag = |name: &str| args.iter().any(|arg| *arg == format!("--{}", name));
+    let mock_llm = match (flag("mock-llm"), arg_value(&args, "mock-fixture")) {
+        (false, _) => None,
+        (true, None) => Some(Fixture::default()),
+        (true, Some(path)) => match Fixture::load(Path::new(&path)) {
+            Ok(fixture) => Some(fixture),
+            Err(e) => {
+                log::error!("{}", e);
+                return Some(2);
+            }
+        },
+    };
+    let Some(data_dir) = dirs::data_dir().map(|dir| dir.join(identifier)) else {
+        log::error!("Cannot find the app data directory");
+        return Some(2);
+    };
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            log::error!("Failed to start the async runtime: {}", e);
+            return Some(2);
+        }
+    };
+    Some(runtime.block_on(async {
+        match Headless::open(&data_dir, flag("mock-search")).await {
+            Ok((headless, _search_server)) => run_headless(headless.services(), spec, mock_llm).await,
+            Err(e) => {
+                log::error!("{}", e);
+                2
+            }
+        }
+    }))
+}
+
+/// Run a suite and print its report, returning the exit code. With
+/// `mock_llm` the LLM nodes talk to a local mock server answering from that
+/// fixture.
+async fn run_headless(services: Services<'_>, mut spec: RegressionSpec, mock_llm: Option<Fixture>) -> i32 {
+    let _mock = if let Some(fixture) = mock_llm {
+        match MockLlmServer::with_fixture(fixture).await {
+            Ok(server) => {
+                log::info!("Mock LLM server listening on {}", server.base_url);
+                spec.overrides.server_url = Some(server.base_url.clone());
+                spec.overrides.model.get_or_insert_with(|| provider::mock::MOCK_MODEL.to_string());
+                Some(server)
+            }
+            Err(e) => {
+                log::error!("Failed to start mock LLM server: {}", e);
+                return 2;
+            }
+        }
+    } else {
+        None
+    };
+    let result = run_regression(&spec, services).await;
+    // The app log has no viewer here, so pass it on to stderr
+    if let Ok(mut log_state) = services.log_state.lock() {
+        for entry in log_state.entries.drain(..) {
+            let level = entry.level.parse().unwrap_or(log::Level::Info);
+            log::log!(target: &entry.target, level, "{}", entry.message);
+        }
+    }
+    match result {
+        Ok(report) => {
+            println!("{}", report.summary());
+            if report.failed == 0 { 0 } else { 1 }
+        }
+        Err(e) => {
+            log::error!("{}", e);
+            2
+        }
+    }
+}
+
+#[tauri::command]
+pub async fn run_regression_suite(app: AppHandle, spec: RegressionSpec) -> Result<RegressionReport, String> {
+    run_regression(&spec, Services::from_app(&app)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn reads_option_values() {
+        let args: Vec<String> = ["app", "--regression", "smoke", "--regression-model=llama3", "--mock-llm"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+        assert_eq!(arg_value(&args, "regression").as_deref(), Some("smoke"));
+        assert_eq!(arg_value(&args, "regression-model").as_deref(), Some("llama3"));
+        assert_eq!(arg_value(&args, "mock-llm"), None);
+        assert_eq!(arg_value(&args, "regression-server"), None);
+    }
+
+    #[tokio::test]
+    async fn headless_suite_against_the_mock() {
+        let data_dir = std::env::temp_dir().join(format!("regression-{}", std::process::id()));
+        std::fs::create_dir_all(&data_dir).unwrap();
+        let (headless, _) = Headless::open(&data_dir, false).await.unwrap();
+        let services = headless.services();
+        let workflow: workflow::Workflow = serde_json::from_value(json!({"id": "w", "name": "Echo", "nodes": [
+            {"id": "in", "type": "input", "value": "the quick brown fox"},
+            {"id": "llm", "type": "llm", "server_url": provider::mock::SCHEME, "model": provider::mock::MOCK_MODEL, "prompt": "Say: {{input}}"}
+        ], "edges": [{"from": "in", "to": "llm"}]})).unwrap();
+        let run = workflow::run_workflow(&workflow, services).await.unwrap();
+
+        let spec = RegressionSpec { suite: Some("smoke".into()), ..Default::default() };
+        assert_eq!(run_headless(services, spec.clone(), None).await, 2);
+        headless.history.mark_golden(&run.run_id, "smoke", "").unwrap();
+        assert_eq!(run_headless(services, spec.clone(), Some(Fixture::default())).await, 0);
+        let changed = RegressionSpec {
+            overrides: EvalVariant { prompt: Some("Say it: {{input}}".into()), ..Default::default() },
+            ..spec
+        };
+        assert_eq!(run_headless(services, changed, None).await, 1);
+        assert!(headless.log_state.lock().unwrap().entries.is_empty());
+        drop(headless);
+        std::fs::remove_dir_all(&data_dir).unwrap();
+    }
+}
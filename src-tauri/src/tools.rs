@@ -0,0 +1,109 @@
+// Tools LLM nodes can call, and the registry that advertises them to models.
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, RwLock};
+use tauri::State;
+
+use crate::history::RunHistory;
+use crate::provider::ToolDefinition;
+use crate::LogState;
+use approval::Approvals;
+
+pub mod approval;
+pub mod code;
+pub mod data;
+pub mod files;
+pub mod shell;
+pub mod web;
+
+/// What a tool call can see of the run it belongs to.
+pub struct ToolContext<'a> {
+    pub run_id: &'a str,
+    pub node_id: &'a str,
+    pub log_state: &'a Mutex<LogState>,
+    pub approvals: &'a Approvals,
+    /// Where tools save artifacts for the node.
+    pub history: &'a RunHistory,
+}
+
+#[async_trait]
+pub trait Tool: Send + Sync {
+    fn name(&self) -> &str;
+    /// The node checkbox that enables the tool, e.g. `file_operations`.
+    fn group(&self) -> &str;
+    fn description(&self) -> &str;
+    /// JSON schema of the arguments object.
+    fn parameters(&self) -> Value;
+    /// Run the tool. The returned text is fed back to the model as is.
+    async fn call(&self, ctx: &ToolContext<'_>, arguments: Value) -> Result<String, String>;
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: self.name().to_string(),
+            description: self.description().to_string(),
+            parameters: self.parameters(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolInfo {
+    pub name: String,
+    pub group: String,
+    pub description: String,
+    pub parameters: Value,
+}
+
+#[derive(Default)]
+pub struct ToolRegistry {
+    tools: RwLock<BTreeMap<String, Arc<dyn Tool>>>,
+}
+
+impl ToolRegistry {
+    pub fn register(&self, tool: Arc<dyn Tool>) {
+        if let Ok(mut tools) = self.tools.write() {
+            tools.insert(tool.name().to_string(), tool);
+        }
+    }
+
+    /// Remove every tool of `group`, e.g. when its MCP server disconnects.
+    pub fn unregister_group(&self, group: &str) {
+        if let Ok(mut tools) = self.tools.write() {
+            tools.retain(|_, tool| tool.group() != group);
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn Tool>> {
+        self.tools.read().ok()?.get(name).cloned()
+    }
+
+    /// Tools enabled by `selection`, whose entries name single tools or whole groups.
+    pub fn resolve(&self, selection: &[String]) -> Vec<Arc<dyn Tool>> {
+        self.tools.read()
+            .map(|tools| tools.values()
+                .filter(|tool| selection.iter().any(|s| s == tool.name() || s == tool.group()))
+                .cloned()
+                .collect())
+            .unwrap_or_default()
+    }
+
+    pub fn list(&self) -> Vec<ToolInfo> {
+        self.tools.read()
+            .map(|tools| tools.values()
+                .map(|tool| ToolInfo {
+                    name: tool.name().to_string(),
+                    group: tool.group().to_string(),
+                    description: tool.description().to_string(),
+                    parameters: tool.parameters(),
+                })
+                .collect())
+            .unwrap_or_default()
+    }
+}
+
+#[tauri::command]
+pub async fn list_tools(registry: State<'_, ToolRegistry>) -> Result<Vec<ToolInfo>, String> {
+    Ok(registry.list())
+}
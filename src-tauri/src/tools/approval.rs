@@ -0,0 +1,92 @@
+// Pending tool actions waiting for the user to approve or reject them in the UI.
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::State;
+use tokio::sync::oneshot;
+
+use crate::broadcast_update;
+
+/// Unanswered requests are rejected after this long.
+const APPROVAL_TIMEOUT: Duration = Duration::from_secs(300);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApprovalRequest {
+    pub id: String,
+    pub run_id: String,
+    pub node_id: String,
+    pub tool: String,
+    pub title: String,
+    /// What will happen if approved, e.g. a diff or a command line.
+    pub detail: String,
+    pub created_at: String,
+}
+
+#[derive(Default)]
+pub struct Approvals {
+    pending: Mutex<HashMap<String, (ApprovalRequest, oneshot::Sender<bool>)>>,
+}
+
+impl Approvals {
+    /// Ask the user and wait for the answer. Returns false when rejected or
+    /// when nobody answers in time.
+    pub async fn request(&self, run_id: &str, node_id: &str, tool: &str, title: String, detail: String) -> bool {
+        let request = ApprovalRequest {
+            id: uuid::Uuid::new_v4().to_string(),
+            run_id: run_id.to_string(),
+            node_id: node_id.to_string(),
+            tool: tool.to_string(),
+            title,
+            detail,
+            created_at: chrono::Local::now().to_rfc3339(),
+        };
+        let id = request.id.clone();
+        let (tx, rx) = oneshot::channel();
+        match self.pending.lock() {
+            Ok(mut pending) => {
+                pending.insert(id.clone(), (request.clone(), tx));
+            }
+            Err(_) => return false,
+        }
+
+        broadcast_update(json!({
+            "type": "approval_request",
+            "data": request,
+        })).await;
+
+        let approved = matches!(tokio::time::timeout(APPROVAL_TIMEOUT, rx).await, Ok(Ok(true)));
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.remove(&id);
+        }
+        approved
+    }
+
+    pub fn pending(&self) -> Vec<ApprovalRequest> {
+        let mut requests: Vec<ApprovalRequest> = self.pending.lock()
+            .map(|pending| pending.values().map(|(request, _)| request.clone()).collect())
+            .unwrap_or_default();
+        requests.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+        requests
+    }
+
+    pub fn resolve(&self, id: &str, approved: bool) -> Result<(), String> {
+        let (_, tx) = self.pending.lock()
+            .map_err(|e| e.to_string())?
+            .remove(id)
+            .ok_or_else(|| format!("No pending approval with id {}", id))?;
+        let _ = tx.send(approved);
+        Ok(())
+    }
+}
+
+#[tauri::command]
+pub async fn list_approvals(approvals: State<'_, Approvals>) -> Result<Vec<ApprovalRequest>, String> {
+    Ok(approvals.pending())
+}
+
+#[tauri::command]
+pub async fn resolve_approval(id: String, approved: bool, approvals: State<'_, Approvals>) -> Result<(), String> {
+    approvals.resolve(&id, approved)
+}
@@ -0,0 +1,631 @@
+// Code analysis tools: outlines, definitions, references and complexity,
+// from tree-sitter parses of workspace sources.
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tree_sitter::{Language, Node, Parser, Tree};
+use walkdir::WalkDir;
+
+use super::files::{is_skipped, str_arg, truncate, Workspace, MAX_READ_BYTES};
+use super::{Tool, ToolContext, ToolRegistry};
+use crate::add_log_entry;
+
+const GROUP: &str = "code_analysis";
+const MAX_SOURCE_BYTES: u64 = 512 * 1024;
+const MAX_FILES: usize = 2000;
+
+/// How symbols, functions and branches are spelled in one grammar.
+struct Syntax {
+    extensions: &'static [&'static str],
+    language: fn() -> Language,
+    /// Node kinds that define a symbol, with the label shown for them.
+    definitions: &'static [(&'static str, &'static str)],
+    /// Node kinds measured as functions.
+    functions: &'static [&'static str],
+    /// Node kinds that add a path through a function.
+    branches: &'static [&'static str],
+}
+
+const RUST_DEFINITIONS: &[(&str, &str)] = &[
+    ("function_item", "fn"),
+    ("function_signature_item", "fn"),
+    ("struct_item", "struct"),
+    ("enum_item", "enum"),
+    ("union_item", "union"),
+    ("trait_item", "trait"),
+    ("impl_item", "impl"),
+    ("mod_item", "mod"),
+    ("const_item", "const"),
+    ("static_item", "static"),
+    ("type_item", "type"),
+    ("macro_definition", "macro"),
+];
+const RUST_FUNCTIONS: &[&str] = &["function_item", "closure_expression"];
+const RUST_BRANCHES: &[&str] = &[
+    "if_expression", "match_arm", "while_expression", "for_expression", "loop_expression", "&&", "||",
+];
+
+const PYTHON_DEFINITIONS: &[(&str, &str)] = &[("function_definition", "def"), ("class_definition", "class")];
+const PYTHON_FUNCTIONS: &[&str] = &["function_definition", "lambda"];
+const PYTHON_BRANCHES: &[&str] = &[
+    "if_statement", "elif_clause", "for_statement", "while_statement", "except_clause",
+    "conditional_expression", "boolean_operator", "case_clause", "for_in_clause", "if_clause",
+];
+
+const SCRIPT_DEFINITIONS: &[(&str, &str)] = &[
+    ("function_declaration", "function"),
+    ("generator_function_declaration", "function"),
+    ("variable_declarator", "function"),
+    ("class_declaration", "class"),
+    ("abstract_class_declaration", "class"),
+    ("method_definition", "method"),
+    ("interface_declaration", "interface"),
+    ("type_alias_declaration", "type"),
+    ("enum_declaration", "enum"),
+    ("internal_module", "namespace"),
+];
+const SCRIPT_FUNCTIONS: &[&str] = &[
+    "function_declaration", "generator_function_declaration", "method_definition", "arrow_function", "function_expression",
+];
+const SCRIPT_BRANCHES: &[&str] = &[
+    "if_statement", "for_statement", "for_in_statement", "while_statement", "do_statement",
+    "switch_case", "catch_clause", "ternary_expression", "&&", "||", "??",
+];
+
+const GO_DEFINITIONS: &[(&str, &str)] = &[
+    ("function_declaration", "func"),
+    ("method_declaration", "method"),
+    ("type_spec", "type"),
+];
+const GO_FUNCTIONS: &[&str] = &["function_declaration", "method_declaration", "func_literal"];
+const GO_BRANCHES: &[&str] = &[
+    "if_statement", "for_statement", "expression_case", "type_case", "communication_case", "&&", "||",
+];
+
+fn rust() -> Language {
+    tree_sitter_rust::LANGUAGE.into()
+}
+
+fn python() -> Language {
+    tree_sitter_python::LANGUAGE.into()
+}
+
+fn typescript() -> Language {
+    tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into()
+}
+
+fn tsx() -> Language {
+    tree_sitter_typescript::LANGUAGE_TSX.into()
+}
+
+fn javascript() -> Language {
+    tree_sitter_javascript::LANGUAGE.into()
+}
+
+fn go() -> Language {
+    tree_sitter_go::LANGUAGE.into()
+}
+
+const SYNTAXES: &[Syntax] = &[
+    Syntax { extensions: &["rs"], language: rust, definitions: RUST_DEFINITIONS, functions: RUST_FUNCTIONS, branches: RUST_BRANCHES },
+    Syntax { extensions: &["py", "pyi"], language: python, definitions: PYTHON_DEFINITIONS, functions: PYTHON_FUNCTIONS, branches: PYTHON_BRANCHES },
+    Syntax { extensions: &["ts", "mts", "cts"], language: typescript, definitions: SCRIPT_DEFINITIONS, functions: SCRIPT_FUNCTIONS, branches: SCRIPT_BRANCHES },
+    Syntax { extensions: &["tsx"], language: tsx, definitions: SCRIPT_DEFINITIONS, functions: SCRIPT_FUNCTIONS, branches: SCRIPT_BRANCHES },
+    Syntax { extensions: &["js", "mjs", "cjs", "jsx"], language: javascript, definitions: SCRIPT_DEFINITIONS, functions: SCRIPT_FUNCTIONS, branches: SCRIPT_BRANCHES },
+    Syntax { extensions: &["go"], language: go, definitions: GO_DEFINITIONS, functions: GO_FUNCTIONS, branches: GO_BRANCHES },
+];
+
+fn syntax_for(path: &Path) -> Option<&'static Syntax> {
+    let extension = path.extension()?.to_str()?;
+    SYNTAXES.iter().find(|syntax| syntax.extensions.contains(&extension))
+}
+
+/// A parsed source file.
+struct Parsed {
+    syntax: &'static Syntax,
+    source: String,
+    tree: Tree,
+}
+
+impl Parsed {
+    /// Errors leave out the path, which the caller reports alongside them.
+    fn open(path: &Path) -> Result<Self, String> {
+        let syntax = syntax_for(path).ok_or("unsupported language")?;
+        let source = std::fs::read_to_string(path).map_err(|e| format!("failed to read: {}", e))?;
+        let mut parser = Parser::new();
+        parser.set_language(&(syntax.language)()).map_err(|e| format!("failed to load the grammar: {}", e))?;
+        let tree = parser.parse(&source, None).ok_or("failed to parse")?;
+        Ok(Self { syntax, source, tree })
+    }
+
+    fn text(&self, node: Node) -> &str {
+        node.utf8_text(self.source.as_bytes()).unwrap_or_default()
+    }
+
+    fn symbols(&self) -> Vec<Symbol> {
+        let mut symbols = Vec::new();
+        self.collect_symbols(self.tree.root_node(), 0, &mut symbols);
+        symbols
+    }
+
+    fn collect_symbols(&self, node: Node, depth: usize, symbols: &mut Vec<Symbol>) {
+        let mut child_depth = depth;
+        if let Some(symbol) = self.symbol_at(node, depth) {
+            symbols.push(symbol);
+            child_depth += 1;
+        }
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor) {
+            self.collect_symbols(child, child_depth, symbols);
+        }
+    }
+
+    fn symbol_at(&self, node: Node, depth: usize) -> Option<Symbol> {
+        let (_, label) = self.syntax.definitions.iter().find(|(kind, _)| *kind == node.kind())?;
+        // Only variables holding a function count as definitions.
+        let body = match node.kind() {
+            "variable_declarator" => node.child_by_field_name("value")
+                .filter(|value| self.syntax.functions.contains(&value.kind()))?,
+            _ => node,
+        };
+        let (name, name_id) = match node.kind() {
+            "impl_item" => {
+                let ty = self.text(node.child_by_field_name("type")?);
+                let name = match node.child_by_field_name("trait") {
+                    Some(tr) => format!("{} for {}", self.text(tr), ty),
+                    None => ty.to_string(),
+                };
+                (name, None)
+            }
+            _ => {
+                let name = node.child_by_field_name("name")?;
+                (self.text(name).to_string(), Some(name.id()))
+            }
+        };
+        let complexity = self.syntax.functions.contains(&body.kind())
+            .then(|| 1 + self.count_branches(body, true));
+        Some(Symbol {
+            kind: label,
+            name,
+            name_id,
+            start_line: node.start_position().row + 1,
+            end_line: node.end_position().row + 1,
+            depth,
+            signature: self.text(node).lines().next().unwrap_or_default().trim().to_string(),
+            complexity,
+        })
+    }
+
+    /// Branch points inside a function, not counting nested functions.
+    fn count_branches(&self, node: Node, root: bool) -> u32 {
+        if !root && self.syntax.functions.contains(&node.kind()) {
+            return 0;
+        }
+        let own = self.syntax.branches.contains(&node.kind()) as u32;
+        let mut cursor = node.walk();
+        let nested: u32 = node.children(&mut cursor).map(|child| self.count_branches(child, false)).sum();
+        own + nested
+    }
+
+    /// Lines where `name` is used as an identifier, excluding its definitions.
+    fn references(&self, name: &str, definitions: &BTreeSet<usize>, lines: &mut BTreeSet<usize>) {
+        let mut stack = vec![self.tree.root_node()];
+        while let Some(node) = stack.pop() {
+            if node.kind().ends_with("identifier") && self.text(node) == name && !definitions.contains(&node.id()) {
+                lines.insert(node.start_position().row + 1);
+            }
+            let mut cursor = node.walk();
+            stack.extend(node.children(&mut cursor));
+        }
+    }
+}
+
+struct Symbol {
+    kind: &'static str,
+    name: String,
+    name_id: Option<usize>,
+    start_line: usize,
+    end_line: usize,
+    depth: usize,
+    signature: String,
+    /// Cyclomatic complexity, for functions.
+    complexity: Option<u32>,
+}
+
+/// Supported source files under `path`, or `path` itself when it is a file.
+fn source_files(path: &Path) -> Vec<PathBuf> {
+    WalkDir::new(path)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|entry| !is_skipped(entry))
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_file() && syntax_for(entry.path()).is_some())
+        .filter(|entry| entry.metadata().is_ok_and(|m| m.len() <= MAX_SOURCE_BYTES))
+        .take(MAX_FILES)
+        .map(|entry| entry.into_path())
+        .collect()
+}
+
+/// Parse on a blocking thread; a directory can mean many files. `f` gets the
+/// files as they are parsed; those that fail are listed after its output, and
+/// when none parse the tool fails.
+async fn analyze<F>(ctx: &ToolContext<'_>, workspace: &Arc<Workspace>, tool: &str, path: &str, f: F) -> Result<String, String>
+where
+    F: FnOnce(&Workspace, &mut dyn Iterator<Item = (PathBuf, Parsed)>) -> String + Send + 'static,
+{
+    let root = workspace.resolve(path)?;
+    add_log_entry(ctx.log_state, "info", &format!("{} {}", tool, root.display()), "tools")?;
+    if !root.exists() {
+        return Err(format!("{} does not exist", path));
+    }
+    let workspace = workspace.clone();
+    let text = tokio::task::spawn_blocking(move || {
+        let files = source_files(&root);
+        if files.is_empty() {
+            return Ok("No supported source files found".to_string());
+        }
+        let total = files.len();
+        let mut errors = Vec::new();
+        let mut parsed = files.into_iter().filter_map(|file| match Parsed::open(&file) {
+            Ok(parsed) => Some((file, parsed)),
+            Err(e) => {
+                errors.push(format!("  {}: {}", workspace.relative(&file).display(), e));
+                None
+            }
+        });
+        let text = f(&workspace, &mut parsed);
+        drop(parsed);
+        if errors.len() == total {
+            Err(format!("Could not analyze any of the {} files:\n{}", total, errors.join("\n")))
+        } else if errors.is_empty() {
+            Ok(text)
+        } else {
+            Ok(format!("{}\n\nCould not analyze {} files:\n{}", text, errors.len(), errors.join("\n")))
+        }
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+    Ok(truncate(text, MAX_READ_BYTES))
+}
+
+pub struct CodeOutline(Arc<Workspace>);
+
+#[async_trait]
+impl Tool for CodeOutline {
+    fn name(&self) -> &str {
+        "code_outline"
+    }
+
+    fn group(&self) -> &str {
+        GROUP
+    }
+
+    fn description(&self) -> &str {
+        "Outline the symbols (functions, types, classes, impls) defined in a source file or directory, with line ranges."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": { "type": "string", "description": "File or directory, relative to the workspace root" }
+            },
+            "required": ["path"]
+        })
+    }
+
+    async fn call(&self, ctx: &ToolContext<'_>, arguments: Value) -> Result<String, String> {
+        analyze(ctx, &self.0, self.name(), str_arg(&arguments, "path")?, |workspace, files| {
+            let mut out = Vec::new();
+            for (file, parsed) in files {
+                out.push(format!("{}:", workspace.relative(&file).display()));
+                for symbol in parsed.symbols() {
+                    out.push(format!(
+                        "{}{} {} [{}-{}]",
+                        "  ".repeat(symbol.depth + 1),
+                        symbol.kind,
+                        symbol.name,
+                        symbol.start_line,
+                        symbol.end_line
+                    ));
+                }
+            }
+            out.join("\n")
+        })
+        .await
+    }
+}
+
+pub struct FindDefinition(Arc<Workspace>);
+
+#[async_trait]
+impl Tool for FindDefinition {
+    fn name(&self) -> &str {
+        "find_definition"
+    }
+
+    fn group(&self) -> &str {
+        GROUP
+    }
+
+    fn description(&self) -> &str {
+        "Find where a symbol is defined, with the first line of each definition."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "name": { "type": "string", "description": "Symbol name, e.g. a function or type" },
+                "path": { "type": "string", "description": "Directory to search, relative to the workspace root" }
+            },
+            "required": ["name"]
+        })
+    }
+
+    async fn call(&self, ctx: &ToolContext<'_>, arguments: Value) -> Result<String, String> {
+        let name = str_arg(&arguments, "name")?.to_string();
+        let path = arguments["path"].as_str().unwrap_or(".");
+        analyze(ctx, &self.0, self.name(), path, move |workspace, files| {
+            let mut out = Vec::new();
+            for (file, parsed) in files {
+                for symbol in parsed.symbols().into_iter().filter(|s| s.name == name) {
+                    out.push(format!(
+                        "{}:{}: {} {}\n    {}",
+                        workspace.relative(&file).display(),
+                        symbol.start_line,
+                        symbol.kind,
+                        symbol.name,
+                        symbol.signature
+                    ));
+                }
+            }
+            if out.is_empty() {
+                format!("No definition of {} found", name)
+            } else {
+                out.join("\n")
+            }
+        })
+        .await
+    }
+}
+
+pub struct FindReferences(Arc<Workspace>);
+
+#[async_trait]
+impl Tool for FindReferences {
+    fn name(&self) -> &str {
+        "find_references"
+    }
+
+    fn group(&self) -> &str {
+        GROUP
+    }
+
+    fn description(&self) -> &str {
+        "Find the lines where a symbol is used, excluding its definitions. Matches identifiers only, not comments or strings."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "name": { "type": "string", "description": "Identifier to look for" },
+                "path": { "type": "string", "description": "Directory to search, relative to the workspace root" },
+                "max_results": { "type": "integer" }
+            },
+            "required": ["name"]
+        })
+    }
+
+    async fn call(&self, ctx: &ToolContext<'_>, arguments: Value) -> Result<String, String> {
+        let name = str_arg(&arguments, "name")?.to_string();
+        let path = arguments["path"].as_str().unwrap_or(".");
+        let max_results = arguments["max_results"].as_u64().unwrap_or(100) as usize;
+        analyze(ctx, &self.0, self.name(), path, move |workspace, files| {
+            let mut out = Vec::new();
+            'files: for (file, parsed) in files {
+                let definitions = parsed.symbols().into_iter().filter_map(|s| s.name_id).collect();
+                let mut lines = BTreeSet::new();
+                parsed.references(&name, &definitions, &mut lines);
+                let source: Vec<&str> = parsed.source.lines().collect();
+                for line in lines {
+                    let text = source.get(line - 1).map_or("", |l| l.trim());
+                    out.push(format!("{}:{}: {}", workspace.relative(&file).display(), line, text));
+                    if out.len() == max_results {
+                        out.push(format!("[stopped after {} results]", max_results));
+                        break 'files;
+                    }
+                }
+            }
+            if out.is_empty() {
+                format!("No references to {} found", name)
+            } else {
+                out.join("\n")
+            }
+        })
+        .await
+    }
+}
+
+pub struct CodeMetrics(Arc<Workspace>);
+
+#[async_trait]
+impl Tool for CodeMetrics {
+    fn name(&self) -> &str {
+        "code_metrics"
+    }
+
+    fn group(&self) -> &str {
+        GROUP
+    }
+
+    fn description(&self) -> &str {
+        "Report line counts and cyclomatic complexity for a source file or directory, listing the most complex functions."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": { "type": "string", "description": "File or directory, relative to the workspace root" },
+                "top": { "type": "integer", "description": "Number of most complex functions to list" }
+            },
+            "required": ["path"]
+        })
+    }
+
+    async fn call(&self, ctx: &ToolContext<'_>, arguments: Value) -> Result<String, String> {
+        let top = arguments["top"].as_u64().unwrap_or(20) as usize;
+        analyze(ctx, &self.0, self.name(), str_arg(&arguments, "path")?, move |workspace, files| {
+            let mut out = Vec::new();
+            let mut functions = Vec::new();
+            let (mut total_lines, mut file_count) = (0, 0);
+            for (file, parsed) in files {
+                let relative = workspace.relative(&file).display().to_string();
+                let lines = parsed.source.lines().count();
+                let measured: Vec<Symbol> = parsed.symbols().into_iter().filter(|s| s.complexity.is_some()).collect();
+                let max = measured.iter().filter_map(|s| s.complexity).max().unwrap_or(0);
+                out.push(format!("{}: {} lines, {} functions, max complexity {}", relative, lines, measured.len(), max));
+                total_lines += lines;
+                file_count += 1;
+                functions.extend(measured.into_iter().map(|s| (relative.clone(), s)));
+            }
+
+            let average = if functions.is_empty() {
+                0.0
+            } else {
+                functions.iter().filter_map(|(_, s)| s.complexity).sum::<u32>() as f64 / functions.len() as f64
+            };
+            out.push(format!(
+                "\nTotal: {} files, {} lines, {} functions, average complexity {:.1}",
+                file_count, total_lines, functions.len(), average
+            ));
+
+            functions.sort_by_key(|(_, s)| std::cmp::Reverse(s.complexity));
+            out.push("\nMost complex functions:".to_string());
+            for (file, symbol) in functions.iter().take(top) {
+                out.push(format!(
+                    "  {:>3}  {} {} ({}:{}, {} lines)",
+                    symbol.complexity.unwrap_or(0),
+                    symbol.kind,
+                    symbol.name,
+                    file,
+                    symbol.start_line,
+                    symbol.end_line - symbol.start_line + 1
+                ));
+            }
+            out.join("\n")
+        })
+        .await
+    }
+}
+
+pub fn register(registry: &ToolRegistry, workspace: Arc<Workspace>) {
+    registry.register(Arc::new(CodeOutline(workspace.clone())));
+    registry.register(Arc::new(FindDefinition(workspace.clone())));
+    registry.register(Arc::new(FindReferences(workspace.clone())));
+    registry.register(Arc::new(CodeMetrics(workspace)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::RunHistory;
+    use crate::tools::approval::Approvals;
+    use crate::LogState;
+    use std::sync::Mutex;
+
+    const RUST_SOURCE: &str = "\
+struct Foo { a: u32 }
+impl Foo {
+    fn bar(&self, x: u32) -> u32 {
+        if x > 1 && self.a > 2 { 1 } else { match x { 0 => 0, _ => 2 } }
+    }
+}
+fn main() { let f = Foo { a: 1 }; f.bar(3); }
+";
+
+    const PYTHON_SOURCE: &str = "\
+class A:
+    def run(self, x):
+        if x or x > 2:
+            return 1
+        return helper(x)
+
+def helper(x):
+    return [i for i in x if i]
+";
+
+    /// Run a code tool on a workspace holding `files`.
+    async fn call(name: &str, files: &[(&str, &[u8])], arguments: Value) -> Result<String, String> {
+        let dir = std::env::temp_dir().join(format!("code-{}-{}", name, std::process::id()));
+        for (path, contents) in files {
+            std::fs::create_dir_all(dir.join(path).parent().unwrap()).unwrap();
+            std::fs::write(dir.join(path), contents).unwrap();
+        }
+        let registry = ToolRegistry::default();
+        register(&registry, Arc::new(Workspace::with_roots(vec![dir.canonicalize().unwrap()])));
+        let tool = registry.resolve(&[GROUP.to_string()]).into_iter().find(|tool| tool.name() == name).unwrap();
+        let (log_state, approvals) = (Mutex::new(LogState { entries: Default::default() }), Approvals::default());
+        let history = RunHistory::open_in_memory().unwrap();
+        let ctx = ToolContext { run_id: "r", node_id: "n", log_state: &log_state, approvals: &approvals, history: &history };
+        let result = tool.call(&ctx, arguments).await;
+        std::fs::remove_dir_all(&dir).unwrap();
+        result
+    }
+
+    #[tokio::test]
+    async fn outlines_rust_and_python() {
+        let files: &[(&str, &[u8])] = &[("src/lib.rs", RUST_SOURCE.as_bytes()), ("app.py", PYTHON_SOURCE.as_bytes())];
+        let outline = call("code_outline", files, json!({"path": "."})).await.unwrap();
+        assert_eq!(outline, "\
+app.py:
+  class A [1-5]
+    def run [2-5]
+  def helper [7-8]
+src/lib.rs:
+  struct Foo [1-1]
+  impl Foo [2-6]
+    fn bar [3-5]
+  fn main [7-7]");
+    }
+
+    #[tokio::test]
+    async fn measures_rust_and_python() {
+        let files: &[(&str, &[u8])] = &[("src/lib.rs", RUST_SOURCE.as_bytes()), ("app.py", PYTHON_SOURCE.as_bytes())];
+        let metrics = call("code_metrics", files, json!({"path": ".", "top": 3})).await.unwrap();
+        // bar: if, &&, two match arms; run: if, or; helper: for and if clauses
+        assert_eq!(metrics, "\
+app.py: 8 lines, 2 functions, max complexity 3
+src/lib.rs: 7 lines, 2 functions, max complexity 5
+
+Total: 2 files, 15 lines, 4 functions, average complexity 3.0
+
+Most complex functions:
+    5  fn bar (src/lib.rs:3, 3 lines)
+    3  def run (app.py:2, 4 lines)
+    3  def helper (app.py:7, 2 lines)");
+    }
+
+    #[tokio::test]
+    async fn definitions_and_references() {
+        let files: &[(&str, &[u8])] = &[("src/lib.rs", RUST_SOURCE.as_bytes()), ("app.py", PYTHON_SOURCE.as_bytes())];
+        let definition = call("find_definition", files, json!({"name": "bar"})).await.unwrap();
+        assert_eq!(definition, "src/lib.rs:3: fn bar\n    fn bar(&self, x: u32) -> u32 {");
+        let references = call("find_references", files, json!({"name": "helper"})).await.unwrap();
+        assert_eq!(references, "app.py:5: return helper(x)");
+    }
+
+    #[tokio::test]
+    async fn unreadable_files_are_reported() {
+        let files: &[(&str, &[u8])] = &[("a.rs", b"fn ok() {}\n"), ("b.rs", b"fn \xff() {}\n")];
+        let outline = call("code_outline", files, json!({"path": "."})).await.unwrap();
+        assert!(outline.starts_with("a.rs:\n  fn ok [1-1]\n\nCould not analyze 1 files:\n  b.rs: failed to read:"), "{}", outline);
+
+        let error = call("code_metrics", &files[1..], json!({"path": "."})).await.unwrap_err();
+        assert!(error.starts_with("Could not analyze any of the 1 files:\n  b.rs: failed to read:"), "{}", error);
+    }
+}
@@ -0,0 +1,281 @@
+// Data tools: query CSV, JSON and Parquet files in the workspace with SQL.
+use async_trait::async_trait;
+use polars::prelude::*;
+use polars::sql::SQLContext;
+use regex::Regex;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock};
+
+use super::files::{str_arg, truncate, Workspace, MAX_READ_BYTES};
+use super::{Tool, ToolContext, ToolRegistry};
+use crate::add_log_entry;
+
+const GROUP: &str = "data_processing";
+const MAX_CELL_CHARS: usize = 80;
+const MAX_PREVIEW_ROWS: usize = 200;
+
+/// SQL table functions that read files directly, bypassing the workspace check.
+static FILE_FUNCTIONS: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)\bread_(csv|parquet|json|ndjson|ipc)\s*\(").unwrap());
+/// Statements that write, which a `WITH` clause may lead into.
+static WRITE_KEYWORDS: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)\b(copy|insert|update|delete|create|drop|alter|truncate)\b").unwrap());
+/// Quoted strings and identifiers, which may contain those words harmlessly.
+static QUOTED: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"'(?:[^']|'')*'|"(?:[^"]|"")*""#).unwrap());
+
+/// Scan a data file lazily, picking the reader from the extension.
+fn load(path: &Path) -> Result<LazyFrame, String> {
+    let source = PlPathRef::from_local_path(path).into_owned();
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or_default().to_lowercase();
+    let frame = match extension.as_str() {
+        "csv" | "tsv" => LazyCsvReader::new(source)
+            .with_has_header(true)
+            .with_separator(if extension == "tsv" { b'\t' } else { b',' })
+            .with_infer_schema_length(Some(1000))
+            .finish(),
+        "parquet" => LazyFrame::scan_parquet(source, ScanArgsParquet::default()),
+        "jsonl" | "ndjson" => LazyJsonLineReader::new(source).finish(),
+        "json" => std::fs::File::open(path)
+            .map_err(PolarsError::from)
+            .and_then(|file| JsonReader::new(file).finish())
+            .map(DataFrame::lazy),
+        _ => return Err(format!("Unsupported data file {}; use CSV, TSV, JSON, NDJSON or Parquet", path.display())),
+    };
+    frame.map_err(|e| format!("Failed to load {}: {}", path.display(), e))
+}
+
+/// Allow a single SELECT, and no reading of files from inside the query.
+fn check_read_only(sql: &str) -> Result<(), String> {
+    let statement = sql.trim().trim_end_matches(';').trim();
+    let unquoted = QUOTED.replace_all(statement, "''");
+    if unquoted.contains(';') {
+        return Err("Only one statement may be run at a time".to_string());
+    }
+    let keyword = statement.split_whitespace().next().unwrap_or_default().to_lowercase();
+    if (keyword != "select" && keyword != "with") || WRITE_KEYWORDS.is_match(&unquoted) {
+        return Err("Only read-only SELECT queries are allowed".to_string());
+    }
+    if FILE_FUNCTIONS.is_match(&unquoted) {
+        return Err("Queries cannot read files directly; pass them in `tables`".to_string());
+    }
+    Ok(())
+}
+
+/// Table name for a file: its stem, with anything but letters, digits and
+/// underscores replaced.
+fn table_name(path: &Path) -> String {
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("data");
+    let name: String = stem.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect();
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("t_{}", name)
+    } else {
+        name
+    }
+}
+
+fn cell(series: &Series, row: usize) -> String {
+    let text = series.str_value(row).map(|v| v.into_owned()).unwrap_or_default();
+    let text = text.replace('|', "\\|").replace('\n', " ");
+    if text.chars().count() > MAX_CELL_CHARS {
+        format!("{}…", text.chars().take(MAX_CELL_CHARS).collect::<String>())
+    } else {
+        text
+    }
+}
+
+/// Markdown table of the first `rows` rows.
+fn preview(df: &DataFrame, rows: usize) -> String {
+    let columns: Vec<&Series> = df.get_columns().iter().map(|c| c.as_materialized_series()).collect();
+    let mut lines = vec![
+        format!("| {} |", columns.iter().map(|s| s.name().as_str()).collect::<Vec<_>>().join(" | ")),
+        format!("|{}|", vec!["---"; columns.len()].join("|")),
+    ];
+    for row in 0..rows.min(df.height()) {
+        lines.push(format!("| {} |", columns.iter().map(|s| cell(s, row)).collect::<Vec<_>>().join(" | ")));
+    }
+    lines.join("\n")
+}
+
+/// One line per column: type, nulls and, for numbers, the range and mean.
+fn summarize(df: &DataFrame) -> String {
+    let mut lines = vec![format!("{} rows × {} columns", df.height(), df.width())];
+    for column in df.get_columns() {
+        let series = column.as_materialized_series();
+        let mut line = format!("- {} ({}): {} null", series.name(), series.dtype(), series.null_count());
+        if series.dtype().is_primitive_numeric() {
+            let min = series.min::<f64>().ok().flatten();
+            let max = series.max::<f64>().ok().flatten();
+            if let (Some(min), Some(max), Some(mean)) = (min, max, series.mean()) {
+                line.push_str(&format!(", min {}, max {}, mean {:.4}", min, max, mean));
+            }
+        }
+        lines.push(line);
+    }
+    lines.join("\n")
+}
+
+fn to_csv(df: &mut DataFrame) -> Result<Vec<u8>, String> {
+    let mut buffer = Vec::new();
+    CsvWriter::new(&mut buffer).finish(df).map_err(|e| e.to_string())?;
+    Ok(buffer)
+}
+
+/// Workspace files named in the `tables` argument, keyed by table name.
+fn resolve_tables(workspace: &Workspace, arguments: &Value) -> Result<Vec<(String, PathBuf)>, String> {
+    let tables = match &arguments["tables"] {
+        Value::Object(map) => map.iter()
+            .map(|(name, path)| {
+                let path = path.as_str().ok_or_else(|| format!("Table {} needs a file path", name))?;
+                Ok((name.clone(), workspace.resolve(path)?))
+            })
+            .collect::<Result<Vec<_>, String>>()?,
+        Value::Array(paths) => paths.iter()
+            .map(|path| {
+                let path = workspace.resolve(path.as_str().ok_or("Table paths must be strings")?)?;
+                Ok((table_name(&path), path))
+            })
+            .collect::<Result<Vec<_>, String>>()?,
+        _ => return Err("Pass the files to query in `tables`".to_string()),
+    };
+    if tables.is_empty() {
+        return Err("Pass the files to query in `tables`".to_string());
+    }
+    Ok(tables)
+}
+
+pub struct DescribeData(Arc<Workspace>);
+
+#[async_trait]
+impl Tool for DescribeData {
+    fn name(&self) -> &str {
+        "describe_data"
+    }
+
+    fn group(&self) -> &str {
+        GROUP
+    }
+
+    fn description(&self) -> &str {
+        "Show the columns, types, summary statistics and first rows of a CSV, JSON or Parquet file."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": { "type": "string", "description": "Data file, relative to the workspace root" },
+                "rows": { "type": "integer", "description": "Number of sample rows" }
+            },
+            "required": ["path"]
+        })
+    }
+
+    async fn call(&self, ctx: &ToolContext<'_>, arguments: Value) -> Result<String, String> {
+        let path = self.0.resolve(str_arg(&arguments, "path")?)?;
+        add_log_entry(ctx.log_state, "info", &format!("describe_data {}", path.display()), "tools")?;
+        let rows = (arguments["rows"].as_u64().unwrap_or(5) as usize).min(MAX_PREVIEW_ROWS);
+        let text = tokio::task::spawn_blocking(move || -> Result<String, String> {
+            let df = load(&path)?.collect().map_err(|e| e.to_string())?;
+            Ok(format!("Table {}: {}\n\n{}", table_name(&path), summarize(&df), preview(&df, rows)))
+        })
+        .await
+        .map_err(|e| e.to_string())??;
+        Ok(truncate(text, MAX_READ_BYTES))
+    }
+}
+
+pub struct QueryData(Arc<Workspace>);
+
+#[async_trait]
+impl Tool for QueryData {
+    fn name(&self) -> &str {
+        "query_data"
+    }
+
+    fn group(&self) -> &str {
+        GROUP
+    }
+
+    fn description(&self) -> &str {
+        "Run a read-only SQL SELECT over CSV, JSON or Parquet files. Returns a summary and the first rows; the full result is saved as a CSV artifact."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "sql": { "type": "string", "description": "A single SELECT statement" },
+                "tables": {
+                    "type": "object",
+                    "description": "Table name to data file path, relative to the workspace root",
+                    "additionalProperties": { "type": "string" }
+                },
+                "max_rows": { "type": "integer", "description": "Rows to show in the reply" }
+            },
+            "required": ["sql", "tables"]
+        })
+    }
+
+    async fn call(&self, ctx: &ToolContext<'_>, arguments: Value) -> Result<String, String> {
+        let sql = str_arg(&arguments, "sql")?.to_string();
+        check_read_only(&sql)?;
+        let tables = resolve_tables(&self.0, &arguments)?;
+        let max_rows = (arguments["max_rows"].as_u64().unwrap_or(20) as usize).min(MAX_PREVIEW_ROWS);
+        for (name, path) in &tables {
+            add_log_entry(ctx.log_state, "info", &format!("query_data table {} = {}", name, path.display()), "tools")?;
+        }
+        add_log_entry(ctx.log_state, "debug", &format!("query_data: {}", sql), "tools")?;
+
+        let (summary, table, csv) = tokio::task::spawn_blocking(move || -> Result<_, String> {
+            let mut context = SQLContext::new();
+            for (name, path) in &tables {
+                context.register(name, load(path)?);
+            }
+            let mut df = context.execute(&sql)
+                .and_then(LazyFrame::collect)
+                .map_err(|e| format!("Query failed: {}", e))?;
+            Ok((summarize(&df), preview(&df, max_rows), to_csv(&mut df)?))
+        })
+        .await
+        .map_err(|e| e.to_string())??;
+
+        let name = format!("query-{}.csv", chrono::Local::now().format("%H%M%S%3f"));
+        let saved = match ctx.history.save_artifact(ctx.run_id, ctx.node_id, &name, "text/csv", &csv) {
+            Ok(artifact) => format!("Full result saved as artifact {} ({} bytes).", artifact.name, artifact.size),
+            Err(e) => {
+                add_log_entry(ctx.log_state, "warn", &format!("query_data could not save its result: {}", e), "tools")?;
+                "The full result could not be saved.".to_string()
+            }
+        };
+        Ok(truncate(format!("{}\n\n{}\n\n{}", summary, table, saved), MAX_READ_BYTES))
+    }
+}
+
+pub fn register(registry: &ToolRegistry, workspace: Arc<Workspace>) {
+    registry.register(Arc::new(DescribeData(workspace.clone())));
+    registry.register(Arc::new(QueryData(workspace)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_only_allows_selects() {
+        assert!(check_read_only("SELECT * FROM sales;").is_ok());
+        assert!(check_read_only("with t as (select region from sales) select * from t").is_ok());
+        assert!(check_read_only("SELECT 'copy; read_csv(x)' AS note, \"update\" FROM sales").is_ok());
+    }
+
+    #[test]
+    fn read_only_rejects_writes_and_file_reads() {
+        assert!(check_read_only("DROP TABLE sales").is_err());
+        assert!(check_read_only("SELECT 1; SELECT 2").is_err());
+        assert!(check_read_only("WITH t AS (SELECT 1) COPY t TO 'out.csv'").is_err());
+        assert!(check_read_only("with t as (select 1) insert into sales select * from t").is_err());
+        assert!(check_read_only("SELECT * FROM read_csv('/etc/passwd')").is_err());
+        assert!(check_read_only("WITH t AS (SELECT * FROM READ_PARQUET ('a.parquet')) SELECT * FROM t").is_err());
+        assert!(check_read_only("SELECT * FROM read_ndjson('a.jsonl')").is_err());
+    }
+}
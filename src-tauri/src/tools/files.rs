@@ -0,0 +1,607 @@
+// File-operation tools confined to the workspace roots the user has approved.
+use async_trait::async_trait;
+use regex::Regex;
+use serde_json::{json, Value};
+use similar::TextDiff;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, Mutex, RwLock};
+use tauri::{AppHandle, State};
+use tauri_plugin_fs::FsExt;
+use walkdir::{DirEntry, WalkDir};
+
+use super::{Tool, ToolContext, ToolRegistry};
+use crate::{add_log_entry, LogState};
+
+const GROUP: &str = "file_operations";
+pub(super) const MAX_READ_BYTES: usize = 64 * 1024;
+const MAX_SEARCH_FILE_BYTES: u64 = 1024 * 1024;
+/// A glob metacharacter escaped by `glob::Pattern::escape`, e.g. `[*]`.
+static ESCAPED_GLOB: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\[([\[\]*?])\]").unwrap());
+
+pub(crate) const SKIPPED_DIRS: &[&str] = &[".git", "node_modules", "target"];
+
+/// Directories the file tools may touch. Each root is a recursive grant in
+/// the fs scope, which the persisted-scope plugin keeps across restarts. The
+/// scope cannot drop a grant, so removed roots are remembered in `store` and
+/// left out when the roots are restored.
+pub struct Workspace {
+    roots: RwLock<Vec<PathBuf>>,
+    removed: RwLock<Vec<PathBuf>>,
+    store: Option<PathBuf>,
+}
+
+impl Workspace {
+    pub fn with_roots(roots: Vec<PathBuf>) -> Self {
+        Self {
+            roots: RwLock::new(roots),
+            removed: RwLock::new(Vec::new()),
+            store: None,
+        }
+    }
+
+    /// The roots granted in the fs scope, as restored by persisted-scope,
+    /// less those removed since.
+    pub fn from_scope(scope: &tauri::fs::Scope, store: PathBuf) -> Self {
+        let patterns = scope.allowed_patterns().iter().map(|pattern| pattern.as_str().to_string()).collect::<Vec<_>>();
+        Self::restore(scope_roots(&patterns, |dir| scope.is_allowed(dir)), store)
+    }
+
+    fn restore(granted: Vec<PathBuf>, store: PathBuf) -> Self {
+        let removed: Vec<PathBuf> = std::fs::read_to_string(&store)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+        Self {
+            roots: RwLock::new(granted.into_iter().filter(|root| !removed.contains(root)).collect()),
+            removed: RwLock::new(removed),
+            store: Some(store),
+        }
+    }
+
+    fn save_removed(&self, removed: &[PathBuf]) -> Result<(), String> {
+        let Some(store) = &self.store else {
+            return Ok(());
+        };
+        let json = serde_json::to_string_pretty(removed).map_err(|e| e.to_string())?;
+        std::fs::write(store, json).map_err(|e| format!("Failed to save the removed workspace roots: {}", e))
+    }
+
+    pub fn roots(&self) -> Vec<PathBuf> {
+        self.roots.read().map(|roots| roots.clone()).unwrap_or_default()
+    }
+
+    pub fn add_root(&self, path: &Path) -> Result<PathBuf, String> {
+        let root = path.canonicalize()
+            .map_err(|e| format!("Cannot use {} as a workspace root: {}", path.display(), e))?;
+        if !root.is_dir() {
+            return Err(format!("{} is not a directory", root.display()));
+        }
+        let mut removed = self.removed.write().map_err(|e| e.to_string())?;
+        if removed.contains(&root) {
+            removed.retain(|existing| *existing != root);
+            self.save_removed(&removed)?;
+        }
+        let mut roots = self.roots.write().map_err(|e| e.to_string())?;
+        if !roots.contains(&root) {
+            roots.push(root.clone());
+        }
+        Ok(root)
+    }
+
+    /// Drop a root, returning it as it was stored.
+    pub fn remove_root(&self, path: &Path) -> Result<PathBuf, String> {
+        let root = canonicalize_lenient(path)?;
+        let mut roots = self.roots.write().map_err(|e| e.to_string())?;
+        let count = roots.len();
+        roots.retain(|existing| *existing != root);
+        if roots.len() == count {
+            return Err(format!("{} is not a workspace root", path.display()));
+        }
+        let mut removed = self.removed.write().map_err(|e| e.to_string())?;
+        removed.push(root.clone());
+        self.save_removed(&removed)?;
+        Ok(root)
+    }
+
+    /// Resolve a path given by a model, relative paths being taken from the
+    /// first root, and check that it stays inside a root.
+    pub fn resolve(&self, path: &str) -> Result<PathBuf, String> {
+        let roots = self.roots();
+        let first = roots.first().ok_or_else(|| "No workspace roots have been approved".to_string())?;
+        let requested = Path::new(path);
+        let joined = if requested.is_absolute() {
+            requested.to_path_buf()
+        } else {
+            first.join(requested)
+        };
+        let resolved = canonicalize_lenient(&joined)?;
+        if roots.iter().any(|root| resolved.starts_with(root)) {
+            Ok(resolved)
+        } else {
+            Err(format!("{} is outside the workspace", path))
+        }
+    }
+
+    pub(super) fn relative<'a>(&self, path: &'a Path) -> &'a Path {
+        self.roots().iter()
+            .find_map(|root| path.strip_prefix(root).ok())
+            .unwrap_or(path)
+    }
+}
+
+/// Directories granted recursively by the fs scope's allowed `patterns`,
+/// which end in `/**` and escape glob characters as `[*]`, skipping those
+/// `allowed` rejects because a later removal forbade them.
+fn scope_roots(patterns: &[String], allowed: impl Fn(&Path) -> bool) -> Vec<PathBuf> {
+    let suffix = format!("{}**", std::path::MAIN_SEPARATOR);
+    let mut roots: Vec<PathBuf> = patterns.iter()
+        .filter_map(|pattern| pattern.strip_suffix(&suffix))
+        .map(|dir| PathBuf::from(ESCAPED_GLOB.replace_all(dir, "$1").into_owned()))
+        .filter(|dir| dir.is_dir() && allowed(dir))
+        .collect();
+    roots.sort();
+    roots.dedup();
+    roots
+}
+
+/// Canonicalize the longest existing ancestor and append the remaining names,
+/// so paths of files about to be created can be checked too.
+fn canonicalize_lenient(path: &Path) -> Result<PathBuf, String> {
+    let mut base = path.to_path_buf();
+    let mut missing = Vec::new();
+    while !base.exists() {
+        let name = base.file_name()
+            .ok_or_else(|| format!("Invalid path {}", path.display()))?
+            .to_owned();
+        missing.push(name);
+        base.pop();
+    }
+    let mut resolved = base.canonicalize().map_err(|e| format!("Invalid path {}: {}", path.display(), e))?;
+    resolved.extend(missing.iter().rev());
+    Ok(resolved)
+}
+
+pub(super) fn str_arg<'a>(arguments: &'a Value, name: &str) -> Result<&'a str, String> {
+    arguments[name].as_str().ok_or_else(|| format!("Missing string argument '{}'", name))
+}
+
+fn log_operation(log_state: &Mutex<LogState>, tool: &str, path: &Path) -> Result<(), String> {
+    add_log_entry(log_state, "info", &format!("{} {}", tool, path.display()), "tools")
+}
+
+pub(crate) fn is_skipped(entry: &DirEntry) -> bool {
+    entry.depth() > 0
+        && entry.file_type().is_dir()
+        && entry.file_name().to_str().is_some_and(|name| SKIPPED_DIRS.contains(&name))
+}
+
+pub(super) fn truncate(mut text: String, max_bytes: usize) -> String {
+    if text.len() > max_bytes {
+        let mut end = max_bytes;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        text.truncate(end);
+        text.push_str("\n[truncated]");
+    }
+    text
+}
+
+/// Show the diff of a write, then apply it once the user approves. With
+/// `dry_run` only the diff is returned.
+async fn propose_write(
+    ctx: &ToolContext<'_>,
+    workspace: &Workspace,
+    tool: &str,
+    path: &Path,
+    current: &str,
+    updated: &str,
+    dry_run: bool,
+) -> Result<String, String> {
+    let display = workspace.relative(path).display().to_string();
+    let diff = TextDiff::from_lines(current, updated)
+        .unified_diff()
+        .header(&display, &display)
+        .to_string();
+    if dry_run {
+        add_log_entry(ctx.log_state, "info", &format!("{} dry run for {}", tool, path.display()), "tools")?;
+        return Ok(if diff.is_empty() { "No changes".to_string() } else { diff });
+    }
+
+    let approved = ctx.approvals
+        .request(ctx.run_id, ctx.node_id, tool, format!("Write {}", display), diff.clone())
+        .await;
+    if !approved {
+        add_log_entry(ctx.log_state, "warn", &format!("{} rejected for {}", tool, path.display()), "tools")?;
+        return Err(format!("The user rejected the change to {}", display));
+    }
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    std::fs::write(path, updated).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    log_operation(ctx.log_state, tool, path)?;
+    Ok(format!("Applied change to {}:\n{}", display, diff))
+}
+
+pub struct ReadFile(Arc<Workspace>);
+
+#[async_trait]
+impl Tool for ReadFile {
+    fn name(&self) -> &str {
+        "read_file"
+    }
+
+    fn group(&self) -> &str {
+        GROUP
+    }
+
+    fn description(&self) -> &str {
+        "Read a text file from the workspace, optionally a range of lines."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": { "type": "string", "description": "File path, relative to the workspace root" },
+                "start_line": { "type": "integer", "description": "First line to return, starting at 1" },
+                "max_lines": { "type": "integer", "description": "Number of lines to return" }
+            },
+            "required": ["path"]
+        })
+    }
+
+    async fn call(&self, ctx: &ToolContext<'_>, arguments: Value) -> Result<String, String> {
+        let path = self.0.resolve(str_arg(&arguments, "path")?)?;
+        log_operation(ctx.log_state, self.name(), &path)?;
+        let content = std::fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let start = arguments["start_line"].as_u64().unwrap_or(1).max(1) as usize;
+        let max_lines = arguments["max_lines"].as_u64().map_or(usize::MAX, |n| n as usize);
+        let text = content.lines().skip(start - 1).take(max_lines).collect::<Vec<_>>().join("\n");
+        Ok(truncate(text, MAX_READ_BYTES))
+    }
+}
+
+pub struct ListDirectory(Arc<Workspace>);
+
+#[async_trait]
+impl Tool for ListDirectory {
+    fn name(&self) -> &str {
+        "list_directory"
+    }
+
+    fn group(&self) -> &str {
+        GROUP
+    }
+
+    fn description(&self) -> &str {
+        "List the files and directories under a workspace directory."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": { "type": "string", "description": "Directory path, relative to the workspace root. Defaults to the root." },
+                "recursive": { "type": "boolean" },
+                "max_entries": { "type": "integer" }
+            }
+        })
+    }
+
+    async fn call(&self, ctx: &ToolContext<'_>, arguments: Value) -> Result<String, String> {
+        let path = self.0.resolve(arguments["path"].as_str().unwrap_or("."))?;
+        log_operation(ctx.log_state, self.name(), &path)?;
+        let recursive = arguments["recursive"].as_bool().unwrap_or(false);
+        let max_entries = arguments["max_entries"].as_u64().unwrap_or(200) as usize;
+
+        let mut lines = Vec::new();
+        let walker = WalkDir::new(&path)
+            .min_depth(1)
+            .max_depth(if recursive { usize::MAX } else { 1 })
+            .sort_by_file_name()
+            .into_iter()
+            .filter_entry(|entry| !is_skipped(entry));
+        for entry in walker.filter_map(Result::ok) {
+            if lines.len() == max_entries {
+                lines.push(format!("[stopped after {} entries]", max_entries));
+                break;
+            }
+            let relative = entry.path().strip_prefix(&path).unwrap_or(entry.path());
+            let suffix = if entry.file_type().is_dir() { "/" } else { "" };
+            lines.push(format!("{}{}", relative.display(), suffix));
+        }
+        Ok(if lines.is_empty() { "Empty directory".to_string() } else { lines.join("\n") })
+    }
+}
+
+pub struct SearchFiles(Arc<Workspace>);
+
+#[async_trait]
+impl Tool for SearchFiles {
+    fn name(&self) -> &str {
+        "search_files"
+    }
+
+    fn group(&self) -> &str {
+        GROUP
+    }
+
+    fn description(&self) -> &str {
+        "Search workspace files for lines matching a regular expression."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "pattern": { "type": "string", "description": "Regular expression to look for" },
+                "path": { "type": "string", "description": "Directory to search, relative to the workspace root" },
+                "max_results": { "type": "integer" }
+            },
+            "required": ["pattern"]
+        })
+    }
+
+    async fn call(&self, ctx: &ToolContext<'_>, arguments: Value) -> Result<String, String> {
+        let pattern = str_arg(&arguments, "pattern")?;
+        let re = Regex::new(pattern).map_err(|e| format!("Invalid regex '{}': {}", pattern, e))?;
+        let path = self.0.resolve(arguments["path"].as_str().unwrap_or("."))?;
+        log_operation(ctx.log_state, self.name(), &path)?;
+        let max_results = arguments["max_results"].as_u64().unwrap_or(50) as usize;
+
+        let mut matches = Vec::new();
+        let files = WalkDir::new(&path)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_entry(|entry| !is_skipped(entry))
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_type().is_file())
+            .filter(|entry| entry.metadata().is_ok_and(|m| m.len() <= MAX_SEARCH_FILE_BYTES));
+        'files: for entry in files {
+            let Ok(content) = std::fs::read_to_string(entry.path()) else {
+                continue;
+            };
+            let relative = self.0.relative(entry.path());
+            for (i, line) in content.lines().enumerate() {
+                if re.is_match(line) {
+                    matches.push(format!("{}:{}: {}", relative.display(), i + 1, line.trim()));
+                    if matches.len() == max_results {
+                        break 'files;
+                    }
+                }
+            }
+        }
+        Ok(if matches.is_empty() { "No matches".to_string() } else { matches.join("\n") })
+    }
+}
+
+pub struct WriteFile(Arc<Workspace>);
+
+#[async_trait]
+impl Tool for WriteFile {
+    fn name(&self) -> &str {
+        "write_file"
+    }
+
+    fn group(&self) -> &str {
+        GROUP
+    }
+
+    fn description(&self) -> &str {
+        "Create or overwrite a workspace file. The user reviews the diff before it is written; set dry_run to only see the diff."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": { "type": "string", "description": "File path, relative to the workspace root" },
+                "content": { "type": "string", "description": "The complete new file content" },
+                "dry_run": { "type": "boolean" }
+            },
+            "required": ["path", "content"]
+        })
+    }
+
+    async fn call(&self, ctx: &ToolContext<'_>, arguments: Value) -> Result<String, String> {
+        let path = self.0.resolve(str_arg(&arguments, "path")?)?;
+        let content = str_arg(&arguments, "content")?;
+        let current = std::fs::read_to_string(&path).unwrap_or_default();
+        let dry_run = arguments["dry_run"].as_bool().unwrap_or(false);
+        propose_write(ctx, &self.0, self.name(), &path, &current, content, dry_run).await
+    }
+}
+
+pub struct PatchFile(Arc<Workspace>);
+
+#[async_trait]
+impl Tool for PatchFile {
+    fn name(&self) -> &str {
+        "patch_file"
+    }
+
+    fn group(&self) -> &str {
+        GROUP
+    }
+
+    fn description(&self) -> &str {
+        "Replace one exact occurrence of text in a workspace file. The user reviews the diff before it is written; set dry_run to only see the diff."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": { "type": "string", "description": "File path, relative to the workspace root" },
+                "find": { "type": "string", "description": "Text to replace; must occur exactly once" },
+                "replace": { "type": "string" },
+                "dry_run": { "type": "boolean" }
+            },
+            "required": ["path", "find", "replace"]
+        })
+    }
+
+    async fn call(&self, ctx: &ToolContext<'_>, arguments: Value) -> Result<String, String> {
+        let path = self.0.resolve(str_arg(&arguments, "path")?)?;
+        let find = str_arg(&arguments, "find")?;
+        let replace = str_arg(&arguments, "replace")?;
+        let current = std::fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        match current.matches(find).count() {
+            1 => {}
+            0 => return Err("The text to replace was not found".to_string()),
+            n => return Err(format!("The text to replace occurs {} times; include more context", n)),
+        }
+        let updated = current.replacen(find, replace, 1);
+        let dry_run = arguments["dry_run"].as_bool().unwrap_or(false);
+        propose_write(ctx, &self.0, self.name(), &path, &current, &updated, dry_run).await
+    }
+}
+
+pub fn register(registry: &ToolRegistry, workspace: Arc<Workspace>) {
+    registry.register(Arc::new(ReadFile(workspace.clone())));
+    registry.register(Arc::new(ListDirectory(workspace.clone())));
+    registry.register(Arc::new(SearchFiles(workspace.clone())));
+    registry.register(Arc::new(WriteFile(workspace.clone())));
+    registry.register(Arc::new(PatchFile(workspace)));
+}
+
+#[tauri::command]
+pub async fn list_workspace_roots(workspace: State<'_, Arc<Workspace>>) -> Result<Vec<PathBuf>, String> {
+    Ok(workspace.roots())
+}
+
+#[tauri::command]
+pub async fn add_workspace_root(
+    app: AppHandle,
+    path: String,
+    workspace: State<'_, Arc<Workspace>>,
+    log_state: State<'_, Mutex<LogState>>,
+) -> Result<PathBuf, String> {
+    let requested = canonicalize_lenient(Path::new(&path))?;
+    if app.fs_scope().is_forbidden(&requested) {
+        return Err(format!("{} is forbidden in the file system scope and cannot be added", requested.display()));
+    }
+    let root = workspace.add_root(Path::new(&path))?;
+    app.fs_scope().allow_directory(&root, true).map_err(|e| e.to_string())?;
+    add_log_entry(&log_state, "info", &format!("Added workspace root {}", root.display()), "tools")?;
+    Ok(root)
+}
+
+/// Stop the file tools from using a root. Its fs scope grant stays, since a
+/// forbidden directory could never be granted again, but the workspace
+/// remembers the removal.
+#[tauri::command]
+pub async fn remove_workspace_root(
+    path: String,
+    workspace: State<'_, Arc<Workspace>>,
+    log_state: State<'_, Mutex<LogState>>,
+) -> Result<(), String> {
+    let root = workspace.remove_root(Path::new(&path))?;
+    add_log_entry(&log_state, "info", &format!("Removed workspace root {}", root.display()), "tools")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::RunHistory;
+    use crate::tools::approval::Approvals;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("workspace-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        dir.canonicalize().unwrap()
+    }
+
+    #[test]
+    fn resolve_stays_inside_roots() {
+        let dir = temp_dir("resolve");
+        let workspace = Workspace::with_roots(vec![dir.clone()]);
+        assert_eq!(workspace.resolve("sub/new.txt").unwrap(), dir.join("sub/new.txt"));
+        assert!(workspace.resolve("../outside").is_err());
+        assert!(workspace.resolve("new/../../outside").is_err());
+        assert!(workspace.resolve("/etc/passwd").is_err());
+        assert!(Workspace::with_roots(Vec::new()).resolve("a.txt").is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn remove_root_canonicalizes_like_add_root() {
+        let dir = temp_dir("remove");
+        let workspace = Workspace::with_roots(Vec::new());
+        workspace.add_root(&dir.join("sub")).unwrap();
+        let removed = workspace.remove_root(&dir.join("sub/../sub/.")).unwrap();
+        assert_eq!(removed, dir.join("sub"));
+        assert!(workspace.roots().is_empty());
+        assert!(workspace.remove_root(&dir).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn scope_roots_unescape_and_skip_forbidden() {
+        let dir = temp_dir("scope");
+        let odd = dir.join("a[1]*");
+        std::fs::create_dir_all(&odd).unwrap();
+        let sep = std::path::MAIN_SEPARATOR;
+        let patterns = vec![
+            dir.join("sub").display().to_string(),
+            format!("{}{}**", dir.join("sub").display(), sep),
+            format!("{}{}a[[]1[]][*]{}**", dir.display(), sep, sep),
+            format!("{}{}missing{}**", dir.display(), sep, sep),
+            format!("{}{}**", dir.display(), sep),
+        ];
+        let roots = scope_roots(&patterns, |path| path != dir.as_path());
+        assert_eq!(roots, vec![odd, dir.join("sub")]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn removed_roots_stay_removed_until_added_again() {
+        let dir = temp_dir("removed");
+        let store = dir.join("workspace.json");
+        let workspace = Workspace::restore(vec![dir.clone(), dir.join("sub")], store.clone());
+        workspace.remove_root(&dir.join("sub")).unwrap();
+
+        let restored = Workspace::restore(vec![dir.clone(), dir.join("sub")], store.clone());
+        assert_eq!(restored.roots(), vec![dir.clone()]);
+        restored.add_root(&dir.join("sub")).unwrap();
+        let restored = Workspace::restore(vec![dir.clone(), dir.join("sub")], store);
+        assert_eq!(restored.roots(), vec![dir.clone(), dir.join("sub")]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn dry_runs_and_writes_are_logged_apart() {
+        let dir = temp_dir("dry-run");
+        let registry = ToolRegistry::default();
+        register(&registry, Arc::new(Workspace::with_roots(vec![dir.clone()])));
+        let write_file = registry.get("write_file").unwrap();
+        let (log_state, approvals) = (Mutex::new(LogState { entries: Default::default() }), Approvals::default());
+        let history = RunHistory::open_in_memory().unwrap();
+        let ctx = ToolContext { run_id: "r", node_id: "n", log_state: &log_state, approvals: &approvals, history: &history };
+        let messages = || log_state.lock().unwrap().entries.iter().map(|e| e.message.clone()).collect::<Vec<_>>();
+        let path = dir.join("a.txt");
+
+        let diff = write_file.call(&ctx, json!({"path": "a.txt", "content": "hi\n", "dry_run": true})).await.unwrap();
+        assert!(diff.contains("+hi"), "{}", diff);
+        assert!(!path.exists());
+        assert_eq!(messages(), [format!("write_file dry run for {}", path.display())]);
+
+        let approve = async {
+            loop {
+                if let Some(request) = approvals.pending().pop() {
+                    return approvals.resolve(&request.id, true).unwrap();
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+            }
+        };
+        let (result, ()) = tokio::join!(write_file.call(&ctx, json!({"path": "a.txt", "content": "hi\n"})), approve);
+        assert!(result.unwrap().starts_with("Applied change to"));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "hi\n");
+        assert_eq!(messages()[1..], [format!("write_file {}", path.display())]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,388 @@
+// Shell commands for agent nodes, run through the shell plugin.
+use async_trait::async_trait;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use tauri::{AppHandle, State};
+use tauri_plugin_shell::process::CommandEvent;
+use tauri_plugin_shell::ShellExt;
+
+use super::files::Workspace;
+use super::{Tool, ToolContext, ToolRegistry};
+use crate::{add_log_entry, LogState};
+
+/// Characters that chain or redirect commands. A command containing them is
+/// never auto-approved, whatever the allowlist says.
+const SHELL_METACHARACTERS: &[char] = &[';', '&', '|', '$', '`', '<', '>', '(', ')', '\n'];
+
+/// Options that write files or run other programs, e.g. `git diff
+/// --output=<path>` or `git difftool --extcmd=<cmd>`. A command passing one
+/// always needs approval.
+const UNSAFE_OPTIONS: &[&str] = &["--output", "--ext-diff", "--extcmd", "-x"];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShellSettings {
+    /// Command lines that run without asking. `*` matches anything, except
+    /// that a trailing `*` only matches whole further arguments.
+    #[serde(default = "default_allowlist")]
+    pub allowlist: Vec<String>,
+    /// Upper bound for a command's run time; models may ask for less.
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+    /// Captured stdout and stderr are each cut off beyond this.
+    #[serde(default = "default_max_output_bytes")]
+    pub max_output_bytes: usize,
+    /// Pass the app's environment on to commands. Otherwise only PATH and
+    /// the variables given in the call are set.
+    #[serde(default = "default_inherit_env")]
+    pub inherit_env: bool,
+}
+
+fn default_allowlist() -> Vec<String> {
+    ["pwd", "ls *", "git status *", "git diff *", "git log *"]
+        .iter()
+        .map(|pattern| pattern.to_string())
+        .collect()
+}
+
+fn default_timeout_secs() -> u64 {
+    60
+}
+
+fn default_max_output_bytes() -> usize {
+    32 * 1024
+}
+
+fn default_inherit_env() -> bool {
+    true
+}
+
+impl Default for ShellSettings {
+    fn default() -> Self {
+        Self {
+            allowlist: default_allowlist(),
+            timeout_secs: default_timeout_secs(),
+            max_output_bytes: default_max_output_bytes(),
+            inherit_env: default_inherit_env(),
+        }
+    }
+}
+
+impl ShellSettings {
+    pub fn is_allowed(&self, command: &str) -> bool {
+        let command = command.trim();
+        !command.contains(SHELL_METACHARACTERS)
+            && !command.split_whitespace().any(is_unsafe_option)
+            && self.allowlist.iter().any(|pattern| pattern_matches(pattern, command))
+    }
+}
+
+/// Match a command against an allowlist pattern. A trailing `*` must start
+/// at a word boundary, so `git diff *` (or `git diff*`) allows `git diff` and
+/// `git diff --stat` but not `git difftool`.
+fn pattern_matches(pattern: &str, command: &str) -> bool {
+    let pattern = pattern.trim();
+    let regex = match pattern.strip_suffix('*') {
+        Some(prefix) => format!(r"^{}(?:\s.*)?$", glob_regex(prefix.trim_end())),
+        None => format!("^{}$", glob_regex(pattern)),
+    };
+    Regex::new(&regex).is_ok_and(|re| re.is_match(command))
+}
+
+fn glob_regex(pattern: &str) -> String {
+    regex::escape(pattern).replace(r"\*", ".*")
+}
+
+/// Whether an argument passes one of the `UNSAFE_OPTIONS`, allowing for
+/// `--output=<path>`, git's abbreviated long options such as `--out`, and
+/// short options bundled or given a value, as in `-yx` or `-x<cmd>`.
+fn is_unsafe_option(argument: &str) -> bool {
+    if let Some(name) = argument.strip_prefix("--") {
+        let name = name.split('=').next().unwrap_or_default();
+        !name.is_empty()
+            && UNSAFE_OPTIONS.iter()
+                .filter_map(|option| option.strip_prefix("--"))
+                .any(|option| option.starts_with(name))
+    } else if let Some(flags) = argument.strip_prefix('-') {
+        UNSAFE_OPTIONS.iter()
+            .filter(|option| !option.starts_with("--"))
+            .any(|option| flags.contains(&option[1..]))
+    } else {
+        false
+    }
+}
+
+/// Shell settings, saved as JSON next to the other app data.
+pub struct ShellPolicy {
+    settings: RwLock<ShellSettings>,
+    store: PathBuf,
+}
+
+impl ShellPolicy {
+    pub fn load(store: PathBuf) -> Self {
+        let settings = std::fs::read_to_string(&store)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+        Self {
+            settings: RwLock::new(settings),
+            store,
+        }
+    }
+
+    pub fn settings(&self) -> ShellSettings {
+        self.settings.read().map(|s| s.clone()).unwrap_or_default()
+    }
+
+    pub fn update(&self, settings: ShellSettings) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(&settings).map_err(|e| e.to_string())?;
+        std::fs::write(&self.store, json).map_err(|e| format!("Failed to save shell settings: {}", e))?;
+        *self.settings.write().map_err(|e| e.to_string())? = settings;
+        Ok(())
+    }
+}
+
+/// Commands currently running, by pid, so the system status can list them.
+#[derive(Default)]
+pub struct RunningCommands {
+    children: Mutex<HashMap<u32, String>>,
+}
+
+impl RunningCommands {
+    fn insert(&self, pid: u32, command: &str) {
+        if let Ok(mut children) = self.children.lock() {
+            children.insert(pid, command.to_string());
+        }
+    }
+
+    fn remove(&self, pid: u32) {
+        if let Ok(mut children) = self.children.lock() {
+            children.remove(&pid);
+        }
+    }
+
+    pub fn pids(&self) -> Vec<u32> {
+        self.children.lock().map(|c| c.keys().copied().collect()).unwrap_or_default()
+    }
+
+    pub fn command(&self, pid: u32) -> Option<String> {
+        self.children.lock().ok().and_then(|c| c.get(&pid).cloned())
+    }
+}
+
+/// Output of one stream, cut off at the size cap.
+struct Capture {
+    bytes: Vec<u8>,
+    dropped: usize,
+}
+
+impl Capture {
+    fn new() -> Self {
+        Self { bytes: Vec::new(), dropped: 0 }
+    }
+
+    fn push(&mut self, chunk: &[u8], limit: usize) {
+        let room = limit.saturating_sub(self.bytes.len());
+        let taken = room.min(chunk.len());
+        self.bytes.extend_from_slice(&chunk[..taken]);
+        self.dropped += chunk.len() - taken;
+    }
+
+    fn text(&self) -> String {
+        let mut text = String::from_utf8_lossy(&self.bytes).into_owned();
+        if self.dropped > 0 {
+            text.push_str(&format!("\n[{} more bytes truncated]", self.dropped));
+        }
+        text
+    }
+}
+
+pub struct RunCommand {
+    app: AppHandle,
+    workspace: Arc<Workspace>,
+    policy: Arc<ShellPolicy>,
+    running: Arc<RunningCommands>,
+}
+
+#[async_trait]
+impl Tool for RunCommand {
+    fn name(&self) -> &str {
+        "run_command"
+    }
+
+    fn group(&self) -> &str {
+        "shell"
+    }
+
+    fn description(&self) -> &str {
+        "Run a shell command in the workspace and return its exit code, stdout and stderr. Commands outside the allowlist need the user's approval."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "command": { "type": "string", "description": "Command line, run with sh -c" },
+                "cwd": { "type": "string", "description": "Working directory, relative to the workspace root" },
+                "env": { "type": "object", "additionalProperties": { "type": "string" } },
+                "timeout_secs": { "type": "integer" }
+            },
+            "required": ["command"]
+        })
+    }
+
+    async fn call(&self, ctx: &ToolContext<'_>, arguments: Value) -> Result<String, String> {
+        let command = arguments["command"].as_str()
+            .map(str::trim)
+            .filter(|c| !c.is_empty())
+            .ok_or_else(|| "Missing string argument 'command'".to_string())?;
+        let cwd = self.workspace.resolve(arguments["cwd"].as_str().unwrap_or("."))?;
+        if !cwd.is_dir() {
+            return Err(format!("{} is not a directory", cwd.display()));
+        }
+        let env: HashMap<String, String> = arguments["env"].as_object()
+            .map(|vars| vars.iter()
+                .filter_map(|(key, value)| Some((key.clone(), value.as_str()?.to_string())))
+                .collect())
+            .unwrap_or_default();
+        let settings = self.policy.settings();
+        let timeout = arguments["timeout_secs"].as_u64()
+            .unwrap_or(settings.timeout_secs)
+            .min(settings.timeout_secs);
+
+        if !settings.is_allowed(command) {
+            let mut detail = format!("$ {}\nin {}", command, cwd.display());
+            for (key, value) in &env {
+                detail.push_str(&format!("\n{}={}", key, value));
+            }
+            let approved = ctx.approvals
+                .request(ctx.run_id, ctx.node_id, self.name(), "Run command".to_string(), detail)
+                .await;
+            if !approved {
+                add_log_entry(ctx.log_state, "warn", &format!("run_command rejected: {}", command), "tools")?;
+                return Err("The user rejected the command".to_string());
+            }
+        }
+
+        add_log_entry(ctx.log_state, "info", &format!("run_command in {}: {}", cwd.display(), command), "tools")?;
+        let (program, flag) = if cfg!(windows) { ("cmd", "/C") } else { ("sh", "-c") };
+        let mut cmd = self.app.shell()
+            .command(program)
+            .args([flag, command])
+            .current_dir(&cwd)
+            .set_raw_out(true);
+        if !settings.inherit_env {
+            cmd = cmd.env_clear();
+            if let Ok(path) = std::env::var("PATH") {
+                cmd = cmd.env("PATH", path);
+            }
+        }
+        let (mut events, child) = cmd.envs(env).spawn().map_err(|e| format!("Failed to start command: {}", e))?;
+        let pid = child.pid();
+        self.running.insert(pid, command);
+
+        let mut stdout = Capture::new();
+        let mut stderr = Capture::new();
+        let collect = async {
+            let mut exit = None;
+            while let Some(event) = events.recv().await {
+                match event {
+                    CommandEvent::Stdout(chunk) => stdout.push(&chunk, settings.max_output_bytes),
+                    CommandEvent::Stderr(chunk) => stderr.push(&chunk, settings.max_output_bytes),
+                    CommandEvent::Error(e) => stderr.push(e.as_bytes(), settings.max_output_bytes),
+                    CommandEvent::Terminated(payload) => exit = Some(payload),
+                    _ => {}
+                }
+            }
+            exit
+        };
+        let finished = tokio::time::timeout(Duration::from_secs(timeout), collect).await;
+        self.running.remove(pid);
+
+        let status = match finished {
+            Ok(Some(payload)) => match (payload.code, payload.signal) {
+                (Some(code), _) => format!("exit code {}", code),
+                (None, Some(signal)) => format!("killed by signal {}", signal),
+                (None, None) => "exited".to_string(),
+            },
+            Ok(None) => "exited".to_string(),
+            Err(_) => {
+                let _ = child.kill();
+                add_log_entry(ctx.log_state, "warn", &format!("run_command timed out after {}s: {}", timeout, command), "tools")?;
+                format!("timed out after {}s and was killed", timeout)
+            }
+        };
+        Ok(format!("{}\n--- stdout ---\n{}\n--- stderr ---\n{}", status, stdout.text(), stderr.text()))
+    }
+}
+
+pub fn register(
+    registry: &ToolRegistry,
+    app: AppHandle,
+    workspace: Arc<Workspace>,
+    policy: Arc<ShellPolicy>,
+    running: Arc<RunningCommands>,
+) {
+    registry.register(Arc::new(RunCommand { app, workspace, policy, running }));
+}
+
+#[tauri::command]
+pub async fn get_shell_settings(policy: State<'_, Arc<ShellPolicy>>) -> Result<ShellSettings, String> {
+    Ok(policy.settings())
+}
+
+#[tauri::command]
+pub async fn set_shell_settings(
+    settings: ShellSettings,
+    policy: State<'_, Arc<ShellPolicy>>,
+    log_state: State<'_, Mutex<LogState>>,
+) -> Result<(), String> {
+    policy.update(settings)?;
+    add_log_entry(&log_state, "info", "Updated shell command settings", "tools")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_allowlist_needs_whole_arguments() {
+        let settings = ShellSettings::default();
+        assert!(settings.is_allowed("ls"));
+        assert!(settings.is_allowed("ls -la"));
+        assert!(settings.is_allowed("git status"));
+        assert!(settings.is_allowed("git diff --stat HEAD~1"));
+        assert!(settings.is_allowed("git log --oneline -5"));
+        assert!(!settings.is_allowed("lsblk"));
+        assert!(!settings.is_allowed("git difftool -y --extcmd=rm"));
+        assert!(!settings.is_allowed("git status-foo"));
+        assert!(!settings.is_allowed("ls; rm -rf /"));
+        assert!(!settings.is_allowed("git status && curl x"));
+    }
+
+    #[test]
+    fn trailing_star_without_space_keeps_the_boundary() {
+        let settings = ShellSettings { allowlist: vec!["git diff*".into()], ..Default::default() };
+        assert!(settings.is_allowed("git diff"));
+        assert!(settings.is_allowed("git diff --cached"));
+        assert!(!settings.is_allowed("git difftool"));
+    }
+
+    #[test]
+    fn unsafe_options_need_approval() {
+        let settings = ShellSettings { allowlist: vec!["git *".into()], ..Default::default() };
+        assert!(!settings.is_allowed("git diff --output=/tmp/x"));
+        assert!(!settings.is_allowed("git diff --output /tmp/x"));
+        assert!(!settings.is_allowed("git log --out=/tmp/x"));
+        assert!(!settings.is_allowed("git diff --ext-diff"));
+        assert!(!settings.is_allowed("git difftool --extcmd=rm"));
+        assert!(!settings.is_allowed("git difftool -x rm"));
+        assert!(!settings.is_allowed("git difftool -yx rm"));
+        assert!(settings.is_allowed("git diff --no-ext-diff"));
+        assert!(settings.is_allowed("git diff --output-indicator-new=+"));
+    }
+}
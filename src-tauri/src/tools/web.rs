@@ -0,0 +1,576 @@
+// Web search and page fetching for agent nodes.
+use async_trait::async_trait;
+use reqwest::Client;
+use scraper::{ElementRef, Html, Node, Selector};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use tauri::State;
+
+use super::files::{str_arg, truncate};
+use super::{Tool, ToolContext, ToolRegistry};
+use crate::provider::ToolCall;
+use crate::{add_log_entry, LogState};
+
+pub mod mock;
+
+const GROUP: &str = "web_search";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(20);
+const MAX_PAGE_BYTES: usize = 2 * 1024 * 1024;
+const MAX_REDIRECTS: usize = 5;
+/// Elements whose text is never part of the readable content.
+const SKIPPED_ELEMENTS: &[&str] = &[
+    "script", "style", "noscript", "template", "svg", "nav", "header", "footer", "aside", "form", "button", "iframe",
+];
+/// Elements that start a new line in the extracted text.
+const BLOCK_ELEMENTS: &[&str] = &[
+    "p", "div", "section", "article", "main", "br", "li", "ul", "ol", "tr", "table", "blockquote", "pre",
+    "h1", "h2", "h3", "h4", "h5", "h6", "dd", "dt", "figcaption",
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchResult {
+    pub title: String,
+    pub url: String,
+    pub snippet: String,
+}
+
+/// A search backend. Results must keep their source URLs so answers can cite them.
+#[async_trait]
+pub trait SearchProvider: Send + Sync {
+    fn name(&self) -> &str;
+    async fn search(&self, query: &str, limit: usize) -> Result<Vec<SearchResult>, String>;
+}
+
+/// Any server speaking the SearXNG JSON API (`/search?format=json`).
+pub struct SearxngProvider {
+    base_url: String,
+    client: Client,
+}
+
+impl SearxngProvider {
+    pub fn new(base_url: &str, client: Client) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            client,
+        }
+    }
+}
+
+#[async_trait]
+impl SearchProvider for SearxngProvider {
+    fn name(&self) -> &str {
+        "searxng"
+    }
+
+    async fn search(&self, query: &str, limit: usize) -> Result<Vec<SearchResult>, String> {
+        let response = self.client.get(format!("{}/search", self.base_url))
+            .query(&[("q", query), ("format", "json")])
+            .send()
+            .await
+            .map_err(|e| format!("Search request failed: {}", e))?;
+        if !response.status().is_success() {
+            return Err(format!("Search failed: {}", response.status()));
+        }
+        let body: Value = response.json().await.map_err(|e| format!("Invalid search response: {}", e))?;
+        let results = body["results"].as_array()
+            .map(|results| results.iter()
+                .filter_map(|result| Some(SearchResult {
+                    title: result["title"].as_str().unwrap_or_default().trim().to_string(),
+                    url: result["url"].as_str()?.to_string(),
+                    snippet: result["content"].as_str().unwrap_or_default().trim().to_string(),
+                }))
+                .take(limit)
+                .collect())
+            .unwrap_or_default();
+        Ok(results)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebSearchSettings {
+    /// Base URL of the SearXNG instance.
+    #[serde(default = "default_searxng_url")]
+    pub searxng_url: String,
+    #[serde(default = "default_max_results")]
+    pub max_results: usize,
+    /// Extracted page text is cut off beyond this many characters.
+    #[serde(default = "default_max_page_chars")]
+    pub max_page_chars: usize,
+}
+
+fn default_searxng_url() -> String {
+    "http://localhost:8888".to_string()
+}
+
+fn default_max_results() -> usize {
+    5
+}
+
+fn default_max_page_chars() -> usize {
+    12_000
+}
+
+impl Default for WebSearchSettings {
+    fn default() -> Self {
+        Self {
+            searxng_url: default_searxng_url(),
+            max_results: default_max_results(),
+            max_page_chars: default_max_page_chars(),
+        }
+    }
+}
+
+/// Settings and HTTP client shared by the web tools.
+pub struct WebSearch {
+    settings: RwLock<WebSearchSettings>,
+    store: PathBuf,
+    /// Search endpoint used instead of the configured one, e.g. the mock server.
+    endpoint_override: RwLock<Option<String>>,
+    client: Client,
+    /// Client for fetching pages. It does not follow redirects itself, so
+    /// that every hop can be checked for local addresses.
+    pages: Client,
+}
+
+impl WebSearch {
+    pub fn load(store: PathBuf) -> Self {
+        let settings = std::fs::read_to_string(&store)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+        let client = || Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .user_agent(concat!("nexa-ai-core/", env!("CARGO_PKG_VERSION")));
+        Self {
+            settings: RwLock::new(settings),
+            store,
+            endpoint_override: RwLock::new(None),
+            client: client().build().unwrap_or_default(),
+            pages: client().redirect(reqwest::redirect::Policy::none()).build().unwrap_or_default(),
+        }
+    }
+
+    pub fn settings(&self) -> WebSearchSettings {
+        self.settings.read().map(|s| s.clone()).unwrap_or_default()
+    }
+
+    pub fn update(&self, settings: WebSearchSettings) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(&settings).map_err(|e| e.to_string())?;
+        std::fs::write(&self.store, json).map_err(|e| format!("Failed to save web search settings: {}", e))?;
+        *self.settings.write().map_err(|e| e.to_string())? = settings;
+        Ok(())
+    }
+
+    /// Search through `url` for the rest of the session, without saving it.
+    pub fn override_endpoint(&self, url: &str) {
+        if let Ok(mut endpoint) = self.endpoint_override.write() {
+            *endpoint = Some(url.to_string());
+        }
+    }
+
+    pub fn provider(&self) -> Box<dyn SearchProvider> {
+        let endpoint = self.endpoint_override.read().ok().and_then(|e| e.clone());
+        let url = endpoint.unwrap_or_else(|| self.settings().searxng_url);
+        Box::new(SearxngProvider::new(&url, self.client.clone()))
+    }
+
+    /// Whether `url` points at this machine or a private network. Host names
+    /// are resolved, so names of local addresses count too. Pages of the
+    /// overridden search endpoint, such as the mock server's, do not.
+    pub async fn is_local(&self, url: &reqwest::Url) -> Result<bool, String> {
+        let endpoint = self.endpoint_override.read().ok().and_then(|e| e.clone());
+        if endpoint.and_then(|e| reqwest::Url::parse(&e).ok()).is_some_and(|e| e.origin() == url.origin()) {
+            return Ok(false);
+        }
+        let host = url.host_str().ok_or_else(|| format!("{} has no host", url))?;
+        let addresses = match host.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() {
+            Ok(ip) => vec![ip],
+            Err(_) => tokio::net::lookup_host((host, url.port_or_known_default().unwrap_or(80)))
+                .await
+                .map_err(|e| format!("Failed to resolve {}: {}", host, e))?
+                .map(|address| address.ip())
+                .collect(),
+        };
+        Ok(addresses.into_iter().any(is_private_address))
+    }
+
+    /// Download a page and extract its title and readable text. Local and
+    /// private addresses, including redirects to them, are refused unless
+    /// `allow_local` is set.
+    pub async fn fetch(&self, url: &str, allow_local: bool) -> Result<(Option<String>, String), String> {
+        let mut target = page_url(url)?;
+        let mut redirects = 0;
+        let mut response = loop {
+            if !allow_local && self.is_local(&target).await? {
+                return Err(format!("{} is a local or private address", target));
+            }
+            let response = self.pages.get(target.clone())
+                .send()
+                .await
+                .map_err(|e| format!("Failed to fetch {}: {}", url, e))?;
+            if !response.status().is_redirection() {
+                break response;
+            }
+            redirects += 1;
+            if redirects > MAX_REDIRECTS {
+                return Err(format!("Fetching {} redirected more than {} times", url, MAX_REDIRECTS));
+            }
+            let location = response.headers()
+                .get(reqwest::header::LOCATION)
+                .and_then(|v| v.to_str().ok())
+                .ok_or_else(|| format!("Fetching {} redirected without a location", url))?;
+            target = page_url(target.join(location).map_err(|e| format!("Invalid redirect from {}: {}", url, e))?.as_str())?;
+        };
+        if !response.status().is_success() {
+            return Err(format!("Fetching {} failed: {}", url, response.status()));
+        }
+        let content_type = response.headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("text/html")
+            .to_lowercase();
+        if !(content_type.contains("html") || content_type.starts_with("text/") || content_type.contains("json")) {
+            return Err(format!("{} is {}, not a readable page", url, content_type));
+        }
+
+        let mut body = Vec::new();
+        while let Some(chunk) = response.chunk().await.map_err(|e| e.to_string())? {
+            body.extend_from_slice(&chunk);
+            if body.len() >= MAX_PAGE_BYTES {
+                body.truncate(MAX_PAGE_BYTES);
+                break;
+            }
+        }
+        let body = String::from_utf8_lossy(&body);
+        if content_type.contains("html") {
+            Ok(readable_text(&body))
+        } else {
+            Ok((None, body.into_owned()))
+        }
+    }
+}
+
+fn page_url(url: &str) -> Result<reqwest::Url, String> {
+    let parsed = reqwest::Url::parse(url).map_err(|e| format!("Invalid URL {}: {}", url, e))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(format!("Only http and https URLs can be fetched, not {}", parsed.scheme()));
+    }
+    Ok(parsed)
+}
+
+/// Loopback, private, link-local and shared addresses.
+fn is_private_address(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            ip.is_loopback() || ip.is_private() || ip.is_link_local() || ip.is_unspecified() || ip.is_broadcast()
+                || (a == 100 && (64..128).contains(&b))
+        }
+        IpAddr::V6(ip) => {
+            let first = ip.segments()[0];
+            ip.is_loopback() || ip.is_unspecified() || (first & 0xfe00) == 0xfc00 || (first & 0xffc0) == 0xfe80
+                || ip.to_ipv4_mapped().is_some_and(|ip| is_private_address(ip.into()))
+        }
+    }
+}
+
+/// Title and main text of an HTML page, leaving out scripts, navigation and
+/// other chrome. Prefers the `article` or `main` element when there is one.
+pub fn readable_text(html: &str) -> (Option<String>, String) {
+    let document = Html::parse_document(html);
+    let select = |selector: &str| Selector::parse(selector).ok()
+        .and_then(|selector| document.select(&selector).next());
+
+    let title = select("title")
+        .map(|title| title.text().collect::<String>().trim().to_string())
+        .filter(|title| !title.is_empty());
+    let root = select("article").or_else(|| select("main")).or_else(|| select("body"));
+    let mut text = String::new();
+    if let Some(root) = root {
+        collect_text(root, &mut text);
+    }
+
+    let lines: Vec<&str> = text.lines().map(str::trim).filter(|line| !line.is_empty()).collect();
+    (title, lines.join("\n"))
+}
+
+fn collect_text(element: ElementRef, out: &mut String) {
+    for child in element.children() {
+        match child.value() {
+            Node::Text(text) => {
+                let words = text.split_whitespace().collect::<Vec<_>>().join(" ");
+                if !words.is_empty() {
+                    if !out.is_empty() && !out.ends_with(['\n', ' ']) {
+                        out.push(' ');
+                    }
+                    out.push_str(&words);
+                }
+            }
+            Node::Element(tag) => {
+                if SKIPPED_ELEMENTS.contains(&tag.name()) {
+                    continue;
+                }
+                let block = BLOCK_ELEMENTS.contains(&tag.name());
+                if block {
+                    out.push('\n');
+                }
+                if let Some(child) = ElementRef::wrap(child) {
+                    collect_text(child, out);
+                }
+                if block {
+                    out.push('\n');
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+pub struct WebSearchTool(Arc<WebSearch>);
+
+#[async_trait]
+impl Tool for WebSearchTool {
+    fn name(&self) -> &str {
+        "web_search"
+    }
+
+    fn group(&self) -> &str {
+        GROUP
+    }
+
+    fn description(&self) -> &str {
+        "Search the web. Returns numbered results with titles, URLs and snippets; cite the URLs of the results you use."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "query": { "type": "string" },
+                "limit": { "type": "integer", "description": "Maximum number of results" }
+            },
+            "required": ["query"]
+        })
+    }
+
+    async fn call(&self, ctx: &ToolContext<'_>, arguments: Value) -> Result<String, String> {
+        let query = str_arg(&arguments, "query")?;
+        let max_results = self.0.settings().max_results;
+        let limit = arguments["limit"].as_u64().map_or(max_results, |n| n as usize).min(max_results.max(1));
+        let provider = self.0.provider();
+        add_log_entry(ctx.log_state, "info", &format!("web_search via {}: {}", provider.name(), query), "tools")?;
+
+        let results = provider.search(query, limit).await?;
+        if results.is_empty() {
+            return Ok(format!("No results for \"{}\"", query));
+        }
+        let mut out: Vec<String> = results.iter()
+            .enumerate()
+            .map(|(i, result)| format!("[{}] {}\n    {}\n    {}", i + 1, result.title, result.url, result.snippet))
+            .collect();
+        out.push("Cite sources by their URLs. Use fetch_page to read a result in full.".to_string());
+        Ok(out.join("\n"))
+    }
+}
+
+pub struct FetchPage(Arc<WebSearch>);
+
+#[async_trait]
+impl Tool for FetchPage {
+    fn name(&self) -> &str {
+        "fetch_page"
+    }
+
+    fn group(&self) -> &str {
+        GROUP
+    }
+
+    fn description(&self) -> &str {
+        "Fetch a web page and return its readable text, headed by the source URL to cite."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "url": { "type": "string" }
+            },
+            "required": ["url"]
+        })
+    }
+
+    async fn call(&self, ctx: &ToolContext<'_>, arguments: Value) -> Result<String, String> {
+        let url = str_arg(&arguments, "url")?;
+        let local = self.0.is_local(&page_url(url)?).await?;
+        if local {
+            let approved = ctx.approvals
+                .request(ctx.run_id, ctx.node_id, self.name(), "Fetch local address".to_string(), url.to_string())
+                .await;
+            if !approved {
+                add_log_entry(ctx.log_state, "warn", &format!("fetch_page rejected: {}", url), "tools")?;
+                return Err(format!("The user rejected fetching {}", url));
+            }
+        }
+        add_log_entry(ctx.log_state, "info", &format!("fetch_page {}", url), "tools")?;
+        let (title, text) = self.0.fetch(url, local).await?;
+
+        let max_chars = self.0.settings().max_page_chars;
+        let text = match text.char_indices().nth(max_chars) {
+            Some((end, _)) => format!("{}\n[truncated]", &text[..end]),
+            None => text,
+        };
+        let header = match title {
+            Some(title) => format!("Source: {}\nTitle: {}", url, title),
+            None => format!("Source: {}", url),
+        };
+        Ok(truncate(format!("{}\n\n{}", header, text), super::files::MAX_READ_BYTES))
+    }
+}
+
+/// URL of the page a successful `fetch_page` call read.
+pub fn fetched_source(call: &ToolCall, result: &str) -> Option<String> {
+    if call.name != "fetch_page" {
+        return None;
+    }
+    result.lines().next()?.strip_prefix("Source: ").map(str::to_string)
+}
+
+/// Add the pages read while answering, unless the answer already cites them.
+pub fn cite_sources(answer: String, sources: &[String]) -> String {
+    let mut missing: Vec<&String> = Vec::new();
+    for url in sources {
+        if !answer.contains(url.as_str()) && !missing.contains(&url) {
+            missing.push(url);
+        }
+    }
+    if missing.is_empty() {
+        return answer;
+    }
+    let list: Vec<String> = missing.iter().map(|url| format!("- {}", url)).collect();
+    format!("{}\n\nSources:\n{}", answer.trim_end(), list.join("\n"))
+}
+
+pub fn register(registry: &ToolRegistry, web: Arc<WebSearch>) {
+    registry.register(Arc::new(WebSearchTool(web.clone())));
+    registry.register(Arc::new(FetchPage(web)));
+}
+
+#[tauri::command]
+pub async fn get_web_search_settings(web: State<'_, Arc<WebSearch>>) -> Result<WebSearchSettings, String> {
+    Ok(web.settings())
+}
+
+#[tauri::command]
+pub async fn set_web_search_settings(
+    settings: WebSearchSettings,
+    web: State<'_, Arc<WebSearch>>,
+    log_state: State<'_, Mutex<LogState>>,
+) -> Result<(), String> {
+    web.update(settings)?;
+    add_log_entry(&log_state, "info", "Updated web search settings", "tools")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::RunHistory;
+    use crate::tools::approval::Approvals;
+    use mock::{default_pages, MockSearchServer};
+
+    fn web_search(name: &str) -> Arc<WebSearch> {
+        Arc::new(WebSearch::load(std::env::temp_dir().join(format!("web-{}-{}.json", name, std::process::id()))))
+    }
+
+    #[tokio::test]
+    async fn search_and_fetch_against_the_mock() {
+        let server = MockSearchServer::start(default_pages()).await.unwrap();
+        let web = web_search("fetch");
+        web.override_endpoint(&server.base_url);
+
+        let results = web.provider().search("rust ownership", 5).await.unwrap();
+        assert_eq!(results[0].title, "Understanding Ownership in Rust");
+        assert!(results[0].url.starts_with(&server.base_url));
+        assert!(results[0].snippet.contains("Ownership is a set of rules"), "{:?}", results);
+        assert!(web.provider().search("zzzz", 5).await.unwrap().is_empty());
+
+        // The mock's own pages are not treated as local
+        let url = format!("{}/pages/tauri-overview", server.base_url);
+        let (title, text) = web.fetch(&url, false).await.unwrap();
+        assert_eq!(title.as_deref(), Some("Tauri Overview"));
+        assert!(text.starts_with("Tauri Overview\nTauri is a framework"), "{}", text);
+        assert!(!text.contains("track()") && !text.contains("Home") && !text.contains("Mock search fixture"));
+        let error = web.fetch(&format!("{}/pages/nope", server.base_url), false).await.unwrap_err();
+        assert!(error.contains("404"), "{}", error);
+        assert!(web.fetch("file:///etc/passwd", false).await.unwrap_err().contains("not file"));
+    }
+
+    #[tokio::test]
+    async fn local_addresses_need_approval() {
+        let server = MockSearchServer::start(default_pages()).await.unwrap();
+        let url = format!("{}/pages/searxng", server.base_url);
+        let web = web_search("local");
+        let error = web.fetch(&url, false).await.unwrap_err();
+        assert!(error.contains("local or private address"), "{}", error);
+        assert!(web.fetch(&url, true).await.is_ok());
+
+        let registry = ToolRegistry::default();
+        register(&registry, web);
+        let tools = registry.resolve(&[GROUP.to_string()]);
+        let fetch_page = tools.iter().find(|tool| tool.name() == "fetch_page").unwrap();
+        let (log_state, approvals) = (Mutex::new(LogState { entries: Default::default() }), Approvals::default());
+        let history = RunHistory::open_in_memory().unwrap();
+        let ctx = ToolContext { run_id: "r", node_id: "n", log_state: &log_state, approvals: &approvals, history: &history };
+        let (approvals, url) = (&approvals, &url);
+        let answer = move |approved: bool| async move {
+            loop {
+                if let Some(request) = approvals.pending().pop() {
+                    assert_eq!(&request.detail, url);
+                    return approvals.resolve(&request.id, approved).unwrap();
+                }
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        };
+
+        let (result, ()) = tokio::join!(fetch_page.call(&ctx, json!({"url": url})), answer(false));
+        assert_eq!(result.unwrap_err(), format!("The user rejected fetching {}", url));
+        let (result, ()) = tokio::join!(fetch_page.call(&ctx, json!({"url": url})), answer(true));
+        assert!(result.unwrap().starts_with(&format!("Source: {}\nTitle: SearXNG Metasearch Engine", url)));
+    }
+
+    #[test]
+    fn private_addresses() {
+        for ip in ["127.0.0.1", "10.1.2.3", "172.16.0.1", "192.168.1.1", "169.254.169.254", "100.64.0.1", "0.0.0.0", "::1", "fd00::1", "fe80::1", "::ffff:192.168.0.1"] {
+            assert!(is_private_address(ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in ["8.8.8.8", "100.128.0.1", "2606:4700::1111"] {
+            assert!(!is_private_address(ip.parse().unwrap()), "{}", ip);
+        }
+    }
+
+    #[test]
+    fn readable_text_keeps_the_article() {
+        let (title, text) = readable_text(
+            "<html><head><title> Post </title><style>p{}</style></head><body><nav>Menu</nav>\
+             <main><h1>Heading</h1><p>First <b>bold</b> line.</p><ul><li>one</li><li>two</li></ul></main>\
+             <footer>Footer</footer></body></html>",
+        );
+        assert_eq!(title.as_deref(), Some("Post"));
+        assert_eq!(text, "Heading\nFirst bold line.\none\ntwo");
+    }
+
+    #[test]
+    fn sources_are_cited_once() {
+        let url = "https://example.com/page".to_string();
+        let call = ToolCall { id: "1".into(), name: "fetch_page".into(), arguments: json!({}) };
+        assert_eq!(fetched_source(&call, &format!("Source: {}\nTitle: Page\n\ntext", url)), Some(url.clone()));
+        assert_eq!(fetched_source(&ToolCall { name: "web_search".into(), ..call }, "Source: x"), None);
+
+        let answer = cite_sources("It is small. ".into(), &[url.clone(), url.clone()]);
+        assert_eq!(answer, format!("It is small.\n\nSources:\n- {}", url));
+        assert_eq!(cite_sources(format!("See {}", url), std::slice::from_ref(&url)), format!("See {}", url));
+    }
+}
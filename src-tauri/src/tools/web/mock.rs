@@ -0,0 +1,147 @@
+// Local stand-in for a SearXNG server, for testing web search offline.
+use serde_json::json;
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// A page served by the mock server and returned by its searches.
+pub struct MockPage {
+    pub slug: String,
+    pub title: String,
+    pub html: String,
+}
+
+impl MockPage {
+    pub fn new(slug: &str, title: &str, body: &str) -> Self {
+        Self {
+            slug: slug.to_string(),
+            title: title.to_string(),
+            html: format!(
+                "<html><head><title>{title}</title><script>track()</script></head>\
+                 <body><nav><a href=\"/\">Home</a></nav><article><h1>{title}</h1>{body}</article>\
+                 <footer>Mock search fixture</footer></body></html>"
+            ),
+        }
+    }
+
+    /// Visible text of the page, used for matching queries and for snippets.
+    fn text(&self) -> String {
+        super::readable_text(&self.html).1
+    }
+}
+
+/// Pages served when none are given.
+pub fn default_pages() -> Vec<MockPage> {
+    vec![
+        MockPage::new(
+            "rust-ownership",
+            "Understanding Ownership in Rust",
+            "<p>Ownership is a set of rules that govern how a Rust program manages memory.</p>\
+             <p>Each value in Rust has an owner. There can only be one owner at a time.\
+             When the owner goes out of scope, the value will be dropped.</p>",
+        ),
+        MockPage::new(
+            "tauri-overview",
+            "Tauri Overview",
+            "<p>Tauri is a framework for building small, fast binaries for all major desktop\
+             and mobile platforms.</p><p>The backend is written in Rust and the frontend can use\
+             any framework that compiles to HTML, JavaScript and CSS.</p>",
+        ),
+        MockPage::new(
+            "searxng",
+            "SearXNG Metasearch Engine",
+            "<p>SearXNG is a free internet metasearch engine which aggregates results from\
+             various search services.</p><p>Its JSON API is enabled with the json format.</p>",
+        ),
+    ]
+}
+
+pub struct MockSearchServer {
+    pub base_url: String,
+    handle: tokio::task::JoinHandle<()>,
+}
+
+impl MockSearchServer {
+    /// Listen on a free local port and serve `pages` until dropped.
+    pub async fn start(pages: Vec<MockPage>) -> Result<Self, String> {
+        let listener = TcpListener::bind("127.0.0.1:0").await.map_err(|e| e.to_string())?;
+        let base_url = format!("http://{}", listener.local_addr().map_err(|e| e.to_string())?);
+        let pages = Arc::new(pages);
+        let base = base_url.clone();
+        let handle = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let pages = pages.clone();
+                let base = base.clone();
+                tokio::spawn(async move {
+                    let _ = respond(stream, &pages, &base).await;
+                });
+            }
+        });
+        Ok(Self { base_url, handle })
+    }
+}
+
+impl Drop for MockSearchServer {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+async fn respond(mut stream: TcpStream, pages: &[MockPage], base_url: &str) -> std::io::Result<()> {
+    let mut request = Vec::new();
+    let mut buffer = [0u8; 4096];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") && request.len() < 64 * 1024 {
+        let read = stream.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        request.extend_from_slice(&buffer[..read]);
+    }
+    let request = String::from_utf8_lossy(&request);
+    let target = request.split_whitespace().nth(1).unwrap_or("/");
+    let url = reqwest::Url::parse(&format!("{}{}", base_url, target)).ok();
+
+    let (status, content_type, body) = match url.as_ref().map(|u| u.path()) {
+        Some("/search") => {
+            let query = url.as_ref()
+                .and_then(|u| u.query_pairs().find(|(key, _)| key == "q"))
+                .map(|(_, q)| q.into_owned())
+                .unwrap_or_default();
+            ("200 OK", "application/json", search(pages, &query, base_url).to_string())
+        }
+        Some(path) => match pages.iter().find(|page| path == format!("/pages/{}", page.slug)) {
+            Some(page) => ("200 OK", "text/html; charset=utf-8", page.html.clone()),
+            None => ("404 Not Found", "text/plain", "Not found".to_string()),
+        },
+        None => ("400 Bad Request", "text/plain", "Bad request".to_string()),
+    };
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status, content_type, body.len(), body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// Pages containing any query word, most matches first, in SearXNG's format.
+fn search(pages: &[MockPage], query: &str, base_url: &str) -> serde_json::Value {
+    let words: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+    let mut hits: Vec<(usize, &MockPage, String)> = pages.iter()
+        .filter_map(|page| {
+            let text = page.text();
+            let haystack = format!("{} {}", page.title, text).to_lowercase();
+            let score = words.iter().filter(|word| haystack.contains(word.as_str())).count();
+            (score > 0).then_some((score, page, text))
+        })
+        .collect();
+    hits.sort_by_key(|hit| std::cmp::Reverse(hit.0));
+    let results: Vec<_> = hits.into_iter()
+        .map(|(_, page, text)| json!({
+            "title": page.title,
+            "url": format!("{}/pages/{}", base_url, page.slug),
+            "content": text.lines().nth(1).unwrap_or_default(),
+            "engine": "mock",
+        }))
+        .collect();
+    json!({ "query": query, "number_of_results": results.len(), "results": results })
+}
@@ -0,0 +1,1384 @@
+// Backend workflow executor: runs a graph of nodes built in the canvas.
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::Instant;
+use tauri::{AppHandle, Manager};
+
+use crate::cache::ResponseCache;
+use crate::history::{Artifact, RunHistory};
+use crate::knowledge::KnowledgeBase;
+use crate::memory::MemoryStore;
+use crate::prompts::{PromptLibrary, PromptRef};
+use crate::provider::{self, ChatMessage, ChatOptions, ChatReply, ImageInput, ModelInfo, ToolCall, Usage};
+use crate::tools::approval::Approvals;
+use crate::tools::{web, Tool, ToolContext, ToolRegistry};
+use crate::{add_log_entry, broadcast_update, LogState};
+
+pub mod condition;
+pub mod context;
+pub mod iteration;
+pub mod memory;
+pub mod retriever;
+pub mod schema;
+
+use condition::EdgeCondition;
+use context::{ContextConfig, ContextReport};
+use iteration::{MapConfig, OutputBuffers, WhileConfig};
+use memory::MemoryNodeConfig;
+use retriever::RetrieverConfig;
+
+/// `{{input.path}}` placeholders picking a field out of the node's input.
+static INPUT_FIELD: LazyLock<regex::Regex> = LazyLock::new(|| regex::Regex::new(r"\{\{input\.([\w.]+)\}\}").unwrap());
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Workflow {
+    pub id: String,
+    #[serde(default)]
+    pub name: String,
+    pub nodes: Vec<WorkflowNode>,
+    #[serde(default)]
+    pub edges: Vec<WorkflowEdge>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkflowNode {
+    pub id: String,
+    #[serde(flatten)]
+    pub kind: NodeKind,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum NodeKind {
+    /// Emits a fixed value; the usual entry point of a workflow.
+    Input {
+        #[serde(default)]
+        value: String,
+    },
+    Llm(LlmNodeConfig),
+    /// Forwards its input down the outgoing edges whose conditions match.
+    Router(RouterConfig),
+    /// Runs the LLM task once per item of the upstream list.
+    Map(MapConfig),
+    /// Feeds the LLM task its own output until a condition holds.
+    While(WhileConfig),
+    /// Looks up the knowledge base chunks closest to its input.
+    Retriever(RetrieverConfig),
+    /// Writes its input to long-term memory, or recalls memories.
+    Memory(MemoryNodeConfig),
+    /// Collects whatever reaches it.
+    Output,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LlmNodeConfig {
+    pub server_url: String,
+    pub model: String,
+    /// Task text. Upstream output replaces `{{input}}` if present and is
+    /// appended after the task otherwise.
+    #[serde(default)]
+    pub prompt: String,
+    /// Sent as the system message, before the task.
+    #[serde(default)]
+    pub system: String,
+    /// Library prompt whose non-empty parts take the place of `system` and
+    /// `prompt`.
+    #[serde(default)]
+    pub prompt_ref: Option<PromptRef>,
+    /// Images sent along with the task.
+    #[serde(default)]
+    pub images: Vec<ImageInput>,
+    #[serde(default = "default_temperature")]
+    pub temperature: f32,
+    /// Tool or tool group names the model may call.
+    #[serde(default)]
+    pub tools: Vec<String>,
+    /// Model turns allowed before giving up on a final answer.
+    #[serde(default = "default_max_tool_steps")]
+    pub max_tool_steps: usize,
+    /// JSON schema the reply must satisfy. Validated replies travel
+    /// downstream as typed JSON.
+    #[serde(default)]
+    pub output_schema: Option<Value>,
+    /// Follow-up requests asking the model to fix a reply that fails the schema.
+    #[serde(default = "default_max_repairs")]
+    pub max_repairs: usize,
+    /// What to do when a request would overflow the model's context window.
+    #[serde(default)]
+    pub context: ContextConfig,
+}
+
+fn default_temperature() -> f32 {
+    0.7
+}
+
+fn default_max_tool_steps() -> usize {
+    8
+}
+
+fn default_max_repairs() -> usize {
+    2
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RouterConfig {
+    /// When set, the router asks a model to label its input and edges are
+    /// matched with `EdgeCondition::Label`.
+    #[serde(default)]
+    pub classifier: Option<ClassifierConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClassifierConfig {
+    pub server_url: String,
+    pub model: String,
+    pub labels: Vec<String>,
+    #[serde(default)]
+    pub instructions: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkflowEdge {
+    pub from: String,
+    pub to: String,
+    /// Only evaluated on edges leaving a router node. A router edge without a
+    /// condition behaves like `EdgeCondition::Default`.
+    #[serde(default)]
+    pub condition: Option<EdgeCondition>,
+}
+
+impl NodeKind {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            NodeKind::Input { .. } => "input",
+            NodeKind::Llm(_) => "llm",
+            NodeKind::Router(_) => "router",
+            NodeKind::Map(_) => "map",
+            NodeKind::While(_) => "while",
+            NodeKind::Retriever(_) => "retriever",
+            NodeKind::Memory(_) => "memory",
+            NodeKind::Output => "output",
+        }
+    }
+
+    /// Settings of the LLM task the node runs, if it runs one.
+    pub fn llm_config(&self) -> Option<&LlmNodeConfig> {
+        match self {
+            NodeKind::Llm(config) => Some(config),
+            NodeKind::Map(config) => Some(&config.llm),
+            NodeKind::While(config) => Some(&config.llm),
+            _ => None,
+        }
+    }
+
+    pub fn llm_config_mut(&mut self) -> Option<&mut LlmNodeConfig> {
+        match self {
+            NodeKind::Llm(config) => Some(config),
+            NodeKind::Map(config) => Some(&mut config.llm),
+            NodeKind::While(config) => Some(&mut config.llm),
+            _ => None,
+        }
+    }
+
+    pub fn model(&self) -> Option<&str> {
+        match self {
+            NodeKind::Llm(config) => Some(&config.model),
+            NodeKind::Map(config) => Some(&config.llm.model),
+            NodeKind::While(config) => Some(&config.llm.model),
+            NodeKind::Router(RouterConfig { classifier: Some(classifier) }) => Some(&classifier.model),
+            NodeKind::Retriever(RetrieverConfig { rerank: Some(rerank), .. }) => Some(&rerank.model),
+            _ => None,
+        }
+    }
+
+    /// Schema of the typed value the node emits, if it emits one.
+    pub fn output_schema(&self) -> Option<Value> {
+        match self {
+            NodeKind::Llm(config) => config.output_schema.clone(),
+            NodeKind::Map(config) => config.llm.output_schema.as_ref().map(|items| json!({ "type": "array", "items": items })),
+            NodeKind::While(config) => config.llm.output_schema.clone(),
+            _ => None,
+        }
+    }
+
+    /// Generation and control parameters worth keeping in the run history.
+    pub fn params(&self) -> Value {
+        match self {
+            NodeKind::Llm(config) => json!({
+                "prompt_ref": config.prompt_ref,
+                "temperature": config.temperature,
+                "tools": config.tools,
+                "output_schema": config.output_schema,
+                "context": config.context,
+                "images": config.images.iter().map(|image| &image.name).collect::<Vec<_>>(),
+            }),
+            NodeKind::Map(config) => json!({
+                "prompt_ref": config.llm.prompt_ref,
+                "temperature": config.llm.temperature,
+                "tools": config.llm.tools,
+                "output_schema": config.llm.output_schema,
+                "context": config.llm.context,
+                "images": config.llm.images.iter().map(|image| &image.name).collect::<Vec<_>>(),
+                "concurrency": config.concurrency,
+                "max_items": config.max_items,
+            }),
+            NodeKind::While(config) => json!({
+                "prompt_ref": config.llm.prompt_ref,
+                "temperature": config.llm.temperature,
+                "tools": config.llm.tools,
+                "output_schema": config.llm.output_schema,
+                "context": config.llm.context,
+                "images": config.llm.images.iter().map(|image| &image.name).collect::<Vec<_>>(),
+                "max_iterations": config.max_iterations,
+                "until": config.until,
+            }),
+            NodeKind::Router(RouterConfig { classifier: Some(classifier) }) => json!({ "labels": classifier.labels }),
+            NodeKind::Retriever(config) => json!({
+                "knowledge_base": config.knowledge_base,
+                "top_k": config.top_k,
+                "mode": config.mode,
+                "rerank": config.rerank,
+            }),
+            NodeKind::Memory(config) => json!({
+                "operation": config.operation,
+                "scope": config.scope,
+                "kind": config.kind,
+                "kinds": config.kinds,
+                "top_k": config.top_k,
+            }),
+            _ => Value::Null,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum NodeStatus {
+    Completed,
+    Skipped,
+    Failed,
+}
+
+impl NodeStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NodeStatus::Completed => "completed",
+            NodeStatus::Skipped => "skipped",
+            NodeStatus::Failed => "failed",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "completed" => Some(NodeStatus::Completed),
+            "skipped" => Some(NodeStatus::Skipped),
+            "failed" => Some(NodeStatus::Failed),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NodeResult {
+    pub node_id: String,
+    pub node_type: String,
+    pub status: NodeStatus,
+    pub input: Option<String>,
+    pub output: Option<String>,
+    /// The output as typed JSON, when it was validated against a schema.
+    #[serde(default)]
+    pub value: Option<Value>,
+    pub error: Option<String>,
+    /// Label picked by a classifier router.
+    pub label: Option<String>,
+    pub model: Option<String>,
+    pub params: Value,
+    #[serde(flatten)]
+    pub usage: Usage,
+    pub latency_ms: u64,
+    pub started_at: String,
+    /// Model calls answered by the response cache.
+    #[serde(default)]
+    pub cache_hits: u32,
+    /// Set when the output was carried over from this earlier run instead of
+    /// being recomputed.
+    #[serde(default)]
+    pub reused_from: Option<String>,
+    /// Files saved by the node's tool calls.
+    #[serde(default)]
+    pub artifacts: Vec<Artifact>,
+    /// How requests that overflowed the context window were shrunk.
+    #[serde(default)]
+    pub context: Option<ContextReport>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RunStatus {
+    Running,
+    Completed,
+    Failed,
+}
+
+impl RunStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RunStatus::Running => "running",
+            RunStatus::Completed => "completed",
+            RunStatus::Failed => "failed",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "running" => Some(RunStatus::Running),
+            "completed" => Some(RunStatus::Completed),
+            "failed" => Some(RunStatus::Failed),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkflowRun {
+    pub run_id: String,
+    pub workflow_id: String,
+    pub status: RunStatus,
+    pub started_at: String,
+    pub finished_at: String,
+    #[serde(default)]
+    pub resumed_from: Option<String>,
+    pub nodes: Vec<NodeResult>,
+}
+
+/// Completed node results from an earlier run, reused instead of executed.
+pub struct Checkpoint {
+    pub run_id: String,
+    pub nodes: HashMap<String, NodeResult>,
+}
+
+/// What travels along edges: the text prompts are built from and, when the
+/// upstream node validated its reply, the same output as typed JSON.
+#[derive(Debug, Clone, Default)]
+pub struct NodeInput {
+    pub text: String,
+    pub value: Option<Value>,
+}
+
+impl NodeInput {
+    pub fn text(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            value: None,
+        }
+    }
+
+    /// Combine the outputs reaching a node. Typed values of several upstream
+    /// nodes become an array, in edge order.
+    fn join(inputs: &[&NodeInput]) -> Self {
+        let text = inputs.iter().map(|input| input.text.as_str()).collect::<Vec<_>>().join("\n\n");
+        let value = match inputs {
+            [only] => only.value.clone(),
+            _ if inputs.iter().any(|input| input.value.is_some()) => Some(Value::Array(
+                inputs.iter()
+                    .map(|input| input.value.clone().unwrap_or_else(|| Value::String(input.text.clone())))
+                    .collect(),
+            )),
+            _ => None,
+        };
+        Self { text, value }
+    }
+}
+
+#[derive(Default)]
+struct NodeOutcome {
+    output: String,
+    value: Option<Value>,
+    label: Option<String>,
+    usage: Usage,
+    cache_hits: u32,
+    context: Option<ContextReport>,
+}
+
+impl NodeOutcome {
+    fn passthrough(input: &NodeInput) -> Self {
+        Self {
+            output: input.text.clone(),
+            value: input.value.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+impl From<ChatReply> for NodeOutcome {
+    fn from(reply: ChatReply) -> Self {
+        Self {
+            output: reply.content,
+            value: None,
+            label: None,
+            usage: reply.usage,
+            cache_hits: reply.cached as u32,
+            context: None,
+        }
+    }
+}
+
+/// Shared state a workflow run reads and writes.
+#[derive(Clone, Copy)]
+pub struct Services<'a> {
+    pub log_state: &'a Mutex<LogState>,
+    pub buffers: &'a OutputBuffers,
+    pub history: &'a RunHistory,
+    pub cache: &'a ResponseCache,
+    pub tools: &'a ToolRegistry,
+    pub approvals: &'a Approvals,
+    pub knowledge: &'a KnowledgeBase,
+    pub memory: &'a MemoryStore,
+    pub prompts: &'a PromptLibrary,
+}
+
+impl<'a> Services<'a> {
+    /// Borrow the services from the state managed by the app.
+    pub fn from_app(app: &'a AppHandle) -> Self {
+        Self {
+            log_state: app.state::<Mutex<LogState>>().inner(),
+            buffers: app.state::<OutputBuffers>().inner(),
+            history: app.state::<RunHistory>().inner(),
+            cache: app.state::<ResponseCache>().inner(),
+            tools: app.state::<ToolRegistry>().inner(),
+            approvals: app.state::<Approvals>().inner(),
+            knowledge: app.state::<KnowledgeBase>().inner(),
+            memory: app.state::<MemoryStore>().inner(),
+            prompts: app.state::<PromptLibrary>().inner(),
+        }
+    }
+}
+
+/// What every node of a single run can reach.
+struct RunContext<'a> {
+    run_id: &'a str,
+    workflow_id: &'a str,
+    services: Services<'a>,
+}
+
+impl Workflow {
+    fn node_index(&self) -> HashMap<&str, usize> {
+        self.nodes.iter().enumerate().map(|(i, n)| (n.id.as_str(), i)).collect()
+    }
+
+    /// Order nodes so that every node comes after all of its upstream nodes.
+    fn topological_order(&self) -> Result<Vec<usize>, String> {
+        let index = self.node_index();
+        let mut in_degree = vec![0usize; self.nodes.len()];
+        for edge in &self.edges {
+            let to = *index.get(edge.to.as_str())
+                .ok_or_else(|| format!("Edge points to unknown node '{}'", edge.to))?;
+            if !index.contains_key(edge.from.as_str()) {
+                return Err(format!("Edge starts at unknown node '{}'", edge.from));
+            }
+            in_degree[to] += 1;
+        }
+
+        let mut queue: VecDeque<usize> = (0..self.nodes.len()).filter(|&i| in_degree[i] == 0).collect();
+        let mut order = Vec::with_capacity(self.nodes.len());
+        while let Some(i) = queue.pop_front() {
+            order.push(i);
+            for edge in self.edges.iter().filter(|e| e.from == self.nodes[i].id) {
+                let to = index[edge.to.as_str()];
+                in_degree[to] -= 1;
+                if in_degree[to] == 0 {
+                    queue.push_back(to);
+                }
+            }
+        }
+
+        if order.len() != self.nodes.len() {
+            return Err("Workflow contains a cycle".to_string());
+        }
+        Ok(order)
+    }
+
+    /// The node and everything reachable from it.
+    fn downstream_of(&self, node_id: &str) -> HashSet<String> {
+        let mut seen = HashSet::from([node_id.to_string()]);
+        let mut queue = VecDeque::from([node_id.to_string()]);
+        while let Some(current) = queue.pop_front() {
+            for edge in self.edges.iter().filter(|e| e.from == current) {
+                if seen.insert(edge.to.clone()) {
+                    queue.push_back(edge.to.clone());
+                }
+            }
+        }
+        seen
+    }
+}
+
+/// Pick the edges a router follows: the first edge whose condition matches,
+/// or every default edge when none does.
+fn select_router_edges(edges: &[(usize, &WorkflowEdge)], input: &NodeInput, label: Option<&str>) -> Vec<usize> {
+    for (i, edge) in edges {
+        if edge.condition.as_ref().is_some_and(|condition| condition.matches(input, label)) {
+            return vec![*i];
+        }
+    }
+    edges.iter()
+        .filter(|(_, e)| e.condition.as_ref().is_none_or(EdgeCondition::is_default))
+        .map(|(i, _)| *i)
+        .collect()
+}
+
+/// Fill in the task text. `{{input.path}}` picks a field out of typed (or
+/// JSON) input; other placeholder-free prompts get the input appended.
+fn render_prompt(prompt: &str, input: &NodeInput) -> String {
+    let picks_fields = prompt.contains("{{input.");
+    let prompt = if picks_fields {
+        let parsed = input.value.clone().or_else(|| serde_json::from_str(input.text.trim()).ok());
+        INPUT_FIELD.replace_all(prompt, |caps: &regex::Captures| {
+            match parsed.as_ref().and_then(|document| condition::lookup(document, &caps[1])) {
+                Some(Value::String(text)) => text.clone(),
+                Some(other) => other.to_string(),
+                None => String::new(),
+            }
+        })
+        .into_owned()
+    } else {
+        prompt.to_string()
+    };
+    let input = input.text.as_str();
+    if prompt.contains("{{input}}") {
+        prompt.replace("{{input}}", input)
+    } else if input.is_empty() || picks_fields {
+        prompt
+    } else if prompt.trim().is_empty() {
+        input.to_string()
+    } else {
+        format!("{}\n\n{}", prompt.trim_end(), input)
+    }
+}
+
+async fn classify(ctx: &RunContext<'_>, config: &ClassifierConfig, input: &NodeInput) -> Result<NodeOutcome, String> {
+    if config.labels.is_empty() {
+        return Err("Classifier router has no labels".to_string());
+    }
+    let mut instructions = format!(
+        "Classify the user's input into exactly one of these labels: {}.\nReply with the label only.",
+        config.labels.join(", ")
+    );
+    if let Some(extra) = config.instructions.as_deref().filter(|s| !s.trim().is_empty()) {
+        instructions = format!("{}\n\n{}", extra.trim(), instructions);
+    }
+
+    let reply = ctx.services.cache.chat(
+        &config.server_url,
+        &config.model,
+        &[ChatMessage::system(instructions), ChatMessage::user(input.text.clone())],
+        &ChatOptions::with_temperature(0.0),
+    ).await?;
+
+    let answer = reply.content.trim().trim_matches(|c: char| c == '"' || c == '\'' || c == '.').to_lowercase();
+    let label = config.labels.iter()
+        .find(|label| answer == label.trim().to_lowercase())
+        .or_else(|| config.labels.iter().find(|label| answer.contains(&label.trim().to_lowercase())))
+        .cloned();
+    Ok(NodeOutcome {
+        label,
+        usage: reply.usage,
+        cache_hits: reply.cached as u32,
+        ..NodeOutcome::passthrough(input)
+    })
+}
+
+/// Run the node's task on `input`. With an output schema the reply is
+/// validated, repaired if needed, and returned as typed JSON as well.
+async fn call_llm(ctx: &RunContext<'_>, node_id: &str, config: &LlmNodeConfig, input: &NodeInput) -> Result<NodeOutcome, String> {
+    let (system, task) = match &config.prompt_ref {
+        Some(reference) => {
+            let library = ctx.services.prompts.render(reference)?;
+            add_log_entry(
+                ctx.services.log_state,
+                "debug",
+                &format!("Node {} using prompt '{}' v{}", node_id, library.name, library.version),
+                "workflow",
+            )?;
+            let pick = |part: String, own: &str| if part.trim().is_empty() { own.to_string() } else { part };
+            (pick(library.system, &config.system), pick(library.user, &config.prompt))
+        }
+        None => (config.system.clone(), config.prompt.clone()),
+    };
+    let prompt = render_prompt(&task, input);
+    if prompt.trim().is_empty() {
+        return Err("Node has no task or input".to_string());
+    }
+    let images = if config.images.is_empty() {
+        Vec::new()
+    } else {
+        check_vision(ctx, config).await?;
+        config.images.iter().cloned().map(ImageInput::checked).collect::<Result<Vec<_>, _>>()?
+    };
+    let mut context = None;
+    let mut messages = Vec::new();
+    if !system.trim().is_empty() {
+        messages.push(ChatMessage::system(system));
+    }
+    messages.push(ChatMessage::user(prompt).with_images(images));
+    let reply = if config.tools.is_empty() {
+        let options = ChatOptions {
+            temperature: config.temperature,
+            response_schema: config.output_schema.clone(),
+            ..Default::default()
+        };
+        context::fit(ctx, node_id, config, &options, &mut messages, &mut context).await?;
+        stream_chat(ctx, node_id, config, &messages, &options).await?
+    } else {
+        chat_with_tools(ctx, node_id, config, &mut messages, &mut context).await?
+    };
+    let mut outcome = match &config.output_schema {
+        Some(output_schema) => {
+            let (reply, value) = schema::conform(ctx, node_id, config, output_schema, messages, reply, &mut context).await?;
+            NodeOutcome {
+                output: serde_json::to_string_pretty(&value).map_err(|e| e.to_string())?,
+                value: Some(value),
+                ..reply.into()
+            }
+        }
+        None => reply.into(),
+    };
+    if let Some(report) = &context {
+        outcome.usage.add(report.summary_usage);
+    }
+    outcome.context = context;
+    Ok(outcome)
+}
+
+/// Chat for an LLM node, broadcasting the reply's text as `node_chunk`
+/// updates while it arrives.
+async fn stream_chat(
+    ctx: &RunContext<'_>,
+    node_id: &str,
+    config: &LlmNodeConfig,
+    messages: &[ChatMessage],
+    options: &ChatOptions,
+) -> Result<ChatReply, String> {
+    let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+    let chat = async move {
+        ctx.services.cache.chat_streaming(&config.server_url, &config.model, messages, options, Some(&sender)).await
+    };
+    let forward = async {
+        while let Some(chunk) = receiver.recv().await {
+            broadcast_update(json!({
+                "type": "node_chunk",
+                "data": { "run_id": ctx.run_id, "node_id": node_id, "chunk": chunk }
+            })).await;
+        }
+    };
+    let (reply, ()) = tokio::join!(chat, forward);
+    reply
+}
+
+/// Refuse to send images to a model its server says cannot see them. When
+/// the server does not say, the images are sent anyway.
+async fn check_vision(ctx: &RunContext<'_>, config: &LlmNodeConfig) -> Result<(), String> {
+    match provider::model_info(&config.server_url, &config.model).await {
+        Ok(ModelInfo { vision: Some(false), .. }) => Err(format!("Model {} does not accept images", config.model)),
+        Ok(_) => Ok(()),
+        Err(e) => add_log_entry(
+            ctx.services.log_state,
+            "debug",
+            &format!("Could not check whether {} accepts images: {}", config.model, e),
+            "workflow",
+        ),
+    }
+}
+
+/// Chat with the node's tools available, running whatever the model calls and
+/// feeding the results back until it answers without calling any. The tool
+/// turns are left in `messages`, so a schema repair sees the whole exchange.
+async fn chat_with_tools(
+    ctx: &RunContext<'_>,
+    node_id: &str,
+    config: &LlmNodeConfig,
+    messages: &mut Vec<ChatMessage>,
+    context: &mut Option<ContextReport>,
+) -> Result<ChatReply, String> {
+    let tools = ctx.services.tools.resolve(&config.tools);
+    if tools.is_empty() {
+        add_log_entry(
+            ctx.services.log_state,
+            "warn",
+            &format!("Node {} selected tools that are not available: {}", node_id, config.tools.join(", ")),
+            "workflow",
+        )?;
+    }
+    let options = ChatOptions {
+        temperature: config.temperature,
+        tools: tools.iter().map(|tool| tool.definition()).collect(),
+        ..Default::default()
+    };
+
+    let mut usage = Usage::default();
+    let mut cached = true;
+    let mut sources = Vec::new();
+    for _ in 0..config.max_tool_steps.max(1) {
+        context::fit(ctx, node_id, config, &options, messages, context).await?;
+        let reply = stream_chat(ctx, node_id, config, messages, &options).await?;
+        usage.add(reply.usage);
+        cached &= reply.cached;
+        if reply.tool_calls.is_empty() {
+            let content = web::cite_sources(reply.content, &sources);
+            return Ok(ChatReply { content, usage, cached, ..reply });
+        }
+
+        messages.push(ChatMessage::assistant(reply.content, reply.tool_calls.clone()));
+        for call in &reply.tool_calls {
+            let result = run_tool(ctx, node_id, &tools, call).await?;
+            sources.extend(web::fetched_source(call, &result));
+            messages.push(ChatMessage::tool(call, result));
+        }
+    }
+    Err(format!("Model gave no final answer within {} tool steps", config.max_tool_steps.max(1)))
+}
+
+/// Run one tool call. Tool failures are reported back to the model rather
+/// than failing the node.
+async fn run_tool(ctx: &RunContext<'_>, node_id: &str, tools: &[Arc<dyn Tool>], call: &ToolCall) -> Result<String, String> {
+    let log_state = ctx.services.log_state;
+    add_log_entry(log_state, "debug", &format!("Node {} calling tool {} with {}", node_id, call.name, call.arguments), "tools")?;
+
+    let result = match tools.iter().find(|tool| tool.name() == call.name) {
+        Some(tool) => {
+            let tool_ctx = ToolContext {
+                run_id: ctx.run_id,
+                node_id,
+                log_state,
+                approvals: ctx.services.approvals,
+                history: ctx.services.history,
+            };
+            tool.call(&tool_ctx, call.arguments.clone()).await
+        }
+        None => Err(format!("Tool '{}' is not available to this node", call.name)),
+    };
+    if let Err(e) = &result {
+        add_log_entry(log_state, "warn", &format!("Tool {} failed for node {}: {}", call.name, node_id, e), "tools")?;
+    }
+
+    broadcast_update(json!({
+        "type": "tool_call",
+        "data": {
+            "run_id": ctx.run_id,
+            "node_id": node_id,
+            "tool": call.name,
+            "arguments": call.arguments,
+            "result": result.as_ref().ok(),
+            "error": result.as_ref().err(),
+        }
+    })).await;
+    Ok(result.unwrap_or_else(|e| format!("Error: {}", e)))
+}
+
+async fn run_node(ctx: &RunContext<'_>, node: &WorkflowNode, input: &NodeInput) -> Result<NodeOutcome, String> {
+    match &node.kind {
+        NodeKind::Input { value } => Ok(NodeOutcome::passthrough(&NodeInput::text(render_prompt(value, input)))),
+        NodeKind::Llm(config) => call_llm(ctx, &node.id, config, input).await,
+        NodeKind::Map(config) => iteration::run_map(ctx, &node.id, config, input).await,
+        NodeKind::While(config) => {
+            let (outcome, converged) = iteration::run_while(ctx, &node.id, config, input).await?;
+            if !converged {
+                add_log_entry(
+                    ctx.services.log_state,
+                    "warn",
+                    &format!("Node {} stopped after {} iterations without meeting its condition", node.id, config.max_iterations),
+                    "workflow",
+                )?;
+            }
+            Ok(outcome)
+        }
+        NodeKind::Router(config) => match &config.classifier {
+            Some(classifier) => classify(ctx, classifier, input).await,
+            None => Ok(NodeOutcome::passthrough(input)),
+        },
+        NodeKind::Retriever(config) => retriever::run_retriever(ctx, &node.id, config, input).await,
+        NodeKind::Memory(config) => memory::run_memory(ctx, &node.id, config, input).await,
+        NodeKind::Output => Ok(NodeOutcome::passthrough(input)),
+    }
+}
+
+async fn announce(run_id: &str, result: &NodeResult) {
+    broadcast_update(json!({
+        "type": "node_status",
+        "data": {
+            "run_id": run_id,
+            "node_id": result.node_id,
+            "status": result.status,
+            "output": result.output,
+            "error": result.error,
+            "label": result.label,
+        }
+    })).await;
+}
+
+pub async fn run_workflow(workflow: &Workflow, services: Services<'_>) -> Result<WorkflowRun, String> {
+    execute(workflow, None, services).await
+}
+
+/// Re-run `from_node` and everything downstream of it, reusing the outputs
+/// the source run completed for every other node. Pinned nodes keep their
+/// checkpointed output even when they are downstream.
+pub async fn resume_run(
+    source_run_id: &str,
+    from_node: &str,
+    pinned: &[String],
+    workflow: Option<Workflow>,
+    services: Services<'_>,
+) -> Result<WorkflowRun, String> {
+    let source = services.history.get_run(source_run_id)?;
+    let workflow = workflow.unwrap_or(source.workflow);
+    if !workflow.nodes.iter().any(|n| n.id == from_node) {
+        return Err(format!("Node '{}' is not part of the workflow", from_node));
+    }
+    if let Some(missing) = pinned.iter().find(|id| !source.nodes.iter().any(|n| &n.node_id == *id && n.status == NodeStatus::Completed)) {
+        return Err(format!("Node '{}' has no completed output in run {} to pin", missing, source_run_id));
+    }
+
+    let rerun = workflow.downstream_of(from_node);
+    let checkpoint = Checkpoint {
+        run_id: source_run_id.to_string(),
+        nodes: source.nodes.into_iter()
+            .filter(|n| n.status == NodeStatus::Completed)
+            .filter(|n| !rerun.contains(&n.node_id) || pinned.contains(&n.node_id))
+            .map(|n| (n.node_id.clone(), n))
+            .collect(),
+    };
+    execute(&workflow, Some(&checkpoint), services).await
+}
+
+async fn execute(workflow: &Workflow, checkpoint: Option<&Checkpoint>, services: Services<'_>) -> Result<WorkflowRun, String> {
+    let Services { log_state, history, .. } = services;
+    let order = workflow.topological_order()?;
+    let run_id = uuid::Uuid::new_v4().to_string();
+    let started_at = chrono::Local::now().to_rfc3339();
+    let ctx = RunContext {
+        run_id: &run_id,
+        workflow_id: &workflow.id,
+        services,
+    };
+    let resumed_from = checkpoint.map(|c| c.run_id.clone());
+    match &resumed_from {
+        Some(source) => add_log_entry(
+            log_state,
+            "info",
+            &format!("Resuming workflow '{}' from run {} (run {})", workflow.id, source, run_id),
+            "workflow",
+        )?,
+        None => add_log_entry(log_state, "info", &format!("Starting workflow '{}' (run {})", workflow.id, run_id), "workflow")?,
+    }
+    if let Err(e) = history.start_run(&run_id, workflow, &started_at, resumed_from.as_deref()) {
+        add_log_entry(log_state, "error", &format!("Failed to record run {}: {}", run_id, e), "workflow")?;
+    }
+
+    let mut outputs: HashMap<&str, NodeInput> = HashMap::new();
+    let mut taken: HashSet<usize> = HashSet::new();
+    let mut results = Vec::with_capacity(order.len());
+
+    for i in order {
+        let node = &workflow.nodes[i];
+        let incoming: Vec<usize> = workflow.edges.iter().enumerate()
+            .filter(|(_, e)| e.to == node.id)
+            .map(|(j, _)| j)
+            .collect();
+        let inputs: Vec<&NodeInput> = incoming.iter()
+            .filter(|j| taken.contains(j))
+            .filter_map(|&j| outputs.get(workflow.edges[j].from.as_str()))
+            .collect();
+
+        let mut result = NodeResult {
+            node_id: node.id.clone(),
+            node_type: node.kind.type_name().to_string(),
+            status: NodeStatus::Skipped,
+            input: None,
+            output: None,
+            value: None,
+            error: None,
+            label: None,
+            model: node.kind.model().map(str::to_string),
+            params: node.kind.params(),
+            usage: Usage::default(),
+            latency_ms: 0,
+            started_at: chrono::Local::now().to_rfc3339(),
+            cache_hits: 0,
+            reused_from: None,
+            artifacts: Vec::new(),
+            context: None,
+        };
+
+        if incoming.is_empty() || !inputs.is_empty() {
+            let input = NodeInput::join(&inputs);
+            let started = Instant::now();
+            let previous = checkpoint.and_then(|c| c.nodes.get(&node.id).map(|n| (c, n)));
+            let outcome = match previous {
+                Some((checkpoint, previous)) => {
+                    result.reused_from = Some(checkpoint.run_id.clone());
+                    Ok(NodeOutcome {
+                        output: previous.output.clone().unwrap_or_default(),
+                        value: previous.value.clone(),
+                        label: previous.label.clone(),
+                        ..Default::default()
+                    })
+                }
+                None => run_node(&ctx, node, &input).await,
+            };
+            match outcome {
+                Ok(outcome) => {
+                    let outgoing: Vec<(usize, &WorkflowEdge)> = workflow.edges.iter().enumerate()
+                        .filter(|(_, e)| e.from == node.id)
+                        .collect();
+                    let produced = NodeInput {
+                        text: outcome.output,
+                        value: outcome.value,
+                    };
+                    if matches!(node.kind, NodeKind::Router(_)) {
+                        taken.extend(select_router_edges(&outgoing, &produced, outcome.label.as_deref()));
+                    } else {
+                        taken.extend(outgoing.iter().map(|(j, _)| *j));
+                    }
+                    result.status = NodeStatus::Completed;
+                    result.output = Some(produced.text.clone());
+                    result.value = produced.value.clone();
+                    outputs.insert(node.id.as_str(), produced);
+                    result.label = outcome.label;
+                    result.usage = outcome.usage;
+                    result.cache_hits = outcome.cache_hits;
+                    result.context = outcome.context;
+                }
+                Err(e) => {
+                    result.status = NodeStatus::Failed;
+                    result.error = Some(e);
+                }
+            }
+            result.latency_ms = started.elapsed().as_millis() as u64;
+            result.input = Some(input.text);
+            result.artifacts = history.artifacts(&run_id, Some(&node.id)).unwrap_or_default();
+        }
+
+        match result.status {
+            NodeStatus::Failed => add_log_entry(
+                log_state,
+                "error",
+                &format!("Node {} failed: {}", node.id, result.error.as_deref().unwrap_or_default()),
+                "workflow",
+            )?,
+            NodeStatus::Skipped => add_log_entry(log_state, "debug", &format!("Node {} skipped", node.id), "workflow")?,
+            NodeStatus::Completed => match &result.reused_from {
+                Some(source) => add_log_entry(log_state, "debug", &format!("Node {} reused output from run {}", node.id, source), "workflow")?,
+                None if result.cache_hits > 0 => add_log_entry(
+                    log_state,
+                    "info",
+                    &format!("Node {} completed with {} cached response(s)", node.id, result.cache_hits),
+                    "workflow",
+                )?,
+                None => add_log_entry(log_state, "debug", &format!("Node {} completed", node.id), "workflow")?,
+            },
+        }
+        if let Err(e) = history.record_node(&run_id, results.len(), &result) {
+            add_log_entry(log_state, "error", &format!("Failed to record node {}: {}", node.id, e), "workflow")?;
+        }
+        announce(&run_id, &result).await;
+        results.push(result);
+    }
+
+    let status = if results.iter().any(|r| r.status == NodeStatus::Failed) {
+        RunStatus::Failed
+    } else {
+        RunStatus::Completed
+    };
+    let finished_at = chrono::Local::now().to_rfc3339();
+    if let Err(e) = history.finish_run(&run_id, status, &finished_at) {
+        add_log_entry(log_state, "error", &format!("Failed to record run {}: {}", run_id, e), "workflow")?;
+    }
+    add_log_entry(log_state, "info", &format!("Workflow '{}' {} (run {})", workflow.id, status.as_str(), run_id), "workflow")?;
+
+    Ok(WorkflowRun {
+        run_id,
+        workflow_id: workflow.id.clone(),
+        status,
+        started_at,
+        finished_at,
+        resumed_from,
+        nodes: results,
+    })
+}
+
+#[tauri::command]
+pub async fn execute_workflow(app: AppHandle, workflow: Workflow) -> Result<WorkflowRun, String> {
+    run_workflow(&workflow, Services::from_app(&app)).await
+}
+
+/// Resume a run from `from_node`. Passing `workflow` re-runs against an
+/// edited graph; otherwise the definition stored with the source run is used.
+#[tauri::command]
+pub async fn resume_workflow(
+    app: AppHandle,
+    run_id: String,
+    from_node: String,
+    pinned: Option<Vec<String>>,
+    workflow: Option<Workflow>,
+) -> Result<WorkflowRun, String> {
+    resume_run(&run_id, &from_node, &pinned.unwrap_or_default(), workflow, Services::from_app(&app)).await
+}
+
+/// Stores and tools for running workflows in tests.
+#[cfg(test)]
+pub(crate) mod testing {
+    use super::*;
+    use async_trait::async_trait;
+
+    /// Owns the state a run borrows through `Services`.
+    pub(crate) struct TestServices {
+        log_state: Mutex<LogState>,
+        buffers: OutputBuffers,
+        history: RunHistory,
+        cache: ResponseCache,
+        tools: ToolRegistry,
+        approvals: Approvals,
+        knowledge: KnowledgeBase,
+        memory: MemoryStore,
+        prompts: PromptLibrary,
+    }
+
+    impl TestServices {
+        pub(crate) fn new() -> Self {
+            Self {
+                log_state: Mutex::new(LogState { entries: Default::default() }),
+                buffers: OutputBuffers::default(),
+                history: RunHistory::open_in_memory().unwrap(),
+                cache: ResponseCache::open_in_memory().unwrap(),
+                tools: ToolRegistry::default(),
+                approvals: Approvals::default(),
+                knowledge: KnowledgeBase::open_in_memory().unwrap(),
+                memory: MemoryStore::open_in_memory().unwrap(),
+                prompts: PromptLibrary::open_in_memory().unwrap(),
+            }
+        }
+
+        pub(crate) fn with_tool(self, tool: Arc<dyn Tool>) -> Self {
+            self.tools.register(tool);
+            self
+        }
+
+        pub(crate) fn services(&self) -> Services<'_> {
+            Services {
+                log_state: &self.log_state,
+                buffers: &self.buffers,
+                history: &self.history,
+                cache: &self.cache,
+                tools: &self.tools,
+                approvals: &self.approvals,
+                knowledge: &self.knowledge,
+                memory: &self.memory,
+                prompts: &self.prompts,
+            }
+        }
+
+        pub(crate) async fn run(&self, workflow: Value) -> WorkflowRun {
+            run_workflow(&serde_json::from_value(workflow).unwrap(), self.services()).await.unwrap()
+        }
+    }
+
+    /// Echoes its `text` argument, in the `testing` group.
+    pub(crate) struct Echo;
+
+    #[async_trait]
+    impl Tool for Echo {
+        fn name(&self) -> &str {
+            "echo"
+        }
+
+        fn group(&self) -> &str {
+            "testing"
+        }
+
+        fn description(&self) -> &str {
+            "Echo the text back"
+        }
+
+        fn parameters(&self) -> Value {
+            json!({"type": "object", "properties": {"text": {"type": "string"}}})
+        }
+
+        async fn call(&self, _ctx: &ToolContext<'_>, arguments: Value) -> Result<String, String> {
+            Ok(format!("echo: {}", arguments["text"].as_str().unwrap_or_default()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::testing::{Echo, TestServices};
+    use crate::provider::mock::{Fixture, MockLlmServer, MOCK_MODEL, SCHEME};
+    use tokio_tungstenite::tungstenite::Message;
+
+    fn fixture(responses: Value) -> Fixture {
+        serde_json::from_value(json!({"responses": responses})).unwrap()
+    }
+
+    /// Write `responses` to a fixture file, returning its `mock://` URL.
+    fn fixture_url(name: &str, responses: Value) -> (String, std::path::PathBuf) {
+        let path = std::env::temp_dir().join(format!("workflow-{}-{}.json", name, std::process::id()));
+        std::fs::write(&path, json!({"responses": responses}).to_string()).unwrap();
+        (format!("{}{}", SCHEME, path.display()), path)
+    }
+
+    #[tokio::test]
+    async fn mock_urls_drive_tools_and_failures() {
+        let (url, path) = fixture_url("run", json!([
+            {"match": "weather", "times": 1, "tool_calls": [{"name": "echo", "arguments": {"text": "hi"}}]},
+            {"match": "weather", "content": "sunny"},
+            {"match": "explode", "error": "model overloaded", "status": 503}
+        ]));
+        let services = TestServices::new().with_tool(Arc::new(Echo));
+        let run = services.run(json!({"id": "w", "nodes": [
+            {"id": "a", "type": "llm", "server_url": url, "model": "m", "prompt": "weather?", "tools": ["testing"]},
+            {"id": "b", "type": "llm", "server_url": url, "model": "m", "prompt": "explode"},
+            {"id": "c", "type": "llm", "server_url": SCHEME, "model": MOCK_MODEL, "prompt": "echo me"}
+        ], "edges": []})).await;
+        assert_eq!(run.nodes[0].output.as_deref(), Some("sunny"));
+        assert_eq!(run.nodes[1].status, NodeStatus::Failed);
+        assert!(run.nodes[1].error.as_deref().unwrap().contains("503 model overloaded"));
+        assert_eq!(run.nodes[2].output.as_deref(), Some("echo me"));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn node_replies_stream_over_the_websocket() {
+        let (url, path) = fixture_url("stream", json!([
+            {"chunks": ["Hel", "lo ", "world"], "chunk_delay_ms": 10}
+        ]));
+        let (sender, mut receiver) = tokio::sync::mpsc::channel(1024);
+        *crate::WS_SENDER.lock().await = Some(sender);
+        let updates = tokio::spawn(async move {
+            let mut updates = Vec::new();
+            while let Some(Message::Text(text)) = receiver.recv().await {
+                updates.push(serde_json::from_str::<Value>(text.as_str()).unwrap());
+            }
+            updates
+        });
+
+        let services = TestServices::new().with_tool(Arc::new(Echo));
+        let run = services.run(json!({"id": "w", "nodes": [
+            {"id": "a", "type": "llm", "server_url": url, "model": "m", "prompt": "hi"}
+        ], "edges": []})).await;
+        *crate::WS_SENDER.lock().await = None;
+        assert_eq!(run.nodes[0].output.as_deref(), Some("Hello world"));
+
+        // Other tests may broadcast at the same time; keep this run's updates
+        let updates: Vec<Value> = updates.await.unwrap().into_iter()
+            .filter(|update| update["data"]["run_id"] == run.run_id.as_str())
+            .collect();
+        let kinds: Vec<&str> = updates.iter().map(|update| update["type"].as_str().unwrap()).collect();
+        assert_eq!(kinds, ["node_chunk", "node_chunk", "node_chunk", "node_status"]);
+        let chunks: Vec<&str> = updates[..3].iter().map(|update| update["data"]["chunk"].as_str().unwrap()).collect();
+        assert_eq!(chunks, ["Hel", "lo ", "world"]);
+        assert_eq!(updates[0]["data"]["node_id"], "a");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn schema_repair_sees_tool_turns() {
+        let server = MockLlmServer::with_fixture(fixture(json!([
+            {"match": "weather", "times": 1, "tool_calls": [{"name": "echo", "arguments": {"text": "sunny"}}]},
+            {"match": "weather", "times": 1, "content": "It is sunny"},
+            {"match": "corrected JSON", "content": "{\"sky\": \"sunny\"}"}
+        ]))).await.unwrap();
+        let services = TestServices::new().with_tool(Arc::new(Echo));
+        let run = services.run(json!({"id": "w", "nodes": [{
+            "id": "a", "type": "llm", "server_url": server.base_url, "model": "m",
+            "prompt": "weather?", "tools": ["testing"],
+            "output_schema": {"type": "object", "required": ["sky"]}
+        }]})).await;
+        assert_eq!(run.nodes[0].status, NodeStatus::Completed, "{:?}", run.nodes[0].error);
+        assert_eq!(run.nodes[0].value, Some(json!({"sky": "sunny"})));
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 3);
+        let roles: Vec<&str> = requests[2]["messages"].as_array().unwrap().iter()
+            .map(|message| message["role"].as_str().unwrap())
+            .collect();
+        assert_eq!(roles, ["user", "assistant", "tool", "assistant", "user"]);
+        assert_eq!(requests[2]["messages"][2]["content"], "echo: sunny");
+    }
+
+    fn router(input: &str) -> Value {
+        json!({"id": "w", "nodes": [
+            {"id": "in", "type": "input", "value": input},
+            {"id": "route", "type": "router"},
+            {"id": "refund", "type": "input", "value": "refund: {{input}}"},
+            {"id": "refund_done", "type": "output"},
+            {"id": "other", "type": "input", "value": "other: {{input}}"}
+        ], "edges": [
+            {"from": "in", "to": "route"},
+            {"from": "route", "to": "refund", "condition": {"kind": "regex", "pattern": "(?i)\\brefund\\b"}},
+            {"from": "refund", "to": "refund_done"},
+            {"from": "route", "to": "other", "condition": {"kind": "default"}}
+        ]})
+    }
+
+    fn node<'a>(run: &'a WorkflowRun, id: &str) -> &'a NodeResult {
+        run.nodes.iter().find(|n| n.node_id == id).unwrap()
+    }
+
+    #[tokio::test]
+    async fn routers_take_the_matching_branch() {
+        let run = TestServices::new().run(router("I want a Refund please")).await;
+        assert_eq!(run.status, RunStatus::Completed);
+        for id in ["in", "route", "refund", "refund_done"] {
+            assert_eq!(node(&run, id).status, NodeStatus::Completed, "{}", id);
+        }
+        assert_eq!(node(&run, "other").status, NodeStatus::Skipped);
+        assert_eq!(node(&run, "refund_done").output.as_deref(), Some("refund: I want a Refund please"));
+    }
+
+    #[tokio::test]
+    async fn routers_fall_back_to_the_default_edge() {
+        let run = TestServices::new().run(router("where is my parcel")).await;
+        assert_eq!(run.status, RunStatus::Completed);
+        for id in ["refund", "refund_done"] {
+            assert_eq!(node(&run, id).status, NodeStatus::Skipped, "{}", id);
+        }
+        assert_eq!(node(&run, "other").output.as_deref(), Some("other: where is my parcel"));
+    }
+
+    #[test]
+    fn invalid_patterns_reject_the_workflow() {
+        let mut workflow = router("x");
+        workflow["edges"][1]["condition"]["pattern"] = json!("(unclosed");
+        let error = serde_json::from_value::<Workflow>(workflow).unwrap_err().to_string();
+        assert!(error.starts_with("Invalid regex '(unclosed'"), "{}", error);
+    }
+
+    fn map(url: &str, items: Value, extra: Value) -> Value {
+        let mut map = json!({"id": "each", "type": "map", "server_url": url, "model": "m", "prompt": "{{input}}"});
+        map.as_object_mut().unwrap().extend(extra.as_object().unwrap().clone());
+        json!({"id": "w", "nodes": [
+            {"id": "in", "type": "input", "value": items.to_string()},
+            map,
+            {"id": "out", "type": "output"}
+        ], "edges": [{"from": "in", "to": "each"}, {"from": "each", "to": "out"}]})
+    }
+
+    #[tokio::test]
+    async fn map_keeps_item_order_while_running_concurrently() {
+        let server = MockLlmServer::with_fixture(fixture(json!([
+            {"match": "^slow$", "content": "S", "delay_ms": 300},
+            {"match": "^medium$", "content": "M", "delay_ms": 150}
+        ]))).await.unwrap();
+        let services = TestServices::new();
+        let run = services.run(map(&server.base_url, json!(["slow", "medium", "fast"]), json!({"concurrency": 3}))).await;
+        assert_eq!(node(&run, "out").output.as_deref(), Some(r#"["S","M","fast"]"#));
+
+        // Records land as items finish, so the fast item was not held up.
+        let finished: Vec<usize> = services.services().buffers.get("each").iter().map(|r| r.index).collect();
+        assert_eq!(finished, [2, 1, 0]);
+    }
+
+    #[tokio::test]
+    async fn map_fails_on_a_failed_item_or_too_many_items() {
+        let server = MockLlmServer::with_fixture(fixture(json!([
+            {"match": "^bad$", "error": "model overloaded", "status": 503}
+        ]))).await.unwrap();
+        let services = TestServices::new();
+        let run = services.run(map(&server.base_url, json!(["good", "bad", "good too"]), json!({}))).await;
+        assert_eq!(run.status, RunStatus::Failed);
+        let each = node(&run, "each");
+        assert!(each.error.as_deref().unwrap().starts_with("Item 1 failed: "), "{:?}", each.error);
+        assert!(each.error.as_deref().unwrap().contains("503"), "{:?}", each.error);
+        assert_eq!(node(&run, "out").status, NodeStatus::Skipped);
+        assert_eq!(services.services().buffers.get("each").len(), 3);
+
+        let run = services.run(map(&server.base_url, json!(["a", "b", "c"]), json!({"max_items": 2}))).await;
+        assert_eq!(node(&run, "each").error.as_deref(), Some("List has 3 items, more than the limit of 2"));
+        assert_eq!(server.requests().len(), 3);
+    }
+
+    fn while_loop(url: &str, pattern: &str, max_iterations: usize) -> Value {
+        json!({"id": "w", "nodes": [
+            {"id": "in", "type": "input", "value": "start"},
+            {"id": "loop", "type": "while", "server_url": url, "model": "m", "prompt": "{{input}}",
+             "until": {"kind": "regex", "pattern": pattern}, "max_iterations": max_iterations}
+        ], "edges": [{"from": "in", "to": "loop"}]})
+    }
+
+    #[tokio::test]
+    async fn while_stops_when_the_condition_holds() {
+        let server = MockLlmServer::with_fixture(fixture(json!([
+            {"match": "^start$", "content": "draft"},
+            {"match": "^draft$", "content": "DONE"}
+        ]))).await.unwrap();
+        let services = TestServices::new();
+        let run = services.run(while_loop(&server.base_url, "DONE", 5)).await;
+        assert_eq!(node(&run, "loop").output.as_deref(), Some("DONE"));
+        let inputs: Vec<String> = services.services().buffers.get("loop").into_iter().map(|r| r.input).collect();
+        assert_eq!(inputs, ["start", "draft"]);
+    }
+
+    #[tokio::test]
+    async fn while_is_capped_and_runs_at_least_once() {
+        let server = MockLlmServer::with_fixture(fixture(json!([
+            {"content": "again"}
+        ]))).await.unwrap();
+        let services = TestServices::new();
+        let run = services.run(while_loop(&server.base_url, "DONE", 3)).await;
+        assert_eq!(node(&run, "loop").status, NodeStatus::Completed);
+        assert_eq!(node(&run, "loop").output.as_deref(), Some("again"));
+        assert_eq!(services.services().buffers.get("loop").len(), 3);
+        let warned = services.services().log_state.lock().unwrap().entries.iter()
+            .any(|e| e.message == "Node loop stopped after 3 iterations without meeting its condition");
+        assert!(warned);
+
+        services.services().buffers.clear("loop");
+        services.run(while_loop(&server.base_url, "DONE", 0)).await;
+        assert_eq!(services.services().buffers.get("loop").len(), 1);
+    }
+
+    #[tokio::test]
+    async fn resuming_reruns_only_the_failed_node_and_downstream() {
+        let server = MockLlmServer::with_fixture(fixture(json!([
+            {"match": "^Summarize", "content": "summary"},
+            {"match": "^Translate", "times": 1, "error": "model overloaded", "status": 503},
+            {"match": "^Translate", "content": "résumé"}
+        ]))).await.unwrap();
+        let workflow = json!({"id": "w", "nodes": [
+            {"id": "in", "type": "input", "value": "a long text"},
+            {"id": "summarize", "type": "llm", "server_url": server.base_url, "model": "m", "prompt": "Summarize: {{input}}"},
+            {"id": "translate", "type": "llm", "server_url": server.base_url, "model": "m", "prompt": "Translate: {{input}}"},
+            {"id": "out", "type": "output"}
+        ], "edges": [
+            {"from": "in", "to": "summarize"},
+            {"from": "summarize", "to": "translate"},
+            {"from": "translate", "to": "out"}
+        ]});
+        let parsed: Workflow = serde_json::from_value(workflow.clone()).unwrap();
+        assert_eq!(parsed.downstream_of("translate"), HashSet::from(["translate".to_string(), "out".to_string()]));
+
+        let services = TestServices::new();
+        let failed = services.run(workflow).await;
+        assert_eq!(failed.status, RunStatus::Failed);
+        assert_eq!(node(&failed, "out").status, NodeStatus::Skipped);
+        assert_eq!(server.requests().len(), 2);
+
+        let resumed = resume_run(&failed.run_id, "translate", &[], None, services.services()).await.unwrap();
+        assert_eq!(resumed.status, RunStatus::Completed);
+        assert_eq!(resumed.resumed_from.as_deref(), Some(failed.run_id.as_str()));
+        for id in ["in", "summarize"] {
+            assert_eq!(node(&resumed, id).reused_from.as_deref(), Some(failed.run_id.as_str()), "{}", id);
+        }
+        assert_eq!(node(&resumed, "translate").reused_from, None);
+        assert_eq!(node(&resumed, "out").output.as_deref(), Some("résumé"));
+
+        // Only the failed node went back to the model, with the reused summary.
+        let requests = server.requests();
+        assert_eq!(requests.len(), 3);
+        assert_eq!(requests[2]["messages"][0]["content"], "Translate: summary");
+    }
+}
@@ -0,0 +1,140 @@
+// Edge conditions deciding which branches a router node takes.
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+use std::cmp::Ordering;
+
+use super::NodeInput;
+
+/// Condition attached to an edge leaving a router node.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum EdgeCondition {
+    /// Matches when the router input matches the regular expression.
+    Regex { pattern: Pattern },
+    /// Parses the router input as JSON and compares the field at `path`
+    /// (dot separated, array indices allowed) against `value`.
+    JsonField {
+        path: String,
+        op: CompareOp,
+        #[serde(default)]
+        value: Value,
+    },
+    /// Matches when the router's classifier picked this label.
+    Label { label: String },
+    /// Taken when no other edge of the router matched.
+    Default,
+}
+
+/// A regular expression, compiled when the workflow is loaded so that an
+/// invalid pattern rejects the workflow instead of failing a run.
+#[derive(Debug, Clone)]
+pub struct Pattern(Regex);
+
+impl Serialize for Pattern {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.0.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Pattern {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let pattern = String::deserialize(deserializer)?;
+        Regex::new(&pattern)
+            .map(Pattern)
+            .map_err(|e| serde::de::Error::custom(format!("Invalid regex '{}': {}", pattern, e)))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+    Contains,
+    Exists,
+}
+
+impl EdgeCondition {
+    pub fn is_default(&self) -> bool {
+        matches!(self, EdgeCondition::Default)
+    }
+
+    /// Evaluate the condition against a router's input and, for classifier
+    /// routers, the label the model picked. JSON fields are read from the
+    /// typed input when there is one.
+    pub fn matches(&self, input: &NodeInput, label: Option<&str>) -> bool {
+        match self {
+            EdgeCondition::Regex { pattern } => pattern.0.is_match(&input.text),
+            EdgeCondition::JsonField { path, op, value } => {
+                let document = match &input.value {
+                    Some(document) => document.clone(),
+                    None => match serde_json::from_str::<Value>(input.text.trim()) {
+                        Ok(document) => document,
+                        Err(_) => return false,
+                    },
+                };
+                compare(lookup(&document, path), *op, value)
+            }
+            EdgeCondition::Label { label: expected } => label.is_some_and(|l| l.eq_ignore_ascii_case(expected.trim())),
+            EdgeCondition::Default => false,
+        }
+    }
+}
+
+pub(super) fn lookup<'a>(document: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.')
+        .filter(|segment| !segment.is_empty())
+        .try_fold(document, |current, segment| match current {
+            Value::Object(map) => map.get(segment),
+            Value::Array(items) => segment.parse::<usize>().ok().and_then(|i| items.get(i)),
+            _ => None,
+        })
+}
+
+fn compare(actual: Option<&Value>, op: CompareOp, expected: &Value) -> bool {
+    let Some(actual) = actual else {
+        return op == CompareOp::Ne;
+    };
+
+    match op {
+        CompareOp::Exists => !actual.is_null(),
+        CompareOp::Eq => values_equal(actual, expected),
+        CompareOp::Ne => !values_equal(actual, expected),
+        CompareOp::Contains => match (actual, expected) {
+            (Value::String(haystack), Value::String(needle)) => haystack.contains(needle.as_str()),
+            (Value::Array(items), needle) => items.iter().any(|item| values_equal(item, needle)),
+            (Value::Object(map), Value::String(key)) => map.contains_key(key),
+            _ => false,
+        },
+        CompareOp::Gt | CompareOp::Gte | CompareOp::Lt | CompareOp::Lte => {
+            let Some(ordering) = order(actual, expected) else {
+                return false;
+            };
+            match op {
+                CompareOp::Gt => ordering == Ordering::Greater,
+                CompareOp::Gte => ordering != Ordering::Less,
+                CompareOp::Lt => ordering == Ordering::Less,
+                _ => ordering != Ordering::Greater,
+            }
+        }
+    }
+}
+
+fn values_equal(a: &Value, b: &Value) -> bool {
+    match (a.as_f64(), b.as_f64()) {
+        (Some(x), Some(y)) => x == y,
+        _ => a == b,
+    }
+}
+
+fn order(a: &Value, b: &Value) -> Option<Ordering> {
+    match (a, b) {
+        (Value::String(x), Value::String(y)) => Some(x.cmp(y)),
+        _ => a.as_f64()?.partial_cmp(&b.as_f64()?),
+    }
+}
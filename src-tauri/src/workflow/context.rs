@@ -0,0 +1,447 @@
+// Keeps chat requests within the model's context window.
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+use tiktoken_rs::tokenizer::{get_tokenizer, Tokenizer};
+use tiktoken_rs::CoreBPE;
+
+use super::retriever::passages;
+use super::{LlmNodeConfig, RunContext};
+use crate::add_log_entry;
+use crate::provider::{self, ChatMessage, ChatOptions, Usage};
+
+/// Template overhead of each message, as OpenAI documents it for its models.
+const TOKENS_PER_MESSAGE: usize = 4;
+
+/// Rough cost of one image; vision models tile and encode images differently.
+const TOKENS_PER_IMAGE: usize = 768;
+
+/// Tokens that prime the reply.
+const REPLY_PRIMING: usize = 3;
+
+/// Smallest context window worth assuming; requests within it are sent
+/// without looking the model up.
+const MIN_CONTEXT_LENGTH: usize = 2048;
+
+/// Tool results are not shortened below this many tokens.
+const MIN_TOOL_RESULT_TOKENS: usize = 256;
+
+const SUMMARY_PROMPT: &str = "Summarize the conversation below for the assistant that will continue it. \
+Keep facts, decisions, tool results and open questions; drop pleasantries. Reply with the summary only.";
+
+/// What to do when a request would not fit the model's context window.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ContextStrategy {
+    /// Drop whole turns, oldest first. The system prompt and the task stay.
+    #[default]
+    DropOldest,
+    /// Replace the turns between the task and the latest ones with a summary
+    /// written by the same model.
+    SummarizeMiddle,
+    /// Cut the lowest-ranked retrieved passages, then shorten tool results.
+    TruncateChunks,
+}
+
+impl ContextStrategy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ContextStrategy::DropOldest => "drop_oldest",
+            ContextStrategy::SummarizeMiddle => "summarize_middle",
+            ContextStrategy::TruncateChunks => "truncate_chunks",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContextConfig {
+    #[serde(default)]
+    pub strategy: ContextStrategy,
+    /// Tokens kept free for the reply.
+    #[serde(default = "default_reserve_tokens")]
+    pub reserve_tokens: usize,
+    /// Used instead of the context length the server reports.
+    #[serde(default)]
+    pub context_length: Option<usize>,
+}
+
+fn default_reserve_tokens() -> usize {
+    1024
+}
+
+impl Default for ContextConfig {
+    fn default() -> Self {
+        Self {
+            strategy: ContextStrategy::default(),
+            reserve_tokens: default_reserve_tokens(),
+            context_length: None,
+        }
+    }
+}
+
+/// How a node's requests were shrunk to fit the context window. Only present
+/// when at least one request overflowed.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ContextReport {
+    pub strategy: ContextStrategy,
+    pub context_length: usize,
+    /// Requests that had to be shrunk.
+    pub requests: u32,
+    /// Size of the largest overflowing request before and after fitting.
+    pub tokens_before: usize,
+    pub tokens_after: usize,
+    /// Turns dropped or summarized, passages cut and tool results shortened.
+    pub removed: usize,
+    /// Text was cut as a last resort because the strategy could not make room.
+    #[serde(default)]
+    pub truncated: bool,
+    /// Tokens spent writing summaries.
+    #[serde(default)]
+    pub summary_usage: Usage,
+}
+
+impl ContextReport {
+    /// Fold `other` into `report`, e.g. the reports of a map node's items.
+    pub fn merge(report: &mut Option<ContextReport>, other: Option<ContextReport>) {
+        let Some(other) = other else {
+            return;
+        };
+        match report {
+            Some(report) => {
+                report.requests += other.requests;
+                report.tokens_before = report.tokens_before.max(other.tokens_before);
+                report.tokens_after = report.tokens_after.max(other.tokens_after);
+                report.removed += other.removed;
+                report.truncated |= other.truncated;
+                report.summary_usage.add(other.summary_usage);
+            }
+            None => *report = Some(other),
+        }
+    }
+}
+
+/// Local models bring their own tokenizers; OpenAI's `cl100k` is a close
+/// enough estimate for them, and the reply reserve absorbs the difference.
+fn tokenizer(model: &str) -> &'static CoreBPE {
+    match get_tokenizer(model) {
+        Some(Tokenizer::O200kBase) => tiktoken_rs::o200k_base_singleton(),
+        _ => tiktoken_rs::cl100k_base_singleton(),
+    }
+}
+
+pub fn count_tokens(model: &str, text: &str) -> usize {
+    tokenizer(model).encode_ordinary(text).len()
+}
+
+fn message_tokens(model: &str, message: &ChatMessage) -> usize {
+    let calls: usize = message.tool_calls.iter()
+        .map(|call| count_tokens(model, &call.name) + count_tokens(model, &call.arguments.to_string()))
+        .sum();
+    TOKENS_PER_MESSAGE + count_tokens(model, &message.content) + calls + message.images.len() * TOKENS_PER_IMAGE
+}
+
+/// Tokens `messages` take up in a request.
+pub fn count_messages(model: &str, messages: &[ChatMessage]) -> usize {
+    REPLY_PRIMING + messages.iter().map(|message| message_tokens(model, message)).sum::<usize>()
+}
+
+/// Tool definitions and the reply schema take up context too.
+fn options_tokens(model: &str, options: &ChatOptions) -> usize {
+    let tools = if options.tools.is_empty() {
+        0
+    } else {
+        count_tokens(model, &serde_json::to_string(&options.tools).unwrap_or_default())
+    };
+    tools + options.response_schema.as_ref().map_or(0, |schema| count_tokens(model, &schema.to_string()))
+}
+
+/// `text` cut to about `max_tokens`, keeping its start and end.
+fn shorten(model: &str, text: &str, max_tokens: usize) -> String {
+    let bpe = tokenizer(model);
+    let tokens = bpe.encode_ordinary(text);
+    if tokens.len() <= max_tokens {
+        return text.to_string();
+    }
+    let head = max_tokens / 2;
+    let tail = max_tokens - head;
+    // A cut may split a character across tokens; drop the tokens holding
+    // its stray bytes, at the cut end of the slice
+    let decode = |mut range: &[u32], cut_at_start: bool| loop {
+        match bpe.decode(range.to_vec()) {
+            Ok(text) => break text,
+            Err(_) if range.is_empty() => break String::new(),
+            Err(_) if cut_at_start => range = &range[1..],
+            Err(_) => range = &range[..range.len() - 1],
+        }
+    };
+    format!(
+        "{}\n[... {} tokens omitted ...]\n{}",
+        decode(&tokens[..head], false),
+        tokens.len() - max_tokens,
+        decode(&tokens[tokens.len() - tail..], true)
+    )
+}
+
+/// The context window of the node's model: the configured override, what the
+/// server reports, or tiktoken's table (4096 for models it does not know).
+async fn context_length(ctx: &RunContext<'_>, config: &LlmNodeConfig) -> usize {
+    if let Some(length) = config.context.context_length {
+        return length;
+    }
+    let fallback = || tiktoken_rs::model::get_context_size(&config.model);
+    match provider::model_info(&config.server_url, &config.model).await {
+        Ok(info) => info.context_length.unwrap_or_else(fallback),
+        Err(e) => {
+            let _ = add_log_entry(
+                ctx.services.log_state,
+                "debug",
+                &format!("No context length for {}: {}", config.model, e),
+                "workflow",
+            );
+            fallback()
+        }
+    }
+}
+
+/// Leading system messages and the first user message: the instructions and
+/// the task, which every strategy keeps.
+fn pinned(messages: &[ChatMessage]) -> usize {
+    let system = messages.iter().take_while(|message| message.role == "system").count();
+    match messages.get(system) {
+        Some(message) if message.role == "user" => system + 1,
+        _ => system,
+    }
+}
+
+/// Turns after the pinned messages. Tool results stay with the assistant
+/// message that called them, so a turn is never split.
+fn turns(messages: &[ChatMessage], start: usize) -> Vec<Range<usize>> {
+    let mut turns: Vec<Range<usize>> = Vec::new();
+    for (i, message) in messages.iter().enumerate().skip(start) {
+        match turns.last_mut() {
+            Some(turn) if message.role == "tool" => turn.end = i + 1,
+            _ => turns.push(i..i + 1),
+        }
+    }
+    turns
+}
+
+fn drop_oldest(model: &str, messages: &mut Vec<ChatMessage>, budget: usize) -> usize {
+    let start = pinned(messages);
+    let mut total = count_messages(model, messages);
+    let mut dropped = 0;
+    while total > budget {
+        let turns = turns(messages, start);
+        // The latest turn is what the model is answering
+        if turns.len() < 2 {
+            break;
+        }
+        let oldest = turns[0].clone();
+        total -= messages[oldest.clone()].iter().map(|message| message_tokens(model, message)).sum::<usize>();
+        dropped += oldest.len();
+        messages.drain(oldest);
+    }
+    dropped
+}
+
+/// Transcript of the turns to summarize, as plain text.
+fn transcript(messages: &[ChatMessage]) -> String {
+    messages.iter()
+        .map(|message| {
+            let calls = message.tool_calls.iter()
+                .map(|call| format!("\n(called {} with {})", call.name, call.arguments))
+                .collect::<String>();
+            let images = match message.images.len() {
+                0 => String::new(),
+                count => format!("\n({} image(s) attached)", count),
+            };
+            format!("{}: {}{}{}", message.role, message.content, calls, images)
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+async fn summarize_middle(
+    ctx: &RunContext<'_>,
+    config: &LlmNodeConfig,
+    messages: &mut Vec<ChatMessage>,
+    budget: usize,
+    usage: &mut Usage,
+) -> Result<usize, String> {
+    let model = config.model.as_str();
+    let start = pinned(messages);
+    let turns = turns(messages, start);
+    // Keep the latest turns in half the budget, and always the last one
+    let Some(last) = turns.last() else {
+        return Ok(0);
+    };
+    let mut kept = messages[last.clone()].iter().map(|message| message_tokens(model, message)).sum::<usize>();
+    let mut keep_from = turns.len() - 1;
+    while keep_from > 0 {
+        let cost = messages[turns[keep_from - 1].clone()].iter().map(|message| message_tokens(model, message)).sum::<usize>();
+        if kept + cost > budget / 2 {
+            break;
+        }
+        kept += cost;
+        keep_from -= 1;
+    }
+    if keep_from == 0 {
+        return Ok(0);
+    }
+
+    let middle = turns[0].start..turns[keep_from - 1].end;
+    let text = shorten(model, &transcript(&messages[middle.clone()]), budget / 2);
+    let reply = ctx.services.cache.chat(
+        &config.server_url,
+        &config.model,
+        &[ChatMessage::system(SUMMARY_PROMPT), ChatMessage::user(text)],
+        &ChatOptions::with_temperature(0.0),
+    ).await?;
+    usage.add(reply.usage);
+
+    let summarized = middle.len();
+    let summary = ChatMessage::user(format!("Summary of the earlier conversation:\n{}", reply.content.trim()));
+    messages.splice(middle, [summary]);
+    Ok(summarized)
+}
+
+fn truncate_chunks(model: &str, messages: &mut [ChatMessage], budget: usize) -> usize {
+    let mut total = count_messages(model, messages);
+    let mut removed = 0;
+    // Lowest-ranked passage first, wherever it is
+    while total > budget {
+        let worst = messages.iter()
+            .enumerate()
+            .flat_map(|(i, message)| passages(&message.content).into_iter().map(move |(rank, range)| (rank, i, range)))
+            .max_by_key(|(rank, i, _)| (*rank, *i));
+        let Some((_, i, range)) = worst else {
+            break;
+        };
+        let before = message_tokens(model, &messages[i]);
+        messages[i].content.replace_range(range, "");
+        total = total - before + message_tokens(model, &messages[i]);
+        removed += 1;
+    }
+
+    // Then halve the longest tool results
+    while total > budget {
+        let longest = messages.iter()
+            .enumerate()
+            .filter(|(_, message)| message.role == "tool")
+            .map(|(i, message)| (count_tokens(model, &message.content), i))
+            .filter(|(tokens, _)| *tokens > MIN_TOOL_RESULT_TOKENS)
+            .max();
+        let Some((tokens, i)) = longest else {
+            break;
+        };
+        let before = message_tokens(model, &messages[i]);
+        let target = (tokens / 2).max(MIN_TOOL_RESULT_TOKENS).max(tokens.saturating_sub(total - budget));
+        messages[i].content = shorten(model, &messages[i].content, target);
+        total = total - before + message_tokens(model, &messages[i]);
+        removed += 1;
+    }
+    removed
+}
+
+/// Last resort: cut the longest messages, keeping their start and end.
+fn truncate_longest(model: &str, messages: &mut [ChatMessage], budget: usize) -> bool {
+    let mut total = count_messages(model, messages);
+    let mut truncated = false;
+    while total > budget {
+        let Some((tokens, i)) = messages.iter()
+            .enumerate()
+            .map(|(i, message)| (count_tokens(model, &message.content), i))
+            .max()
+            .filter(|(tokens, _)| *tokens > 0)
+        else {
+            break;
+        };
+        let before = message_tokens(model, &messages[i]);
+        messages[i].content = shorten(model, &messages[i].content, tokens.saturating_sub(total - budget));
+        let after = message_tokens(model, &messages[i]);
+        truncated = true;
+        if after >= before {
+            break;
+        }
+        total = total - before + after;
+    }
+    truncated
+}
+
+/// Shrink `messages` with the node's strategy so the request leaves
+/// `reserve_tokens` free, recording what was done in `report`. Requests
+/// that already fit are left alone.
+pub(super) async fn fit(
+    ctx: &RunContext<'_>,
+    node_id: &str,
+    config: &LlmNodeConfig,
+    options: &ChatOptions,
+    messages: &mut Vec<ChatMessage>,
+    report: &mut Option<ContextReport>,
+) -> Result<(), String> {
+    let model = config.model.as_str();
+    let fixed = options_tokens(model, options);
+    let before = count_messages(model, messages);
+    let needed = before + fixed + config.context.reserve_tokens;
+    if config.context.context_length.is_none() && needed <= MIN_CONTEXT_LENGTH {
+        return Ok(());
+    }
+    let context_length = context_length(ctx, config).await;
+    if needed <= context_length {
+        return Ok(());
+    }
+    let budget = context_length.saturating_sub(config.context.reserve_tokens + fixed);
+
+    let strategy = config.context.strategy;
+    let mut summary_usage = Usage::default();
+    let removed = match strategy {
+        ContextStrategy::DropOldest => drop_oldest(model, messages, budget),
+        ContextStrategy::SummarizeMiddle => summarize_middle(ctx, config, messages, budget, &mut summary_usage).await?,
+        ContextStrategy::TruncateChunks => truncate_chunks(model, messages, budget),
+    };
+    let truncated = truncate_longest(model, messages, budget);
+    let after = count_messages(model, messages);
+
+    add_log_entry(
+        ctx.services.log_state,
+        if truncated { "warn" } else { "info" },
+        &format!(
+            "Node {} request of {} tokens exceeded {}'s context of {}; {} brought it to {}{}",
+            node_id,
+            before + fixed,
+            model,
+            context_length,
+            strategy.as_str(),
+            after + fixed,
+            if truncated { " after cutting text" } else { "" }
+        ),
+        "workflow",
+    )?;
+    ContextReport::merge(report, Some(ContextReport {
+        strategy,
+        context_length,
+        requests: 1,
+        tokens_before: before + fixed,
+        tokens_after: after + fixed,
+        removed,
+        truncated,
+        summary_usage,
+    }));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shorten_keeps_both_ends_of_multibyte_text() {
+        let text = format!("start {} end", "日本語のテキスト😀".repeat(200));
+        let short = shorten("gpt-4o", &text, 40);
+        assert!(short.starts_with("start "), "{}", short);
+        assert!(short.ends_with(" end"), "{}", short);
+        assert!(short.contains("tokens omitted"));
+        assert!(!short.contains('\u{fffd}'));
+        assert!(count_tokens("gpt-4o", &short) < 60);
+        assert_eq!(shorten("gpt-4o", "short text", 40), "short text");
+    }
+}
@@ -0,0 +1,212 @@
+// Map and While nodes: running an LLM over list items or until a condition holds.
+use futures_util::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use tauri::State;
+
+use super::condition::EdgeCondition;
+use super::context::ContextReport;
+use super::{call_llm, LlmNodeConfig, NodeInput, NodeOutcome, RunContext};
+use crate::broadcast_update;
+
+const MAX_RECORDS_PER_NODE: usize = 1000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MapConfig {
+    #[serde(flatten)]
+    pub llm: LlmNodeConfig,
+    /// How many items may be in flight at once.
+    #[serde(default = "default_concurrency")]
+    pub concurrency: usize,
+    /// Lists longer than this fail the node instead of running.
+    #[serde(default = "default_max_iterations")]
+    pub max_items: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WhileConfig {
+    #[serde(flatten)]
+    pub llm: LlmNodeConfig,
+    /// The loop stops as soon as an output satisfies this condition.
+    pub until: EdgeCondition,
+    #[serde(default = "default_max_iterations")]
+    pub max_iterations: usize,
+}
+
+fn default_concurrency() -> usize {
+    4
+}
+
+fn default_max_iterations() -> usize {
+    10
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IterationRecord {
+    pub run_id: String,
+    pub index: usize,
+    pub input: String,
+    pub output: Option<String>,
+    /// The output as typed JSON, when the task has an output schema.
+    #[serde(default)]
+    pub value: Option<Value>,
+    pub error: Option<String>,
+    #[serde(default)]
+    pub cached: bool,
+    pub timestamp: String,
+}
+
+/// Per-node history of Map and While iterations.
+#[derive(Debug, Default)]
+pub struct OutputBuffers {
+    buffers: Mutex<HashMap<String, VecDeque<IterationRecord>>>,
+}
+
+impl OutputBuffers {
+    fn push(&self, node_id: &str, record: IterationRecord) {
+        if let Ok(mut buffers) = self.buffers.lock() {
+            let buffer = buffers.entry(node_id.to_string()).or_default();
+            if buffer.len() >= MAX_RECORDS_PER_NODE {
+                buffer.pop_front();
+            }
+            buffer.push_back(record);
+        }
+    }
+
+    pub fn get(&self, node_id: &str) -> Vec<IterationRecord> {
+        self.buffers.lock()
+            .map(|buffers| buffers.get(node_id).map(|b| b.iter().cloned().collect()).unwrap_or_default())
+            .unwrap_or_default()
+    }
+
+    pub fn clear(&self, node_id: &str) {
+        if let Ok(mut buffers) = self.buffers.lock() {
+            buffers.remove(node_id);
+        }
+    }
+}
+
+/// Split upstream output into list items: the typed array or a JSON array if
+/// it parses as one, otherwise one item per non-empty line. Object items stay
+/// typed so tasks can pick fields out of them.
+fn split_items(input: &NodeInput) -> Vec<NodeInput> {
+    let array = match &input.value {
+        Some(Value::Array(items)) => Some(items.clone()),
+        _ => match serde_json::from_str::<Value>(input.text.trim()) {
+            Ok(Value::Array(items)) => Some(items),
+            _ => None,
+        },
+    };
+    if let Some(items) = array {
+        return items.into_iter()
+            .map(|item| match item {
+                Value::String(s) => NodeInput::text(s),
+                other => NodeInput {
+                    text: other.to_string(),
+                    value: Some(other),
+                },
+            })
+            .collect();
+    }
+    input.text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(NodeInput::text)
+        .collect()
+}
+
+async fn record(ctx: &RunContext<'_>, node_id: &str, index: usize, input: &NodeInput, result: &Result<NodeOutcome, String>) {
+    let record = IterationRecord {
+        run_id: ctx.run_id.to_string(),
+        index,
+        input: input.text.clone(),
+        output: result.as_ref().ok().map(|outcome| outcome.output.clone()),
+        value: result.as_ref().ok().and_then(|outcome| outcome.value.clone()),
+        error: result.as_ref().err().cloned(),
+        cached: result.as_ref().is_ok_and(|outcome| outcome.cache_hits > 0),
+        timestamp: chrono::Local::now().to_rfc3339(),
+    };
+    broadcast_update(json!({
+        "type": "node_iteration",
+        "data": {
+            "node_id": node_id,
+            "record": record,
+        }
+    })).await;
+    ctx.services.buffers.push(node_id, record);
+}
+
+/// Run the node's task over every item, returning the outputs as a JSON array
+/// in item order. With an output schema the array holds the typed values.
+pub(super) async fn run_map(ctx: &RunContext<'_>, node_id: &str, config: &MapConfig, input: &NodeInput) -> Result<NodeOutcome, String> {
+    let items = split_items(input);
+    if items.len() > config.max_items {
+        return Err(format!("List has {} items, more than the limit of {}", items.len(), config.max_items));
+    }
+
+    let results: Vec<Result<NodeOutcome, String>> = stream::iter(items.into_iter().enumerate())
+        .map(|(index, item)| async move {
+            let result = call_llm(ctx, node_id, &config.llm, &item).await;
+            record(ctx, node_id, index, &item, &result).await;
+            result
+        })
+        .buffered(config.concurrency.max(1))
+        .collect()
+        .await;
+
+    let mut outputs = Vec::with_capacity(results.len());
+    let mut outcome = NodeOutcome::default();
+    for (index, result) in results.into_iter().enumerate() {
+        let item = result.map_err(|e| format!("Item {} failed: {}", index, e))?;
+        outcome.usage.add(item.usage);
+        outcome.cache_hits += item.cache_hits;
+        ContextReport::merge(&mut outcome.context, item.context);
+        outputs.push(item.value.unwrap_or(Value::String(item.output)));
+    }
+    outcome.output = serde_json::to_string(&outputs).map_err(|e| e.to_string())?;
+    if config.llm.output_schema.is_some() {
+        outcome.value = Some(Value::Array(outputs));
+    }
+    Ok(outcome)
+}
+
+/// Feed the task its own output until `until` matches or the iteration limit
+/// is reached. Returns the last output and whether the condition was met.
+pub(super) async fn run_while(ctx: &RunContext<'_>, node_id: &str, config: &WhileConfig, input: &NodeInput) -> Result<(NodeOutcome, bool), String> {
+    let mut outcome = NodeOutcome::passthrough(input);
+    for index in 0..config.max_iterations.max(1) {
+        let current = NodeInput {
+            text: std::mem::take(&mut outcome.output),
+            value: outcome.value.take(),
+        };
+        let result = call_llm(ctx, node_id, &config.llm, &current).await;
+        record(ctx, node_id, index, &current, &result).await;
+        let step = result.map_err(|e| format!("Iteration {} failed: {}", index, e))?;
+        outcome.usage.add(step.usage);
+        outcome.cache_hits += step.cache_hits;
+        ContextReport::merge(&mut outcome.context, step.context);
+        outcome.output = step.output;
+        outcome.value = step.value;
+        let converged = config.until.matches(&NodeInput {
+            text: outcome.output.clone(),
+            value: outcome.value.clone(),
+        }, None);
+        if converged {
+            return Ok((outcome, true));
+        }
+    }
+    Ok((outcome, false))
+}
+
+#[tauri::command]
+pub async fn get_iteration_buffer(node_id: String, buffers: State<'_, OutputBuffers>) -> Result<Vec<IterationRecord>, String> {
+    Ok(buffers.get(&node_id))
+}
+
+#[tauri::command]
+pub async fn clear_iteration_buffer(node_id: String, buffers: State<'_, OutputBuffers>) -> Result<(), String> {
+    buffers.clear(&node_id);
+    Ok(())
+}
@@ -0,0 +1,106 @@
+// Memory nodes: storing and recalling facts, notes and episodes across runs.
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use super::{render_prompt, NodeInput, NodeOutcome, RunContext};
+use crate::add_log_entry;
+use crate::memory::{workflow_namespace, MemoryEmbedding, MemoryKind, RecalledMemory, GLOBAL_NAMESPACE};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MemoryOperation {
+    /// Store the input as a memory and pass it on.
+    Write,
+    /// Look up memories matching the query.
+    Recall,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MemoryScope {
+    /// This workflow's own namespace. Recall also searches the global one.
+    #[default]
+    Workflow,
+    Global,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryNodeConfig {
+    pub operation: MemoryOperation,
+    #[serde(default)]
+    pub scope: MemoryScope,
+    /// Kind of memory written.
+    #[serde(default)]
+    pub kind: MemoryKind,
+    /// Key of a written note; `{{input}}` placeholders are filled in.
+    #[serde(default)]
+    pub key: String,
+    /// Recall query. Upstream output fills `{{input}}`, and is the whole
+    /// query when this is empty.
+    #[serde(default)]
+    pub query: String,
+    /// Kinds recalled; all when empty.
+    #[serde(default)]
+    pub kinds: Vec<MemoryKind>,
+    #[serde(default = "default_top_k")]
+    pub top_k: usize,
+    /// Embedding model for similarity; keyword matching is used without one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub embedding: Option<MemoryEmbedding>,
+}
+
+fn default_top_k() -> usize {
+    5
+}
+
+fn format_memories(memories: &[RecalledMemory]) -> String {
+    memories.iter()
+        .map(|recalled| {
+            let memory = &recalled.memory;
+            match &memory.key {
+                Some(key) => format!("- [{}] {}: {}", memory.kind.as_str(), key, memory.content),
+                None => format!("- [{}] {}", memory.kind.as_str(), memory.content),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+pub(super) async fn run_memory(ctx: &RunContext<'_>, node_id: &str, config: &MemoryNodeConfig, input: &NodeInput) -> Result<NodeOutcome, String> {
+    let namespace = match config.scope {
+        MemoryScope::Workflow => workflow_namespace(ctx.workflow_id),
+        MemoryScope::Global => GLOBAL_NAMESPACE.to_string(),
+    };
+    match config.operation {
+        MemoryOperation::Write => {
+            let key = if config.key.contains("{{input") {
+                render_prompt(&config.key, input)
+            } else {
+                config.key.clone()
+            };
+            let memory = ctx.services.memory.remember(&namespace, config.kind, Some(&key), &input.text, Some(ctx.run_id))?;
+            add_log_entry(
+                ctx.services.log_state,
+                "info",
+                &format!("Node {} saved a {} to {}", node_id, memory.kind.as_str(), namespace),
+                "memory",
+            )?;
+            Ok(NodeOutcome::passthrough(input))
+        }
+        MemoryOperation::Recall => {
+            let query = render_prompt(&config.query, input);
+            let mut namespaces = vec![namespace];
+            if config.scope == MemoryScope::Workflow {
+                namespaces.push(GLOBAL_NAMESPACE.to_string());
+            }
+            let memories = ctx.services.memory
+                .recall(&namespaces, &config.kinds, &query, config.top_k.max(1), config.embedding.as_ref())
+                .await?;
+            Ok(NodeOutcome {
+                output: format_memories(&memories),
+                value: Some(json!(memories)),
+                ..Default::default()
+            })
+        }
+    }
+}
@@ -0,0 +1,237 @@
+// Retriever nodes: search a knowledge base and pass on the best passages.
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::ops::Range;
+use std::sync::LazyLock;
+
+use super::{render_prompt, schema, NodeInput, NodeOutcome, RunContext};
+use crate::add_log_entry;
+use crate::knowledge::{SearchHit, SearchMode};
+use crate::provider::{self, ChatMessage, ChatOptions};
+
+/// Characters of each passage shown to an LLM reranker.
+const RERANK_PASSAGE_CHARS: usize = 1000;
+
+/// The header line `format_hits` writes above each passage.
+static PASSAGE_HEADER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?m)^\[(\d+)\] .+ \(lines (\d+)-(\d+), score -?[\d.]+\)$").unwrap());
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetrieverConfig {
+    /// Id of the knowledge base to search.
+    pub knowledge_base: String,
+    #[serde(default = "default_top_k")]
+    pub top_k: usize,
+    /// Query text. Upstream output fills `{{input}}`, and is the whole query
+    /// when this is empty.
+    #[serde(default)]
+    pub query: String,
+    #[serde(default)]
+    pub mode: SearchMode,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rerank: Option<RerankConfig>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RerankMethod {
+    /// A chat model scores every candidate in one request.
+    Llm,
+    /// A cross-encoder served behind a `/rerank` endpoint.
+    CrossEncoder,
+}
+
+/// Re-score the best candidates of the search with a stronger model.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RerankConfig {
+    pub method: RerankMethod,
+    pub server_url: String,
+    pub model: String,
+    /// Candidates fetched from the search for reranking.
+    #[serde(default = "default_candidates")]
+    pub candidates: usize,
+}
+
+fn default_top_k() -> usize {
+    5
+}
+
+fn default_candidates() -> usize {
+    20
+}
+
+/// Numbered passages with their sources, ready to paste into a prompt. Each
+/// starts with a header that `PASSAGE_HEADER` matches.
+fn format_hits(hits: &[SearchHit]) -> String {
+    hits.iter()
+        .enumerate()
+        .map(|(i, hit)| format!(
+            "[{}] {} (lines {}-{}, score {:.3})\n{}",
+            i + 1,
+            hit.source,
+            hit.start_line,
+            hit.end_line,
+            hit.score,
+            hit.text.trim()
+        ))
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// Byte ranges of the passages `format_hits` wrote into `text`, which may
+/// have been pasted into a longer prompt, with their rank.
+pub(super) fn passages(text: &str) -> Vec<(usize, Range<usize>)> {
+    let headers: Vec<_> = PASSAGE_HEADER.captures_iter(text).collect();
+    headers.iter()
+        .enumerate()
+        .map(|(i, caps)| {
+            let header = caps.get(0).unwrap();
+            let rank = caps[1].parse().unwrap_or(0);
+            // A passage runs to the next header, and is never longer than its
+            // line range, so text after the last passage survives
+            let lines = caps[3].parse::<usize>().unwrap_or(0).saturating_sub(caps[2].parse().unwrap_or(0)) + 1;
+            let limit = text[header.end()..]
+                .match_indices('\n')
+                .nth(lines)
+                .map_or(text.len(), |(offset, _)| header.end() + offset);
+            let next = headers.get(i + 1).map_or(text.len(), |next| next.get(0).unwrap().start());
+            (rank, header.start()..limit.min(next))
+        })
+        .collect()
+}
+
+/// Ask a chat model for a 0-10 relevance score per passage.
+async fn llm_scores(ctx: &RunContext<'_>, config: &RerankConfig, query: &str, hits: &[SearchHit], outcome: &mut NodeOutcome) -> Result<Vec<f32>, String> {
+    let passages = hits.iter()
+        .enumerate()
+        .map(|(i, hit)| format!("[{}]\n{}", i + 1, hit.text.trim().chars().take(RERANK_PASSAGE_CHARS).collect::<String>()))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    let scores_schema = json!({
+        "type": "object",
+        "properties": {
+            "scores": {
+                "type": "array",
+                "items": { "type": "number", "minimum": 0, "maximum": 10 },
+                "minItems": hits.len(),
+                "maxItems": hits.len()
+            }
+        },
+        "required": ["scores"]
+    });
+    let messages = vec![
+        ChatMessage::system(
+            "You rate how well passages answer a search query. Reply with JSON of the form \
+             {\"scores\": [...]}, one score from 0 (irrelevant) to 10 (answers it exactly) per passage, in order.",
+        ),
+        ChatMessage::user(format!("Query: {}\n\nPassages:\n\n{}", query, passages)),
+    ];
+    let options = ChatOptions {
+        temperature: 0.0,
+        response_schema: Some(scores_schema.clone()),
+        ..Default::default()
+    };
+    let reply = ctx.services.cache.chat(&config.server_url, &config.model, &messages, &options).await?;
+    outcome.usage.add(reply.usage);
+    if reply.cached {
+        outcome.cache_hits += 1;
+    }
+    let value = schema::check_reply(&scores_schema, &reply.content)
+        .map_err(|e| format!("Reranker reply is invalid: {}", e))?;
+    Ok(value["scores"].as_array()
+        .map(|scores| scores.iter().filter_map(|score| score.as_f64()).map(|score| score as f32).collect())
+        .unwrap_or_default())
+}
+
+/// Re-order hits by the reranker's scores.
+async fn rerank(ctx: &RunContext<'_>, config: &RerankConfig, query: &str, hits: &mut [SearchHit], outcome: &mut NodeOutcome) -> Result<(), String> {
+    if hits.is_empty() {
+        return Ok(());
+    }
+    let scores = match config.method {
+        RerankMethod::Llm => llm_scores(ctx, config, query, hits, outcome).await?,
+        RerankMethod::CrossEncoder => {
+            let documents: Vec<String> = hits.iter().map(|hit| hit.text.clone()).collect();
+            provider::rerank(&config.server_url, &config.model, query, &documents).await?
+        }
+    };
+    if scores.len() != hits.len() {
+        return Err(format!("Reranker returned {} scores for {} passages", scores.len(), hits.len()));
+    }
+    for (hit, score) in hits.iter_mut().zip(scores) {
+        hit.rerank_score = Some(score);
+        hit.score = score;
+    }
+    hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+    Ok(())
+}
+
+/// Look up the chunks best matching the query, optionally reranked. The
+/// output is the passages as text and, as typed JSON, the hits with their
+/// source metadata and scores.
+pub(super) async fn run_retriever(ctx: &RunContext<'_>, node_id: &str, config: &RetrieverConfig, input: &NodeInput) -> Result<NodeOutcome, String> {
+    let query = render_prompt(&config.query, input);
+    let query = query.trim();
+    if query.is_empty() {
+        return Err("Retriever has no query".to_string());
+    }
+    let top_k = config.top_k.max(1);
+    let candidates = config.rerank.as_ref().map_or(top_k, |rerank| rerank.candidates.max(top_k));
+    let mut hits = ctx.services.knowledge.search(&config.knowledge_base, query, candidates, config.mode).await?;
+
+    let mut outcome = NodeOutcome::default();
+    if let Some(rerank_config) = &config.rerank {
+        rerank(ctx, rerank_config, query, &mut hits, &mut outcome).await?;
+    }
+    hits.truncate(top_k);
+    add_log_entry(
+        ctx.services.log_state,
+        "debug",
+        &format!(
+            "Node {} retrieved {} passage(s): {}",
+            node_id,
+            hits.len(),
+            hits.iter().map(|hit| format!("{}#{} {:.3}", hit.source, hit.chunk, hit.score)).collect::<Vec<_>>().join(", ")
+        ),
+        "workflow",
+    )?;
+
+    outcome.output = format_hits(&hits);
+    outcome.value = Some(json!(hits));
+    Ok(outcome)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hit(source: &str, lines: (usize, usize), text: &str) -> SearchHit {
+        SearchHit {
+            source: source.to_string(),
+            chunk: 0,
+            start_line: lines.0,
+            end_line: lines.1,
+            text: text.to_string(),
+            score: -0.25,
+            vector_score: None,
+            keyword_score: None,
+            rerank_score: None,
+        }
+    }
+
+    #[test]
+    fn passages_are_found_in_formatted_hits() {
+        let formatted = format_hits(&[
+            hit("notes/a (draft).md", (1, 2), "first line\nsecond line"),
+            hit("b.txt", (7, 7), "only line"),
+        ]);
+        let prompt = format!("Answer from these:\n{}\nQuestion follows", formatted);
+        let found = passages(&prompt);
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].0, 1);
+        assert_eq!(&prompt[found[0].1.clone()], "[1] notes/a (draft).md (lines 1-2, score -0.250)\nfirst line\nsecond line");
+        assert_eq!(found[1].0, 2);
+        assert_eq!(&prompt[found[1].1.clone()], "[2] b.txt (lines 7-7, score -0.250)\nonly line");
+    }
+}
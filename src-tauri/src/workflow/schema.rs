@@ -0,0 +1,178 @@
+// JSON output schemas for LLM nodes: validating replies and asking for repairs.
+use serde_json::Value;
+
+use super::context::ContextReport;
+use super::{LlmNodeConfig, RunContext};
+use crate::add_log_entry;
+use crate::provider::{ChatMessage, ChatOptions, ChatReply};
+
+const MAX_REPORTED_ERRORS: usize = 5;
+
+/// Read the JSON document out of a reply, tolerating a Markdown code fence or
+/// prose around it.
+pub fn extract_json(reply: &str) -> Result<Value, String> {
+    let trimmed = reply.trim();
+    if let Ok(value) = serde_json::from_str(trimmed) {
+        return Ok(value);
+    }
+    if let Some(value) = fenced_blocks(trimmed).into_iter().find_map(|block| serde_json::from_str(block).ok()) {
+        return Ok(value);
+    }
+    let start = trimmed.find(['{', '[']);
+    let end = trimmed.rfind(['}', ']']);
+    match (start, end) {
+        (Some(start), Some(end)) if start < end => serde_json::from_str(&trimmed[start..=end])
+            .map_err(|e| format!("Reply is not valid JSON: {}", e)),
+        _ => Err("Reply contains no JSON".to_string()),
+    }
+}
+
+/// What lies between each Markdown fence and the next, with the language tag
+/// (`json`, `JSON`, ...) of the opening line dropped. Every fence is tried as
+/// an opening one, so a stray fence in the prose does not hide the block.
+fn fenced_blocks(text: &str) -> Vec<&str> {
+    let fences: Vec<usize> = text.match_indices("```").map(|(i, _)| i).collect();
+    fences.windows(2)
+        .map(|pair| {
+            let block = &text[pair[0] + 3..pair[1]];
+            match block.split_once('\n') {
+                Some((tag, body)) if tag.trim().chars().all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '+')) => body.trim(),
+                _ => block.trim(),
+            }
+        })
+        .collect()
+}
+
+/// Check `value` against `schema`, returning the first few violations.
+pub fn validate(schema: &Value, value: &Value) -> Result<(), Vec<String>> {
+    let validator = jsonschema::validator_for(schema).map_err(|e| vec![format!("Invalid schema: {}", e)])?;
+    let errors: Vec<String> = validator
+        .iter_errors(value)
+        .take(MAX_REPORTED_ERRORS)
+        .map(|error| {
+            let path = error.instance_path.to_string();
+            if path.is_empty() {
+                error.to_string()
+            } else {
+                format!("{}: {}", path, error)
+            }
+        })
+        .collect();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Parse and validate a reply, as a single error message on failure.
+pub fn check_reply(schema: &Value, reply: &str) -> Result<Value, String> {
+    let value = extract_json(reply)?;
+    validate(schema, &value).map_err(|errors| errors.join("\n"))?;
+    Ok(value)
+}
+
+fn repair_prompt(schema: &Value, error: &str) -> String {
+    format!(
+        "Your reply does not match the required JSON schema:\n{}\n\nSchema:\n{}\n\nReply again with only the corrected JSON.",
+        error,
+        serde_json::to_string_pretty(schema).unwrap_or_default()
+    )
+}
+
+/// Validate `reply` against the node's schema, asking the model to repair it
+/// up to `max_repairs` times. Returns the final reply, with usage summed over
+/// every attempt, and the validated value.
+pub(super) async fn conform(
+    ctx: &RunContext<'_>,
+    node_id: &str,
+    config: &LlmNodeConfig,
+    schema: &Value,
+    mut messages: Vec<ChatMessage>,
+    mut reply: ChatReply,
+    context: &mut Option<ContextReport>,
+) -> Result<(ChatReply, Value), String> {
+    let options = ChatOptions {
+        temperature: config.temperature,
+        response_schema: Some(schema.clone()),
+        ..Default::default()
+    };
+    let mut attempt = 0;
+    loop {
+        let error = match check_reply(schema, &reply.content) {
+            Ok(value) => return Ok((reply, value)),
+            Err(error) => error,
+        };
+        if attempt == config.max_repairs {
+            return Err(format!("Reply does not match the output schema after {} repair(s): {}", attempt, error));
+        }
+        attempt += 1;
+        add_log_entry(
+            ctx.services.log_state,
+            "warn",
+            &format!("Node {} reply failed schema validation, repair {} of {}: {}", node_id, attempt, config.max_repairs, error),
+            "workflow",
+        )?;
+
+        messages.push(ChatMessage::assistant(reply.content.clone(), Vec::new()));
+        messages.push(ChatMessage::user(repair_prompt(schema, &error)));
+        super::context::fit(ctx, node_id, config, &options, &mut messages, context).await?;
+        let repaired = ctx.services.cache.chat(&config.server_url, &config.model, &messages, &options).await?;
+        let mut usage = reply.usage;
+        usage.add(repaired.usage);
+        reply = ChatReply {
+            usage,
+            cached: reply.cached && repaired.cached,
+            ..repaired
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn json_is_found_in_fences_and_prose() {
+        let expected = json!({"city": "Paris"});
+        for reply in [
+            "{\"city\": \"Paris\"}",
+            "```json\n{\"city\": \"Paris\"}\n```",
+            "```JSON\n{\"city\": \"Paris\"}\n```",
+            "```\n{\"city\": \"Paris\"}\n```",
+            "Here it is:\n```Json\n{\"city\": \"Paris\"}\n```\nAnything else?",
+            "Wrap code in ``` fences. The answer:\n```json\n{\"city\": \"Paris\"}\n```",
+            "The answer is {\"city\": \"Paris\"}, as requested.",
+        ] {
+            assert_eq!(extract_json(reply), Ok(expected.clone()), "{}", reply);
+        }
+        assert_eq!(extract_json("```json\n[1, 2]\n```"), Ok(json!([1, 2])));
+    }
+
+    #[test]
+    fn replies_without_json_are_errors() {
+        assert_eq!(extract_json("Paris"), Err("Reply contains no JSON".to_string()));
+        assert!(extract_json("{city: Paris}").unwrap_err().starts_with("Reply is not valid JSON: "));
+    }
+
+    #[test]
+    fn violations_are_reported_with_their_path() {
+        let schema = json!({
+            "type": "object",
+            "properties": {"city": {"type": "string"}, "population": {"type": "integer"}},
+            "required": ["city"]
+        });
+        assert_eq!(validate(&schema, &json!({"city": "Paris", "population": 2102650})), Ok(()));
+
+        let errors = validate(&schema, &json!({"population": "many"})).unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert!(errors.iter().any(|error| error.starts_with("/population: ")), "{:?}", errors);
+        assert!(errors.iter().any(|error| error.contains("\"city\" is a required property")), "{:?}", errors);
+
+        let many = json!({"type": "array", "items": {"type": "integer"}});
+        assert_eq!(validate(&many, &json!(["a", "b", "c", "d", "e", "f", "g"])).unwrap_err().len(), MAX_REPORTED_ERRORS);
+        assert!(validate(&json!({"type": 5}), &json!(1)).unwrap_err()[0].starts_with("Invalid schema: "));
+        assert_eq!(check_reply(&schema, "```json\n{\"city\": 1}\n```").unwrap_err(), "/city: 1 is not of type \"string\"");
+    }
+}
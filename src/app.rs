@@ -69,6 +69,19 @@ struct SystemStatus {
     memory_usage: f64,
     cpu_usage: f32,
     processes: Vec<ProcessInfo>,
+    #[serde(default)]
+    index: IndexStatus,
+}
+
+/// Document index totals over every knowledge base.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[allow(dead_code)]
+struct IndexStatus {
+    knowledge_bases: u64,
+    documents: u64,
+    chunks: u64,
+    pending: usize,
+    last_error: Option<String>,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -87,6 +100,39 @@ struct ServerConfig {
     selected_model: String,
 }
 
+/// An MCP server whose tools nodes can use. `command` and `args` apply to
+/// stdio servers, `url` to HTTP ones.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct McpServerConfig {
+    id: String,
+    name: String,
+    transport: String,
+    #[serde(default)]
+    command: String,
+    #[serde(default)]
+    args: Vec<String>,
+    #[serde(default)]
+    env: std::collections::HashMap<String, String>,
+    #[serde(default)]
+    cwd: Option<String>,
+    #[serde(default)]
+    url: String,
+    #[serde(default)]
+    headers: std::collections::HashMap<String, String>,
+    enabled: bool,
+}
+
+/// Connection state of an MCP server, as reported by the backend.
+#[derive(Clone, Debug, Deserialize)]
+struct McpServerStatus {
+    id: String,
+    connected: bool,
+    error: Option<String>,
+    tools: Vec<serde_json::Value>,
+    resources: Vec<serde_json::Value>,
+    prompts: Vec<serde_json::Value>,
+}
+
 /// General configuration for the LLM application including the WebSocket URL.
 #[derive(Clone, Debug, Deserialize, Serialize)]
 struct LLMConfig {
@@ -94,6 +140,38 @@ struct LLMConfig {
     servers: Vec<ServerConfig>,
     available_models: Vec<ModelConfig>,
     selected_model: String,
+    #[serde(default)]
+    mcp_servers: Vec<McpServerConfig>,
+}
+
+/// Settings of the backend response cache.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct CacheSettings {
+    enabled: bool,
+    ttl_secs: u64,
+    max_bytes: u64,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct CacheStats {
+    entries: u64,
+    bytes: u64,
+    settings: CacheSettings,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct ShellSettings {
+    allowlist: Vec<String>,
+    timeout_secs: u64,
+    max_output_bytes: usize,
+    inherit_env: bool,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct WebSearchSettings {
+    searxng_url: String,
+    max_results: usize,
+    max_page_chars: usize,
 }
 
 /// Server connection status
@@ -436,6 +514,7 @@ fn StatusBar() -> impl IntoView {
         memory_usage: 0.0,
         cpu_usage: 0.0,
         processes: Vec::new(),
+        index: IndexStatus::default(),
     });
     let (show_settings, set_show_settings) = signal(false);
     let (config, set_config) = signal(LLMConfig {
@@ -458,7 +537,24 @@ fn StatusBar() -> impl IntoView {
         ],
         available_models: vec![],
         selected_model: String::new(),
+        mcp_servers: vec![],
+    });
+    let (cache_settings, set_cache_settings) = signal(CacheSettings {
+        enabled: false,
+        ttl_secs: 7 * 24 * 3600,
+        max_bytes: 50 * 1024 * 1024,
     });
+    let (cache_stats, set_cache_stats) = signal(None::<CacheStats>);
+    let (workspace_roots, set_workspace_roots) = signal(Vec::<String>::new());
+    let (new_root, set_new_root) = signal(String::new());
+    let (root_error, set_root_error) = signal(None::<String>);
+    let (shell_settings, set_shell_settings) = signal(None::<ShellSettings>);
+    let (web_settings, set_web_settings) = signal(None::<WebSearchSettings>);
+    let (mcp_status, set_mcp_status) = signal(Vec::<McpServerStatus>::new());
+    let (new_mcp_name, set_new_mcp_name) = signal(String::new());
+    let (new_mcp_transport, set_new_mcp_transport) = signal(String::from("stdio"));
+    let (new_mcp_target, set_new_mcp_target) = signal(String::new());
+    let (mcp_error, set_mcp_error) = signal(None::<String>);
     let (loading_models, _set_loading_models) = signal(false);
     let (server_statuses, set_server_statuses) = signal(std::collections::HashMap::new());
 
@@ -505,7 +601,164 @@ fn StatusBar() -> impl IntoView {
         set_is_resizing.set(false);
     };
 
-    let toggle_settings = move |_| set_show_settings.update(|s| *s = !*s);
+    let refresh_cache = move || {
+        spawn_local(async move {
+            let args = serde_wasm_bindgen::to_value(&()).unwrap_or(JsValue::NULL);
+            match invoke_with_timeout::<CacheStats>("get_cache_stats", args, 2000).await {
+                Ok(stats) => {
+                    set_cache_settings.set(stats.settings.clone());
+                    set_cache_stats.set(Some(stats));
+                }
+                Err(e) => log!("Failed to load cache stats: {}", e),
+            }
+        });
+    };
+
+    let clear_cache = move |_| {
+        spawn_local(async move {
+            let args = serde_wasm_bindgen::to_value(&()).unwrap_or(JsValue::NULL);
+            if let Err(e) = invoke_with_timeout::<()>("clear_response_cache", args, 2000).await {
+                log!("Failed to clear cache: {}", e);
+            }
+            refresh_cache();
+        });
+    };
+
+    let refresh_roots = move || {
+        spawn_local(async move {
+            let args = serde_wasm_bindgen::to_value(&()).unwrap_or(JsValue::NULL);
+            match invoke_with_timeout::<Vec<String>>("list_workspace_roots", args, 2000).await {
+                Ok(roots) => set_workspace_roots.set(roots),
+                Err(e) => log!("Failed to load workspace roots: {}", e),
+            }
+        });
+    };
+
+    let add_root = move |_| {
+        let path = new_root.get_untracked().trim().to_string();
+        if path.is_empty() {
+            return;
+        }
+        spawn_local(async move {
+            let args = serde_wasm_bindgen::to_value(&json!({ "path": path })).unwrap_or(JsValue::NULL);
+            match invoke_with_timeout::<String>("add_workspace_root", args, 2000).await {
+                Ok(_) => {
+                    set_new_root.set(String::new());
+                    set_root_error.set(None);
+                }
+                Err(e) => set_root_error.set(Some(e)),
+            }
+            refresh_roots();
+        });
+    };
+
+    let remove_root = move |path: String| {
+        spawn_local(async move {
+            let args = serde_wasm_bindgen::to_value(&json!({ "path": path })).unwrap_or(JsValue::NULL);
+            if let Err(e) = invoke_with_timeout::<()>("remove_workspace_root", args, 2000).await {
+                log!("Failed to remove workspace root: {}", e);
+            }
+            refresh_roots();
+        });
+    };
+
+    let refresh_shell = move || {
+        spawn_local(async move {
+            let args = serde_wasm_bindgen::to_value(&()).unwrap_or(JsValue::NULL);
+            match invoke_with_timeout::<ShellSettings>("get_shell_settings", args, 2000).await {
+                Ok(settings) => set_shell_settings.set(Some(settings)),
+                Err(e) => log!("Failed to load shell settings: {}", e),
+            }
+        });
+    };
+
+    let refresh_web = move || {
+        spawn_local(async move {
+            let args = serde_wasm_bindgen::to_value(&()).unwrap_or(JsValue::NULL);
+            match invoke_with_timeout::<WebSearchSettings>("get_web_search_settings", args, 2000).await {
+                Ok(settings) => set_web_settings.set(Some(settings)),
+                Err(e) => log!("Failed to load web search settings: {}", e),
+            }
+        });
+    };
+
+    let refresh_mcp = move || {
+        spawn_local(async move {
+            let args = serde_wasm_bindgen::to_value(&()).unwrap_or(JsValue::NULL);
+            match invoke_with_timeout::<Vec<McpServerStatus>>("list_mcp_servers", args, 2000).await {
+                Ok(status) => set_mcp_status.set(status),
+                Err(e) => log!("Failed to load MCP server status: {}", e),
+            }
+        });
+    };
+
+    let add_mcp_server = move |_| {
+        let name = new_mcp_name.get_untracked().trim().to_string();
+        let target = new_mcp_target.get_untracked().trim().to_string();
+        let transport = new_mcp_transport.get_untracked();
+        if name.is_empty() || target.is_empty() {
+            set_mcp_error.set(Some("Give the server a name and a command or URL".to_string()));
+            return;
+        }
+        if transport == "http" && !(target.starts_with("http://") || target.starts_with("https://")) {
+            set_mcp_error.set(Some("HTTP servers need an http:// or https:// URL".to_string()));
+            return;
+        }
+        set_config.update(|c| {
+            let slug: String = name.to_lowercase()
+                .chars()
+                .map(|ch| if ch.is_ascii_alphanumeric() { ch } else { '-' })
+                .collect();
+            let mut id = slug.clone();
+            let mut n = 2;
+            while c.mcp_servers.iter().any(|s| s.id == id) {
+                id = format!("{}-{}", slug, n);
+                n += 1;
+            }
+            let mut words = target.split_whitespace().map(str::to_string);
+            let (command, args, url) = if transport == "stdio" {
+                (words.next().unwrap_or_default(), words.collect(), String::new())
+            } else {
+                (String::new(), Vec::new(), target.clone())
+            };
+            c.mcp_servers.push(McpServerConfig {
+                id,
+                name: name.clone(),
+                transport: transport.clone(),
+                command,
+                args,
+                env: Default::default(),
+                cwd: None,
+                url,
+                headers: Default::default(),
+                enabled: true,
+            });
+        });
+        set_new_mcp_name.set(String::new());
+        set_new_mcp_target.set(String::new());
+        set_mcp_error.set(None);
+    };
+
+    let reconnect_mcp_server = move |id: String| {
+        spawn_local(async move {
+            let args = serde_wasm_bindgen::to_value(&json!({ "id": id })).unwrap_or(JsValue::NULL);
+            if let Err(e) = invoke_with_timeout::<()>("reconnect_mcp_server", args, 65000).await {
+                log!("Failed to reconnect MCP server: {}", e);
+            }
+            refresh_mcp();
+        });
+    };
+
+    let toggle_settings = move |_| {
+        set_show_settings.update(|s| *s = !*s);
+        if show_settings.get_untracked() {
+            refresh_cache();
+            refresh_roots();
+            refresh_shell();
+            refresh_web();
+            refresh_mcp();
+        }
+    };
     
     let add_server = move |provider: &str| {
         set_config.update(|c| {
@@ -518,6 +771,7 @@ fn StatusBar() -> impl IntoView {
                     url: match provider {
                         "LM Studio" => "http://localhost:1234/v1".to_string(),
                         "Ollama" => "http://localhost:11434".to_string(),
+                        "Mock" => "mock://".to_string(),
                         _ => "http://localhost:8000".to_string(),
                     },
                     provider: provider.to_string(),
@@ -536,7 +790,26 @@ fn StatusBar() -> impl IntoView {
     let save_settings = move |ev: SubmitEvent| {
         ev.prevent_default();
         spawn_local(async move {
-            let args = serde_wasm_bindgen::to_value(&config.get()).unwrap();
+            let args = serde_wasm_bindgen::to_value(&json!({ "settings": cache_settings.get_untracked() })).unwrap();
+            if let Err(e) = invoke_with_timeout::<()>("set_cache_settings", args, 2000).await {
+                log!("Failed to save cache settings: {}", e);
+            }
+
+            if let Some(shell) = shell_settings.get_untracked() {
+                let args = serde_wasm_bindgen::to_value(&json!({ "settings": shell })).unwrap();
+                if let Err(e) = invoke_with_timeout::<()>("set_shell_settings", args, 2000).await {
+                    log!("Failed to save shell settings: {}", e);
+                }
+            }
+
+            if let Some(web) = web_settings.get_untracked() {
+                let args = serde_wasm_bindgen::to_value(&json!({ "settings": web })).unwrap();
+                if let Err(e) = invoke_with_timeout::<()>("set_web_search_settings", args, 2000).await {
+                    log!("Failed to save web search settings: {}", e);
+                }
+            }
+
+            let args = serde_wasm_bindgen::to_value(&json!({ "config": config.get() })).unwrap();
             let result = invoke("save_llm_config", args).await;
             if let Ok(_) = serde_wasm_bindgen::from_value::<()>(result) {
                 set_show_settings.set(false);
@@ -561,7 +834,9 @@ fn StatusBar() -> impl IntoView {
             set_server_statuses.update(|s| { s.insert(id.clone(), ConnectionStatus::Checking); });
             
             let base_url = server.url.trim_end_matches('/');
-            let full_url = if server.provider == "LM Studio" {
+            let full_url = if server.provider == "Mock" {
+                server.url.clone()
+            } else if server.provider == "LM Studio" {
                 if base_url.ends_with("/v1") {
                     format!("{}/models", base_url)
                 } else {
@@ -586,7 +861,7 @@ fn StatusBar() -> impl IntoView {
                 }
             };
             
-            let cmd = if server.provider == "LM Studio" { "fetch_models_lmstudio" } else { "fetch_models_ollama" };
+            let cmd = if server.provider == "LM Studio" || server.provider == "Mock" { "fetch_models_lmstudio" } else { "fetch_models_ollama" };
             
             match invoke_with_timeout::<Result<Vec<String>, String>>(cmd, args, 5000).await {
                 Ok(Ok(models)) => {
@@ -647,8 +922,14 @@ fn StatusBar() -> impl IntoView {
             }
         });
 
-        // Check connections for all servers at boot
+        // Load the saved configuration, then check connections for all servers
         spawn_local(async move {
+            let args = serde_wasm_bindgen::to_value(&()).unwrap_or(JsValue::NULL);
+            match invoke_with_timeout::<Option<LLMConfig>>("load_llm_config", args, 2000).await {
+                Ok(Some(saved)) if !saved.servers.is_empty() => set_config.set(saved),
+                Ok(_) => {}
+                Err(e) => log!("Failed to load saved configuration: {}", e),
+            }
             let config = config.get();
             for server in config.servers.iter() {
                 let server_clone = server.clone();
@@ -751,6 +1032,18 @@ fn StatusBar() -> impl IntoView {
         <div class="status-bar">
             <span class="status-item">"CPU: " {move || format!("{:.1}%", status.get().cpu_usage)}</span>
             <span class="status-item">"Memory: " {move || format!("{:.1}%", status.get().memory_usage)}</span>
+            <span class="status-item index-status" title=move || status.get().index.last_error.unwrap_or_default()>
+                "Index: " {move || {
+                    let index = status.get().index;
+                    format!("{} docs · {} chunks", index.documents, index.chunks)
+                }}
+                {move || (status.get().index.pending > 0).then(|| view! {
+                    <span class="index-pending">{format!("· {} pending", status.get().index.pending)}</span>
+                })}
+                {move || status.get().index.last_error.is_some().then(|| view! {
+                    <span class="index-error">"⚠ error"</span>
+                })}
+            </span>
             <span class="status-item">"Connections: " {move || status.get().active_connections}</span>
             <span class="status-item">"Uptime: " {move || status.get().uptime} "s"</span>
             <div class="status-item model-select-container">
@@ -885,6 +1178,277 @@ fn StatusBar() -> impl IntoView {
                                 >
                                     "Add Ollama Server"
                                 </button>
+                                <button
+                                    type="button"
+                                    on:click=move |_| add_server("Mock")
+                                >
+                                    "Add Mock Server"
+                                </button>
+                            </div>
+                        </div>
+                        <div class="cache-section">
+                            <h3>"Response Cache"</h3>
+                            <div class="form-group">
+                                <label>
+                                    <input
+                                        type="checkbox"
+                                        prop:checked=move || cache_settings.get().enabled
+                                        on:change=move |ev| set_cache_settings.update(|c| c.enabled = event_target_checked(&ev))
+                                    />
+                                    " Reuse replies to identical requests"
+                                </label>
+                            </div>
+                            <div class="form-group">
+                                <label for="cache-ttl">"Expire after (hours):"</label>
+                                <input
+                                    type="number"
+                                    id="cache-ttl"
+                                    min="1"
+                                    value=move || (cache_settings.get().ttl_secs / 3600).to_string()
+                                    on:input=move |ev| {
+                                        if let Ok(hours) = event_target_value(&ev).parse::<u64>() {
+                                            set_cache_settings.update(|c| c.ttl_secs = hours * 3600);
+                                        }
+                                    }
+                                />
+                            </div>
+                            <div class="form-group">
+                                <label for="cache-size">"Max size (MB):"</label>
+                                <input
+                                    type="number"
+                                    id="cache-size"
+                                    min="1"
+                                    value=move || (cache_settings.get().max_bytes / (1024 * 1024)).to_string()
+                                    on:input=move |ev| {
+                                        if let Ok(mb) = event_target_value(&ev).parse::<u64>() {
+                                            set_cache_settings.update(|c| c.max_bytes = mb * 1024 * 1024);
+                                        }
+                                    }
+                                />
+                            </div>
+                            <div class="cache-stats">
+                                <span>
+                                    {move || cache_stats.get().map(|stats| format!(
+                                        "{} entries, {:.1} MB",
+                                        stats.entries,
+                                        stats.bytes as f64 / (1024.0 * 1024.0)
+                                    ))}
+                                </span>
+                                <button type="button" on:click=clear_cache>"Clear Cache"</button>
+                            </div>
+                        </div>
+                        <div class="mcp-section">
+                            <h3>"MCP Servers"</h3>
+                            <p class="workspace-hint">"Nodes can use the tools, resources and prompts of these servers. Changes apply when settings are saved."</p>
+                            <ul class="mcp-servers">
+                                <For
+                                    each=move || config.get().mcp_servers
+                                    key=|server| (server.id.clone(), server.enabled)
+                                    children=move |server| {
+                                        let id = server.id.clone();
+                                        let toggle_id = server.id.clone();
+                                        let remove_id = server.id.clone();
+                                        let reconnect_id = server.id.clone();
+                                        let target = if server.transport == "http" {
+                                            server.url.clone()
+                                        } else {
+                                            std::iter::once(server.command.clone()).chain(server.args.clone()).collect::<Vec<_>>().join(" ")
+                                        };
+                                        view! {
+                                            <li>
+                                                <label>
+                                                    <input
+                                                        type="checkbox"
+                                                        prop:checked=server.enabled
+                                                        on:change=move |ev| {
+                                                            let enabled = event_target_checked(&ev);
+                                                            set_config.update(|c| {
+                                                                if let Some(s) = c.mcp_servers.iter_mut().find(|s| s.id == toggle_id) {
+                                                                    s.enabled = enabled;
+                                                                }
+                                                            });
+                                                        }
+                                                    />
+                                                    " "{server.name.clone()}
+                                                </label>
+                                                <span class="mcp-server-target">{format!("{}: {}", server.transport, target)}</span>
+                                                <span class="mcp-server-status">
+                                                    {move || {
+                                                        let status = mcp_status.get();
+                                                        match status.iter().find(|s| s.id == id) {
+                                                            Some(s) if s.connected => format!(
+                                                                "{} tools, {} resources, {} prompts",
+                                                                s.tools.len(),
+                                                                s.resources.len(),
+                                                                s.prompts.len()
+                                                            ),
+                                                            Some(s) => format!("Failed: {}", s.error.clone().unwrap_or_default()),
+                                                            None => "Not connected".to_string(),
+                                                        }
+                                                    }}
+                                                </span>
+                                                <button type="button" on:click=move |_| reconnect_mcp_server(reconnect_id.clone())>"Reconnect"</button>
+                                                <button
+                                                    type="button"
+                                                    on:click=move |_| set_config.update(|c| c.mcp_servers.retain(|s| s.id != remove_id))
+                                                >"Remove"</button>
+                                            </li>
+                                        }
+                                    }
+                                />
+                            </ul>
+                            <div class="workspace-add">
+                                <input
+                                    type="text"
+                                    placeholder="Name"
+                                    prop:value=move || new_mcp_name.get()
+                                    on:input=move |ev| set_new_mcp_name.set(event_target_value(&ev))
+                                />
+                                <select on:change=move |ev| set_new_mcp_transport.set(event_target_value(&ev))>
+                                    <option value="stdio" selected=move || new_mcp_transport.get() == "stdio">"stdio"</option>
+                                    <option value="http" selected=move || new_mcp_transport.get() == "http">"HTTP"</option>
+                                </select>
+                                <input
+                                    type="text"
+                                    placeholder=move || if new_mcp_transport.get() == "http" { "http://localhost:3000/mcp" } else { "npx -y @modelcontextprotocol/server-everything" }
+                                    prop:value=move || new_mcp_target.get()
+                                    on:input=move |ev| set_new_mcp_target.set(event_target_value(&ev))
+                                />
+                                <button type="button" on:click=add_mcp_server>"Add Server"</button>
+                            </div>
+                            {move || mcp_error.get().map(|e| view! { <div class="workspace-error">{e}</div> })}
+                        </div>
+                        <div class="workspace-section">
+                            <h3>"Workspace Roots"</h3>
+                            <p class="workspace-hint">"File tools can only read and write inside these directories."</p>
+                            <ul class="workspace-roots">
+                                <For
+                                    each=move || workspace_roots.get()
+                                    key=|root| root.clone()
+                                    children=move |root| {
+                                        let path = root.clone();
+                                        view! {
+                                            <li>
+                                                <span class="workspace-root-path">{root}</span>
+                                                <button type="button" on:click=move |_| remove_root(path.clone())>"Remove"</button>
+                                            </li>
+                                        }
+                                    }
+                                />
+                            </ul>
+                            <div class="workspace-add">
+                                <input
+                                    type="text"
+                                    placeholder="/path/to/project"
+                                    prop:value=move || new_root.get()
+                                    on:input=move |ev| set_new_root.set(event_target_value(&ev))
+                                />
+                                <button type="button" on:click=add_root>"Add Root"</button>
+                            </div>
+                            {move || root_error.get().map(|e| view! { <div class="workspace-error">{e}</div> })}
+                        </div>
+                        <div class="shell-section">
+                            <h3>"Shell Commands"</h3>
+                            <div class="form-group">
+                                <label for="shell-allowlist">"Run without asking (one pattern per line, * matches anything, a trailing * whole arguments):"</label>
+                                <textarea
+                                    id="shell-allowlist"
+                                    rows="4"
+                                    prop:value=move || shell_settings.get().map(|s| s.allowlist.join("\n")).unwrap_or_default()
+                                    on:change=move |ev| {
+                                        let patterns = event_target_value(&ev)
+                                            .lines()
+                                            .map(|line| line.trim().to_string())
+                                            .filter(|line| !line.is_empty())
+                                            .collect();
+                                        set_shell_settings.update(|s| if let Some(s) = s { s.allowlist = patterns });
+                                    }
+                                ></textarea>
+                            </div>
+                            <div class="form-group">
+                                <label for="shell-timeout">"Timeout (seconds):"</label>
+                                <input
+                                    type="number"
+                                    id="shell-timeout"
+                                    min="1"
+                                    value=move || shell_settings.get().map(|s| s.timeout_secs.to_string()).unwrap_or_default()
+                                    on:input=move |ev| {
+                                        if let Ok(secs) = event_target_value(&ev).parse::<u64>() {
+                                            set_shell_settings.update(|s| if let Some(s) = s { s.timeout_secs = secs });
+                                        }
+                                    }
+                                />
+                            </div>
+                            <div class="form-group">
+                                <label for="shell-output">"Output limit (KB per stream):"</label>
+                                <input
+                                    type="number"
+                                    id="shell-output"
+                                    min="1"
+                                    value=move || shell_settings.get().map(|s| (s.max_output_bytes / 1024).to_string()).unwrap_or_default()
+                                    on:input=move |ev| {
+                                        if let Ok(kb) = event_target_value(&ev).parse::<usize>() {
+                                            set_shell_settings.update(|s| if let Some(s) = s { s.max_output_bytes = kb * 1024 });
+                                        }
+                                    }
+                                />
+                            </div>
+                            <div class="form-group">
+                                <label>
+                                    <input
+                                        type="checkbox"
+                                        prop:checked=move || shell_settings.get().is_some_and(|s| s.inherit_env)
+                                        on:change=move |ev| {
+                                            let checked = event_target_checked(&ev);
+                                            set_shell_settings.update(|s| if let Some(s) = s { s.inherit_env = checked });
+                                        }
+                                    />
+                                    " Pass the app's environment variables to commands"
+                                </label>
+                            </div>
+                        </div>
+                        <div class="web-search-section">
+                            <h3>"Web Search"</h3>
+                            <div class="form-group">
+                                <label for="searxng-url">"SearXNG URL:"</label>
+                                <input
+                                    type="text"
+                                    id="searxng-url"
+                                    placeholder="http://localhost:8888"
+                                    value=move || web_settings.get().map(|s| s.searxng_url).unwrap_or_default()
+                                    on:input=move |ev| {
+                                        let url = event_target_value(&ev);
+                                        set_web_settings.update(|s| if let Some(s) = s { s.searxng_url = url });
+                                    }
+                                />
+                            </div>
+                            <div class="form-group">
+                                <label for="web-max-results">"Results per search:"</label>
+                                <input
+                                    type="number"
+                                    id="web-max-results"
+                                    min="1"
+                                    value=move || web_settings.get().map(|s| s.max_results.to_string()).unwrap_or_default()
+                                    on:input=move |ev| {
+                                        if let Ok(count) = event_target_value(&ev).parse::<usize>() {
+                                            set_web_settings.update(|s| if let Some(s) = s { s.max_results = count });
+                                        }
+                                    }
+                                />
+                            </div>
+                            <div class="form-group">
+                                <label for="web-page-chars">"Page text limit (characters):"</label>
+                                <input
+                                    type="number"
+                                    id="web-page-chars"
+                                    min="1000"
+                                    value=move || web_settings.get().map(|s| s.max_page_chars.to_string()).unwrap_or_default()
+                                    on:input=move |ev| {
+                                        if let Ok(chars) = event_target_value(&ev).parse::<usize>() {
+                                            set_web_settings.update(|s| if let Some(s) = s { s.max_page_chars = chars });
+                                        }
+                                    }
+                                />
                             </div>
                         </div>
                         <div class="settings-actions">
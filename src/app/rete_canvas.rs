@@ -14,6 +14,9 @@ extern "C" {
     #[wasm_bindgen(method)]
     fn addNode(this: &ReteEditor) -> js_sys::Promise;
 
+    #[wasm_bindgen(method)]
+    fn runWorkflow(this: &ReteEditor) -> js_sys::Promise;
+
     #[wasm_bindgen(method)]
     fn clear(this: &ReteEditor);
 
@@ -26,6 +29,7 @@ pub fn ReteCanvas() -> impl IntoView {
     let editor: std::rc::Rc<std::cell::RefCell<Option<ReteEditor>>> = std::rc::Rc::new(std::cell::RefCell::new(None));
     let editor_for_effect = editor.clone();
     let editor_for_commands = editor.clone();
+    let editor_for_run = editor.clone();
     let editor_for_clear = editor.clone();
 
     // Initialize editor when component mounts
@@ -50,6 +54,13 @@ pub fn ReteCanvas() -> impl IntoView {
                     }>
                         "Add Node"
                     </button>
+                    <button on:click=move |_| {
+                        if let Some(editor_ref) = editor_for_run.borrow().as_ref() {
+                            let _ = editor_ref.runWorkflow();
+                        }
+                    }>
+                        "Run Workflow"
+                    </button>
                     <button on:click=move |_| {
                         if let Some(editor_ref) = editor_for_clear.borrow().as_ref() {
                             editor_ref.clear();